@@ -10,17 +10,45 @@
 //! | `swch exit`   | Tell the running daemon to shut down.                       |
 //! | `swch status` | Ask the daemon for its current state (running/disabled +    |
 //! |               | binding count). Useful for scripts.                         |
+//! | `swch record <file>` | Start recording key events to a trace file.          |
+//! | `swch record stop`   | Stop recording and flush the trace.                  |
+//! | `swch enable-tag <tag>` | Switch on every binding with that `tags:` entry,  |
+//! |               | including `enabled: false` ones, until the daemon exits.    |
+//! | `swch disable-tag <tag>` | Switch every binding with that tag off.          |
+//! | `swch watch`  | Stay connected and print what every config reload added,    |
+//! |               | removed or changed, until Ctrl+C.                           |
+//! | `swch replay <trace> [--config <file>]` | Run a trace through the        |
+//! |               | bindings offline; print what fired and what was suppressed. |
+//! | `swch check [path] [--strict]` | Validate a config offline: print every    |
+//! |               | error and lint warning; exit 1 on errors (or warnings with  |
+//! |               | `--strict`).                                                |
+//! | `swch import ahk <script>` | Convert an AutoHotkey script's hotkeys to    |
+//! |               | `config.ron` text on stdout; list what wasn't converted.    |
+//! | `swch import powertoys [settings.json]` | The same for PowerToys Keyboard  |
+//! |               | Manager remaps (default: its `default.json`).               |
+//! | `swch config path [--config <file>]` | Print the config file the daemon   |
+//! |               | would use and which rule picked it.                         |
+//! | `swch keys`   | List every key name a config accepts, with its aliases.     |
 //!
-//! All commands except `open` talk to the daemon over a named pipe.
+//! Without a path, `replay`, `check` and `config path` find the config the
+//! way the daemon does: `--config`, `KEY_SWITCH_CONFIG`, the per-user config
+//! directory, then next to the executable.
+//!
+//! All commands except `open`, `replay`, `check`, `import`, `config` and
+//! `keys` talk to the daemon over a named pipe.
 //! If the daemon isn't running, they print a one-liner and exit 1.
 
 use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::time::Duration;
 
 use interprocess::local_socket::traits::Stream as StreamTrait;
 use interprocess::local_socket::{prelude::*, GenericNamespaced, Stream};
 
+use key_switch_rs::config::{self, ConfigError};
+use key_switch_rs::data::vk_name;
+use key_switch_rs::hook::trace;
 use key_switch_rs::ipc::{parse_response, Command, PIPE_NAME};
 
 fn main() -> ExitCode {
@@ -44,6 +72,45 @@ fn main() -> ExitCode {
         "off" => send(Command::Off),
         "exit" | "quit" | "shutdown" | "stop" => send(Command::Exit),
         "status" | "ping" => send(Command::Status),
+        "watch" | "subscribe" => cmd_watch(),
+        "enable-tag" | "disable-tag" => {
+            let tag = args[2..].join(" ");
+            if tag.is_empty() {
+                eprintln!("swch: usage: swch {} <tag>", subcommand);
+                return ExitCode::FAILURE;
+            }
+            send(if subcommand == "enable-tag" {
+                Command::EnableTag(tag)
+            } else {
+                Command::DisableTag(tag)
+            })
+        }
+        "record" => match args.get(2).map(String::as_str) {
+            None => {
+                eprintln!("swch: usage: swch record <trace-file> | swch record stop");
+                ExitCode::FAILURE
+            }
+            Some("stop") => send(Command::RecordStop),
+            // The daemon resolves relative paths against its own working
+            // directory, which is not ours — send an absolute one.
+            Some(file) => match std::path::absolute(file) {
+                Ok(path) => send(Command::Record(path)),
+                Err(e) => {
+                    eprintln!("swch: invalid path {:?}: {}", file, e);
+                    ExitCode::FAILURE
+                }
+            },
+        },
+        "replay" => cmd_replay(&args[2..]),
+        "check" => cmd_check(&args[2..]),
+        "import" => cmd_import(&args[2..]),
+        "config" => cmd_config(&args[2..]),
+        "keys" => {
+            for line in vk_name::key_name_list() {
+                println!("{}", line);
+            }
+            ExitCode::SUCCESS
+        }
         "help" | "--help" | "-h" => {
             print_usage(&args[0]);
             ExitCode::SUCCESS
@@ -65,6 +132,236 @@ fn print_usage(prog: &str) {
     eprintln!("  off     Disable the keyboard hook");
     eprintln!("  status  Show daemon state and binding count");
     eprintln!("  exit    Shut the daemon down");
+    eprintln!("  enable-tag <tag> | disable-tag <tag>");
+    eprintln!("          Switch every binding with that tag on or off");
+    eprintln!("  watch   Print what each config reload changed, until Ctrl+C");
+    eprintln!("  record <file>  Record key events to a trace file (`record stop` ends it)");
+    eprintln!("  replay <trace> [--config <file>]");
+    eprintln!("          Run a trace through the bindings offline (no daemon needed)");
+    eprintln!("  check [path] [--strict]");
+    eprintln!("          Validate a config offline; --strict also fails on warnings");
+    eprintln!("  import ahk <script> | import powertoys [settings.json]");
+    eprintln!("          Print AutoHotkey hotkeys or PowerToys remaps as config.ron bindings");
+    eprintln!("  config path [--config <file>]");
+    eprintln!("          Print which config file is used, and why");
+    eprintln!("  keys    List every key name a config accepts");
+}
+
+// ---- `replay` ----
+
+fn cmd_replay(args: &[String]) -> ExitCode {
+    let mut trace_path: Option<PathBuf> = None;
+    let mut config_path: Option<PathBuf> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--config" | "-c" => match iter.next() {
+                Some(p) => config_path = Some(PathBuf::from(p)),
+                None => {
+                    eprintln!("swch: --config needs a path");
+                    return ExitCode::FAILURE;
+                }
+            },
+            other if trace_path.is_none() => trace_path = Some(PathBuf::from(other)),
+            other => {
+                eprintln!("swch: unexpected argument {:?}", other);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let Some(trace_path) = trace_path else {
+        eprintln!("swch: usage: swch replay <trace> [--config <file>]");
+        return ExitCode::FAILURE;
+    };
+
+    match run_replay(&trace_path, config_path) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("swch: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run_replay(trace_path: &Path, config_path: Option<PathBuf>) -> Result<(), String> {
+    let config_path = config::locate_config(config_path.as_deref())
+        .map_err(|e| e.to_string())?
+        .path;
+    let bindings = config::load_existing(&config_path)
+        .map_err(|e| e.to_string())?
+        .bindings;
+
+    let text = std::fs::read_to_string(trace_path)
+        .map_err(|e| format!("cannot read {}: {}", trace_path.display(), e))?;
+    let events = trace::parse_trace(&text)
+        .map_err(|e| format!("{}: {}", trace_path.display(), e))?;
+
+    println!(
+        "replaying {} events from {} against {} ({} bindings)",
+        events.len(),
+        trace_path.display(),
+        config_path.display(),
+        bindings.len()
+    );
+    let steps = trace::replay(&events, bindings);
+    let fired: usize = steps.iter().map(|s| s.fired.len()).sum();
+    let suppressed = steps.iter().filter(|s| s.suppressed).count();
+    for step in &steps {
+        println!("{}", step);
+    }
+    println!("{} actions fired, {} events suppressed", fired, suppressed);
+    Ok(())
+}
+
+// ---- `check` ----
+
+fn cmd_check(args: &[String]) -> ExitCode {
+    let mut config_path: Option<PathBuf> = None;
+    let mut strict = false;
+    for arg in args {
+        match arg.as_str() {
+            "--strict" => strict = true,
+            other if config_path.is_none() => config_path = Some(PathBuf::from(other)),
+            other => {
+                eprintln!("swch: unexpected argument {:?}", other);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let config_path = match config::locate_config(config_path.as_deref()) {
+        Ok(location) => location.path,
+        Err(e) => {
+            eprintln!("swch: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // Warnings are only known once every binding converts, so a config
+    // with errors reports those alone.
+    let loaded = match config::load_existing(&config_path) {
+        Ok(loaded) => loaded,
+        Err(ConfigError::Bindings(errors)) => {
+            for e in &errors {
+                eprintln!("error: {}", e);
+            }
+            println!("{}: {} error(s)", config_path.display(), errors.len());
+            return ExitCode::FAILURE;
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    for w in &loaded.warnings {
+        eprintln!("warning: {}", w);
+    }
+    println!(
+        "{}: ok, {} binding(s), {} warning(s)",
+        config_path.display(),
+        loaded.bindings.len(),
+        loaded.warnings.len()
+    );
+    if strict && !loaded.warnings.is_empty() {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+// ---- `import` ----
+
+fn cmd_import(args: &[String]) -> ExitCode {
+    type Importer = fn(&str) -> Result<config::Imported, ConfigError>;
+    let (import, path): (Importer, PathBuf) = match args {
+        [source, path] if source.eq_ignore_ascii_case("ahk") => {
+            (|text| Ok(config::import_ahk(text)), PathBuf::from(path))
+        }
+        [source, rest @ ..] if source.eq_ignore_ascii_case("powertoys") && rest.len() <= 1 => {
+            let path = match rest.first() {
+                Some(path) => PathBuf::from(path),
+                None => match powertoys_settings_path() {
+                    Some(path) => path,
+                    None => {
+                        eprintln!("swch: %LOCALAPPDATA% is not set; pass the settings file");
+                        return ExitCode::FAILURE;
+                    }
+                },
+            };
+            (config::import_powertoys, path)
+        }
+        _ => {
+            eprintln!("swch: usage: swch import ahk <script> | swch import powertoys [settings.json]");
+            return ExitCode::FAILURE;
+        }
+    };
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("swch: cannot read {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let imported = match import(&text) {
+        Ok(imported) => imported,
+        Err(e) => {
+            eprintln!("swch: {}: {}", path.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // The config goes to stdout, so it can be redirected to a file; the
+    // report goes to stderr.
+    for skipped in &imported.unsupported {
+        eprintln!("{}:{}: not imported: {}", path.display(), skipped.line, skipped.reason);
+    }
+    let summary = format!(
+        "{}: {} binding(s) imported, {} skipped",
+        path.display(),
+        imported.binding_count(),
+        imported.unsupported.len()
+    );
+    match imported.to_ron_string() {
+        Ok(ron) => print!("{}", ron),
+        Err(e) => {
+            eprintln!("swch: {}", e);
+            return ExitCode::FAILURE;
+        }
+    }
+    eprintln!("{}", summary);
+    ExitCode::SUCCESS
+}
+
+/// Where PowerToys keeps the Keyboard Manager remaps.
+fn powertoys_settings_path() -> Option<PathBuf> {
+    let local = std::env::var_os("LOCALAPPDATA")?;
+    Some(Path::new(&local).join(r"Microsoft\PowerToys\Keyboard Manager\default.json"))
+}
+
+// ---- `config` ----
+
+fn cmd_config(args: &[String]) -> ExitCode {
+    let flag = match args {
+        [sub] if sub == "path" => None,
+        [sub, flag, path] if sub == "path" && (flag == "--config" || flag == "-c") => {
+            Some(PathBuf::from(path))
+        }
+        _ => {
+            eprintln!("swch: usage: swch config path [--config <file>]");
+            return ExitCode::FAILURE;
+        }
+    };
+    match config::locate_config(flag.as_deref()) {
+        Ok(location) => {
+            println!("{}", location.path.display());
+            println!("  {}", location);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("swch: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
 // ---- `open` ----
@@ -89,10 +386,17 @@ fn cmd_open() -> Result<(), String> {
         ));
     }
 
-    // Use ShellExecuteW with the "runas" verb to trigger UAC. The daemon's
-    // embedded manifest demands admin anyway, so a plain Command::spawn
-    // would just fail with "operation requires elevation" (os error 740)
-    // when launched from a medium-integrity console.
+    launch_elevated(&exe_path)?;
+    println!("swch: daemon launched");
+    Ok(())
+}
+
+/// Start the daemon through `ShellExecuteW` with the "runas" verb, which
+/// triggers UAC. The daemon's embedded manifest demands admin anyway, so a
+/// plain Command::spawn would just fail with "operation requires
+/// elevation" (os error 740) when launched from a medium-integrity console.
+#[cfg(windows)]
+fn launch_elevated(exe_path: &Path) -> Result<(), String> {
     use std::os::windows::ffi::OsStrExt;
     use windows::core::PCWSTR;
     use windows::Win32::UI::Shell::ShellExecuteW;
@@ -119,11 +423,14 @@ fn cmd_open() -> Result<(), String> {
             val
         ));
     }
-
-    println!("swch: daemon launched");
     Ok(())
 }
 
+#[cfg(not(windows))]
+fn launch_elevated(_exe_path: &Path) -> Result<(), String> {
+    Err("starting the daemon needs Windows".to_string())
+}
+
 // ---- pipe-talking subcommands ----
 
 fn send(cmd: Command) -> ExitCode {
@@ -155,6 +462,53 @@ fn send(cmd: Command) -> ExitCode {
     }
 }
 
+/// `swch watch`: subscribe, then copy every reload report to stdout until
+/// the daemon goes away.
+fn cmd_watch() -> ExitCode {
+    let mut stream = match connect() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!(
+                "swch: cannot reach daemon ({}). Use `swch open` to start it.",
+                e
+            );
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = stream.write_all(Command::Subscribe.as_wire().as_bytes()) {
+        eprintln!("swch: IPC error: write: {}", e);
+        return ExitCode::FAILURE;
+    }
+    stream.flush().ok();
+
+    let mut lines = BufReader::new(stream).lines();
+    match lines.next() {
+        Some(Ok(first)) => match parse_response(&first) {
+            (true, msg) => eprintln!("swch: {}", msg),
+            (false, msg) => {
+                eprintln!("swch: {}", msg);
+                return ExitCode::FAILURE;
+            }
+        },
+        Some(Err(e)) => {
+            eprintln!("swch: IPC error: read: {}", e);
+            return ExitCode::FAILURE;
+        }
+        None => {
+            eprintln!("swch: IPC error: daemon closed the connection");
+            return ExitCode::FAILURE;
+        }
+    }
+    for line in lines {
+        match line {
+            Ok(line) => println!("{}", line),
+            Err(_) => break,
+        }
+    }
+    eprintln!("swch: daemon closed the connection");
+    ExitCode::SUCCESS
+}
+
 fn connect() -> Result<Stream, String> {
     let name = PIPE_NAME
         .to_ns_name::<GenericNamespaced>()
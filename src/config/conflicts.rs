@@ -0,0 +1,198 @@
+//! Conflict analyser: bindings that another binding gets in the way of.
+//!
+//! The engine matches bindings in specificity order (highest
+//! `BindingKind::specificity` first, then load order — see
+//! `engine::sort_by_specificity`), and this module walks them in that same
+//! order. Per key press, the engine fires only the first matching
+//! `Immediate` combo and arms only the first gesture of each kind. A later
+//! binding on the same combo whose `when:` is covered by an earlier one's
+//! therefore never fires. This module finds those, plus the softer cases:
+//! shadowing limited to where two `when:` conditions overlap, `ModTap` keys
+//! that take every press of their key, and sequences that fire other
+//! bindings on the way to completing.
+//!
+//! Bindings in different layers never conflict: a layer shadowing the base
+//! bindings is what layers are for.
+
+use std::mem::discriminant;
+
+use crate::data::binding::{Binding, BindingKind};
+use crate::data::key_combination::{KeyCombination, MatchMode};
+use crate::data::trigger::Trigger;
+
+use super::spans::{Problem, Seg};
+
+/// Every conflict in `bindings` (load order, before auto-blockers), each
+/// reported on the binding that loses. `origin` renders the position of
+/// the binding that wins. A binding that never fires gets that one report
+/// and no partial ones.
+pub(super) fn conflicts(
+    bindings: &[Binding],
+    origin: &dyn Fn(usize) -> String,
+) -> Vec<(usize, Problem)> {
+    let mut order: Vec<usize> = (0..bindings.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(bindings[i].kind.specificity()));
+
+    let mut found = Vec::new();
+    for (pos, &first) in order.iter().enumerate() {
+        for &second in &order[pos + 1..] {
+            let (a, b) = (&bindings[first], &bindings[second]);
+            if a.layer != b.layer || a.condition.excludes(&b.condition) {
+                continue;
+            }
+            let problem = match (&a.kind, &b.kind) {
+                (BindingKind::Combo(ca), BindingKind::Combo(cb)) if ca == cb => {
+                    same_combo(bindings, first, second, origin)
+                }
+                (BindingKind::Sequence { steps: sa, .. }, BindingKind::Sequence { steps: sb, .. }) => {
+                    sequence_prefix(bindings, sa, sb, first, second, origin)
+                }
+                (BindingKind::Combo(combo), BindingKind::Sequence { steps, .. }) => {
+                    sequence_step_combo(steps, combo, second, first, origin)
+                }
+                (BindingKind::Sequence { steps, .. }, BindingKind::Combo(combo)) => {
+                    sequence_step_combo(steps, combo, first, second, origin)
+                }
+                _ => None,
+            };
+            found.extend(problem);
+        }
+    }
+
+    let dead: Vec<usize> = found.iter().filter(|f| f.dead).map(|f| f.loser).collect();
+    let mut reported_dead = Vec::new();
+    found.retain(|f| {
+        if !dead.contains(&f.loser) {
+            return true;
+        }
+        let keep = f.dead && !reported_dead.contains(&f.loser);
+        if keep {
+            reported_dead.push(f.loser);
+        }
+        keep
+    });
+    found.into_iter().map(|f| (f.loser, f.problem)).collect()
+}
+
+struct Found {
+    loser: usize,
+    problem: Problem,
+    /// The loser can never fire, rather than losing only sometimes.
+    dead: bool,
+}
+
+/// Two bindings on the same combo, `first` matched first.
+fn same_combo(
+    bindings: &[Binding],
+    first: usize,
+    second: usize,
+    origin: &dyn Fn(usize) -> String,
+) -> Option<Found> {
+    let (a, b) = (&bindings[first], &bindings[second]);
+    let is_mod_tap = |binding: &Binding| matches!(binding.trigger, Trigger::ModTap { .. });
+    let (winner, loser, what) = match (is_mod_tap(a), is_mod_tap(b)) {
+        // A `ModTap` key is resolved before any matching, whatever the order.
+        (true, false) => (first, second, "is a `ModTap` key and takes every press of it"),
+        (false, true) => (second, first, "is a `ModTap` key and takes every press of it"),
+        _ if discriminant(&a.trigger) != discriminant(&b.trigger) => {
+            // Different triggers on one combo (Tap + Hold, ...) coexist.
+            return None;
+        }
+        // An `Exact` or `Ordered` combo lets the keys through whenever it
+        // doesn't match, so it only takes every press from one in its mode.
+        _ if !same_press(a, b) => return None,
+        _ if a.trigger.is_immediate() => {
+            (first, second, "matches the same combo first and fires instead")
+        }
+        _ => (first, second, "arms the same kind of gesture on this combo first"),
+    };
+
+    let at = if bindings[loser].trigger.is_immediate() {
+        Seg::Field("keys")
+    } else {
+        Seg::Field("trigger")
+    };
+    let what = format!("the binding at {} {}", origin(winner), what);
+    Some(shadowed(&bindings[winner], &bindings[loser], loser, at, &what))
+}
+
+/// `first`, matched first, matches every press that `second` does.
+fn same_press(first: &Binding, second: &Binding) -> bool {
+    match (first.combination(), second.combination()) {
+        (Some(a), Some(b)) => a.mode == MatchMode::Subset || a.mode == b.mode,
+        _ => true,
+    }
+}
+
+/// One sequence's steps start with all of the other's: typing the longer
+/// one completes the shorter one on the way. Equal steps share one pending
+/// slot in the engine, and the binding matched first wins it.
+fn sequence_prefix(
+    bindings: &[Binding],
+    sa: &[KeyCombination],
+    sb: &[KeyCombination],
+    first: usize,
+    second: usize,
+    origin: &dyn Fn(usize) -> String,
+) -> Option<Found> {
+    if sa == sb {
+        let what = format!(
+            "the binding at {} is the same sequence and completes first",
+            origin(first)
+        );
+        let (winner, loser) = (&bindings[first], &bindings[second]);
+        return Some(shadowed(winner, loser, second, Seg::Field("sequence"), &what));
+    }
+    let (short, long, long_index, short_index) = if sa.len() < sb.len() {
+        (sa, sb, second, first)
+    } else {
+        (sb, sa, first, second)
+    };
+    if !long.starts_with(short) {
+        return None;
+    }
+    let problem = Problem::at(
+        Seg::Field("sequence"),
+        format!(
+            "typing this sequence also completes the shorter sequence at {} after step #{}",
+            origin(short_index),
+            short.len() - 1
+        ),
+    );
+    Some(Found { loser: long_index, problem, dead: false })
+}
+
+/// A combo binding that fires while a sequence step with the same keys is
+/// typed. Reported on the sequence.
+fn sequence_step_combo(
+    steps: &[KeyCombination],
+    combo: &KeyCombination,
+    sequence_index: usize,
+    combo_index: usize,
+    origin: &dyn Fn(usize) -> String,
+) -> Option<Found> {
+    let step = steps.iter().position(|s| s == combo)?;
+    let problem = Problem::at(
+        Seg::Field("sequence"),
+        format!(
+            "step #{} is also the combo of the binding at {}, which fires every time \
+             the sequence is typed",
+            step,
+            origin(combo_index)
+        ),
+    );
+    Some(Found { loser: sequence_index, problem, dead: false })
+}
+
+/// "never fires" when `winner`'s `when:` covers `loser`'s, else the
+/// narrower "shadowed while both apply". `at` is the field of `loser` the
+/// report points at.
+fn shadowed(winner: &Binding, loser: &Binding, loser_index: usize, at: Seg, what: &str) -> Found {
+    let dead = winner.condition.covers(&loser.condition);
+    let message = if dead {
+        format!("never fires: {}", what)
+    } else {
+        format!("shadowed wherever both `when:` conditions hold: {}", what)
+    };
+    Found { loser: loser_index, problem: Problem::at(at, message), dead }
+}
@@ -0,0 +1,86 @@
+//! The file formats a config can be written in: RON, TOML or JSON, picked
+//! by the file's extension.
+//!
+//! All three deserialize into the same `RawConfig`. RON writes enum
+//! variants as literals (`Sequence([Window(Restore), Delay(100)])`); TOML
+//! and JSON use serde's externally tagged encoding, where a unit variant is
+//! its name as a string and any other variant a one-entry table keyed by
+//! its name:
+//!
+//! ```toml
+//! [[bindings]]
+//! keys = "Alt+1"
+//! action = { Sequence = [{ Window = "Restore" }, { Delay = 100 }, { Text = "hi" }] }
+//! when = { Not = { AppEquals = "chrome.exe" } }
+//! trigger = { Tap = 200 }
+//! ```
+//!
+//! ```json
+//! { "keys": "CapsLock", "action": "SwitchLanguage", "trigger": { "Hold": 300 } }
+//! ```
+
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+
+/// Error from one of the three deserializers.
+pub type ParseError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Ron,
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// `.toml` and `.json` (any case) are TOML and JSON; every other
+    /// extension, or none, is RON.
+    pub fn from_path(path: &Path) -> Format {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "toml" => Format::Toml,
+            "json" => Format::Json,
+            _ => Format::Ron,
+        }
+    }
+
+    /// The file extension, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Ron => "ron",
+            Format::Toml => "toml",
+            Format::Json => "json",
+        }
+    }
+
+    /// Deserializes `text`. RON gets `IMPLICIT_SOME`, so `keys: "CapsLock"`
+    /// needn't be written `keys: Some("CapsLock")`; TOML and JSON express
+    /// a missing option by leaving the key out.
+    pub(crate) fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, ParseError> {
+        Ok(match self {
+            Format::Ron => ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+                .from_str(text)?,
+            Format::Toml => toml::from_str(text)?,
+            Format::Json => serde_json::from_str(text)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_format_by_extension() {
+        assert_eq!(Format::from_path(Path::new("config.ron")), Format::Ron);
+        assert_eq!(Format::from_path(Path::new("dir/Config.TOML")), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("config.json")), Format::Json);
+        assert_eq!(Format::from_path(Path::new("my-config")), Format::Ron);
+        assert_eq!(Format::from_path(Path::new("config.cfg")), Format::Ron);
+    }
+}
@@ -0,0 +1,921 @@
+//! AutoHotkey v1 scripts to bindings: `swch import ahk script.ahk`.
+//!
+//! The common subset of a hotkey script carries over:
+//!
+//! | AutoHotkey                              | Binding                                                   |
+//! | --------------------------------------- | --------------------------------------------------------- |
+//! | `^!+#` modifiers, key names, `a & b`    | `keys: "Ctrl+Alt+Shift+Win+..."`, `keys: "A+B"`           |
+//! | `vkE2` raw key codes                    | `keys: "Oem102"`, or `"VK(0x..)"` if unnamed              |
+//! | `<^`, `>!`, ... sided modifiers, `<^>!` | `keys: "LCtrl+..."`, `"RAlt+..."`, `"AltGr+..."`          |
+//! | `~` prefix                              | `block_default: false`                                    |
+//! | `CapsLock::Esc` (remap)                 | `PressKey("Esc")`                                         |
+//! | `Send` / `SendInput` / `SendRaw`        | `Sequence([Text(..), Key(..), Combo(..)])`                |
+//! | `Run`                                   | `Launch(..)` for a program, else `OpenUrl(..)`            |
+//! | `Sleep`, `WinMinimize, A`, ...          | `Delay(..)`, `Window(..)` steps                           |
+//! | `#IfWinActive ahk_exe x.exe`            | `when: AppEquals("x.exe")` (`#IfWinNotActive`: `Not(..)`) |
+//! | `::btw::by the way`                     | `sequence:` of the typed keys, `on_fail: Replay`          |
+//!
+//! A hotstring becomes a sequence, which fires as soon as its last key is
+//! typed and anywhere in a word — AutoHotkey's `*` and `?` options — and
+//! holds the keys back until then instead of erasing them afterwards.
+//!
+//! Everything else — other commands, variables, key-up hotkeys,
+//! `{Ctrl down}`, `#IfWinActive` on window titles — is reported with its
+//! line number; a hotkey that uses any of it is skipped whole rather than
+//! imported half-done.
+
+use crate::config::schema::{
+    RawAction, RawBinding, RawCondition, RawOnFail, RawStep, RawWindowKind, SequenceSpec,
+};
+use crate::config::vars::literal;
+use crate::data::vk_name::{parse_vk, vk_name};
+
+use super::{binding, split_words, Imported, Unsupported};
+
+/// Converts the hotkeys, remaps and hotstrings of an AutoHotkey v1 script.
+pub fn import_ahk(script: &str) -> Imported {
+    // Notepad saves scripts with a byte order mark.
+    let script = script.strip_prefix('\u{feff}').unwrap_or(script);
+    let lines = code_lines(script);
+    let mut importer = Importer::default();
+    let mut at = 0;
+    while at < lines.len() {
+        at = importer.line(&lines, at);
+    }
+    Imported { bindings: importer.bindings, unsupported: importer.unsupported }
+}
+
+/// A script line with its 1-based number, comment stripped and trimmed.
+type Line<'a> = (usize, &'a str);
+
+/// A problem with a hotkey, at the line it was found on.
+type Skip = (usize, String);
+
+/// The lines of `script` that hold code.
+fn code_lines(script: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut in_comment = false;
+    for (index, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if in_comment {
+            in_comment = !(line.starts_with("*/") || line.ends_with("*/"));
+            continue;
+        }
+        if line.starts_with("/*") {
+            in_comment = !line.ends_with("*/");
+            continue;
+        }
+        let code = strip_comment(line).trim_end();
+        if !code.is_empty() {
+            lines.push((index + 1, code));
+        }
+    }
+    lines
+}
+
+/// `line` without its `;` comment. A `;` starts one at the start of the
+/// line or after whitespace; `` `; `` is a literal semicolon.
+fn strip_comment(line: &str) -> &str {
+    if line.starts_with(';') {
+        return "";
+    }
+    let bytes = line.as_bytes();
+    line.match_indices(';')
+        .find(|&(i, _)| matches!(bytes[i - 1], b' ' | b'\t'))
+        .map_or(line, |(i, _)| &line[..i])
+}
+
+/// A line that starts a hotkey or hotstring.
+enum Label<'a> {
+    Hotkey { spec: &'a str, rest: &'a str },
+    Hotstring { options: &'a str, abbreviation: &'a str, rest: &'a str },
+}
+
+impl Label<'_> {
+    /// What follows the `::` — empty when the body is on the lines below.
+    fn rest(&self) -> &str {
+        match self {
+            Label::Hotkey { rest, .. } | Label::Hotstring { rest, .. } => rest,
+        }
+    }
+
+    /// The label as written, for messages.
+    fn shown(&self) -> String {
+        match self {
+            Label::Hotkey { spec, .. } => format!("`{}::`", spec),
+            Label::Hotstring { options, abbreviation, .. } => {
+                format!("`:{}:{}::`", options, abbreviation)
+            }
+        }
+    }
+}
+
+fn label(line: &str) -> Option<Label<'_>> {
+    if let Some(after) = line.strip_prefix(':') {
+        let (options, after) = after.split_once(':')?;
+        let (abbreviation, rest) = after.split_once("::")?;
+        return Some(Label::Hotstring { options, abbreviation, rest: rest.trim() });
+    }
+    let (spec, rest) = line.split_once("::")?;
+    // `Send, a::b` is a command with `::` in its text, not a hotkey.
+    let plain = !spec.contains(' ') || spec.contains(" & ") || ends_with_up(spec);
+    (!spec.is_empty() && !spec.contains(',') && plain)
+        .then(|| Label::Hotkey { spec, rest: rest.trim() })
+}
+
+fn ends_with_up(spec: &str) -> bool {
+    // `get`, not slicing: the cut may fall inside a non-ASCII key name.
+    spec.len() > 3
+        && spec.get(spec.len() - 3..).is_some_and(|end| end.eq_ignore_ascii_case(" up"))
+}
+
+/// The lines of a multi-line hotkey body starting at `from`: up to its
+/// `return`, or up to the next label or directive if it has none. Returns
+/// the body and where the next line to read is.
+fn body<'a, 'b>(lines: &'b [Line<'a>], from: usize) -> (&'b [Line<'a>], usize) {
+    for (end, &(_, text)) in lines.iter().enumerate().skip(from) {
+        if text.eq_ignore_ascii_case("return") {
+            return (&lines[from..end], end + 1);
+        }
+        if label(text).is_some() || text.starts_with('#') {
+            return (&lines[from..end], end);
+        }
+    }
+    (&lines[from..], lines.len())
+}
+
+/// The `#IfWinActive` section a hotkey is in.
+#[derive(Default)]
+enum Context {
+    #[default]
+    Global,
+    App { exe: String, negated: bool },
+    /// Under a directive that isn't imported, on this line. Its hotkeys
+    /// would fire in the wrong places, so they are skipped.
+    Unsupported(usize),
+}
+
+#[derive(Default)]
+struct Importer {
+    bindings: Vec<RawBinding>,
+    unsupported: Vec<Unsupported>,
+    context: Context,
+}
+
+impl Importer {
+    /// Reads the line at `at` (and a hotkey's body after it); returns the
+    /// index of the next line to read.
+    fn line(&mut self, lines: &[Line<'_>], at: usize) -> usize {
+        let (number, text) = lines[at];
+        if let Some(label) = label(text) {
+            let (body, next) = if label.rest().is_empty() {
+                body(lines, at + 1)
+            } else {
+                (&[][..], at + 1)
+            };
+            self.label(number, &label, body);
+            return next;
+        }
+        if text.starts_with('#') {
+            self.directive(number, text);
+        } else if !is_setup(text) {
+            self.skip(
+                number,
+                format!("`{}` is not a hotkey, a hotstring or an `#IfWinActive` section", text),
+            );
+        }
+        at + 1
+    }
+
+    fn label(&mut self, number: usize, label: &Label<'_>, body: &[Line<'_>]) {
+        if let Context::Unsupported(directive) = self.context {
+            let reason = format!(
+                "{} skipped: it is under the unsupported directive on line {}",
+                label.shown(),
+                directive
+            );
+            self.skip(number, reason);
+            return;
+        }
+        let binding = match *label {
+            Label::Hotkey { spec, rest } => self.hotkey(number, spec, rest, body),
+            Label::Hotstring { options, abbreviation, rest } => {
+                self.hotstring(number, options, abbreviation, rest, body)
+            }
+        };
+        match binding {
+            Ok(binding) => self.bindings.push(binding),
+            Err((line, reason)) => self.skip(line, format!("{} skipped: {}", label.shown(), reason)),
+        }
+    }
+
+    fn hotkey(
+        &self,
+        number: usize,
+        spec: &str,
+        rest: &str,
+        body: &[Line<'_>],
+    ) -> Result<RawBinding, Skip> {
+        let (keys, pass_through) = hotkey_keys(spec).map_err(|e| (number, e))?;
+        let action = if rest.is_empty() {
+            actions(body)?
+        } else {
+            // `F3::return` is a command, `CapsLock::Esc` a remap.
+            match command(rest) {
+                Ok(steps) => action_from_steps(steps),
+                Err(e) => remap_target(rest).map_err(|_| (number, e))?,
+            }
+        };
+        let mut binding = self.binding(action);
+        binding.keys = Some(keys);
+        binding.block_default = !pass_through;
+        Ok(binding)
+    }
+
+    fn hotstring(
+        &self,
+        number: usize,
+        options: &str,
+        abbreviation: &str,
+        rest: &str,
+        body: &[Line<'_>],
+    ) -> Result<RawBinding, Skip> {
+        let raw = hotstring_options(options).map_err(|e| (number, e))?;
+        let steps = abbreviation_steps(abbreviation).map_err(|e| (number, e))?;
+        let action = if rest.is_empty() {
+            actions(body)?
+        } else {
+            // Replacement text is sent as-is apart from `{...}` keys;
+            // `^!+#` are plain characters in it.
+            let text = unescape(rest);
+            action_from_steps(send_steps(&text, !raw, false).map_err(|e| (number, e))?)
+        };
+        let mut binding = self.binding(action);
+        binding.sequence = Some(SequenceSpec { steps, max_gap_ms: None });
+        binding.on_fail = RawOnFail::Replay;
+        Ok(binding)
+    }
+
+    /// A binding for `action` in the current section, with no keys yet.
+    fn binding(&self, action: RawAction) -> RawBinding {
+        let when = match &self.context {
+            Context::App { exe, negated: false } => RawCondition::AppEquals(exe.clone()),
+            Context::App { exe, negated: true } => {
+                RawCondition::Not(Box::new(RawCondition::AppEquals(exe.clone())))
+            }
+            Context::Global | Context::Unsupported(_) => RawCondition::Always,
+        };
+        binding(action, when)
+    }
+
+    fn directive(&mut self, number: usize, text: &str) {
+        let (name, args) = split_command(text);
+        let name = name.to_ascii_lowercase();
+        match name.as_str() {
+            "#ifwinactive" | "#ifwinnotactive" | "#ifwinexist" | "#ifwinnotexist" | "#if"
+                if args.is_empty() =>
+            {
+                self.context = Context::Global;
+            }
+            "#ifwinactive" | "#ifwinnotactive" => match app_exe(args) {
+                Some(exe) => {
+                    let negated = name == "#ifwinnotactive";
+                    self.context = Context::App { exe: exe.to_string(), negated };
+                }
+                None => {
+                    self.context = Context::Unsupported(number);
+                    let reason = format!(
+                        "`{}` is only imported as `ahk_exe <name>`; the hotkeys under it are skipped",
+                        text
+                    );
+                    self.skip(number, reason);
+                }
+            },
+            "#if" | "#ifwinexist" | "#ifwinnotexist" => {
+                self.context = Context::Unsupported(number);
+                self.skip(number, format!("`{}` is not supported; the hotkeys under it are skipped", text));
+            }
+            // Settings of the AutoHotkey runtime, with nothing to carry over.
+            "#noenv" | "#singleinstance" | "#persistent" | "#warn" | "#requires"
+            | "#installkeybdhook" | "#usehook" | "#maxhotkeysperinterval" | "#hotkeyinterval"
+            | "#notrayicon" => {}
+            _ => self.skip(number, format!("`{}` is not supported", text)),
+        }
+    }
+
+    fn skip(&mut self, line: usize, reason: String) {
+        self.unsupported.push(Unsupported { line, reason });
+    }
+}
+
+/// Auto-execute lines that only configure AutoHotkey itself.
+fn is_setup(text: &str) -> bool {
+    let (name, _) = split_command(text);
+    ["return", "sendmode", "setworkingdir", "settitlematchmode", "setbatchlines", "setkeydelay"]
+        .iter()
+        .any(|setup| name.eq_ignore_ascii_case(setup))
+}
+
+/// `ahk_exe code.exe` → `code.exe`. Any other window spec is `None`.
+fn app_exe(args: &str) -> Option<&str> {
+    let (kind, exe) = args.split_once(char::is_whitespace)?;
+    let exe = exe.trim();
+    let plain = !exe.is_empty() && !exe.contains(',') && !exe.to_ascii_lowercase().contains("ahk_");
+    (kind.eq_ignore_ascii_case("ahk_exe") && plain).then_some(exe)
+}
+
+/// `"Send, hi"` / `"Send hi"` → `("Send", "hi")`.
+fn split_command(text: &str) -> (&str, &str) {
+    let end = text.find(|c: char| c == ',' || c.is_whitespace()).unwrap_or(text.len());
+    let (name, args) = text.split_at(end);
+    let args = args.trim_start();
+    (name, args.strip_prefix(',').unwrap_or(args).trim())
+}
+
+/// The config name of an AutoHotkey key name.
+fn key_name(name: &str) -> Option<String> {
+    let lowered = name.trim().to_ascii_lowercase();
+    // `Media_Play_Pause`, `Volume_Up`, ...
+    let lowered = if lowered.len() > 1 { lowered.replace('_', "") } else { lowered };
+    // `vkE2`: a raw virtual-key code.
+    if let Some(hex) = lowered.strip_prefix("vk")
+        && !hex.is_empty()
+        && hex.len() <= 2
+    {
+        return parse_vk(&format!("VK(0x{})", hex)).map(vk_name);
+    }
+    let name = match lowered.as_str() {
+        "bs" => "backspace",
+        "lcontrol" => "lctrl",
+        "rcontrol" => "rctrl",
+        other => other,
+    };
+    parse_vk(name).map(vk_name)
+}
+
+fn modifier(symbol: char) -> Option<&'static str> {
+    match symbol {
+        '^' => Some("Ctrl"),
+        '!' => Some("Alt"),
+        '+' => Some("Shift"),
+        '#' => Some("Win"),
+        _ => None,
+    }
+}
+
+/// The sided form of a modifier symbol after `<` (left) or `>` (right).
+fn sided_modifier(side: char, symbol: char) -> Option<&'static str> {
+    match (side, symbol) {
+        ('<', '^') => Some("LCtrl"),
+        ('>', '^') => Some("RCtrl"),
+        ('<', '!') => Some("LAlt"),
+        ('>', '!') => Some("RAlt"),
+        ('<', '+') => Some("LShift"),
+        ('>', '+') => Some("RShift"),
+        ('<', '#') => Some("LWin"),
+        ('>', '#') => Some("RWin"),
+        _ => None,
+    }
+}
+
+/// `modifiers` and `keys` as a combo string.
+fn combo(modifiers: &[&str], keys: &[String]) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in modifiers.iter().copied().chain(keys.iter().map(String::as_str)) {
+        if !parts.contains(&part) {
+            parts.push(part);
+        }
+    }
+    parts.join("+")
+}
+
+/// A hotkey's keys as a combo string, and whether it has the `~`
+/// (don't block the key) prefix.
+fn hotkey_keys(spec: &str) -> Result<(String, bool), String> {
+    let mut pass_through = false;
+    let mut modifiers = Vec::new();
+    let mut rest = spec;
+    while rest.chars().count() > 1 {
+        let symbol = rest.chars().next().unwrap();
+        match symbol {
+            '~' => pass_through = true,
+            // Wildcard and hook prefixes: combos already match with extra
+            // modifiers held, and the hook sees every key.
+            '*' | '$' => {}
+            // AutoHotkey's spelling of AltGr.
+            '<' if rest.starts_with("<^>!") && rest.len() > 4 => {
+                modifiers.push("AltGr");
+                rest = &rest[4..];
+                continue;
+            }
+            '<' | '>' => {
+                let name = rest[1..].chars().next().and_then(|next| sided_modifier(symbol, next));
+                let Some(name) = name else {
+                    return Err(format!("`{}` must come right before a modifier (`^!+#`)", symbol));
+                };
+                modifiers.push(name);
+                rest = &rest[2..];
+                continue;
+            }
+            _ => match modifier(symbol) {
+                Some(name) => modifiers.push(name),
+                None => break,
+            },
+        }
+        rest = &rest[symbol.len_utf8()..];
+    }
+    if ends_with_up(rest) {
+        return Err("key-release (` up`) hotkeys are not supported".into());
+    }
+    let names: Vec<&str> = match rest.split_once(" & ") {
+        Some((prefix, key)) => vec![prefix, key],
+        None => vec![rest],
+    };
+    let keys = names
+        .into_iter()
+        .map(|name| key_name(name).ok_or_else(|| format!("unknown key `{}`", name.trim())))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((combo(&modifiers, &keys), pass_through))
+}
+
+/// `a::b` remaps: the action for a one-line hotkey body that is a key.
+fn remap_target(rest: &str) -> Result<RawAction, String> {
+    let (target, _) = hotkey_keys(rest)?;
+    Ok(if target.contains('+') {
+        RawAction::Sequence(vec![RawStep::Combo(target)])
+    } else {
+        RawAction::PressKey(target)
+    })
+}
+
+/// The action for the commands of a hotkey body.
+fn actions(body: &[Line<'_>]) -> Result<RawAction, Skip> {
+    let mut steps = Vec::new();
+    for &(number, text) in body {
+        if text == "{" || text == "}" {
+            continue;
+        }
+        steps.extend(command(text).map_err(|e| (number, e))?);
+    }
+    Ok(action_from_steps(steps))
+}
+
+/// A lone key, program or URL is an action of its own; anything else is
+/// a `Sequence`, and no steps at all (`^j::return`) disable the key.
+fn action_from_steps(mut steps: Vec<RawStep>) -> RawAction {
+    if steps.is_empty() {
+        return RawAction::DoNothing;
+    }
+    if steps.len() > 1 {
+        return RawAction::Sequence(steps);
+    }
+    match steps.pop().unwrap() {
+        RawStep::Key(key) => RawAction::PressKey(key),
+        RawStep::Launch { exe, args } => RawAction::Launch { exe, args },
+        RawStep::OpenUrl(target) => RawAction::OpenUrl(target),
+        step => RawAction::Sequence(vec![step]),
+    }
+}
+
+/// The steps one command line runs.
+fn command(text: &str) -> Result<Vec<RawStep>, String> {
+    let (name, args) = split_command(text);
+    let uses_variables = unescaped(args).any(|(_, c)| c == '%');
+    let name = name.to_ascii_lowercase();
+    if uses_variables && name != "sleep" {
+        return Err(format!("`{}` uses a variable, which is not supported", text));
+    }
+    match name.as_str() {
+        "send" | "sendinput" | "sendevent" | "sendplay" => send_steps(&unescape(args), true, true),
+        "sendraw" => send_steps(&unescape(args), false, false),
+        "run" => run(args).map(|step| vec![step]),
+        "sleep" => args
+            .parse()
+            .map(|ms| vec![RawStep::Delay(ms)])
+            .map_err(|_| format!("`{}`: not a number of milliseconds", text)),
+        "winminimize" | "winmaximize" | "winrestore" | "winclose" => {
+            if !args.eq_ignore_ascii_case("a") {
+                return Err(format!("`{}`: only the active window (`A`) is supported", text));
+            }
+            let kind = match name.as_str() {
+                "winminimize" => RawWindowKind::Minimize,
+                "winmaximize" => RawWindowKind::Maximize,
+                "winrestore" => RawWindowKind::Restore,
+                _ => RawWindowKind::Close,
+            };
+            Ok(vec![RawStep::Window(kind)])
+        }
+        "return" => Ok(Vec::new()),
+        _ => Err(format!("`{}` is not supported", text)),
+    }
+}
+
+/// The characters of `text` that aren't escaped with a backtick, by byte
+/// offset.
+fn unescaped(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut escaped = false;
+    text.char_indices().filter(move |&(_, c)| {
+        let plain = !escaped && c != '`';
+        escaped = !escaped && c == '`';
+        plain
+    })
+}
+
+/// `text` with AutoHotkey's backtick escapes resolved: `` `n `` and
+/// `` `t `` are a newline and a tab, any other `` `x `` is `x`.
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '`' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('`'),
+        }
+    }
+    out
+}
+
+/// `Run`'s target as a `Launch` of a program, or an `OpenUrl` of anything
+/// else — a URL, document or folder, which `Run` opens the same way.
+fn run(args: &str) -> Result<RawStep, String> {
+    if let Some((offset, _)) = unescaped(args).find(|&(_, c)| c == ',') {
+        return Err(format!(
+            "`Run, {}`: a working directory or options (`{}`) are not supported",
+            args,
+            args[offset + 1..].trim()
+        ));
+    }
+    // `$` is plain text to AutoHotkey, but starts a variable here.
+    let target = literal(&unescape(args));
+    let lowered = target.to_ascii_lowercase();
+    if lowered.contains("://") || lowered.starts_with("mailto:") {
+        return Ok(RawStep::OpenUrl(target));
+    }
+    let mut words = split_words(&target).into_iter();
+    let Some(exe) = words.next() else {
+        return Err("`Run` with nothing to run".into());
+    };
+    // `notepad` or `...\\app.exe`; a path to anything else is opened.
+    let is_program =
+        exe.to_ascii_lowercase().ends_with(".exe") || !exe.contains(['\\', '/', '.', ':']);
+    if is_program {
+        Ok(RawStep::Launch { exe, args: words.collect() })
+    } else {
+        Ok(RawStep::OpenUrl(target))
+    }
+}
+
+/// Steps, with runs of plain characters gathered into one `Text` step.
+#[derive(Default)]
+struct Steps {
+    steps: Vec<RawStep>,
+    text: String,
+}
+
+impl Steps {
+    fn push(&mut self, step: RawStep) {
+        self.flush();
+        self.steps.push(step);
+    }
+
+    /// `key`, or `key` with `modifiers` held.
+    fn key(&mut self, modifiers: &[&str], key: String) {
+        if modifiers.is_empty() {
+            self.push(RawStep::Key(key));
+        } else {
+            self.push(RawStep::Combo(combo(modifiers, &[key])));
+        }
+    }
+
+    /// A character: typed as text, or pressed as its key when `modifiers`
+    /// are held — with Shift for an uppercase letter, as `Send` does.
+    fn char(&mut self, modifiers: &[&str], c: char) -> Result<(), String> {
+        match c {
+            '\n' => self.key(modifiers, "Enter".into()),
+            '\t' => self.key(modifiers, "Tab".into()),
+            '\r' => {}
+            c if modifiers.is_empty() => self.text.push(c),
+            c => {
+                let key = key_name(&c.to_string())
+                    .ok_or_else(|| format!("`{}` has no key of its own to press with modifiers", c))?;
+                let mut modifiers = modifiers.to_vec();
+                if c.is_ascii_uppercase() {
+                    modifiers.push("Shift");
+                }
+                self.key(&modifiers, key);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            self.steps.push(RawStep::Text(literal(&std::mem::take(&mut self.text))));
+        }
+    }
+
+    fn finish(mut self) -> Vec<RawStep> {
+        self.flush();
+        self.steps
+    }
+}
+
+/// What a `{...}` in `Send` text stands for.
+enum Brace {
+    /// `{Raw}` / `{Text}`: the rest of the text is sent as-is.
+    Raw,
+    /// `{!}`, `{{}`, `{U+00E9}`, `{a 3}`: a character, typed `n` times.
+    Char(char, usize),
+    /// `{Enter}`, `{Tab 3}`: a key, pressed `n` times.
+    Key(String, usize),
+}
+
+fn brace(inner: &str) -> Result<Brace, String> {
+    let (name, count) = match inner.rsplit_once(' ') {
+        Some((name, count)) if !name.is_empty() => match count.parse() {
+            Ok(count) => (name, count),
+            Err(_) => return Err(format!("`{{{}}}` (holding keys down) is not supported", inner)),
+        },
+        _ => (inner, 1),
+    };
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Ok(Brace::Char(c, count));
+    }
+    let lowered = name.to_ascii_lowercase();
+    if let Some(code) = lowered.strip_prefix("u+") {
+        return u32::from_str_radix(code, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map(|c| Brace::Char(c, count))
+            .ok_or_else(|| format!("`{{{}}}` is not a Unicode character", inner));
+    }
+    match lowered.as_str() {
+        "raw" | "text" => Ok(Brace::Raw),
+        "blind" => Err("`{Blind}` is not supported".into()),
+        _ => key_name(name)
+            .map(|key| Brace::Key(key, count))
+            .ok_or_else(|| format!("unknown key `{{{}}}`", inner)),
+    }
+}
+
+/// The steps `Send` text types. `braces` enables `{...}` keys, and
+/// `modifiers` the `^!+#` prefixes.
+fn send_steps(text: &str, mut braces: bool, mut modifiers: bool) -> Result<Vec<RawStep>, String> {
+    let mut steps = Steps::default();
+    let mut held: Vec<&str> = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        rest = &rest[c.len_utf8()..];
+        if braces && c == '{' {
+            // Search from one character in, so `{}}` names `}` itself.
+            let skip = rest.chars().next().map_or(0, char::len_utf8);
+            let end = rest[skip..]
+                .find('}')
+                .map(|i| i + skip)
+                .ok_or_else(|| format!("unclosed `{{` in `{}`", text))?;
+            let inner = &rest[..end];
+            rest = &rest[end + 1..];
+            match brace(inner)? {
+                Brace::Raw => (braces, modifiers) = (false, false),
+                Brace::Char(c, count) => {
+                    for _ in 0..count {
+                        steps.char(&held, c)?;
+                    }
+                }
+                Brace::Key(key, count) => {
+                    for _ in 0..count {
+                        steps.key(&held, key.clone());
+                    }
+                }
+            }
+            held.clear();
+            continue;
+        }
+        if modifiers && let Some(name) = modifier(c) {
+            held.push(name);
+            continue;
+        }
+        steps.char(&held, c)?;
+        held.clear();
+    }
+    if !held.is_empty() {
+        return Err(format!("`{}` ends in a modifier with no key after it", text));
+    }
+    Ok(steps.finish())
+}
+
+/// Whether a hotstring's replacement is raw (`R` / `T`). Options that
+/// change how or when it fires beyond what a sequence does are errors.
+fn hotstring_options(options: &str) -> Result<bool, String> {
+    let mut raw = false;
+    let mut chars = options.chars().peekable();
+    while let Some(option) = chars.next() {
+        let off = chars.next_if_eq(&'0').is_some();
+        if !off {
+            chars.next_if_eq(&'1');
+        }
+        match option.to_ascii_uppercase() {
+            '*' | '?' | 'O' | 'Z' | 'C' => {}
+            'R' | 'T' => raw = !off,
+            'B' if !off => {}
+            _ => {
+                return Err(format!(
+                    "hotstring option `{}{}` is not supported",
+                    option,
+                    if off { "0" } else { "" }
+                ));
+            }
+        }
+    }
+    Ok(raw)
+}
+
+/// The keys that type `abbreviation`, one sequence step each.
+fn abbreviation_steps(abbreviation: &str) -> Result<Vec<String>, String> {
+    if abbreviation.chars().count() < 2 {
+        return Err("a sequence needs at least two keys, so one-character hotstrings are not supported".into());
+    }
+    abbreviation
+        .chars()
+        .map(|c| {
+            let key = if c == ' ' { Some("Space".to_string()) } else { key_name(&c.to_string()) };
+            key.ok_or_else(|| format!("`{}` in the abbreviation has no key of its own", c))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::from_ron_str;
+
+    /// The RON `script` imports to, checked to load.
+    fn import(script: &str) -> (String, Vec<String>) {
+        let imported = import_ahk(script);
+        let unsupported = imported.unsupported.iter().map(ToString::to_string).collect();
+        let ron = imported.to_ron_string().unwrap();
+        from_ron_str(&ron).unwrap_or_else(|e| panic!("{}\n{}", e, ron));
+        (ron, unsupported)
+    }
+
+    /// Just the binding lines of `ron`.
+    fn bindings(ron: &str) -> Vec<&str> {
+        ron.lines()
+            .filter_map(|line| line.trim().strip_prefix('(')?.strip_suffix("),"))
+            .collect()
+    }
+
+    #[test]
+    fn hotkeys_remaps_and_commands() {
+        let (ron, unsupported) = import(
+            r#"#NoEnv
+SendMode Input ; faster
+^!t::Run, wt.exe
+#e::Run explorer.exe C:\Users
+^+g::Run https://github.com
+CapsLock::Esc
+~F1::Send ^c
+RAlt & j::Send, {Left 2}
+^j::SendInput, Hello,{Space}World{Enter}
+>^k::Send, right
+<+>!l::Send, both
+<^>!e::Send, euro
+AppsKey::Send, {Browser_Back}
+vkE2::NumpadMult
+!a::
+    Send, ^a^C
+    Sleep, 50
+    WinMinimize, A
+return
+/* F2::Send nothing
+*/
+F3::return
+"#,
+        );
+        assert!(unsupported.is_empty(), "{:?}", unsupported);
+        assert_eq!(
+            bindings(&ron),
+            [
+                r#"keys: "Ctrl+Alt+T", action: Launch(exe: "wt.exe")"#,
+                r#"keys: "Win+E", action: Launch(exe: "explorer.exe", args: ["C:\\Users"])"#,
+                r#"keys: "Ctrl+Shift+G", action: OpenUrl("https://github.com")"#,
+                r#"keys: "CapsLock", action: PressKey("Esc")"#,
+                r#"keys: "F1", action: Sequence([Combo("Ctrl+C")]), block_default: false"#,
+                r#"keys: "RAlt+J", action: Sequence([Key("Left"), Key("Left")])"#,
+                r#"keys: "Ctrl+J", action: Sequence([Text("Hello,"), Key("Space"), Text("World"), Key("Enter")])"#,
+                r#"keys: "RCtrl+K", action: Sequence([Text("right")])"#,
+                r#"keys: "LShift+RAlt+L", action: Sequence([Text("both")])"#,
+                r#"keys: "AltGr+E", action: Sequence([Text("euro")])"#,
+                r#"keys: "Apps", action: PressKey("BrowserBack")"#,
+                r#"keys: "Oem102", action: PressKey("NumpadMultiply")"#,
+                r#"keys: "Alt+A", action: Sequence([Combo("Ctrl+A"), Combo("Ctrl+Shift+C"), Delay(50), Window(Minimize)])"#,
+                r#"keys: "F3", action: DoNothing"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn if_win_active_sections_become_conditions() {
+        let (ron, unsupported) = import(
+            "#IfWinActive ahk_exe code.exe\n\
+             ^b::Send {Raw}^b{x}\n\
+             #IfWinNotActive, ahk_exe chrome.exe\n\
+             ^w::WinClose, A\n\
+             #IfWinActive\n\
+             ^q::Send, {U+00E9}{!}\n",
+        );
+        assert!(unsupported.is_empty(), "{:?}", unsupported);
+        assert_eq!(
+            bindings(&ron),
+            [
+                r#"keys: "Ctrl+B", action: Sequence([Text("^b{x}")]), when: AppEquals("code.exe")"#,
+                r#"keys: "Ctrl+W", action: Sequence([Window(Close)]), when: Not(AppEquals("chrome.exe"))"#,
+                r#"keys: "Ctrl+Q", action: Sequence([Text("é!")])"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn hotstrings_become_sequences() {
+        let (ron, unsupported) = import(
+            "::btw::by the way\n\
+             :*:sig;;::Best,`nMe\n\
+             :R:2x::{Enter} stays\n\
+             ::ok::\n\
+             Send, {Enter}\n\
+             return\n",
+        );
+        assert!(unsupported.is_empty(), "{:?}", unsupported);
+        assert_eq!(
+            bindings(&ron),
+            [
+                r#"sequence: (steps: ["B", "T", "W"]), action: Sequence([Text("by the way")]), on_fail: Replay"#,
+                r#"sequence: (steps: ["S", "I", "G", ";", ";"]), action: Sequence([Text("Best,"), Key("Enter"), Text("Me")]), on_fail: Replay"#,
+                r#"sequence: (steps: ["2", "X"]), action: Sequence([Text("{Enter} stays")]), on_fail: Replay"#,
+                r#"sequence: (steps: ["O", "K"]), action: PressKey("Enter"), on_fail: Replay"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_lines_are_listed_with_their_numbers() {
+        let (ron, unsupported) = import(
+            "Menu, Tray, Icon, app.ico\n\
+             ^m::MsgBox, hi\n\
+             ^n::\n\
+             Send, hi\n\
+             Send, %clipboard%\n\
+             return\n\
+             <a::Send, x\n\
+             F4 up::Send, x\n\
+             ^d::Send, {Ctrl down}\n\
+             ^r::Run, notepad.exe, C:\\, Max\n\
+             :B0:brb::be right back\n\
+             ::@@::me@example.com\n\
+             ::x::y\n\
+             #IfWinActive Untitled - Notepad\n\
+             ^s::Send, saved\n\
+             #IfWinActive\n\
+             ^k::Send, kept\n",
+        );
+        assert_eq!(
+            unsupported,
+            [
+                "line 1: `Menu, Tray, Icon, app.ico` is not a hotkey, a hotstring or an `#IfWinActive` section",
+                "line 2: `^m::` skipped: `MsgBox, hi` is not supported",
+                "line 5: `^n::` skipped: `Send, %clipboard%` uses a variable, which is not supported",
+                "line 7: `<a::` skipped: `<` must come right before a modifier (`^!+#`)",
+                "line 8: `F4 up::` skipped: key-release (` up`) hotkeys are not supported",
+                "line 9: `^d::` skipped: `{Ctrl down}` (holding keys down) is not supported",
+                "line 10: `^r::` skipped: `Run, notepad.exe, C:\\, Max`: a working directory or options (`C:\\, Max`) are not supported",
+                "line 11: `:B0:brb::` skipped: hotstring option `B0` is not supported",
+                "line 12: `::@@::` skipped: `@` in the abbreviation has no key of its own",
+                "line 13: `::x::` skipped: a sequence needs at least two keys, so one-character hotstrings are not supported",
+                "line 14: `#IfWinActive Untitled - Notepad` is only imported as `ahk_exe <name>`; the hotkeys under it are skipped",
+                "line 15: `^s::` skipped: it is under the unsupported directive on line 14",
+            ]
+        );
+        assert_eq!(bindings(&ron), [r#"keys: "Ctrl+K", action: Sequence([Text("kept")])"#]);
+    }
+
+    #[test]
+    fn non_ascii_key_names_are_reported() {
+        let (ron, unsupported) = import("öü::Send x\nä ö::Send y\nü up::Send z\n");
+        assert_eq!(
+            unsupported,
+            [
+                "line 1: `öü::` skipped: unknown key `öü`",
+                "line 2: `ä ö::Send y` is not a hotkey, a hotstring or an `#IfWinActive` section",
+                "line 3: `ü up::` skipped: key-release (` up`) hotkeys are not supported",
+            ]
+        );
+        assert!(bindings(&ron).is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//! Importers: other remappers' configs converted to bindings, for
+//! `swch import`.
+//!
+//! Each importer reads what it can into the on-disk schema and lists the
+//! rest — nothing is dropped silently. The result prints as `config.ron`
+//! text through the writer, so it reads like a hand-written config.
+//!
+//!   - `ahk.rs`       — AutoHotkey v1 scripts
+//!   - `powertoys.rs` — PowerToys Keyboard Manager remaps
+
+mod ahk;
+mod powertoys;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::loader::ConfigError;
+use super::schema::{
+    RawAction, RawBinding, RawCondition, RawConfig, RawMatchMode, RawOnFail, RawSettings,
+    RawTrigger,
+};
+use super::writer;
+
+pub use ahk::import_ahk;
+pub use powertoys::import_powertoys;
+
+/// What an importer made of a foreign config.
+#[derive(Debug)]
+pub struct Imported {
+    bindings: Vec<RawBinding>,
+    /// Everything that was not imported, in source order.
+    pub unsupported: Vec<Unsupported>,
+}
+
+impl Imported {
+    pub fn binding_count(&self) -> usize {
+        self.bindings.len()
+    }
+
+    /// The imported bindings as a `config.ron` document.
+    pub fn to_ron_string(self) -> Result<String, ConfigError> {
+        writer::ron_document(&RawConfig {
+            include: Vec::new(),
+            bindings: self.bindings,
+            layers: BTreeMap::new(),
+            actions: BTreeMap::new(),
+            vars: BTreeMap::new(),
+            settings: RawSettings::default(),
+        })
+    }
+}
+
+/// A line of the source an importer skipped, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unsupported {
+    /// 1-based.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+/// A binding for `action` under `when`, with no keys yet and every other
+/// field at its default.
+fn binding(action: RawAction, when: RawCondition) -> RawBinding {
+    RawBinding {
+        id: None,
+        name: None,
+        description: None,
+        keys: None,
+        sequence: None,
+        chord: None,
+        action: Some(action),
+        block_default: true,
+        block_original_combo: false,
+        when,
+        trigger: RawTrigger::Immediate,
+        repeat: None,
+        match_mode: RawMatchMode::Subset,
+        on_fail: RawOnFail::Drop,
+        tags: Vec::new(),
+        enabled: true,
+    }
+}
+
+/// A command line split at whitespace, with `"..."` kept together.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    words.push(std::mem::take(&mut word));
+                    started = false;
+                }
+            }
+            c => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        words.push(word);
+    }
+    words
+}
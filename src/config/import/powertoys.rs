@@ -0,0 +1,342 @@
+//! PowerToys Keyboard Manager remaps to bindings: `swch import powertoys`.
+//!
+//! Keyboard Manager keeps its remaps in
+//! `%LOCALAPPDATA%\Microsoft\PowerToys\Keyboard Manager\default.json`,
+//! every key as a decimal virtual-key code and a shortcut as codes joined
+//! by `;` (`"17;67"` is Ctrl+C):
+//!
+//! | Keyboard Manager                       | Binding                                         |
+//! | -------------------------------------- | ----------------------------------------------- |
+//! | key or shortcut → key                  | `PressKey("B")`                                 |
+//! | key or shortcut → shortcut             | `Sequence([Combo("Ctrl+V")])`                   |
+//! | → text                                 | `Sequence([Text("...")])`                       |
+//! | → Disable                              | `DoNothing`                                     |
+//! | shortcut → run program / open URI      | `Launch(..)` / `OpenUrl(..)`                    |
+//! | two-key shortcut (`Ctrl+K`, `D`)       | `sequence: (steps: ["Ctrl+K", "D"])`            |
+//! | app-specific shortcut                  | `when: AppEquals("chrome.exe")`                 |
+//! | `exactMatch` shortcut                  | `match: Exact`                                  |
+//!
+//! Not representable, so reported and skipped: remaps to a lone modifier
+//! (a binding taps its action's key, it can't hold it down), two-key
+//! shortcuts with `exactMatch`, programs started elevated or in a given
+//! directory, and keys without a name here.
+
+use serde::Deserialize;
+
+use crate::config::loader::{is_modifier, ConfigError};
+use crate::config::schema::{
+    RawAction, RawBinding, RawCondition, RawMatchMode, RawStep, SequenceSpec,
+};
+use crate::config::spans::{self, Seg};
+use crate::config::vars::literal;
+use crate::data::vk::Vk;
+use crate::data::vk_name::{parse_vk, vk_name, ANY_WIN};
+
+use super::{binding, split_words, Imported, Unsupported};
+
+/// Keyboard Manager's code for "Disable" as a remap target.
+const VK_DISABLED: u32 = 0x100;
+/// Keyboard Manager's code for "Win", either side.
+const VK_WIN_BOTH: u32 = 0x104;
+
+/// The settings file, as far as remaps go.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct Settings {
+    remap_keys: KeyRemaps,
+    remap_keys_to_text: KeyRemaps,
+    remap_shortcuts: ShortcutRemaps,
+    remap_shortcuts_to_text: ShortcutRemaps,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct KeyRemaps {
+    in_process: Vec<Remap>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct ShortcutRemaps {
+    global: Vec<Remap>,
+    app_specific: Vec<Remap>,
+}
+
+/// One remap. Which fields are set depends on the list it is in and on
+/// `operation_type`.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct Remap {
+    original_keys: String,
+    /// The second key of a two-key shortcut: a code, as a number or a
+    /// string; 0 or missing for a plain shortcut.
+    second_key_of_chord: Option<serde_json::Value>,
+    exact_match: bool,
+    new_remap_keys: String,
+    unicode_text: String,
+    /// 0: remap to keys or text, 1: run a program, 2: open a URI.
+    operation_type: u32,
+    run_program_file_path: String,
+    run_program_args: String,
+    run_program_start_in_dir: String,
+    run_program_elevation_level: u32,
+    open_uri: String,
+    target_app: String,
+}
+
+/// Converts the remaps in a Keyboard Manager settings file. An error only
+/// when the text isn't such a file at all.
+pub fn import_powertoys(json: &str) -> Result<Imported, ConfigError> {
+    let settings: Settings = serde_json::from_str(json).map_err(|e| {
+        ConfigError::Other(format!("not a Keyboard Manager settings file: {}", e))
+    })?;
+    let spans = spans::scan(json);
+
+    // Keyboard Manager picks an app-specific shortcut over a global one on
+    // the same keys; here the binding matched first wins, so they go first.
+    let lists: [(&'static str, &'static str, &[Remap]); 6] = [
+        ("remapShortcuts", "appSpecific", &settings.remap_shortcuts.app_specific),
+        ("remapShortcutsToText", "appSpecific", &settings.remap_shortcuts_to_text.app_specific),
+        ("remapShortcuts", "global", &settings.remap_shortcuts.global),
+        ("remapShortcutsToText", "global", &settings.remap_shortcuts_to_text.global),
+        ("remapKeys", "inProcess", &settings.remap_keys.in_process),
+        ("remapKeysToText", "inProcess", &settings.remap_keys_to_text.in_process),
+    ];
+    let mut imported = Imported { bindings: Vec::new(), unsupported: Vec::new() };
+    for (section, list, remaps) in lists {
+        for (index, remap) in remaps.iter().enumerate() {
+            match convert(remap) {
+                Ok(binding) => imported.bindings.push(binding),
+                Err(reason) => {
+                    let path = [Seg::Field(section), Seg::Field(list), Seg::Item(index)];
+                    let line = spans.as_ref().map_or(1, |root| root.locate(&path).line);
+                    imported.unsupported.push(Unsupported { line, reason });
+                }
+            }
+        }
+    }
+    imported.unsupported.sort_by_key(|skipped| skipped.line);
+    Ok(imported)
+}
+
+fn convert(remap: &Remap) -> Result<RawBinding, String> {
+    let original = keys(&remap.original_keys)
+        .map_err(|e| format!("`{}` skipped: {}", remap.original_keys, e))?;
+    let shown = combo_string(&original);
+    let skip = |reason: String| format!("`{}` skipped: {}", shown, reason);
+    let action = action(remap).map_err(skip)?;
+    let when = if remap.target_app.is_empty() {
+        RawCondition::Always
+    } else {
+        RawCondition::AppEquals(exe_name(&remap.target_app))
+    };
+
+    let mut binding = binding(action, when);
+    match second_key(remap).map_err(skip)? {
+        Some(_) if remap.exact_match => {
+            return Err(skip("`exactMatch` on a two-key shortcut is not supported".into()));
+        }
+        Some(second) => {
+            binding.sequence = Some(SequenceSpec {
+                steps: vec![shown, vk_name(second)],
+                max_gap_ms: None,
+            });
+        }
+        None => {
+            binding.keys = Some(shown);
+            if remap.exact_match {
+                binding.match_mode = RawMatchMode::Exact;
+            }
+        }
+    }
+    Ok(binding)
+}
+
+fn action(remap: &Remap) -> Result<RawAction, String> {
+    if !remap.unicode_text.is_empty() {
+        return Ok(RawAction::Sequence(vec![RawStep::Text(literal(&remap.unicode_text))]));
+    }
+    match remap.operation_type {
+        0 => {
+            if codes(&remap.new_remap_keys)? == [VK_DISABLED] {
+                return Ok(RawAction::DoNothing);
+            }
+            match keys(&remap.new_remap_keys)?.as_slice() {
+                [key] if is_modifier(*key) => Err(format!(
+                    "remapping to {} alone is not supported: a binding taps its key, it can't hold it down",
+                    vk_name(*key)
+                )),
+                [key] => Ok(RawAction::PressKey(vk_name(*key))),
+                keys => Ok(RawAction::Sequence(vec![RawStep::Combo(combo_string(keys))])),
+            }
+        }
+        1 => {
+            if !remap.run_program_start_in_dir.is_empty() {
+                return Err(format!(
+                    "starting {:?} in {:?} is not supported",
+                    remap.run_program_file_path, remap.run_program_start_in_dir
+                ));
+            }
+            if remap.run_program_elevation_level != 0 {
+                return Err(format!(
+                    "starting {:?} elevated or as another user is not supported",
+                    remap.run_program_file_path
+                ));
+            }
+            Ok(RawAction::Launch {
+                exe: literal(&remap.run_program_file_path),
+                args: split_words(&literal(&remap.run_program_args)),
+            })
+        }
+        2 => Ok(RawAction::OpenUrl(literal(&remap.open_uri))),
+        other => Err(format!("operation type {} is not supported", other)),
+    }
+}
+
+/// `"17;67"` → `[17, 67]`.
+fn codes(list: &str) -> Result<Vec<u32>, String> {
+    list.split(';')
+        .map(|code| {
+            code.trim()
+                .parse()
+                .map_err(|_| format!("{:?} is not a list of key codes", list))
+        })
+        .collect()
+}
+
+/// The keys of a code list. Every one needs a name a config can spell.
+fn keys(list: &str) -> Result<Vec<Vk>, String> {
+    codes(list)?.into_iter().map(key).collect()
+}
+
+fn key(code: u32) -> Result<Vk, String> {
+    if code == VK_WIN_BOTH {
+        return Ok(ANY_WIN);
+    }
+    u16::try_from(code)
+        .ok()
+        .map(Vk)
+        .filter(|&vk| parse_vk(&vk_name(vk)) == Some(vk))
+        .ok_or_else(|| format!("key code {:#04X} has no name in a config", code))
+}
+
+fn second_key(remap: &Remap) -> Result<Option<Vk>, String> {
+    let code = match &remap.second_key_of_chord {
+        None => return Ok(None),
+        Some(serde_json::Value::Number(n)) => n.as_u64(),
+        Some(serde_json::Value::String(s)) => s.trim().parse().ok(),
+        Some(_) => None,
+    };
+    match code {
+        Some(0) => Ok(None),
+        Some(code) => key(u32::try_from(code).unwrap_or(u32::MAX)).map(Some),
+        None => Err("`secondKeyOfChord` is not a key code".into()),
+    }
+}
+
+fn combo_string(keys: &[Vk]) -> String {
+    keys.iter().map(|&vk| vk_name(vk)).collect::<Vec<_>>().join("+")
+}
+
+/// Keyboard Manager takes `msedge` as well as `msedge.exe`.
+fn exe_name(app: &str) -> String {
+    if app.contains('.') {
+        app.to_string()
+    } else {
+        format!("{}.exe", app)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::from_ron_str;
+
+    fn import(json: &str) -> (Vec<String>, Vec<String>) {
+        let imported = import_powertoys(json).unwrap();
+        let unsupported = imported.unsupported.iter().map(ToString::to_string).collect();
+        let ron = imported.to_ron_string().unwrap();
+        from_ron_str(&ron).unwrap_or_else(|e| panic!("{}\n{}", e, ron));
+        let bindings = ron
+            .lines()
+            .filter_map(|line| Some(line.trim().strip_prefix('(')?.strip_suffix("),")?.to_string()))
+            .collect();
+        (bindings, unsupported)
+    }
+
+    #[test]
+    fn remaps_become_bindings() {
+        let (bindings, unsupported) = import(
+            r#"{
+  "remapKeys": { "inProcess": [
+    { "originalKeys": "20", "newRemapKeys": "27" },
+    { "originalKeys": "45", "newRemapKeys": "256" },
+    { "originalKeys": "145", "newRemapKeys": "17;67" }
+  ] },
+  "remapKeysToText": { "inProcess": [ { "originalKeys": "123", "unicodeText": "¯\\_(ツ)_/¯" } ] },
+  "remapShortcuts": {
+    "global": [
+      { "originalKeys": "17;67", "exactMatch": false, "newRemapKeys": "17;86" },
+      { "originalKeys": "260;18;78", "operationType": 1, "runProgramFilePath": "C:\\Windows\\notepad.exe", "runProgramArgs": "\"C:\\my notes.txt\"" },
+      { "originalKeys": "17;75", "secondKeyOfChord": 68, "operationType": 2, "openUri": "https://example.com" }
+    ],
+    "appSpecific": [
+      { "originalKeys": "17;87", "exactMatch": true, "newRemapKeys": "17;115", "targetApp": "msedge" }
+    ]
+  },
+  "remapShortcutsToText": { "global": [ { "originalKeys": "17;18;77", "unicodeText": "me@example.com" } ], "appSpecific": [] }
+}"#,
+        );
+        assert!(unsupported.is_empty(), "{:?}", unsupported);
+        assert_eq!(
+            bindings,
+            [
+                r#"keys: "Ctrl+W", action: Sequence([Combo("Ctrl+F4")]), when: AppEquals("msedge.exe"), match: Exact"#,
+                r#"keys: "Ctrl+C", action: Sequence([Combo("Ctrl+V")])"#,
+                r#"keys: "Win+Alt+N", action: Launch(exe: "C:\\Windows\\notepad.exe", args: ["C:\\my notes.txt"])"#,
+                r#"sequence: (steps: ["Ctrl+K", "D"]), action: OpenUrl("https://example.com")"#,
+                r#"keys: "Ctrl+Alt+M", action: Sequence([Text("me@example.com")])"#,
+                r#"keys: "CapsLock", action: PressKey("Esc")"#,
+                r#"keys: "Insert", action: DoNothing"#,
+                r#"keys: "ScrollLock", action: Sequence([Combo("Ctrl+C")])"#,
+                r#"keys: "F12", action: Sequence([Text("¯\\_(ツ)_/¯")])"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn what_cannot_be_represented_is_reported_by_line() {
+        let (bindings, unsupported) = import(
+            r#"{
+  "remapKeys": { "inProcess": [
+    { "originalKeys": "20", "newRemapKeys": "162" },
+    { "originalKeys": "255", "newRemapKeys": "27" }
+  ] },
+  "remapShortcuts": { "global": [
+    { "originalKeys": "17;67", "secondKeyOfChord": 68, "exactMatch": true, "newRemapKeys": "17;86" },
+    { "originalKeys": "17;84", "operationType": 1, "runProgramFilePath": "wt.exe", "runProgramElevationLevel": 1 },
+    { "originalKeys": "17;85", "operationType": 1, "runProgramFilePath": "wt.exe", "runProgramStartInDir": "C:\\src" },
+    { "originalKeys": "17;66", "newRemapKeys": "66" }
+  ] }
+}"#,
+        );
+        assert_eq!(
+            unsupported,
+            [
+                "line 3: `CapsLock` skipped: remapping to LCtrl alone is not supported: a binding taps its key, it can't hold it down",
+                "line 4: `255` skipped: key code 0xFF has no name in a config",
+                "line 7: `Ctrl+C` skipped: `exactMatch` on a two-key shortcut is not supported",
+                "line 8: `Ctrl+T` skipped: starting \"wt.exe\" elevated or as another user is not supported",
+                "line 9: `Ctrl+U` skipped: starting \"wt.exe\" in \"C:\\\\src\" is not supported",
+            ]
+        );
+        assert_eq!(bindings, [r#"keys: "Ctrl+B", action: PressKey("B")"#]);
+    }
+
+    #[test]
+    fn other_json_is_an_error() {
+        assert!(import_powertoys("[1, 2]").is_err());
+        let imported = import_powertoys(r#"{ "remapKeys": { "inProcess": [] } }"#).unwrap();
+        assert_eq!(imported.binding_count(), 0);
+    }
+}
@@ -0,0 +1,157 @@
+//! Warnings for a config that loads fine but probably doesn't do what its
+//! author meant. `config::load` prints them (at startup and on every
+//! hot-reload) and `swch check` reports them; the bindings load as-is.
+//!
+//! Every check works on converted bindings, in load order, before
+//! auto-blockers are added. Each warning names the binding it is about by
+//! index, or none for the config as a whole; the loader turns that into a
+//! file and line.
+
+use crate::data::binding::{Binding, BindingKind};
+use crate::data::key_combination::KeyCombination;
+use crate::data::trigger::Trigger;
+use crate::data::vk::*;
+use crate::data::vk_name::vk_name;
+
+use super::conflicts::conflicts;
+use super::spans::{Problem, Seg};
+
+/// Runs every check over `bindings`. `origin` renders the position of
+/// binding `i`, for warnings that point at a second binding.
+pub(super) fn lint(
+    bindings: &[Binding],
+    origin: impl Fn(usize) -> String,
+) -> Vec<(Option<usize>, Problem)> {
+    if bindings.is_empty() {
+        return vec![(None, Problem::at(Seg::Field("bindings"), "config contains no bindings"))];
+    }
+    let mut warnings = Vec::new();
+    for (i, binding) in bindings.iter().enumerate() {
+        if let Some(problem) = swallows_typing(binding) {
+            warnings.push((Some(i), problem));
+        }
+        if let Some(problem) = never_fires(binding) {
+            warnings.push((Some(i), problem));
+        }
+    }
+    warnings.extend(
+        conflicts(bindings, &origin)
+            .into_iter()
+            .chain(auto_blocker_duplicates(bindings, &origin))
+            .map(|(i, problem)| (Some(i), problem)),
+    );
+    warnings.sort_by_key(|(i, _)| *i);
+    warnings
+}
+
+/// A sequence that starts with, or a chord made only of, keys used for
+/// typing. Every press of those keys is held back while the gesture might
+/// still complete — and lost if it doesn't, unless `on_fail` replays it.
+fn swallows_typing(binding: &Binding) -> Option<Problem> {
+    match &binding.kind {
+        BindingKind::Sequence { steps, .. } => {
+            let first = steps.first()?;
+            if !first.keys.iter().all(|&vk| is_typing_key(vk)) {
+                return None;
+            }
+            Some(Problem::at(
+                Seg::Field("sequence"),
+                format!(
+                    "sequence starts with {:?}, a typing key: every {:?} you type is held \
+                     back until the sequence completes or times out{}",
+                    combo_name(first),
+                    combo_name(first),
+                    lost_unless_replayed(binding)
+                ),
+            ))
+        }
+        BindingKind::Chord { keys, .. } if keys.iter().all(|&vk| is_typing_key(vk)) => {
+            Some(Problem::at(
+                Seg::Field("chord"),
+                format!(
+                    "chord uses only typing keys ({}): each of them is held back for the \
+                     chord window while typing, and typing them together fires the chord{}",
+                    keys.iter().map(|&vk| vk_name(vk)).collect::<Vec<_>>().join(", "),
+                    lost_unless_replayed(binding)
+                ),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn lost_unless_replayed(binding: &Binding) -> &'static str {
+    if binding.on_fail.is_drop() {
+        "; with the default `on_fail: Drop` those presses are lost when it doesn't \
+         complete (consider `on_fail: Replay`)"
+    } else {
+        ""
+    }
+}
+
+/// A `Tap` / `DoubleTap` whose term is zero: the gesture expires the
+/// moment it is armed. (Deferred triggers on a `ModTap` key are found by
+/// the conflict analyser.)
+fn never_fires(binding: &Binding) -> Option<Problem> {
+    match binding.trigger {
+        Trigger::Tap(0) | Trigger::DoubleTap(0) => Some(Problem::at(
+            Seg::Field("trigger"),
+            format!(
+                "`{}` can never fire: a term of 0 ms expires the moment the key goes down",
+                binding.trigger
+            ),
+        )),
+        _ => None,
+    }
+}
+
+/// A binding on the combo that another binding's `block_original_combo`
+/// adds an auto-blocker for. Only one of the two ever fires.
+fn auto_blocker_duplicates(
+    bindings: &[Binding],
+    origin: &dyn Fn(usize) -> String,
+) -> Vec<(usize, Problem)> {
+    let mut warnings = Vec::new();
+    for (owner, binding) in bindings.iter().enumerate() {
+        if !binding.block_original_combo {
+            continue;
+        }
+        let Some(system_combo) = binding.action.get_system_combination() else {
+            continue;
+        };
+        for (i, other) in bindings.iter().enumerate() {
+            if i != owner
+                && other.layer == binding.layer
+                && other.combination() == Some(&system_combo)
+            {
+                warnings.push((i, Problem::at(
+                    Seg::Field("keys"),
+                    format!(
+                        "{:?} is also claimed by the auto-blocker that `block_original_combo` \
+                         on {} adds; only whichever comes first in load order fires",
+                        combo_name(&system_combo),
+                        origin(owner)
+                    ),
+                )));
+            }
+        }
+    }
+    warnings
+}
+
+/// Letters, digits, space and the punctuation keys: keys that produce text
+/// when pressed on their own.
+fn is_typing_key(vk: Vk) -> bool {
+    (VK_A.0..=VK_Z.0).contains(&vk.0)
+        || (VK_0.0..=VK_9.0).contains(&vk.0)
+        || matches!(
+            vk,
+            VK_SPACE
+                | VK_OEM_1 | VK_OEM_2 | VK_OEM_3 | VK_OEM_4 | VK_OEM_5 | VK_OEM_6 | VK_OEM_7
+                | VK_OEM_102 | VK_OEM_PLUS | VK_OEM_COMMA | VK_OEM_MINUS | VK_OEM_PERIOD
+        )
+}
+
+fn combo_name(combo: &KeyCombination) -> String {
+    combo.keys.iter().map(|&vk| vk_name(vk)).collect::<Vec<_>>().join("+")
+}
@@ -2,6 +2,8 @@
 //! ready for `App`. Default-config generation lives here so first run is
 //! zero-touch.
 
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt;
 use std::fs;
 use std::io;
@@ -10,21 +12,32 @@ use std::sync::Arc;
 
 use std::time::Duration;
 
-use crate::core::windows_actions::{BindAction, MediaKey};
+use crate::data::action::{BindAction, LayerOp, MediaKey};
 use crate::data::binding::{Binding, BindingKind};
 use crate::data::condition::Condition;
+use crate::data::key_combination::MatchMode;
+use crate::data::on_fail::OnFail;
 use crate::data::sequence::{SequenceStep, WindowOp};
-use crate::data::trigger::Trigger;
-use crate::data::vk_name::parse_vk;
-
-use super::parsing::{parse_combo, parse_wm_name};
+use crate::data::trigger::{ModTapFlavor, TapDanceStep, Trigger};
+use crate::data::vk::*;
+use crate::data::vk_name::{parse_vk, ALT_GR, ANY_WIN};
+
+use super::format::{Format, ParseError};
+use super::parsing::{did_you_mean, parse_combo, parse_wm_name};
+use super::lint;
+use super::migrate::{self, Migration};
+use super::settings::Settings;
+use super::spans::{self, Node, Problem, Seg};
+use super::vars::Vars;
+use super::writer;
 use super::schema::{
     ChordSpec, MediaKeyRef, MessageRef, RawAction, RawBinding, RawCondition, RawConfig,
-    RawStep, RawTrigger, RawWindowKind, SequenceSpec,
+    RawMatchMode, RawModTapFlavor, RawOnFail, RawStep, RawTrigger, RawWindowKind, SequenceSpec,
 };
 
 /// Bundled default `config.ron`. Written to disk the first time the program
-/// runs without an existing config.
+/// runs without an existing config — converted first when the config is
+/// TOML or JSON.
 const DEFAULT_CONFIG: &str = include_str!("default_config.ron");
 
 #[derive(Debug)]
@@ -35,10 +48,10 @@ pub enum ConfigError {
     },
     Parse {
         path: PathBuf,
-        // Boxed because RON's `SpannedError` carries line/column data that
-        // adds up; keep `Result<_, ConfigError>` small (clippy
+        // Boxed: the RON / TOML / JSON error, whichever format the file is
+        // in. Also keeps `Result<_, ConfigError>` small (clippy
         // `result_large_err`).
-        source: Box<ron::error::SpannedError>,
+        source: ParseError,
     },
     /// One or more individual binding entries failed validation. We collect
     /// every failure rather than bail on the first so the user sees the full
@@ -79,26 +92,56 @@ impl std::error::Error for ConfigError {
     }
 }
 
-/// Path next to the running executable (`<exe_dir>/config.ron`).
-pub fn default_config_path() -> Result<PathBuf, ConfigError> {
-    let exe = std::env::current_exe().map_err(|e| ConfigError::Io {
-        path: PathBuf::from("<current_exe>"),
-        source: e,
-    })?;
-    let dir = exe
-        .parent()
-        .ok_or_else(|| ConfigError::Other(format!(
-            "executable path {:?} has no parent directory",
-            exe
-        )))?;
-    Ok(dir.join("config.ron"))
+/// A config file and everything it includes, converted and ready for `App`.
+#[derive(Debug)]
+pub struct LoadedConfig {
+    /// Merged bindings, already expanded with auto-blockers.
+    pub bindings: Vec<Binding>,
+    /// The merged `settings:` of every file.
+    pub settings: Settings,
+    /// Lint warnings: a config that loads but likely misbehaves. Formatted
+    /// like binding errors, `file:line:column: message`.
+    pub warnings: Vec<String>,
+    pub sources: ConfigSources,
+}
+
+/// Where a loaded config came from. The config watcher reloads when any of
+/// these changes, including files that start matching an include glob.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSources {
+    /// The root file first, then every included file in load order.
+    pub files: Vec<PathBuf>,
+    pub globs: Vec<IncludeGlob>,
+}
+
+/// An `include:` entry with wildcards, split at its first wildcard
+/// component: `dir` is the literal directory in front, `pattern` the rest.
+#[derive(Debug, Clone)]
+pub struct IncludeGlob {
+    pub dir: PathBuf,
+    pub pattern: glob::Pattern,
+}
+
+impl IncludeGlob {
+    /// True if `path` is matched by this include, whether or not it existed
+    /// at load time.
+    pub fn matches(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.dir)
+            .is_ok_and(|rest| self.pattern.matches_path(rest))
+    }
+
+    /// True if the pattern reaches into subdirectories of `dir`, so a
+    /// watcher has to watch `dir` recursively.
+    pub fn is_nested(&self) -> bool {
+        self.pattern.as_str().contains(['/', '\\'])
+    }
 }
 
 /// Loads bindings from `path`, generating the default file there if it does
 /// not yet exist. The "missing → write default" branch is by design: the
 /// first run of the binary should produce a working setup without forcing
-/// the user to author a config by hand.
-pub fn load(path: &Path) -> Result<Vec<Binding>, ConfigError> {
+/// the user to author a config by hand. Lint warnings go to stderr.
+pub fn load(path: &Path) -> Result<LoadedConfig, ConfigError> {
     if !path.exists() {
         write_default(path)?;
         println!("[INFO] generated default config at {}", path.display());
@@ -106,19 +149,39 @@ pub fn load(path: &Path) -> Result<Vec<Binding>, ConfigError> {
         println!("[INFO] loading config from {}", path.display());
     }
 
-    let text = fs::read_to_string(path).map_err(|e| ConfigError::Io {
-        path: path.to_path_buf(),
-        source: e,
-    })?;
+    let loaded = load_tree(path, true, migrate::MIGRATIONS)?;
+    for warning in &loaded.warnings {
+        eprintln!("[WARN] {}", warning);
+    }
+    Ok(loaded)
+}
 
-    from_ron_str(&text).map_err(|e| match e {
-        // Attach the path for I/O-less variants so error messages are useful.
-        ConfigError::Parse { source, .. } => ConfigError::Parse {
-            path: path.to_path_buf(),
-            source,
-        },
-        other => other,
-    })
+/// Loads bindings from an existing `path` without the first-run default
+/// generation or any console output. Used by offline tools (`swch replay`)
+/// that must not create files as a side effect: a file in an older schema
+/// version is migrated in memory only, with a warning saying so.
+///
+/// Files named under `include:` are read depth first and merged in front of
+/// the including file's own bindings. Binding errors start with the file
+/// they come from, relative to `path`'s directory, and the line and column
+/// of the offending value.
+pub fn load_existing(path: &Path) -> Result<LoadedConfig, ConfigError> {
+    load_tree(path, false, migrate::MIGRATIONS)
+}
+
+/// Reads `path` and its includes, upgrading each file through
+/// `migrations`. With `rewrite`, an upgraded file is saved in place after
+/// backing the original up as `<name>.bak`.
+fn load_tree(
+    path: &Path,
+    rewrite: bool,
+    migrations: &'static [Migration],
+) -> Result<LoadedConfig, ConfigError> {
+    let mut tree = IncludeTree::new(path, rewrite, migrations);
+    tree.read(path)?;
+    let (bindings, settings, mut warnings) = convert(&tree.configs)?;
+    warnings.splice(0..0, tree.notes);
+    Ok(LoadedConfig { bindings, settings, warnings, sources: tree.sources })
 }
 
 /// Pure (no-I/O) conversion from RON text to `Vec<Binding>`. Kept available
@@ -127,26 +190,227 @@ pub fn load(path: &Path) -> Result<Vec<Binding>, ConfigError> {
 /// The returned list is already expanded with auto-blocker bindings for any
 /// entries that set `block_original_combo: true`. Callers can hand the list
 /// straight to `KeyboardHook::update_bindings`.
+#[cfg(test)]
 pub(crate) fn from_ron_str(text: &str) -> Result<Vec<Binding>, ConfigError> {
-    // `IMPLICIT_SOME` lets users write `keys: "CapsLock"` instead of
-    // `keys: Some("CapsLock")` for the `Option<String>` fields on
-    // `RawBinding`. Standard ergonomic move when an Option is exposed in a
-    // human-edited config — no one expects to type `Some(...)` everywhere.
-    let options = ron::Options::default()
-        .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME);
-
-    let raw: RawConfig = options.from_str(text).map_err(|e| ConfigError::Parse {
-        path: PathBuf::from("<in-memory>"),
-        source: Box::new(e),
-    })?;
+    let file = ParsedFile::parse(text, Path::new("<in-memory>"), None)?;
+    if !file.raw.include.is_empty() {
+        return Err(ConfigError::Other(
+            "`include:` needs a config file on disk to resolve paths against".into(),
+        ));
+    }
+    convert(&[file]).map(|(bindings, ..)| bindings)
+}
 
-    let mut user_bindings = Vec::with_capacity(raw.bindings.len());
+/// [`from_ron_str`] for a config in any format.
+#[cfg(test)]
+pub(crate) fn from_str_as(text: &str, format: Format) -> Result<Vec<Binding>, ConfigError> {
+    let path = PathBuf::from(format!("<in-memory>.{}", format.extension()));
+    convert(&[ParsedFile::parse(text, &path, None)?]).map(|(bindings, ..)| bindings)
+}
+
+/// One parsed config file, as [`convert`] takes it.
+struct ParsedFile {
+    /// Name errors are reported under; `None` for a lone in-memory config.
+    name: Option<String>,
+    raw: RawConfig,
+    /// Where each value starts in the text. `None` if the scanner gave up,
+    /// in which case errors say which binding they are about instead.
+    spans: Option<Node>,
+}
+
+impl ParsedFile {
+    fn parse(text: &str, path: &Path, name: Option<String>) -> Result<Self, ConfigError> {
+        // The scanner reads RON, and JSON is close enough (objects scan as
+        // maps). TOML's tables and dotted keys are not, so no positions.
+        let spans = match Format::from_path(path) {
+            Format::Ron | Format::Json => spans::scan(text),
+            Format::Toml => None,
+        };
+        Ok(Self { name, raw: parse_raw(text, path)?, spans })
+    }
+
+    /// The entry at `base` in this file. `context` names it in messages
+    /// when the scanner gave up and there are no positions.
+    fn entry(&self, base: Vec<Seg>, context: Option<String>) -> Origin<'_> {
+        Origin { file: self, base, context, label: None }
+    }
+}
+
+/// Where a binding or named action was written.
+struct Origin<'a> {
+    file: &'a ParsedFile,
+    base: Vec<Seg>,
+    context: Option<String>,
+    /// A binding's `name:` or `id:`, put in front of messages that give a
+    /// position (`context` names it already when there is none).
+    label: Option<String>,
+}
+
+impl Origin<'_> {
+    /// `file:line:column` of the value at `path` inside this entry, or
+    /// `file: context` without positions.
+    fn position(&self, path: &[Seg]) -> Option<String> {
+        let name = self.file.name.as_deref();
+        match (&self.file.spans, &self.context) {
+            (Some(root), _) => {
+                let full: Vec<Seg> = self.base.iter().chain(path).cloned().collect();
+                let node = root.locate(&full);
+                Some(match name {
+                    Some(name) => format!("{}:{}:{}", name, node.line, node.column),
+                    None => format!("{}:{}", node.line, node.column),
+                })
+            }
+            (None, Some(context)) => Some(match name {
+                Some(name) => format!("{}: {}", name, context),
+                None => context.clone(),
+            }),
+            (None, None) => name.map(str::to_string),
+        }
+    }
+
+    fn with_label(mut self, label: Option<String>) -> Self {
+        self.label = label;
+        self
+    }
+
+    /// Formats `problem` as `file:line:column: message`.
+    fn report(&self, problem: Problem) -> String {
+        let problem = match (&self.file.spans, &self.label) {
+            (Some(_), Some(label)) => problem.labelled(label),
+            _ => problem,
+        };
+        match self.position(&problem.path) {
+            Some(position) => format!("{}: {}", position, problem.message),
+            None => problem.message,
+        }
+    }
+}
+
+/// Deserializes `text` in the format `path`'s extension names.
+fn parse_raw(text: &str, path: &Path) -> Result<RawConfig, ConfigError> {
+    Format::from_path(path).parse(text).map_err(|e| ConfigError::Parse {
+        path: path.to_path_buf(),
+        source: e,
+    })
+}
+
+/// Converts parsed config files into one binding list, plus the merged
+/// `settings:` and the [`lint`](super::lint) warnings for it. Layers with
+/// the same name in several files are merged, and a `Layer*` action, `Ref`
+/// or `${name}` may name a layer, action or variable defined in any of the
+/// files.
+fn convert(files: &[ParsedFile]) -> Result<(Vec<Binding>, Settings, Vec<String>), ConfigError> {
+    let mut settings = Settings::default();
+    for file in files {
+        settings.apply(&file.raw.settings);
+    }
+
+    let layer_names: BTreeSet<&str> = files
+        .iter()
+        .flat_map(|file| file.raw.layers.keys().map(String::as_str))
+        .collect();
+
+    let mut user_bindings = Vec::new();
+    let mut origins = Vec::new();
     let mut errors = Vec::new();
 
-    for (index, rb) in raw.bindings.iter().enumerate() {
-        match raw_binding_to_binding(rb, index) {
-            Ok(b) => user_bindings.push(b),
-            Err(e) => errors.push(e),
+    let mut actions = ActionTable::default();
+    let mut vars = Vec::new();
+    for file in files {
+        for (name, value) in &file.raw.vars {
+            match actions.vars.insert(name, value, file.name.as_deref()) {
+                Ok(()) => vars.push((name, file)),
+                Err(first) => {
+                    let origin = file.entry(vec![Seg::Field("vars"), Seg::Key(name.clone())], None);
+                    errors.push(origin.report(Problem::from(format!(
+                        "variable {:?} is already defined{}",
+                        name,
+                        first.map(|f| format!(" in {}", f)).unwrap_or_default()
+                    ))));
+                }
+            }
+        }
+    }
+    for (name, file) in vars {
+        if let Err(e) = actions.vars.check(name) {
+            let origin = file.entry(
+                vec![Seg::Field("vars"), Seg::Key(name.clone())],
+                Some(format!("variable {:?}", name)),
+            );
+            errors.push(origin.report(Problem::from(e)));
+        }
+    }
+    for file in files {
+        for (name, action) in &file.raw.actions {
+            if let Err(first) = actions.insert(name, action, file) {
+                let origin = file.entry(vec![Seg::Field("actions"), Seg::Key(name.clone())], None);
+                errors.push(origin.report(Problem::from(format!(
+                    "action {:?} is already defined{}",
+                    name,
+                    first.map(|f| format!(" in {}", f)).unwrap_or_default()
+                ))));
+            }
+        }
+    }
+    for (name, file) in actions.names() {
+        if let Err(problem) = actions.resolve(name) {
+            let origin = file.entry(
+                vec![Seg::Field("actions"), Seg::Key(name.to_string())],
+                Some(format!("action {:?}", name)),
+            );
+            errors.push(origin.report(problem));
+        }
+    }
+
+    let binding_context = |index: usize, rb: &RawBinding| match raw_binding_label(rb) {
+        Some(label) => format!("binding #{} {} [{}]", index, label, format_raw_binding_keys(rb)),
+        None => format!("binding #{} [{}]", index, format_raw_binding_keys(rb)),
+    };
+    let mut entries = Vec::new();
+    for file in files {
+        for (index, rb) in file.raw.bindings.iter().enumerate() {
+            let origin = file.entry(
+                vec![Seg::Field("bindings"), Seg::Item(index)],
+                Some(binding_context(index, rb)),
+            );
+            entries.push((rb, origin, None));
+        }
+    }
+    for file in files {
+        for (name, layer_bindings) in &file.raw.layers {
+            let layer: Arc<str> = Arc::from(name.as_str());
+            for (index, rb) in layer_bindings.iter().enumerate() {
+                let origin = file.entry(
+                    vec![Seg::Field("layers"), Seg::Key(name.clone()), Seg::Item(index)],
+                    Some(format!("layer {:?}: {}", name, binding_context(index, rb))),
+                );
+                entries.push((rb, origin, Some(Arc::clone(&layer))));
+            }
+        }
+    }
+    // Where each `id:` was first used, to name it in the duplicate error.
+    let mut ids: BTreeMap<&str, String> = BTreeMap::new();
+    for (rb, origin, layer) in entries {
+        let origin = origin.with_label(raw_binding_label(rb));
+        if let Some(id) = &rb.id
+            && let Some(first) = ids.get(id.as_str())
+        {
+            errors.push(origin.report(Problem::at(
+                Seg::Field("id"),
+                format!("id {:?} is already used (at {})", id, first),
+            )));
+            continue;
+        }
+        match raw_binding_to_binding(rb, &layer_names, &actions, &settings) {
+            Ok(b) => {
+                if let Some(id) = &rb.id {
+                    let at = origin.position(&[]).unwrap_or_else(|| "another binding".into());
+                    ids.insert(id, at);
+                }
+                user_bindings.push(b.with_layer(layer));
+                origins.push(origin);
+            }
+            Err(problem) => errors.push(origin.report(problem)),
         }
     }
 
@@ -154,11 +418,280 @@ pub(crate) fn from_ron_str(text: &str) -> Result<Vec<Binding>, ConfigError> {
         return Err(ConfigError::Bindings(errors));
     }
 
-    if user_bindings.is_empty() {
-        eprintln!("[WARN] config contains no bindings");
+    // The root file is merged last; a warning about the config as a whole
+    // points at it.
+    let root = files.last().map(|file| file.entry(Vec::new(), None));
+    let warnings = lint::lint(&user_bindings, |i| {
+        origins[i].position(&[]).unwrap_or_else(|| format!("binding #{}", i))
+    })
+    .into_iter()
+    .filter_map(|(i, problem)| match i {
+        Some(i) => Some(origins[i].report(problem)),
+        None => root.as_ref().map(|root| root.report(problem)),
+    })
+    .collect();
+
+    Ok((expand_with_auto_blockers(user_bindings), settings, warnings))
+}
+
+/// Named actions from the `actions:` maps, converted on first use and
+/// cached, so every `Ref` to a name gets a clone of the same value — and a
+/// `Sequence` the same `Arc`, which `BindAction::PartialEq` compares by
+/// pointer. Also holds the `vars:` that action strings substitute.
+#[derive(Default)]
+struct ActionTable<'a> {
+    vars: Vars<'a>,
+    /// Raw definitions, each with the file it was defined in.
+    raw: BTreeMap<&'a str, (&'a RawAction, &'a ParsedFile)>,
+    resolved: RefCell<HashMap<&'a str, Result<BindAction, Problem>>>,
+    /// Names being converted right now, outermost first. A `Ref` to one of
+    /// them is a recursive reference.
+    resolving: RefCell<Vec<&'a str>>,
+}
+
+impl<'a> ActionTable<'a> {
+    /// Adds a definition. A duplicate name keeps the first definition and
+    /// fails with the file that one came from.
+    fn insert(
+        &mut self,
+        name: &'a str,
+        action: &'a RawAction,
+        file: &'a ParsedFile,
+    ) -> Result<(), Option<&'a str>> {
+        if let Some((_, first)) = self.raw.get(name) {
+            return Err(first.name.as_deref());
+        }
+        self.raw.insert(name, (action, file));
+        Ok(())
+    }
+
+    /// Every defined name with the file it came from, in name order.
+    fn names(&self) -> Vec<(&'a str, &'a ParsedFile)> {
+        self.raw.iter().map(|(&name, &(_, file))| (name, file)).collect()
+    }
+
+    /// Converts the action called `name`, or returns the cached result.
+    /// Errors are the definition's own; callers check `name` exists.
+    fn resolve(&self, name: &'a str) -> Result<BindAction, Problem> {
+        if let Some(done) = self.resolved.borrow().get(name) {
+            return done.clone();
+        }
+        let (raw, _) = self.raw[name];
+        self.resolving.borrow_mut().push(name);
+        let result = raw_action_to_bind_action(raw, self);
+        self.resolving.borrow_mut().pop();
+        self.resolved.borrow_mut().insert(name, result.clone());
+        result
+    }
+
+    /// `Ref(name)` from a binding, trigger, sequence or another named
+    /// action. A definition that fails to convert has its error reported
+    /// once, under its own name; a `Ref` to it only points there.
+    fn reference(&self, name: &str) -> Result<BindAction, String> {
+        let Some((&name, _)) = self.raw.get_key_value(name) else {
+            return Err(format!(
+                "Ref({:?}): unknown action (not defined under `actions:`)",
+                name
+            ));
+        };
+        if let Some(pos) = self.resolving.borrow().iter().position(|&n| n == name) {
+            let mut chain = self.resolving.borrow()[pos..].to_vec();
+            chain.push(name);
+            return Err(format!(
+                "Ref({:?}): recursive action reference {}",
+                name,
+                chain.join(" -> ")
+            ));
+        }
+        self.resolve(name)
+            .map_err(|_| format!("Ref({:?}): action {:?} is invalid", name, name))
+    }
+
+    /// `text` with its `${...}` variables substituted; `what` names the
+    /// string in messages.
+    fn expand(&self, what: &str, text: &str) -> Result<String, String> {
+        self.vars.expand(text).map_err(|e| format!("{}: {}", what, e))
     }
 
-    Ok(expand_with_auto_blockers(user_bindings))
+    /// [`ActionTable::expand`] for `Launch`'s program and arguments.
+    fn expand_launch(&self, exe: &str, args: &[String]) -> Result<(String, Vec<String>), String> {
+        let exe = self.expand("Launch: exe", exe)?;
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| self.expand(&format!("Launch: args[{}]", i), arg))
+            .collect::<Result<_, _>>()?;
+        Ok((exe, args))
+    }
+}
+
+/// Depth-first walk over a config file and the files it includes.
+struct IncludeTree {
+    /// Directory that file names in error messages are relative to.
+    root_dir: PathBuf,
+    /// Files currently being read, outermost first, as (identity, name).
+    /// An include that resolves to one of these is a cycle.
+    stack: Vec<(PathBuf, String)>,
+    /// Identities of every file read so far. A file included twice (but
+    /// not cyclically) is merged only once.
+    seen: HashSet<PathBuf>,
+    /// Parsed files in merge order: includes before their includer.
+    configs: Vec<ParsedFile>,
+    sources: ConfigSources,
+    /// Save migrated files back to disk.
+    rewrite: bool,
+    migrations: &'static [Migration],
+    /// Warnings about files migrated in memory only.
+    notes: Vec<String>,
+}
+
+impl IncludeTree {
+    fn new(root: &Path, rewrite: bool, migrations: &'static [Migration]) -> Self {
+        Self {
+            root_dir: root.parent().map(Path::to_path_buf).unwrap_or_default(),
+            stack: Vec::new(),
+            seen: HashSet::new(),
+            configs: Vec::new(),
+            sources: ConfigSources::default(),
+            rewrite,
+            migrations,
+            notes: Vec::new(),
+        }
+    }
+
+    fn read(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let mut text = fs::read_to_string(path).map_err(|e| ConfigError::Io {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        let name = self.display_name(path);
+        let format = Format::from_path(path);
+        if let Some((from, migrated)) = migrate::migrate(&text, format, self.migrations)
+            .map_err(|e| ConfigError::Other(format!("{}: {}", name, e)))?
+        {
+            let to = self.migrations.len() + 1;
+            if self.rewrite {
+                let backup = save_migrated(path, &text, &migrated)?;
+                println!(
+                    "[INFO] migrated {} from version {} to {}; the original is saved as {}",
+                    name,
+                    from,
+                    to,
+                    backup.display()
+                );
+            } else {
+                self.notes.push(format!(
+                    "{}: `version: {}` was migrated to version {} in memory only; \
+                     the daemon rewrites the file when it next loads it",
+                    name, from, to
+                ));
+            }
+            text = migrated;
+        }
+        let file = ParsedFile::parse(&text, path, Some(name.clone()))?;
+        let identity = file_identity(path);
+
+        self.sources.files.push(path.to_path_buf());
+        self.seen.insert(identity.clone());
+        self.stack.push((identity, name.clone()));
+
+        for entry in &file.raw.include {
+            for included in self.resolve(path, &name, entry)? {
+                let identity = file_identity(&included);
+                if let Some(pos) = self.stack.iter().position(|(id, _)| *id == identity) {
+                    let mut chain: Vec<&str> =
+                        self.stack[pos..].iter().map(|(_, n)| n.as_str()).collect();
+                    chain.push(&self.stack[pos].1);
+                    return Err(ConfigError::Other(format!(
+                        "include cycle: {}",
+                        chain.join(" -> ")
+                    )));
+                }
+                if !self.seen.contains(&identity) {
+                    self.read(&included)?;
+                }
+            }
+        }
+
+        self.stack.pop();
+        self.configs.push(file);
+        Ok(())
+    }
+
+    /// Expands one `include:` entry of the file at `from`. A plain path is
+    /// returned as-is (a missing file fails when it is read); a glob returns
+    /// the files it matches now, sorted, and is remembered for the watcher.
+    fn resolve(
+        &mut self,
+        from: &Path,
+        from_name: &str,
+        entry: &str,
+    ) -> Result<Vec<PathBuf>, ConfigError> {
+        let full = from.parent().unwrap_or(Path::new("")).join(entry);
+        if !entry.contains(['*', '?', '[']) {
+            return Ok(vec![full]);
+        }
+
+        let error = |e: &dyn fmt::Display| {
+            ConfigError::Other(format!("{}: include {:?}: {}", from_name, entry, e))
+        };
+        let mut dir = PathBuf::new();
+        let mut rest: Vec<String> = Vec::new();
+        for component in full.components() {
+            let part = component.as_os_str().to_string_lossy();
+            if rest.is_empty() && !part.contains(['*', '?', '[']) {
+                dir.push(component);
+            } else {
+                rest.push(part.into_owned());
+            }
+        }
+        let rest = rest.join("/");
+        let pattern = glob::Pattern::new(&rest).map_err(|e| error(&e))?;
+        let full_pattern = if dir.as_os_str().is_empty() {
+            rest
+        } else {
+            format!("{}/{}", glob::Pattern::escape(&dir.to_string_lossy()), rest)
+        };
+
+        let mut matched: Vec<PathBuf> = glob::glob(&full_pattern)
+            .map_err(|e| error(&e))?
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .collect();
+        matched.sort();
+        self.sources.globs.push(IncludeGlob { dir, pattern });
+        Ok(matched)
+    }
+
+    fn display_name(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root_dir)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+}
+
+/// Copies `original` to `<path>.bak`, then writes `migrated` to `path`.
+/// Returns the backup's path.
+fn save_migrated(path: &Path, original: &str, migrated: &str) -> Result<PathBuf, ConfigError> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    fs::write(&backup, original).map_err(|e| ConfigError::Io {
+        path: backup.clone(),
+        source: e,
+    })?;
+    fs::write(path, migrated).map_err(|e| ConfigError::Io {
+        path: path.to_path_buf(),
+        source: e,
+    })?;
+    Ok(backup)
+}
+
+/// Identity used to detect cycles and repeated includes: the canonical path
+/// when the file exists, so `./a.ron` and `sub/../a.ron` compare equal.
+fn file_identity(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
 }
 
 /// Inserts a no-op binding for the OS-default hotkey of any binding that
@@ -178,11 +711,22 @@ fn expand_with_auto_blockers(user_bindings: Vec<Binding>) -> Vec<Binding> {
         {
             // Only `Combo`-kind bindings can collide with an auto-blocker
             // (sequence/chord don't represent a single hold-this-set combo).
-            let already_present = result
-                .iter()
-                .any(|b| matches!(b.combination(), Some(c) if *c == system_combo));
+            // A layer binding's blocker lives in, and only blocks while,
+            // the same layer, and every blocker is switched on and off with
+            // its binding, by the same tags.
+            let already_present = result.iter().any(|b| {
+                b.layer == binding.layer
+                    && b.tags == binding.tags
+                    && b.enabled == binding.enabled
+                    && matches!(b.combination(), Some(c) if *c == system_combo)
+            });
             if !already_present {
-                result.push(Binding::new_auto_blocker(system_combo));
+                result.push(
+                    Binding::new_auto_blocker(system_combo)
+                        .with_layer(binding.layer.clone())
+                        .with_tags(binding.tags.clone())
+                        .with_enabled(binding.enabled),
+                );
             }
         }
         result.push(binding);
@@ -191,7 +735,17 @@ fn expand_with_auto_blockers(user_bindings: Vec<Binding>) -> Vec<Binding> {
     result
 }
 
+/// Writes the bundled default config to `path`, in the format its
+/// extension names.
 fn write_default(path: &Path) -> Result<(), ConfigError> {
+    let text = match Format::from_path(path) {
+        Format::Ron => DEFAULT_CONFIG.to_string(),
+        format => {
+            let file = ParsedFile::parse(DEFAULT_CONFIG, Path::new("default_config.ron"), None)?;
+            let (bindings, ..) = convert(&[file])?;
+            writer::to_string(&bindings, format)?
+        }
+    };
     if let Some(parent) = path.parent()
         && !parent.as_os_str().is_empty()
     {
@@ -200,114 +754,243 @@ fn write_default(path: &Path) -> Result<(), ConfigError> {
             source: e,
         })?;
     }
-    fs::write(path, DEFAULT_CONFIG).map_err(|e| ConfigError::Io {
+    fs::write(path, text).map_err(|e| ConfigError::Io {
         path: path.to_path_buf(),
         source: e,
     })
 }
 
-fn raw_binding_to_binding(raw: &RawBinding, index: usize) -> Result<Binding, String> {
-    let keys_display = format_raw_binding_keys(raw);
-    let err_prefix = format!("binding #{} [{}]", index, keys_display);
-
-    let kind = raw_binding_to_kind(raw)
-        .map_err(|e| format!("{}: {}", err_prefix, e))?;
-
-    let action = raw_action_to_bind_action(&raw.action)
-        .map_err(|e| format!("{}: {}", err_prefix, e))?;
-
-    let trigger = raw_trigger_to_trigger(raw.trigger);
+/// `layer_names` are the layers defined under `layers:`, used to check that
+/// `Layer*` actions name one of them. `settings` fills in what the binding
+/// leaves out.
+fn raw_binding_to_binding(
+    raw: &RawBinding,
+    layer_names: &BTreeSet<&str>,
+    actions: &ActionTable<'_>,
+    settings: &Settings,
+) -> Result<Binding, Problem> {
+    let kind = raw_binding_to_kind(raw, settings)?;
+
+    let trigger = raw_trigger_to_trigger(&raw.trigger, actions, settings)
+        .map_err(|p| p.under(Seg::Field("trigger")))?;
+
+    // `ModTap` / `TapDance` carry their own actions; every other binding
+    // needs `action:`. A tap dance reports its first tap as the binding's
+    // action (listings, trace attribution).
+    let action = match (&raw.trigger, &raw.action, &trigger) {
+        (RawTrigger::ModTap { .. }, Some(_), _) => {
+            return Err(Problem::at(
+                Seg::Field("action"),
+                "`ModTap` takes its action from `tap:`; drop the binding's `action:`",
+            ));
+        }
+        (RawTrigger::TapDance { .. }, Some(_), _) => {
+            return Err(Problem::at(
+                Seg::Field("action"),
+                "`TapDance` takes its actions from `taps:` / `holds:`; \
+                 drop the binding's `action:`",
+            ));
+        }
+        (RawTrigger::ModTap { tap, .. }, None, _) => raw_action_to_bind_action(tap, actions)
+            .map_err(|p| p.under(Seg::Field("tap")).under(Seg::Field("trigger")))?,
+        (_, None, Trigger::TapDance { steps, .. }) => steps
+            .iter()
+            .find_map(|s| s.tap.clone())
+            .unwrap_or(BindAction::DoNothing),
+        (_, Some(action), _) => raw_action_to_bind_action(action, actions)
+            .map_err(|p| p.under(Seg::Field("action")))?,
+        (_, None, _) => return Err(Problem::from("missing `action`".to_string())),
+    };
+
+    // The mod-tap key is resolved on its own, before any combo matching,
+    // so it must be exactly one key.
+    if matches!(trigger, Trigger::ModTap { .. })
+        && !matches!(&kind, BindingKind::Combo(c) if c.keys.len() == 1)
+    {
+        return Err(Problem::at(Seg::Field("keys"), "`ModTap` needs a single key in `keys:`"));
+    }
 
     // Sequence/Chord have their own temporal semantics; pairing them with
     // a deferred Tap/Hold/DoubleTap trigger would double-up state machines
     // and produce undefined behaviour. Reject at load.
     if !matches!(kind, BindingKind::Combo(_)) && !trigger.is_immediate() {
-        return Err(format!(
-            "{}: Sequence/Chord cannot use a deferred trigger ({:?}); \
-             only `Immediate` (the default) is valid for these kinds",
-            err_prefix, trigger
+        return Err(Problem::at(
+            Seg::Field("trigger"),
+            format!(
+                "Sequence/Chord cannot use a deferred trigger ({}); \
+                 only `Immediate` (the default) is valid for these kinds",
+                trigger
+            ),
         ));
     }
 
+    // Only an `Immediate` combo fires on key-down, where repeats arrive;
+    // `settings: (repeat: ..)` leaves every other binding alone too.
+    let repeats = matches!(kind, BindingKind::Combo(_)) && trigger.is_immediate();
+    if raw.repeat.is_some() && !repeats {
+        return Err(Problem::at(
+            Seg::Field("repeat"),
+            "`repeat` only applies to `keys:` bindings with an `Immediate` trigger",
+        ));
+    }
+
+    let on_fail = raw_on_fail_to_on_fail(&raw.on_fail, actions)
+        .map_err(|p| p.labelled("on_fail").under(Seg::Field("on_fail")))?;
+
+    // Combos have no in-flight state to fail; an `on_fail` there is a
+    // misunderstanding worth pointing out rather than silently ignoring.
+    if matches!(kind, BindingKind::Combo(_)) && !on_fail.is_drop() {
+        return Err(Problem::at(
+            Seg::Field("on_fail"),
+            "`on_fail` only applies to `sequence` / `chord` bindings",
+        ));
+    }
+
+    if raw.id.as_deref().is_some_and(|id| id.trim().is_empty()) {
+        return Err(Problem::at(Seg::Field("id"), "`id` is empty"));
+    }
+    let mut tags: Vec<Arc<str>> = Vec::with_capacity(raw.tags.len());
+    for (index, tag) in raw.tags.iter().enumerate() {
+        if tag.trim().is_empty() || tag.trim() != tag {
+            return Err(Problem::at(Seg::Item(index), format!("tag {:?} is empty or padded", tag))
+                .under(Seg::Field("tags")));
+        }
+        if !tags.iter().any(|t| **t == **tag) {
+            tags.push(Arc::from(tag.as_str()));
+        }
+    }
+
     let binding = Binding::with_kind(kind, action)
         .with_block_default(raw.block_default)
         .with_block_original_combo(raw.block_original_combo)
         .with_condition(raw_condition_to_condition(&raw.when))
-        .with_trigger(trigger);
+        .with_trigger(trigger)
+        .with_repeat(!repeats || raw.repeat.unwrap_or(settings.repeat))
+        .with_on_fail(on_fail)
+        .with_id(raw.id.clone())
+        .with_name(raw.name.clone())
+        .with_description(raw.description.clone())
+        .with_tags(tags)
+        .with_enabled(raw.enabled);
+
+    for action in binding.actions() {
+        if let BindAction::Layer { layer, .. } = action
+            && !layer_names.contains(&**layer)
+        {
+            let message = format!("unknown layer {:?} (not defined under `layers:`)", layer);
+            return Err(match raw.action {
+                Some(_) => Problem::at(Seg::Field("action"), message),
+                None => Problem::at(Seg::Field("trigger"), message),
+            });
+        }
+    }
 
     Ok(binding)
 }
 
-fn raw_binding_to_kind(raw: &RawBinding) -> Result<BindingKind, String> {
+fn raw_on_fail_to_on_fail(raw: &RawOnFail, actions: &ActionTable<'_>) -> Result<OnFail, Problem> {
+    Ok(match raw {
+        RawOnFail::Drop => OnFail::Drop,
+        RawOnFail::Replay => OnFail::Replay,
+        RawOnFail::Action(action) => OnFail::Action(
+            raw_action_to_bind_action(action, actions).map_err(|p| p.under(Seg::Item(0)))?,
+        ),
+    })
+}
+
+fn raw_binding_to_kind(raw: &RawBinding, settings: &Settings) -> Result<BindingKind, Problem> {
     // Exactly one of {keys, sequence, chord} must be set.
     let kind_count = (raw.keys.is_some() as u8)
         + (raw.sequence.is_some() as u8)
         + (raw.chord.is_some() as u8);
     if kind_count == 0 {
-        return Err(
+        return Err(Problem::from(
             "binding has none of `keys` / `sequence` / `chord` set — must specify exactly one"
-                .into(),
-        );
+                .to_string(),
+        ));
     }
     if kind_count > 1 {
-        return Err(
+        return Err(Problem::from(
             "binding has multiple of `keys` / `sequence` / `chord` set — must be exactly one"
-                .into(),
-        );
+                .to_string(),
+        ));
     }
 
     if let Some(s) = &raw.keys {
-        return parse_combo(s).map(BindingKind::Combo);
+        let mode = match raw.match_mode {
+            RawMatchMode::Subset => MatchMode::Subset,
+            RawMatchMode::Exact => MatchMode::Exact,
+            RawMatchMode::Ordered => MatchMode::Ordered,
+        };
+        return parse_combo(s)
+            .map(|combo| BindingKind::Combo(combo.with_mode(mode)))
+            .map_err(|e| Problem::at(Seg::Field("keys"), e));
+    }
+    if raw.match_mode != RawMatchMode::Subset {
+        return Err(Problem::at(Seg::Field("match"), "`match` only applies to `keys:` bindings"));
     }
 
     if let Some(seq) = &raw.sequence {
-        return sequence_spec_to_kind(seq);
+        return sequence_spec_to_kind(seq, settings).map_err(|p| p.under(Seg::Field("sequence")));
     }
 
     if let Some(chord) = &raw.chord {
-        return chord_spec_to_kind(chord);
+        return chord_spec_to_kind(chord, settings).map_err(|p| p.under(Seg::Field("chord")));
     }
 
     unreachable!("kind_count checked above")
 }
 
-fn sequence_spec_to_kind(seq: &SequenceSpec) -> Result<BindingKind, String> {
+fn sequence_spec_to_kind(seq: &SequenceSpec, settings: &Settings) -> Result<BindingKind, Problem> {
     if seq.steps.len() < 2 {
-        return Err(
-            "Sequence must have at least 2 steps; for a single combo use plain `keys: \"...\"`"
-                .into(),
-        );
+        return Err(Problem::at(
+            Seg::Field("steps"),
+            "Sequence must have at least 2 steps; for a single combo use plain `keys: \"...\"`",
+        ));
     }
     let mut parsed = Vec::with_capacity(seq.steps.len());
     for (i, step) in seq.steps.iter().enumerate() {
-        let c = parse_combo(step).map_err(|e| format!("Sequence step #{}: {}", i, e))?;
+        let c = parse_combo(step).map_err(|e| {
+            Problem::at(Seg::Item(i), format!("Sequence step #{}: {}", i, e))
+                .under(Seg::Field("steps"))
+        })?;
         parsed.push(c);
     }
     Ok(BindingKind::Sequence {
         steps: parsed,
-        max_gap: Duration::from_millis(seq.max_gap_ms),
+        max_gap: Duration::from_millis(seq.max_gap_ms.unwrap_or(settings.sequence_gap_ms)),
     })
 }
 
-fn chord_spec_to_kind(chord: &ChordSpec) -> Result<BindingKind, String> {
+fn chord_spec_to_kind(chord: &ChordSpec, settings: &Settings) -> Result<BindingKind, Problem> {
     if chord.keys.len() < 2 {
-        return Err("Chord must have at least 2 keys; a single key is not a chord".into());
+        return Err(Problem::at(
+            Seg::Field("keys"),
+            "Chord must have at least 2 keys; a single key is not a chord",
+        ));
     }
     let mut vks = Vec::with_capacity(chord.keys.len());
     for (i, k) in chord.keys.iter().enumerate() {
-        let vk = parse_vk(k)
-            .ok_or_else(|| format!("Chord key #{}: unknown key name {:?}", i, k))?;
+        let at_key = |message: String| Problem::at(Seg::Item(i), message).under(Seg::Field("keys"));
+        let vk = parse_vk(k).ok_or_else(|| {
+            at_key(format!("Chord key #{}: unknown key name {:?}{}", i, k, did_you_mean(k)))
+        })?;
         if vks.contains(&vk) {
-            return Err(format!("Chord key #{}: duplicate {:?}", i, k));
+            return Err(at_key(format!("Chord key #{}: duplicate {:?}", i, k)));
         }
         vks.push(vk);
     }
     Ok(BindingKind::Chord {
         keys: vks,
-        window: Duration::from_millis(chord.window_ms),
+        window: Duration::from_millis(chord.window_ms.unwrap_or(settings.chord_window_ms)),
     })
 }
 
+/// `binding "name"` (or the `id:`) for a binding that has either.
+fn raw_binding_label(raw: &RawBinding) -> Option<String> {
+    raw.name.as_ref().or(raw.id.as_ref()).map(|label| format!("binding {:?}", label))
+}
+
 fn format_raw_binding_keys(raw: &RawBinding) -> String {
     if let Some(s) = &raw.keys {
         return s.clone();
@@ -321,13 +1004,86 @@ fn format_raw_binding_keys(raw: &RawBinding) -> String {
     "<unset>".into()
 }
 
-fn raw_trigger_to_trigger(raw: RawTrigger) -> Trigger {
-    match raw {
-        RawTrigger::Immediate    => Trigger::Immediate,
-        RawTrigger::Tap(ms)      => Trigger::Tap(ms),
-        RawTrigger::Hold(ms)     => Trigger::Hold(ms),
-        RawTrigger::DoubleTap(ms) => Trigger::DoubleTap(ms),
-    }
+fn raw_trigger_to_trigger(
+    raw: &RawTrigger,
+    actions: &ActionTable<'_>,
+    settings: &Settings,
+) -> Result<Trigger, Problem> {
+    Ok(match raw {
+        RawTrigger::Immediate     => Trigger::Immediate,
+        RawTrigger::Tap(ms)       => Trigger::Tap(*ms),
+        RawTrigger::Hold(ms)      => Trigger::Hold(*ms),
+        RawTrigger::DoubleTap(ms) => Trigger::DoubleTap(*ms),
+        RawTrigger::TapDance { term_ms, taps, holds } => {
+            if taps.is_empty() {
+                return Err(Problem::at(
+                    Seg::Field("taps"),
+                    "TapDance: `taps:` needs at least one action",
+                ));
+            }
+            let mut steps = taps
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    let tap = raw_action_to_bind_action(a, actions).map_err(|p| {
+                        p.labelled("TapDance").under(Seg::Item(i)).under(Seg::Field("taps"))
+                    })?;
+                    Ok(TapDanceStep { tap: Some(tap), hold: None })
+                })
+                .collect::<Result<Vec<_>, Problem>>()?;
+            for (&count, action) in holds {
+                let at_count = Seg::Key(count.to_string());
+                if count == 0 {
+                    return Err(Problem::at(at_count, "TapDance: `holds:` tap counts start at 1")
+                        .under(Seg::Field("holds")));
+                }
+                if count > steps.len() {
+                    steps.resize(count, TapDanceStep::default());
+                }
+                steps[count - 1].hold = Some(raw_action_to_bind_action(action, actions).map_err(
+                    |p| p.labelled("TapDance").under(at_count).under(Seg::Field("holds")),
+                )?);
+            }
+            Trigger::TapDance {
+                term_ms: term_ms.unwrap_or(settings.tap_term_ms),
+                steps: Arc::from(steps),
+            }
+        }
+        RawTrigger::ModTap { hold, term_ms, flavor, .. } => {
+            let vk = parse_vk(hold).ok_or_else(|| {
+                Problem::at(
+                    Seg::Field("hold"),
+                    format!("ModTap: unknown key {:?}{}", hold, did_you_mean(hold)),
+                )
+            })?;
+            if !is_modifier(vk) {
+                return Err(Problem::at(
+                    Seg::Field("hold"),
+                    format!("ModTap: `hold` must be a modifier (Shift, Ctrl, Alt, Win), got {:?}", hold),
+                ));
+            }
+            Trigger::ModTap {
+                hold: vk,
+                term_ms: term_ms.unwrap_or(settings.hold_term_ms),
+                flavor: match flavor {
+                    RawModTapFlavor::HoldOnOtherKeyPress => ModTapFlavor::HoldOnOtherKeyPress,
+                    RawModTapFlavor::PermissiveHold      => ModTapFlavor::PermissiveHold,
+                    RawModTapFlavor::TapPreferred        => ModTapFlavor::TapPreferred,
+                },
+            }
+        }
+    })
+}
+
+pub(super) fn is_modifier(vk: Vk) -> bool {
+    matches!(
+        vk,
+        VK_SHIFT | VK_LSHIFT | VK_RSHIFT
+            | VK_CONTROL | VK_LCONTROL | VK_RCONTROL
+            | VK_MENU | VK_LMENU | VK_RMENU
+            | VK_LWIN | VK_RWIN | ANY_WIN
+            | ALT_GR
+    )
 }
 
 fn raw_condition_to_condition(raw: &RawCondition) -> Condition {
@@ -348,7 +1104,10 @@ fn raw_condition_to_condition(raw: &RawCondition) -> Condition {
     }
 }
 
-fn raw_action_to_bind_action(raw: &RawAction) -> Result<BindAction, String> {
+fn raw_action_to_bind_action(
+    raw: &RawAction,
+    actions: &ActionTable<'_>,
+) -> Result<BindAction, Problem> {
     Ok(match raw {
         RawAction::SwitchLanguage         => BindAction::SwitchLanguage,
         RawAction::SwitchLanguageBackward => BindAction::SwitchLanguageBackward,
@@ -357,7 +1116,7 @@ fn raw_action_to_bind_action(raw: &RawAction) -> Result<BindAction, String> {
 
         RawAction::PressKey(key) => {
             let vk = parse_vk(key)
-                .ok_or_else(|| format!("PressKey: unknown key {:?}", key))?;
+                .ok_or_else(|| format!("PressKey: unknown key {:?}{}", key, did_you_mean(key)))?;
             BindAction::PressKey(vk)
         }
 
@@ -388,25 +1147,36 @@ fn raw_action_to_bind_action(raw: &RawAction) -> Result<BindAction, String> {
             // the BindAction (frozen binding list, hot-path lookups) is O(1).
             let mut converted = Vec::with_capacity(steps.len());
             for (i, raw_step) in steps.iter().enumerate() {
-                converted.push(
-                    raw_step_to_step(raw_step)
-                        .map_err(|e| format!("step #{}: {}", i, e))?,
-                );
+                converted.push(raw_step_to_step(raw_step, actions).map_err(|e| {
+                    Problem::at(Seg::Item(i), format!("step #{}: {}", i, e)).under(Seg::Item(0))
+                })?);
             }
             BindAction::Sequence(Arc::new(converted))
         }
 
-        RawAction::Launch { exe, args } => BindAction::Launch {
-            exe: exe.clone(),
-            args: args.clone(),
-        },
+        RawAction::Launch { exe, args } => {
+            let (exe, args) = actions.expand_launch(exe, args)?;
+            BindAction::Launch { exe, args }
+        }
 
-        RawAction::OpenUrl(url) => BindAction::OpenUrl(url.clone()),
+        RawAction::OpenUrl(url) => BindAction::OpenUrl(actions.expand("OpenUrl", url)?),
 
         RawAction::Media(key) => BindAction::Media(media_key_ref_to_key(*key)),
+
+        RawAction::LayerOn(name)        => layer_action(LayerOp::On, name),
+        RawAction::LayerOff(name)       => layer_action(LayerOp::Off, name),
+        RawAction::LayerToggle(name)    => layer_action(LayerOp::Toggle, name),
+        RawAction::LayerMomentary(name) => layer_action(LayerOp::Momentary, name),
+        RawAction::LayerOneShot(name)   => layer_action(LayerOp::OneShot, name),
+
+        RawAction::Ref(name) => actions.reference(name)?,
     })
 }
 
+fn layer_action(op: LayerOp, name: &str) -> BindAction {
+    BindAction::Layer { op, layer: Arc::from(name) }
+}
+
 fn media_key_ref_to_key(raw: MediaKeyRef) -> MediaKey {
     match raw {
         MediaKeyRef::PlayPause  => MediaKey::PlayPause,
@@ -419,15 +1189,15 @@ fn media_key_ref_to_key(raw: MediaKeyRef) -> MediaKey {
     }
 }
 
-fn raw_step_to_step(raw: &RawStep) -> Result<SequenceStep, String> {
+fn raw_step_to_step(raw: &RawStep, actions: &ActionTable<'_>) -> Result<SequenceStep, String> {
     Ok(match raw {
         RawStep::Delay(ms) => SequenceStep::Delay(*ms),
 
-        RawStep::Text(text) => SequenceStep::TypeText(text.clone()),
+        RawStep::Text(text) => SequenceStep::TypeText(actions.expand("Text", text)?),
 
         RawStep::Key(name) => {
             let vk = parse_vk(name)
-                .ok_or_else(|| format!("Key: unknown key {:?}", name))?;
+                .ok_or_else(|| format!("Key: unknown key {:?}{}", name, did_you_mean(name)))?;
             SequenceStep::PressKey(vk)
         }
 
@@ -447,22 +1217,36 @@ fn raw_step_to_step(raw: &RawStep) -> Result<SequenceStep, String> {
             RawWindowKind::Close    => WindowOp::Close,
         }),
 
-        RawStep::Launch { exe, args } => SequenceStep::Launch {
-            exe: exe.clone(),
-            args: args.clone(),
-        },
+        RawStep::Launch { exe, args } => {
+            let (exe, args) = actions.expand_launch(exe, args)?;
+            SequenceStep::Launch { exe, args }
+        }
 
-        RawStep::OpenUrl(url) => SequenceStep::OpenUrl(url.clone()),
+        RawStep::OpenUrl(url) => SequenceStep::OpenUrl(actions.expand("OpenUrl", url)?),
 
         RawStep::Media(key) => SequenceStep::Media(media_key_ref_to_key(*key)),
+
+        RawStep::Ref(name) => match actions.reference(name)? {
+            BindAction::Sequence(steps) => SequenceStep::Sequence(steps),
+            BindAction::PressKey(vk) => SequenceStep::PressKey(vk),
+            BindAction::Launch { exe, args } => SequenceStep::Launch { exe, args },
+            BindAction::OpenUrl(url) => SequenceStep::OpenUrl(url),
+            BindAction::Media(key) => SequenceStep::Media(key),
+            other => {
+                return Err(format!(
+                    "Ref({:?}): {} has no sequence-step form",
+                    name, other
+                ));
+            }
+        },
     })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
-    use windows::Win32::UI::WindowsAndMessaging::WM_CLOSE;
+
+    const WM_CLOSE: u32 = 0x0010;
 
     #[test]
     fn default_config_parses() {
@@ -1198,6 +1982,762 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chord_and_sequence_on_fail_parse() {
+        let ron = r#"
+            (
+                bindings: [
+                    (chord: (keys: ["j", "k"]), action: PressKey("Esc"), on_fail: Replay),
+                    (
+                        sequence: (steps: ["Space", "f"]),
+                        action: PressKey("Home"),
+                        on_fail: Action(PressKey("Esc")),
+                    ),
+                    (chord: (keys: ["d", "f"]), action: DoNothing),
+                ],
+            )
+        "#;
+        let bindings = from_ron_str(ron).unwrap();
+        assert_eq!(bindings[0].on_fail, OnFail::Replay);
+        assert_eq!(
+            bindings[1].on_fail,
+            OnFail::Action(BindAction::PressKey(VK_ESCAPE)),
+        );
+        assert_eq!(bindings[2].on_fail, OnFail::Drop);
+    }
+
+    #[test]
+    fn on_fail_on_combo_is_error() {
+        let ron = r#"
+            (
+                bindings: [(keys: "CapsLock", action: DoNothing, on_fail: Replay)],
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => assert!(errs[0].contains("on_fail"), "{}", errs[0]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn on_fail_action_errors_are_reported() {
+        let ron = r#"
+            (
+                bindings: [(
+                    chord: (keys: ["j", "k"]),
+                    action: DoNothing,
+                    on_fail: Action(PressKey("Escpae")),
+                )],
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => assert!(errs[0].contains("on_fail"), "{}", errs[0]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_mod_tap_trigger() {
+        let ron = r#"
+            (
+                bindings: [(
+                    keys: "CapsLock",
+                    trigger: ModTap(tap: PressKey("Esc"), hold: "Ctrl", flavor: HoldOnOtherKeyPress),
+                )],
+            )
+        "#;
+        let bindings = from_ron_str(ron).unwrap();
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].action, BindAction::PressKey(VK_ESCAPE));
+        assert_eq!(
+            bindings[0].trigger,
+            Trigger::ModTap {
+                hold: VK_CONTROL,
+                term_ms: 200,
+                flavor: ModTapFlavor::HoldOnOtherKeyPress,
+            }
+        );
+
+        let flavor = |text: &str| {
+            let ron = format!(
+                r#"(bindings: [(keys: "F", trigger: ModTap(tap: PressKey("f"), hold: "Shift"{}))])"#,
+                text
+            );
+            match from_ron_str(&ron).unwrap()[0].trigger {
+                Trigger::ModTap { flavor, .. } => flavor,
+                ref other => panic!("expected ModTap, got {:?}", other),
+            }
+        };
+        assert_eq!(flavor(""), ModTapFlavor::PermissiveHold);
+        assert_eq!(flavor(", flavor: Balanced"), ModTapFlavor::PermissiveHold);
+        assert_eq!(flavor(", flavor: TapPreferred"), ModTapFlavor::TapPreferred);
+    }
+
+    #[test]
+    fn parse_tap_dance_trigger() {
+        let ron = r#"
+            (
+                bindings: [(
+                    keys: "CapsLock",
+                    trigger: TapDance(
+                        taps: [SwitchLanguage, SwitchLanguageBackward, ToggleCapsLock],
+                        holds: { 1: PressKey("Esc"), 4: DoNothing },
+                    ),
+                )],
+            )
+        "#;
+        let bindings = from_ron_str(ron).unwrap();
+        assert_eq!(bindings[0].action, BindAction::SwitchLanguage);
+        let Trigger::TapDance { term_ms, steps } = &bindings[0].trigger else {
+            panic!("expected TapDance, got {:?}", bindings[0].trigger);
+        };
+        assert_eq!(*term_ms, 200);
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0].hold, Some(BindAction::PressKey(VK_ESCAPE)));
+        assert_eq!(steps[2].tap, Some(BindAction::ToggleCapsLock));
+        assert_eq!(steps[2].hold, None);
+        assert_eq!(steps[3], TapDanceStep { tap: None, hold: Some(BindAction::DoNothing) });
+    }
+
+    #[test]
+    fn tap_dance_errors_are_reported() {
+        let ron = r#"
+            (
+                bindings: [
+                    (keys: "F13", action: DoNothing, trigger: TapDance(taps: [DoNothing])),
+                    (keys: "F14", trigger: TapDance(taps: [])),
+                    (keys: "F15", trigger: TapDance(taps: [DoNothing], holds: { 0: DoNothing })),
+                    (keys: "F16", trigger: TapDance(taps: [PressKey("Escpae")])),
+                ],
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => {
+                assert_eq!(errs.len(), 4, "{:?}", errs);
+                assert!(errs[0].contains("taps:"), "{}", errs[0]);
+                assert!(errs[1].contains("at least one"), "{}", errs[1]);
+                assert!(errs[2].contains("start at 1"), "{}", errs[2]);
+                assert!(errs[3].contains("TapDance: PressKey"), "{}", errs[3]);
+            }
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn mod_tap_errors_are_reported() {
+        let ron = r#"
+            (
+                bindings: [
+                    (keys: "a", action: DoNothing, trigger: ModTap(tap: PressKey("a"), hold: "Shift")),
+                    (keys: "s", trigger: ModTap(tap: PressKey("s"), hold: "Esc")),
+                    (keys: "Ctrl+d", trigger: ModTap(tap: PressKey("d"), hold: "Alt")),
+                    (keys: "f"),
+                ],
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => {
+                assert_eq!(errs.len(), 4, "{:?}", errs);
+                assert!(errs[0].contains("tap:"), "{}", errs[0]);
+                assert!(errs[1].contains("must be a modifier"), "{}", errs[1]);
+                assert!(errs[2].contains("single key"), "{}", errs[2]);
+                assert!(errs[3].contains("missing `action`"), "{}", errs[3]);
+            }
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_layers() {
+        let ron = r#"
+            (
+                bindings: [(keys: "F13", action: LayerMomentary("nav"))],
+                layers: {
+                    "nav": [
+                        (keys: "h", action: PressKey("Left")),
+                        (keys: "CapsLock", action: SwitchLanguage, block_original_combo: true),
+                    ],
+                },
+            )
+        "#;
+        let bindings = from_ron_str(ron).unwrap();
+        assert_eq!(
+            bindings[0].action,
+            BindAction::Layer { op: LayerOp::Momentary, layer: Arc::from("nav") }
+        );
+        assert_eq!(bindings[0].layer, None);
+        let nav: Vec<_> = bindings.iter().filter(|b| b.layer.as_deref() == Some("nav")).collect();
+        // h, CapsLock and CapsLock's auto-blocker — scoped to the layer.
+        assert_eq!(nav.len(), 3, "{:?}", bindings);
+        assert_eq!(nav[0].action, BindAction::PressKey(VK_LEFT));
+        assert!(nav[1].is_auto_blocker);
+    }
+
+    #[test]
+    fn layer_errors_are_reported() {
+        let ron = r#"
+            (
+                bindings: [(keys: "F13", action: LayerToggle("navv"))],
+                layers: {
+                    "nav": [(keys: "Hyper+h", action: PressKey("Left"))],
+                },
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => {
+                assert_eq!(errs.len(), 2, "{:?}", errs);
+                assert!(errs[0].contains("unknown layer \"navv\""), "{}", errs[0]);
+                assert!(errs[1].starts_with("5:36: unknown key \"Hyper\""), "{}", errs[1]);
+            }
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn refs_share_the_named_action() {
+        let ron = r#"
+            (
+                actions: {
+                    "save_all": Sequence([Combo("Ctrl+K"), Key("S")]),
+                    "save_and_close": Sequence([Ref("save_all"), Delay(100), Ref("close")]),
+                    "close": PressKey("Esc"),
+                },
+                bindings: [
+                    (keys: "F13", action: Ref("save_all")),
+                    (keys: "F14", action: Ref("save_all")),
+                    (keys: "F15", action: Ref("save_and_close")),
+                    (keys: "F16", trigger: TapDance(taps: [Ref("close"), Ref("save_all")])),
+                ],
+            )
+        "#;
+        let bindings = from_ron_str(ron).unwrap();
+        assert_eq!(bindings[0].action, bindings[1].action);
+        let BindAction::Sequence(save_all) = &bindings[0].action else {
+            panic!("expected Sequence, got {:?}", bindings[0].action);
+        };
+        let BindAction::Sequence(steps) = &bindings[2].action else {
+            panic!("expected Sequence, got {:?}", bindings[2].action);
+        };
+        assert!(matches!(&steps[0], SequenceStep::Sequence(inner) if Arc::ptr_eq(inner, save_all)));
+        assert!(matches!(steps[2], SequenceStep::PressKey(VK_ESCAPE)));
+        assert_eq!(bindings[3].action, BindAction::PressKey(VK_ESCAPE));
+    }
+
+    #[test]
+    fn bad_refs_are_binding_errors() {
+        let ron = r#"
+            (
+                actions: {
+                    "a": Sequence([Ref("b")]),
+                    "b": Sequence([Key("Enter"), Ref("a")]),
+                    "layer": LayerOn("nav"),
+                },
+                bindings: [
+                    (keys: "F13", action: Ref("a")),
+                    (keys: "F14", action: Ref("missing")),
+                    (keys: "F15", action: Sequence([Ref("layer")])),
+                ],
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => {
+                assert_eq!(errs.len(), 5, "{:?}", errs);
+                assert_eq!(errs[0], r#"4:36: step #0: Ref("b"): action "b" is invalid"#);
+                assert!(
+                    errs[1].contains(r#"recursive action reference a -> b -> a"#),
+                    "{}",
+                    errs[1]
+                );
+                assert!(errs[2].contains(r#"Ref("a"): action "a" is invalid"#), "{}", errs[2]);
+                assert!(errs[3].contains(r#"Ref("missing"): unknown action"#), "{}", errs[3]);
+                assert!(errs[4].contains("no sequence-step form"), "{}", errs[4]);
+            }
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn vars_are_substituted_in_strings() {
+        let ron = r#"
+            (
+                vars: { "proj": "${env:PATH}\\work", "repo": "${proj}\\key-switch" },
+                bindings: [
+                    (keys: "F13", action: Launch(exe: "code.exe", args: ["${repo}"])),
+                    (keys: "F14", action: OpenUrl("https://example.com/?q=$$5")),
+                    (keys: "F15", action: Sequence([Text("${repo} costs $5")])),
+                ],
+            )
+        "#;
+        let repo = format!("{}\\work\\key-switch", std::env::var("PATH").unwrap());
+        let bindings = from_ron_str(ron).unwrap();
+        assert_eq!(
+            bindings[0].action,
+            BindAction::Launch { exe: "code.exe".into(), args: vec![repo.clone()] }
+        );
+        assert_eq!(bindings[1].action, BindAction::OpenUrl("https://example.com/?q=$5".into()));
+        let BindAction::Sequence(steps) = &bindings[2].action else {
+            panic!("expected Sequence, got {:?}", bindings[2].action);
+        };
+        assert!(matches!(&steps[0], SequenceStep::TypeText(t) if *t == format!("{} costs $5", repo)));
+    }
+
+    #[test]
+    fn bad_vars_are_binding_errors() {
+        let ron = r#"
+            (
+                vars: { "broken": "${env:KEY_SWITCH_RS_SURELY_UNSET}" },
+                bindings: [
+                    (keys: "F13", action: OpenUrl("${missing}")),
+                    (keys: "F14", action: Sequence([Text("${broken}")])),
+                ],
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => assert_eq!(errs, [
+                "3:35: undefined environment variable `${env:KEY_SWITCH_RS_SURELY_UNSET}`",
+                "5:43: OpenUrl: undefined variable `${missing}` (not defined under `vars:`)",
+                "6:53: step #0: Text: variable `${broken}` is invalid",
+            ]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binding_metadata_is_carried_and_named_in_errors() {
+        let ron = r#"
+            (
+                bindings: [
+                    (
+                        id: "jump", name: "Jump", description: "Space in games",
+                        keys: "Win+Space", action: SwitchLanguage, block_original_combo: true,
+                        tags: ["gaming", "fps", "gaming"], enabled: false,
+                    ),
+                    (keys: "F13", action: DoNothing),
+                ],
+            )
+        "#;
+        let bindings = from_ron_str(ron).unwrap();
+        let jump = bindings.iter().find(|b| b.id.as_deref() == Some("jump")).unwrap();
+        assert_eq!(jump.name.as_deref(), Some("Jump"));
+        assert_eq!(jump.description.as_deref(), Some("Space in games"));
+        assert_eq!(jump.tags, [Arc::from("gaming"), Arc::from("fps")]);
+        assert!(!jump.enabled);
+        let blocker = bindings.iter().find(|b| b.is_auto_blocker).unwrap();
+        assert_eq!((&blocker.tags, blocker.enabled), (&jump.tags, false));
+        assert!(jump.to_string().ends_with(r#""Jump" (jump) — Space in games  #gaming  #fps  (disabled)"#));
+        assert!(bindings.iter().all(|b| b.id.is_some() || b.is_auto_blocker || b.enabled));
+
+        let ron = r#"
+            (
+                bindings: [(id: "a", keys: "F13", action: DoNothing)],
+                layers: { "nav": [
+                    (id: "a", keys: "F14", action: DoNothing),
+                    (name: "Left", keys: "h", action: PressKey("Lfet")),
+                    (keys: "j", action: DoNothing, tags: [""]),
+                ] },
+            )
+        "#;
+        match from_ron_str(ron).unwrap_err() {
+            ConfigError::Bindings(errs) => assert_eq!(errs, [
+                r#"5:26: binding "a": id "a" is already used (at 3:28)"#,
+                r#"6:55: binding "Left": PressKey: unknown key "Lfet" (did you mean "Left"?)"#,
+                r#"7:59: tag "" is empty or padded"#,
+            ]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    /// Lint warnings for an in-memory config that converts cleanly.
+    fn warnings_for(text: &str) -> Vec<String> {
+        let file = ParsedFile::parse(text, Path::new("<in-memory>"), None).unwrap();
+        convert(&[file]).unwrap().2
+    }
+
+    #[test]
+    fn lints_flag_typing_gestures_and_dead_triggers() {
+        let ron = r#"(
+    bindings: [
+        (sequence: (steps: ["g", "g"]), action: DoNothing),
+        (sequence: (steps: ["Ctrl+K", "Ctrl+C"]), action: DoNothing),
+        (chord: (keys: ["j", "k"]), action: DoNothing, on_fail: Replay),
+        (chord: (keys: ["j", "F13"]), action: DoNothing),
+        (keys: "F14", action: DoNothing, trigger: Tap(0)),
+        (keys: "CapsLock", trigger: ModTap(tap: PressKey("Esc"), hold: "Ctrl")),
+        (keys: "CapsLock", action: DoNothing, trigger: Hold(300)),
+    ],
+)"#;
+        let warnings = warnings_for(ron);
+        assert_eq!(warnings.len(), 4, "{:#?}", warnings);
+        assert!(warnings[0].starts_with("3:20: sequence starts with \"G\""), "{}", warnings[0]);
+        assert!(warnings[0].contains("on_fail: Replay"), "{}", warnings[0]);
+        assert!(warnings[1].starts_with("5:17: chord uses only typing keys (J, K)"), "{}", warnings[1]);
+        assert!(!warnings[1].contains("on_fail"), "{}", warnings[1]);
+        assert!(warnings[2].starts_with("7:51: `Tap(0ms)` can never fire"), "{}", warnings[2]);
+        assert!(
+            warnings[3].starts_with("9:56: never fires: the binding at 8:9 is a `ModTap` key"),
+            "{}",
+            warnings[3]
+        );
+    }
+
+    #[test]
+    fn conflicts_between_bindings_are_reported() {
+        let ron = r#"(
+    bindings: [
+        (keys: "Ctrl+S", action: PressKey("F1")),
+        (keys: "Ctrl+S", action: PressKey("F2"), when: AppEquals("code.exe")),
+        (keys: "Ctrl+S", action: PressKey("F3"), when: TitleContains("notes")),
+        (keys: "Ctrl+D", action: PressKey("F4"), when: AppEquals("code.exe")),
+        (keys: "Ctrl+D", action: PressKey("F5"), when: TitleContains("notes")),
+        (keys: "Ctrl+D", action: PressKey("F6"), when: AppEquals("vim.exe")),
+        (keys: "F13", action: PressKey("F7"), trigger: Tap(200)),
+        (keys: "F13", action: PressKey("F8"), trigger: Hold(200)),
+        (sequence: (steps: ["Ctrl+K", "Ctrl+C"]), action: DoNothing),
+        (sequence: (steps: ["Ctrl+K", "Ctrl+C", "Ctrl+X"]), action: DoNothing),
+        (keys: "Ctrl+K", action: DoNothing),
+    ],
+    layers: { "nav": [(keys: "Ctrl+S", action: PressKey("F9"))] },
+)"#;
+        let warnings = warnings_for(ron);
+        assert_eq!(warnings.len(), 7, "{:#?}", warnings);
+        assert_eq!(
+            warnings[0],
+            "4:16: never fires: the binding at 3:9 matches the same combo first and fires instead"
+        );
+        assert!(warnings[1].starts_with("5:16: never fires: the binding at 3:9"), "{}", warnings[1]);
+        let partial = "shadowed wherever both `when:` conditions hold";
+        assert!(warnings[2].starts_with(&format!("7:16: {}: the binding at 6:9", partial)));
+        // A vim window may be titled "notes"; code.exe and vim.exe exclude each other.
+        assert!(warnings[3].starts_with(&format!("8:16: {}: the binding at 7:9", partial)));
+        assert!(
+            warnings[4].starts_with("11:20: step #0 is also the combo of the binding at 13:9"),
+            "{}",
+            warnings[4]
+        );
+        assert!(
+            warnings[5].starts_with("12:20: typing this sequence also completes the shorter \
+                                     sequence at 11:9 after step #1"),
+            "{}",
+            warnings[5]
+        );
+        assert!(warnings[6].starts_with("12:20: step #0 is also the combo"), "{}", warnings[6]);
+    }
+
+    #[test]
+    fn lint_flags_an_empty_config() {
+        assert_eq!(warnings_for("(bindings: [])"), vec!["1:12: config contains no bindings"]);
+        assert!(warnings_for("(layers: {\"nav\": [(keys: \"H\", action: DoNothing)]})").is_empty());
+    }
+
+    #[test]
+    fn match_modes_load_and_only_shadow_their_own_mode() {
+        let ron = r#"(
+    bindings: [
+        (keys: "Ctrl+J", action: PressKey("F1"), match: Exact),
+        (keys: "Ctrl+J", action: PressKey("F2")),
+        (keys: "Shift+CapsLock", action: PressKey("F3"), match: Ordered),
+        (keys: "Ctrl+L", action: PressKey("F4")),
+        (keys: "Ctrl+L", action: PressKey("F5"), match: Exact),
+    ],
+)"#;
+        let modes: Vec<MatchMode> = from_ron_str(ron)
+            .unwrap()
+            .iter()
+            .map(|b| b.combination().unwrap().mode)
+            .collect();
+        assert_eq!(modes[..3], [MatchMode::Exact, MatchMode::Subset, MatchMode::Ordered]);
+        let warnings = warnings_for(ron);
+        assert_eq!(warnings.len(), 1, "{:#?}", warnings);
+        assert!(warnings[0].starts_with("7:16: never fires: the binding at 6:9"), "{}", warnings[0]);
+
+        let err = from_ron_str(
+            r#"(bindings: [(sequence: (steps: ["g", "g"]), action: DoNothing, match: Exact)])"#,
+        )
+        .unwrap_err();
+        match err {
+            ConfigError::Bindings(errs) => {
+                assert_eq!(errs, ["1:71: `match` only applies to `keys:` bindings"])
+            }
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lint_flags_bindings_on_an_auto_blocked_combo() {
+        let Some(system) = BindAction::SwitchLanguage.get_system_combination() else {
+            return;
+        };
+        let keys: Vec<String> =
+            system.keys.iter().map(|&vk| crate::data::vk_name::vk_name(vk)).collect();
+        let ron = format!(
+            r#"(
+    bindings: [
+        (keys: "CapsLock", action: SwitchLanguage, block_original_combo: true),
+        (keys: "{}", action: PressKey("Esc")),
+    ],
+)"#,
+            keys.join("+")
+        );
+        let warnings = warnings_for(&ron);
+        assert_eq!(warnings.len(), 1, "{:#?}", warnings);
+        assert!(warnings[0].starts_with("4:16: "), "{}", warnings[0]);
+        assert!(warnings[0].contains("`block_original_combo` on 3:9 adds"), "{}", warnings[0]);
+    }
+
+    /// Fresh scratch directory under the system temp dir holding `files`.
+    fn config_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("key-switch-rs-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, text) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn include_merges_files_and_globs_in_order() {
+        let dir = config_dir("include-merge", &[
+            ("config.ron", r#"(
+                include: ["team.ron", "local/*.ron"],
+                bindings: [(keys: "F13", action: LayerToggle("nav"))],
+            )"#),
+            ("team.ron", r#"(
+                bindings: [(keys: "F14", action: DoNothing)],
+                layers: { "nav": [(keys: "h", action: PressKey("Left"))] },
+            )"#),
+            ("local/b.ron", r#"(bindings: [(keys: "F16", action: DoNothing)])"#),
+            ("local/a.ron", r#"(bindings: [(keys: "F15", action: DoNothing)])"#),
+        ]);
+        let loaded = load_existing(&dir.join("config.ron")).unwrap();
+        let keys: Vec<Vk> = loaded
+            .bindings
+            .iter()
+            .map(|b| b.combination().unwrap().keys[0])
+            .collect();
+        assert_eq!(keys, vec![VK_F14, VK_F15, VK_F16, VK_F13, VK_H]);
+        assert_eq!(loaded.sources.files.len(), 4);
+        assert_eq!(loaded.sources.globs.len(), 1);
+        assert!(loaded.sources.globs[0].matches(&dir.join("local").join("new.ron")));
+        assert!(!loaded.sources.globs[0].matches(&dir.join("new.ron")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn vars_are_shared_across_included_files() {
+        let dir = config_dir("include-vars", &[
+            ("config.ron", r#"(
+                include: ["team.ron"],
+                vars: { "tools": "C:\\tools" },
+                bindings: [(keys: "F13", action: Launch(exe: "${editor}"))],
+            )"#),
+            ("team.ron", r#"(
+                vars: { "editor": "${tools}\\vim.exe", "tools": "D:\\tools" },
+            )"#),
+        ]);
+        match load_existing(&dir.join("config.ron")).unwrap_err() {
+            ConfigError::Bindings(errs) => assert_eq!(errs, [
+                r#"config.ron:3:34: variable "tools" is already defined in team.ron"#,
+            ]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+        fs::write(dir.join("team.ron"), r#"(vars: { "editor": "${tools}\\vim.exe" })"#).unwrap();
+        let loaded = load_existing(&dir.join("config.ron")).unwrap();
+        assert_eq!(
+            loaded.bindings[0].action,
+            BindAction::Launch { exe: "C:\\tools\\vim.exe".into(), args: vec![] }
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn settings_fill_in_what_bindings_leave_out() {
+        let dir = config_dir("settings", &[
+            ("config.ron", r#"(
+                include: ["team.ron"],
+                settings: (chord_window_ms: 40, repeat: false),
+                bindings: [
+                    (sequence: (steps: ["g", "g"]), action: DoNothing),
+                    (sequence: (steps: ["g", "h"], max_gap_ms: 900), action: DoNothing),
+                    (chord: (keys: ["j", "k"]), action: DoNothing),
+                    (keys: "F13", action: DoNothing),
+                    (keys: "F14", action: DoNothing, repeat: true),
+                    (keys: "CapsLock", trigger: ModTap(tap: PressKey("Esc"), hold: "Ctrl")),
+                ],
+            )"#),
+            ("team.ron", r#"(settings: (sequence_gap_ms: 700, chord_window_ms: 30, hold_term_ms: 150))"#),
+        ]);
+        let loaded = load_existing(&dir.join("config.ron")).unwrap();
+        let b = &loaded.bindings;
+        assert!(matches!(b[0].kind, BindingKind::Sequence { max_gap, .. } if max_gap.as_millis() == 700));
+        assert!(matches!(b[1].kind, BindingKind::Sequence { max_gap, .. } if max_gap.as_millis() == 900));
+        assert!(matches!(b[2].kind, BindingKind::Chord { window, .. } if window.as_millis() == 40));
+        assert_eq!((b[3].repeat, b[4].repeat), (false, true));
+        assert!(matches!(b[5].trigger, Trigger::ModTap { term_ms: 150, .. }));
+        assert_eq!(loaded.settings.tap_term_ms, 200);
+
+        fs::write(
+            dir.join("team.ron"),
+            r#"(bindings: [(sequence: (steps: ["g", "j"]), action: DoNothing, repeat: true)])"#,
+        )
+        .unwrap();
+        match load_existing(&dir.join("config.ron")).unwrap_err() {
+            ConfigError::Bindings(errs) => assert_eq!(errs, [
+                "team.ron:1:72: `repeat` only applies to `keys:` bindings with an `Immediate` trigger",
+            ]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn include_cycle_is_error() {
+        let dir = config_dir("include-cycle", &[
+            ("config.ron", r#"(include: ["a.ron"])"#),
+            ("a.ron", r#"(include: ["sub/b.ron"])"#),
+            ("sub/b.ron", r#"(include: ["../a.ron"])"#),
+        ]);
+        let err = load_existing(&dir.join("config.ron")).unwrap_err().to_string();
+        assert!(err.contains("include cycle: a.ron -> "), "{}", err);
+        assert!(err.ends_with("-> a.ron"), "{}", err);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn toml_and_json_configs_load_and_include_each_other() {
+        let dir = config_dir("formats", &[
+            ("config.toml", r#"
+                include = ["team.json"]
+
+                [[bindings]]
+                keys = "F1"
+                action = { Sequence = [{ Window = "Restore" }, { Delay = 100 }, { Text = "hi" }] }
+                when = { Not = { AppEquals = "chrome.exe" } }
+
+                [[layers.nav]]
+                keys = "H"
+                action = { PressKey = "Left" }
+            "#),
+            ("team.json", r#"{
+                "include": ["base.ron"],
+                "bindings": [
+                    { "keys": "F2", "action": "SwitchLanguage", "trigger": { "Hold": 300 } },
+                    { "chord": { "keys": ["J", "K"] }, "action": { "LayerToggle": "nav" }, "on_fail": "Replay" }
+                ]
+            }"#),
+            ("base.ron", r#"(bindings: [(keys: "F3", action: DoNothing)])"#),
+        ]);
+        let loaded = load_existing(&dir.join("config.toml")).unwrap();
+        let keys: Vec<String> = loaded.bindings.iter().map(|b| b.to_string()).collect();
+        assert_eq!(loaded.bindings.len(), 5, "{:#?}", keys);
+        assert!(matches!(loaded.bindings[1].trigger, Trigger::Hold(300)));
+        assert!(matches!(loaded.bindings[3].condition, Condition::Not(_)));
+        assert_eq!(loaded.bindings[4].layer.as_deref(), Some("nav"));
+    }
+
+    #[test]
+    fn toml_and_json_errors_name_the_binding() {
+        let dir = config_dir("format-errors", &[
+            ("config.toml", "[[bindings]]\nkeys = \"Ctrl+Foo\"\naction = \"DoNothing\"\n"),
+            ("config.json", "{\n  \"bindings\": [\n    { \"keys\": \"Ctrl+Foo\", \"action\": \"DoNothing\" }\n  ]\n}"),
+            ("broken.toml", "[[bindings]\n"),
+        ]);
+        // No positions for TOML: the binding is named instead.
+        let err = load_existing(&dir.join("config.toml")).unwrap_err().to_string();
+        assert!(err.contains("config.toml: binding #0 [Ctrl+Foo]: unknown key \"Foo\""), "{}", err);
+        let err = load_existing(&dir.join("config.json")).unwrap_err().to_string();
+        assert!(err.contains("config.json:3:15: unknown key \"Foo\""), "{}", err);
+        let err = load_existing(&dir.join("broken.toml")).unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { .. }), "{}", err);
+    }
+
+    #[test]
+    fn default_config_is_written_in_the_requested_format() {
+        let dir = config_dir("format-default", &[]);
+        for name in ["config.toml", "config.json"] {
+            let path = dir.join(name);
+            write_default(&path).unwrap();
+            let loaded = load_existing(&path).unwrap();
+            let default = from_ron_str(DEFAULT_CONFIG).unwrap();
+            assert_eq!(format!("{:?}", loaded.bindings), format!("{:?}", default), "{}", name);
+        }
+    }
+
+    #[test]
+    fn old_versions_are_migrated_and_backed_up() {
+        use super::migrate::tests::TEST_MIGRATIONS;
+
+        let old = r#"(version: 1, include: ["team.ron"], legacy_bindings: [(keys: "F1", action: DoNothing)])"#;
+        let dir = config_dir("migrate", &[
+            ("config.ron", old),
+            ("team.ron", r#"(bindings: [(keys: "F2", action: DoNothing)])"#),
+        ]);
+        let path = dir.join("config.ron");
+
+        // Offline: migrated in memory, nothing written.
+        let loaded = load_tree(&path, false, TEST_MIGRATIONS).unwrap();
+        assert_eq!(loaded.bindings.len(), 2);
+        assert!(loaded.warnings[0].starts_with("config.ron: `version: 1` was migrated to version 3 in memory"));
+        assert!(!dir.join("config.ron.bak").exists());
+
+        let loaded = load_tree(&path, true, TEST_MIGRATIONS).unwrap();
+        assert_eq!(loaded.bindings.len(), 2);
+        assert!(loaded.warnings.is_empty(), "{:?}", loaded.warnings);
+        assert_eq!(fs::read_to_string(dir.join("config.ron.bak")).unwrap(), old);
+        let migrated = fs::read_to_string(&path).unwrap();
+        assert!(migrated.starts_with("(version: 3, include"), "{}", migrated);
+        // The unversioned include counts as current and is left alone.
+        assert!(!dir.join("team.ron.bak").exists());
+
+        fs::write(&path, "(version: 9)").unwrap();
+        let err = load_tree(&path, true, TEST_MIGRATIONS).unwrap_err().to_string();
+        assert!(err.contains("config.ron: config is `version: 9`, newer than"), "{}", err);
+    }
+
+    #[test]
+    fn include_errors_name_the_file() {
+        let dir = config_dir("include-errors", &[
+            ("config.ron", r#"(
+                include: ["team.ron"],
+                bindings: [(keys: "Ctrl+Foo", action: DoNothing)],
+            )"#),
+            ("team.ron", r#"(bindings: [(keys: "F13", action: PressKey("Escpae"))])"#),
+            ("broken.ron", r#"(include: ["missing.ron"])"#),
+        ]);
+        match load_existing(&dir.join("config.ron")).unwrap_err() {
+            ConfigError::Bindings(errs) => {
+                assert_eq!(errs.len(), 2, "{:?}", errs);
+                assert!(errs[0].starts_with("team.ron:1:35: PressKey"), "{}", errs[0]);
+                assert!(errs[1].starts_with("config.ron:3:35: unknown key"), "{}", errs[1]);
+            }
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+        match load_existing(&dir.join("broken.ron")).unwrap_err() {
+            ConfigError::Io { path, .. } => assert!(path.ends_with("missing.ron")),
+            other => panic!("expected Io error, got {:?}", other),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn binding_errors_point_at_the_value() {
+        let dir = config_dir("located-errors", &[(
+            "config.ron",
+            "(\n    bindings: [\n        (keys: \"F13\", action: DoNothing),\n        (keys: \"Ctlr+S\", action: DoNothing),\n        (chord: (keys: [\"j\", \"kk\"]), action: DoNothing),\n        (keys: \"F14\", action: Sequence([Delay(10), Key(\"Entr\")])),\n    ],\n)",
+        )]);
+        match load_existing(&dir.join("config.ron")).unwrap_err() {
+            ConfigError::Bindings(errs) => assert_eq!(errs, [
+                r#"config.ron:4:16: unknown key "Ctlr" in combo "Ctlr+S" (did you mean "Ctrl"?)"#,
+                r#"config.ron:5:30: Chord key #1: unknown key name "kk""#,
+                r#"config.ron:6:52: step #1: Key: unknown key "Entr" (did you mean "Enter"?)"#,
+            ]),
+            other => panic!("expected Bindings error, got {:?}", other),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn chord_duplicate_key_is_error() {
         let ron = r#"
@@ -0,0 +1,217 @@
+//! Which config file the daemon and `swch` use.
+//!
+//! Candidates, highest priority first:
+//!   1. `--config <path>`
+//!   2. the `KEY_SWITCH_CONFIG` environment variable
+//!   3. `%APPDATA%\key-switch-rs\config.{ron,toml,json}`, the per-user
+//!      config directory
+//!   4. `config.{ron,toml,json}` next to the executable, the portable layout
+//!
+//! The first two win whether or not the file exists — a missing file is
+//! generated there. The directories win only if they hold a config; with
+//! none anywhere, the default is generated in the first of them that can be
+//! written to, so an exe under Program Files gets its config in the user's
+//! profile instead.
+
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use super::format::Format;
+use super::loader::ConfigError;
+
+/// Environment variable naming the config file.
+pub const CONFIG_ENV_VAR: &str = "KEY_SWITCH_CONFIG";
+
+/// Directory under `%APPDATA%` holding the per-user config.
+const USER_DIR_NAME: &str = "key-switch-rs";
+
+/// Which candidate a [`ConfigLocation`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigOrigin {
+    Flag,
+    EnvVar,
+    UserDir,
+    ExeDir,
+}
+
+/// The config file to use, and why it was picked.
+#[derive(Debug, Clone)]
+pub struct ConfigLocation {
+    pub path: PathBuf,
+    pub origin: ConfigOrigin,
+    /// False if the file is still to be generated.
+    pub exists: bool,
+}
+
+/// Why this candidate won, e.g. `found in the per-user config directory`.
+impl fmt::Display for ConfigLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let missing = if self.exists { "" } else { " (does not exist yet, will be generated)" };
+        match (self.origin, self.exists) {
+            (ConfigOrigin::Flag, _) => write!(f, "given with --config{}", missing),
+            (ConfigOrigin::EnvVar, _) => write!(f, "set by {}{}", CONFIG_ENV_VAR, missing),
+            (ConfigOrigin::UserDir, true) => write!(f, "found in the per-user config directory"),
+            (ConfigOrigin::ExeDir, true) => write!(
+                f,
+                "found next to the executable (no config in the per-user directory)"
+            ),
+            (ConfigOrigin::UserDir, false) => write!(
+                f,
+                "no config found anywhere; the default will be generated in the per-user \
+                 config directory"
+            ),
+            (ConfigOrigin::ExeDir, false) => write!(
+                f,
+                "no config found anywhere and no writable per-user config directory; the \
+                 default will be generated next to the executable"
+            ),
+        }
+    }
+}
+
+/// Resolves the config path through the chain above. `flag` is the value
+/// of `--config`, if given.
+pub fn locate_config(flag: Option<&Path>) -> Result<ConfigLocation, ConfigError> {
+    let exe = std::env::current_exe().map_err(|e| ConfigError::Io {
+        path: PathBuf::from("<current_exe>"),
+        source: e,
+    })?;
+    let exe_dir = exe.parent().ok_or_else(|| {
+        ConfigError::Other(format!("executable path {:?} has no parent directory", exe))
+    })?;
+    let user_dir = std::env::var_os("APPDATA")
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join(USER_DIR_NAME));
+    Ok(locate_in(
+        flag,
+        std::env::var_os(CONFIG_ENV_VAR),
+        user_dir.as_deref(),
+        exe_dir,
+    ))
+}
+
+/// [`locate_config`] with the environment passed in.
+fn locate_in(
+    flag: Option<&Path>,
+    env_var: Option<OsString>,
+    user_dir: Option<&Path>,
+    exe_dir: &Path,
+) -> ConfigLocation {
+    let given = |path: PathBuf, origin| ConfigLocation { exists: path.exists(), path, origin };
+    if let Some(path) = flag {
+        return given(path.to_path_buf(), ConfigOrigin::Flag);
+    }
+    if let Some(path) = env_var.filter(|value| !value.is_empty()) {
+        return given(PathBuf::from(path), ConfigOrigin::EnvVar);
+    }
+
+    let dirs = user_dir
+        .map(|dir| (dir, ConfigOrigin::UserDir))
+        .into_iter()
+        .chain([(exe_dir, ConfigOrigin::ExeDir)]);
+    for (dir, origin) in dirs.clone() {
+        if let Some(path) = existing_config_in(dir) {
+            return ConfigLocation { path, origin, exists: true };
+        }
+    }
+    // The exe directory is the last resort even if it is read-only: the
+    // error from writing there names the path.
+    let (dir, origin) = dirs
+        .clone()
+        .find(|(dir, _)| is_writable(dir))
+        .unwrap_or((exe_dir, ConfigOrigin::ExeDir));
+    ConfigLocation {
+        path: dir.join(format!("config.{}", Format::Ron.extension())),
+        origin,
+        exists: false,
+    }
+}
+
+/// The first of `config.ron`, `config.toml` and `config.json` in `dir`.
+fn existing_config_in(dir: &Path) -> Option<PathBuf> {
+    [Format::Ron, Format::Toml, Format::Json]
+        .map(|format| dir.join(format!("config.{}", format.extension())))
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// True if a file can be created in `dir`, or in its nearest existing
+/// ancestor when `dir` itself is yet to be created. Checked by creating and
+/// removing a probe file: on Windows, ACLs (Program Files) are not visible
+/// in the read-only attribute.
+fn is_writable(dir: &Path) -> bool {
+    let Some(existing) = dir.ancestors().find(|a| a.is_dir()) else {
+        return false;
+    };
+    let probe = existing.join(format!(".key-switch-rs-probe-{}", std::process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(file) => {
+            drop(file);
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fresh scratch directory holding `user/` and `exe/`.
+    fn dirs(test: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let root = std::env::temp_dir()
+            .join(format!("key-switch-rs-locate-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let exe = root.join("exe");
+        fs::create_dir_all(&exe).unwrap();
+        (root.clone(), root.join("user").join(USER_DIR_NAME), exe)
+    }
+
+    #[test]
+    fn flag_and_env_var_win_even_when_missing() {
+        let (root, user, exe) = dirs("explicit");
+        fs::write(exe.join("config.ron"), "()").unwrap();
+        let flag = root.join("mine.toml");
+
+        let found = locate_in(Some(&flag), Some("env.ron".into()), Some(&user), &exe);
+        assert_eq!((found.path, found.origin, found.exists), (flag, ConfigOrigin::Flag, false));
+        let found = locate_in(None, Some("env.ron".into()), Some(&user), &exe);
+        assert_eq!((found.path, found.origin), (PathBuf::from("env.ron"), ConfigOrigin::EnvVar));
+        let found = locate_in(None, Some(OsString::new()), Some(&user), &exe);
+        assert_eq!(found.origin, ConfigOrigin::ExeDir);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn existing_user_config_beats_portable_one() {
+        let (root, user, exe) = dirs("existing");
+        fs::write(exe.join("config.json"), "{}").unwrap();
+        let found = locate_in(None, None, Some(&user), &exe);
+        assert_eq!((found.path, found.origin), (exe.join("config.json"), ConfigOrigin::ExeDir));
+        assert!(found.exists);
+
+        fs::create_dir_all(&user).unwrap();
+        fs::write(user.join("config.toml"), "").unwrap();
+        let found = locate_in(None, None, Some(&user), &exe);
+        assert_eq!((found.path, found.origin), (user.join("config.toml"), ConfigOrigin::UserDir));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn default_goes_to_the_first_writable_directory() {
+        let (root, user, exe) = dirs("default");
+        let found = locate_in(None, None, Some(&user), &exe);
+        assert_eq!((&found.path, found.origin), (&user.join("config.ron"), ConfigOrigin::UserDir));
+        assert!(!found.exists);
+        assert!(!user.exists(), "probing must not create the directory");
+        assert!(found.to_string().contains("will be generated in the per-user"));
+
+        let found = locate_in(None, None, None, &exe);
+        assert_eq!((found.path, found.origin), (exe.join("config.ron"), ConfigOrigin::ExeDir));
+        assert_eq!(fs::read_dir(&exe).unwrap().count(), 0, "probe file is removed");
+        fs::remove_dir_all(root).unwrap();
+    }
+}
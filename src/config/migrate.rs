@@ -0,0 +1,138 @@
+//! Config schema versions and the migrations between them.
+//!
+//! A config file can state the shape it is written in with a top-level
+//! `version: N`. The current shape is [`CURRENT_VERSION`], and a file
+//! without `version:` is taken to be in it. Whenever the on-disk shape
+//! changes, the version goes up by one and a step is appended to
+//! [`MIGRATIONS`] that rewrites a document of the previous version into the
+//! new one — typically by parsing it with a frozen copy of the old schema
+//! and printing it with the serializers in `writer.rs`.
+//!
+//! The loader runs every file through the steps from its version up, backs
+//! the original up as `<name>.bak` and saves the result in its place.
+
+use serde::Deserialize;
+
+use super::format::Format;
+
+/// One migration step: the text of a version-N document in, the text of
+/// the same config as version N + 1 (with `version: N + 1`) out, both in
+/// the given format.
+pub(super) type Migration = fn(&str, Format) -> Result<String, String>;
+
+/// `MIGRATIONS[i]` upgrades version `i + 1` to version `i + 2`.
+pub(super) const MIGRATIONS: &[Migration] = &[];
+
+/// The version `config::to_ron_string` writes and the loader reads.
+pub(super) const CURRENT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Just the `version:` field; every other field is skipped.
+#[derive(Deserialize)]
+struct Header {
+    #[serde(default)]
+    version: Option<u32>,
+}
+
+/// The `version:` of a config document. `None` when it has none, or when
+/// the text doesn't parse — the loader's own parse reports that properly.
+fn version_of(text: &str, format: Format) -> Option<u32> {
+    format.parse::<Header>(text).ok()?.version
+}
+
+/// `text` upgraded to the newest version `migrations` reach, as the
+/// version it started at and the new text. `None` if it is already there.
+pub(super) fn migrate(
+    text: &str,
+    format: Format,
+    migrations: &[Migration],
+) -> Result<Option<(u32, String)>, String> {
+    let current = migrations.len() as u32 + 1;
+    let Some(from) = version_of(text, format) else {
+        return Ok(None);
+    };
+    if from == 0 {
+        return Err("`version: 0` is not a config version (versions start at 1)".into());
+    }
+    if from > current {
+        return Err(format!(
+            "config is `version: {}`, newer than this build understands (up to {})",
+            from, current
+        ));
+    }
+    if from == current {
+        return Ok(None);
+    }
+
+    let mut text = text.to_string();
+    for (version, step) in (from..current).zip(&migrations[from as usize - 1..]) {
+        text = step(&text, format)
+            .map_err(|e| format!("migrating from version {} to {}: {}", version, version + 1, e))?;
+        if version_of(&text, format) != Some(version + 1) {
+            return Err(format!(
+                "migrating from version {} to {}: the result is not `version: {}`",
+                version,
+                version + 1,
+                version + 1
+            ));
+        }
+    }
+    Ok(Some((from, text)))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// Version 1 → 2: renames the `legacy_bindings` field to `bindings`.
+    fn rename_bindings(text: &str, _: Format) -> Result<String, String> {
+        Ok(text.replace("legacy_bindings", "bindings").replace("version: 1", "version: 2"))
+    }
+
+    /// Version 2 → 3: nothing changes but the number.
+    fn bump(text: &str, _: Format) -> Result<String, String> {
+        Ok(text.replace("version: 2", "version: 3"))
+    }
+
+    /// A pipeline three versions long, for tests of the machinery.
+    pub(in crate::config) const TEST_MIGRATIONS: &[Migration] = &[rename_bindings, bump];
+
+    fn run(text: &str, migrations: &[Migration]) -> Result<Option<(u32, String)>, String> {
+        migrate(text, Format::Ron, migrations)
+    }
+
+    #[test]
+    fn runs_every_step_from_the_file_version() {
+        let (from, text) = run("(version: 1, legacy_bindings: [])", TEST_MIGRATIONS)
+            .unwrap()
+            .expect("migrated");
+        assert_eq!(from, 1);
+        assert_eq!(text, "(version: 3, bindings: [])");
+
+        let (from, text) = run("(version: 2)", TEST_MIGRATIONS).unwrap().unwrap();
+        assert_eq!((from, text.as_str()), (2, "(version: 3)"));
+    }
+
+    #[test]
+    fn current_and_unversioned_files_are_left_alone() {
+        assert!(run("(version: 3, bindings: [])", TEST_MIGRATIONS).unwrap().is_none());
+        assert!(run("(bindings: [])", TEST_MIGRATIONS).unwrap().is_none());
+        assert!(run("(bindings: [", TEST_MIGRATIONS).unwrap().is_none(), "parse errors are the loader's");
+        assert!(migrate("version = 3", Format::Toml, TEST_MIGRATIONS).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_versions_and_bad_steps_are_errors() {
+        let err = run("(version: 4)", TEST_MIGRATIONS).unwrap_err();
+        assert!(err.contains("newer than this build understands (up to 3)"), "{}", err);
+        assert!(run("(version: 0)", TEST_MIGRATIONS).unwrap_err().contains("start at 1"));
+        let err = migrate(r#"{"version": 4}"#, Format::Json, TEST_MIGRATIONS).unwrap_err();
+        assert!(err.contains("`version: 4`"), "{}", err);
+
+        let forgets_version: &[Migration] = &[|text, _| Ok(text.to_string())];
+        let err = run("(version: 1)", forgets_version).unwrap_err();
+        assert!(err.contains("from version 1 to 2: the result is not `version: 2`"), "{}", err);
+
+        let fails: &[Migration] = &[|_, _| Err("boom".into())];
+        assert_eq!(run("(version: 1)", fails).unwrap_err(), "migrating from version 1 to 2: boom");
+    }
+}
@@ -1,15 +1,41 @@
-//! Configuration: `config.toml` loading, parsing, and conversion into the
-//! runtime `Binding` representation.
+//! Configuration: `config.ron` (or `.toml` / `.json`) loading, parsing, and
+//! conversion into the runtime `Binding` representation.
 //!
 //! Layout:
 //!   - `schema.rs`  — serde structs for the on-disk shape
+//!   - `format.rs`  — RON / TOML / JSON, picked by file extension
 //!   - `parsing.rs` — combo string and WM_* name parsers
+//!   - `locate.rs`  — which config file to use (`--config`, env, user dir, exe dir)
 //!   - `loader.rs`  — file I/O, default-config generation, conversion
+//!   - `migrate.rs` — schema versions and migrations between them
+//!   - `spans.rs`   — source positions for error messages
+//!   - `lint.rs`    — warnings for valid but suspicious bindings
+//!   - `conflicts.rs` — bindings shadowed by other bindings
+//!   - `vars.rs`    — `vars:` and `${...}` substitution in strings
+//!   - `settings.rs` — the `settings:` block and its defaults
+//!   - `writer.rs`  — runtime bindings back to canonical `config.ron` text
+//!   - `import/`    — other tools' configs converted to bindings
 //!
-//! Only `loader` is re-exported; the rest are internal implementation detail.
+//! Only `locate`, `loader`, `settings`, `writer`, `format` and `import`
+//! are re-exported; the rest are internal implementation detail.
 
+mod conflicts;
+mod format;
+mod import;
+mod lint;
 mod loader;
+mod locate;
+mod migrate;
 mod parsing;
 mod schema;
+mod settings;
+mod spans;
+mod vars;
+mod writer;
 
-pub use loader::{default_config_path, load};
+pub use format::Format;
+pub use import::{import_ahk, import_powertoys, Imported, Unsupported};
+pub use loader::{load, load_existing, ConfigError, ConfigSources, IncludeGlob, LoadedConfig};
+pub use locate::{locate_config, ConfigLocation, ConfigOrigin, CONFIG_ENV_VAR};
+pub use settings::Settings;
+pub use writer::{to_ron_string, to_string};
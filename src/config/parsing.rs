@@ -1,15 +1,11 @@
 //! Parses combo strings (`"Ctrl+Shift+Esc"`) and WM_* names.
 
-use windows::Win32::UI::{
-    Input::KeyboardAndMouse::VIRTUAL_KEY,
-    WindowsAndMessaging::*,
-};
-
 use crate::data::key_combination::KeyCombination;
-use crate::data::vk_name::parse_vk;
+use crate::data::vk::Vk;
+use crate::data::vk_name::{parse_vk, suggest_vk_name};
 
 /// Splits `"Ctrl+Shift+Esc"` into segments by `'+'`, parses each into a
-/// `VIRTUAL_KEY`, and returns the assembled `KeyCombination`.
+/// `Vk`, and returns the assembled `KeyCombination`.
 ///
 /// Empty input or any unrecognised segment is a hard error — surfacing it to
 /// the user is much better than silently dropping a binding.
@@ -19,14 +15,14 @@ pub(crate) fn parse_combo(s: &str) -> Result<KeyCombination, String> {
         return Err("combo is empty".to_string());
     }
 
-    let mut keys: Vec<VIRTUAL_KEY> = Vec::new();
+    let mut keys: Vec<Vk> = Vec::new();
     for raw_segment in trimmed.split('+') {
         let segment = raw_segment.trim();
         if segment.is_empty() {
             return Err(format!("empty segment in combo {:?} (stray '+'?)", s));
         }
         let vk = parse_vk(segment).ok_or_else(|| {
-            format!("unknown key {:?} in combo {:?}", segment, s)
+            format!("unknown key {:?} in combo {:?}{}", segment, s, did_you_mean(segment))
         })?;
         keys.push(vk);
     }
@@ -34,9 +30,32 @@ pub(crate) fn parse_combo(s: &str) -> Result<KeyCombination, String> {
     Ok(KeyCombination::from_keys(keys))
 }
 
-/// Resolves a symbolic WM_* name to its numeric value. Only the constants
-/// most likely to appear in user configs are mapped; anything else should be
-/// written as a numeric literal in TOML.
+/// `" (did you mean \"Ctrl\"?)"` for a key name `parse_vk` rejected, or an
+/// empty string when nothing known is close.
+pub(crate) fn did_you_mean(name: &str) -> String {
+    suggest_vk_name(name)
+        .map(|s| format!(" (did you mean {:?}?)", s))
+        .unwrap_or_default()
+}
+
+/// The WM_* constants most likely to appear in user configs, by name, with
+/// their `WinUser.h` values. Anything else should be written as a numeric
+/// literal.
+const WM_NAMES: &[(&str, u32)] = &[
+    ("WM_CLOSE",                  0x0010),
+    ("WM_QUIT",                   0x0012),
+    ("WM_DESTROY",                0x0002),
+    ("WM_COMMAND",                0x0111),
+    ("WM_SYSCOMMAND",             0x0112),
+    ("WM_INPUTLANGCHANGEREQUEST", 0x0050),
+    ("WM_KEYDOWN",                0x0100),
+    ("WM_KEYUP",                  0x0101),
+    ("WM_SYSKEYDOWN",             0x0104),
+    ("WM_SYSKEYUP",               0x0105),
+    ("WM_HOTKEY",                 0x0312),
+];
+
+/// Resolves a symbolic WM_* name to its numeric value, from [`WM_NAMES`].
 pub(crate) fn parse_wm_name(name: &str) -> Option<u32> {
     let normalized: String = name
         .chars()
@@ -44,26 +63,21 @@ pub(crate) fn parse_wm_name(name: &str) -> Option<u32> {
         .flat_map(|c| c.to_uppercase())
         .collect();
 
-    match normalized.as_str() {
-        "WM_CLOSE"                  => Some(WM_CLOSE),
-        "WM_QUIT"                   => Some(WM_QUIT),
-        "WM_DESTROY"                => Some(WM_DESTROY),
-        "WM_COMMAND"                => Some(WM_COMMAND),
-        "WM_SYSCOMMAND"             => Some(WM_SYSCOMMAND),
-        "WM_INPUTLANGCHANGEREQUEST" => Some(WM_INPUTLANGCHANGEREQUEST),
-        "WM_KEYDOWN"                => Some(WM_KEYDOWN),
-        "WM_KEYUP"                  => Some(WM_KEYUP),
-        "WM_SYSKEYDOWN"             => Some(WM_SYSKEYDOWN),
-        "WM_SYSKEYUP"               => Some(WM_SYSKEYUP),
-        "WM_HOTKEY"                 => Some(WM_HOTKEY),
-        _ => None,
-    }
+    WM_NAMES
+        .iter()
+        .find(|(known, _)| *known == normalized)
+        .map(|&(_, code)| code)
+}
+
+/// The name [`parse_wm_name`] accepts for `code`, if it has one.
+pub(crate) fn wm_name(code: u32) -> Option<&'static str> {
+    WM_NAMES.iter().find(|&&(_, known)| known == code).map(|&(name, _)| name)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use crate::data::vk::*;
 
     #[test]
     fn parse_combo_single_key() {
@@ -120,9 +134,9 @@ mod tests {
 
     #[test]
     fn parse_wm_known() {
-        assert_eq!(parse_wm_name("WM_CLOSE"), Some(WM_CLOSE));
-        assert_eq!(parse_wm_name("wm_close"), Some(WM_CLOSE));
-        assert_eq!(parse_wm_name("WM_INPUTLANGCHANGEREQUEST"), Some(WM_INPUTLANGCHANGEREQUEST));
+        assert_eq!(parse_wm_name("WM_CLOSE"), Some(0x0010));
+        assert_eq!(parse_wm_name("wm_close"), Some(0x0010));
+        assert_eq!(parse_wm_name("WM_INPUTLANGCHANGEREQUEST"), Some(0x0050));
     }
 
     #[test]
@@ -130,4 +144,11 @@ mod tests {
         assert_eq!(parse_wm_name("WM_NOT_REAL"), None);
         assert_eq!(parse_wm_name(""), None);
     }
+
+    #[test]
+    fn wm_name_is_the_inverse_of_parse() {
+        assert_eq!(wm_name(0x0010), Some("WM_CLOSE"));
+        assert_eq!(wm_name(0x0312).and_then(parse_wm_name), Some(0x0312));
+        assert_eq!(wm_name(0x7FFF), None);
+    }
 }
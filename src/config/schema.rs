@@ -2,8 +2,8 @@
 //!
 //! The on-disk shape is intentionally close to — but not identical to —
 //! the runtime `BindAction`/`SequenceStep`/`Binding` types. The gap is the
-//! VK-bearing fields: at runtime we hold `VIRTUAL_KEY` / `Vec<VIRTUAL_KEY>`,
-//! but those come from the `windows` crate and have no `Deserialize` impl.
+//! VK-bearing fields: at runtime we hold `Vk` / `Vec<Vk>`, which have no
+//! `Deserialize` impl.
 //! The user-facing form keeps them as human-readable strings (`"Enter"`,
 //! `"Ctrl+S"`); the loader parses them into VKs after deserialization.
 //!
@@ -12,85 +12,277 @@
 //! no `untagged` discrimination, no `rename_all`. RON's grammar already
 //! understands `Variant`, `Variant(arg)`, and `Variant { field: ... }`,
 //! which is the whole point of choosing it over TOML.
+//!
+//! Bindings and actions serialize back out too, for `config::to_ron_string`.
+//! Every field skips serialization when it holds its default, so written
+//! configs spell out only what differs from the defaults.
+
+use std::collections::BTreeMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+/// The current version of the on-disk shape. A top-level `version: N`
+/// says which version a file is written in; `migrate.rs` reads it and
+/// upgrades older files before they get here, so it is not a field.
 #[derive(Debug, Deserialize)]
 pub(crate) struct RawConfig {
+    /// Other config files merged into this one, as if pasted at the top.
+    /// Relative paths resolve against this file's directory; `*`, `?` and
+    /// `[...]` globs expand to every matching file, in sorted order:
+    /// ```ron
+    /// include: ["team.ron", "local/*.ron"],
+    /// ```
+    #[serde(default)]
+    pub include: Vec<String>,
     /// Top-level list of bindings. Empty / missing is OK (loader warns but
     /// doesn't fail), so iterating on the file never crashes the daemon.
     #[serde(default)]
     pub bindings: Vec<RawBinding>,
+    /// Named binding layers, same entry format as `bindings`. A layer's
+    /// bindings are live only while it is switched on by one of the
+    /// `Layer*` actions:
+    /// ```ron
+    /// layers: { "nav": [(keys: "h", action: PressKey("Left"))] },
+    /// ```
+    #[serde(default)]
+    pub layers: BTreeMap<String, Vec<RawBinding>>,
+    /// Named actions, referenced from bindings and sequences with
+    /// `Ref("name")` instead of repeating the same body:
+    /// ```ron
+    /// actions: { "save_all": Sequence([Combo("Ctrl+K"), Key("S")]) },
+    /// ```
+    #[serde(default)]
+    pub actions: BTreeMap<String, RawAction>,
+    /// Named strings, substituted into `Launch` / `OpenUrl` / `Text`
+    /// strings as `${name}`, next to `${env:NAME}` for environment
+    /// variables; `$$` is a literal `$`:
+    /// ```ron
+    /// vars: { "proj": "${env:USERPROFILE}\\work" },
+    /// ```
+    #[serde(default)]
+    pub vars: BTreeMap<String, String>,
+    /// Defaults for what bindings leave out, and how the daemon runs. See
+    /// [`RawSettings`].
+    #[serde(default)]
+    pub settings: RawSettings,
 }
 
-#[derive(Debug, Deserialize)]
+/// The top-level `settings:` block. Every field is optional; a missing one
+/// keeps the built-in default (see [`super::settings::Settings`]), and a
+/// binding that spells a value out overrides the setting:
+/// ```ron
+/// settings: (sequence_gap_ms: 700, repeat: false, ipc: false),
+/// ```
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub(crate) struct RawSettings {
+    /// `max_gap_ms` of a `sequence:` that leaves it out.
+    pub sequence_gap_ms: Option<u64>,
+    /// `window_ms` of a `chord:` that leaves it out.
+    pub chord_window_ms: Option<u64>,
+    /// `term_ms` of a `TapDance` that leaves it out: how long to wait for
+    /// another tap.
+    pub tap_term_ms: Option<u64>,
+    /// `term_ms` of a `ModTap` that leaves it out: how long the key has to
+    /// be held to act as the modifier.
+    pub hold_term_ms: Option<u64>,
+    /// `repeat:` of a binding that leaves it out.
+    pub repeat: Option<bool>,
+    /// How long the config watcher waits for a burst of saves to settle.
+    pub reload_debounce_ms: Option<u64>,
+    /// Whether the daemon serves `swch` over its named pipe.
+    pub ipc: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct RawBinding {
+    /// Stable identifier, unique across the config. Errors, listings and
+    /// reload reports name the binding by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Human-readable label, shown next to the binding in listings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
     /// Combo-style binding. Mutually exclusive with `sequence` and `chord`.
     /// The loader rejects bindings that set zero or more than one of these.
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub keys: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub sequence: Option<SequenceSpec>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub chord: Option<ChordSpec>,
-    pub action: RawAction,
+    /// Required, except with `trigger: ModTap(...)` / `TapDance(...)`,
+    /// which carry their actions themselves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<RawAction>,
 
-    #[serde(default = "default_true")]
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
     pub block_default: bool,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "is_false")]
     pub block_original_combo: bool,
 
     /// Foreground-window guard. Missing or `Always` means the binding fires
     /// unconditionally. Anything else gates the binding on focused app /
     /// title — see [`RawCondition`].
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "RawCondition::is_always")]
     pub when: RawCondition,
 
     /// Resolution mode for *when* the action fires given the combo just
     /// triggered. Missing or `Immediate` means the existing "fire on
     /// key-down" behaviour; `Tap`/`Hold`/`DoubleTap` defer to the
     /// gesture state machine. See [`RawTrigger`].
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "RawTrigger::is_immediate")]
     pub trigger: RawTrigger,
+
+    /// Whether an `Immediate` combo fires again on auto-repeat while held.
+    /// Missing means `settings: (repeat: ...)`, itself `true` by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<bool>,
+
+    /// How the `keys:` must be held. Missing or `Subset` lets other keys
+    /// be held too. Rejected on `sequence` / `chord` bindings. See
+    /// [`RawMatchMode`].
+    #[serde(rename = "match", default, skip_serializing_if = "RawMatchMode::is_subset")]
+    pub match_mode: RawMatchMode,
+
+    /// What happens to swallowed keys when a `sequence` / `chord` fails to
+    /// complete. Missing or `Drop` keeps the original "keys are lost"
+    /// behaviour. Rejected on `keys:` bindings. See [`RawOnFail`].
+    #[serde(default, skip_serializing_if = "RawOnFail::is_drop")]
+    pub on_fail: RawOnFail,
+
+    /// Groups the binding belongs to; `swch enable-tag` / `disable-tag`
+    /// switch every binding with a tag on or off at runtime.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// `false` loads the binding but leaves it off until one of its tags
+    /// is enabled.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+}
+
+/// On-disk mirror of [`crate::data::on_fail::OnFail`].
+///
+/// RON syntax:
+/// ```ron
+/// on_fail: Replay,
+/// on_fail: Action(PressKey("Esc")),
+/// ```
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) enum RawOnFail {
+    #[default]
+    Drop,
+    Replay,
+    Action(RawAction),
 }
 
 /// On-disk mirror of [`crate::data::trigger::Trigger`]. The tuple argument
 /// for the deferred variants is the term in milliseconds — RON literal form
 /// `Tap(200)`, `Hold(200)`, `DoubleTap(250)`.
-#[derive(Debug, Clone, Copy, Deserialize, Default)]
+///
+/// `ModTap` and `TapDance` are struct literals and replace the binding's
+/// `action:`. A `term_ms` they leave out comes from `settings:`
+/// (`tap_term_ms` / `hold_term_ms`). `TapDance` hold actions are keyed by
+/// tap count, from 1:
+/// ```ron
+/// trigger: ModTap(tap: PressKey("Esc"), hold: "Ctrl", term_ms: 200, flavor: HoldOnOtherKeyPress),
+/// trigger: TapDance(term_ms: 200, taps: [SwitchLanguage, SwitchLanguageBackward], holds: { 1: ToggleCapsLock }),
+/// ```
+#[derive(Debug, Deserialize, Serialize, Default)]
 pub(crate) enum RawTrigger {
     #[default]
     Immediate,
     Tap(u64),
     Hold(u64),
     DoubleTap(u64),
+    TapDance {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        term_ms: Option<u64>,
+        taps: Vec<RawAction>,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        holds: BTreeMap<usize, RawAction>,
+    },
+    ModTap {
+        tap: RawAction,
+        hold: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        term_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "RawModTapFlavor::is_permissive_hold")]
+        flavor: RawModTapFlavor,
+    },
+}
+
+/// On-disk mirror of [`crate::data::key_combination::MatchMode`].
+///
+/// RON syntax:
+/// ```ron
+/// (keys: "Ctrl+K", action: ..., match: Exact),   // not on Ctrl+Shift+K
+/// (keys: "Shift+CapsLock", action: ..., match: Ordered), // Shift first
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) enum RawMatchMode {
+    #[default]
+    Subset,
+    Exact,
+    Ordered,
+}
+
+impl RawMatchMode {
+    fn is_subset(&self) -> bool {
+        matches!(self, RawMatchMode::Subset)
+    }
+}
+
+impl RawOnFail {
+    fn is_drop(&self) -> bool {
+        matches!(self, RawOnFail::Drop)
+    }
+}
+
+impl RawTrigger {
+    fn is_immediate(&self) -> bool {
+        matches!(self, RawTrigger::Immediate)
+    }
+}
+
+/// On-disk mirror of [`crate::data::trigger::ModTapFlavor`].
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default)]
+pub(crate) enum RawModTapFlavor {
+    HoldOnOtherKeyPress,
+    /// `Balanced` is ZMK's name for the same behaviour.
+    #[default]
+    #[serde(alias = "Balanced")]
+    PermissiveHold,
+    TapPreferred,
+}
+
+impl RawModTapFlavor {
+    fn is_permissive_hold(&self) -> bool {
+        matches!(self, RawModTapFlavor::PermissiveHold)
+    }
 }
 
 /// Vim/Emacs-style leader sequence. Used as `sequence: (steps: ["g", "g"])`
 /// at the binding level (RON struct literal).
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct SequenceSpec {
     pub steps: Vec<String>,
-    #[serde(default = "default_seq_gap_ms")]
-    pub max_gap_ms: u64,
+    /// Missing means `settings: (sequence_gap_ms: ...)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_gap_ms: Option<u64>,
 }
 
 /// Simultaneous chord (all keys go down within a tight window).
 /// Used as `chord: (keys: ["j", "k"])` at the binding level.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) struct ChordSpec {
     pub keys: Vec<String>,
-    #[serde(default = "default_chord_window_ms")]
-    pub window_ms: u64,
-}
-
-fn default_seq_gap_ms() -> u64 {
-    500
-}
-
-fn default_chord_window_ms() -> u64 {
-    50
+    /// Missing means `settings: (chord_window_ms: ...)`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_ms: Option<u64>,
 }
 
 /// Predicate against the focused window's exe name / title. On-disk mirror
@@ -104,7 +296,7 @@ fn default_chord_window_ms() -> u64 {
 /// when: And([AppEquals("code.exe"), Not(TitleContains("Settings"))]),
 /// when: Or([AppEquals("chrome.exe"), AppEquals("firefox.exe")]),
 /// ```
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize, Serialize, Default)]
 pub(crate) enum RawCondition {
     #[default]
     Always,
@@ -116,8 +308,14 @@ pub(crate) enum RawCondition {
     Or(Vec<RawCondition>),
 }
 
+impl RawCondition {
+    fn is_always(&self) -> bool {
+        matches!(self, RawCondition::Always)
+    }
+}
+
 /// `BindAction` mirror with VK fields kept as strings until the loader runs.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) enum RawAction {
     SwitchLanguage,
     SwitchLanguageBackward,
@@ -128,9 +326,9 @@ pub(crate) enum RawAction {
     /// `lparam` default to 0.
     PostMessage {
         msg: MessageRef,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "is_zero")]
         wparam: u64,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "is_zero")]
         lparam: i64,
     },
     /// `Sequence([Window(Restore), Delay(100), Text("hi"), ...])`.
@@ -138,7 +336,7 @@ pub(crate) enum RawAction {
     /// `Launch(exe: "notepad.exe")` or `Launch(exe: "code.exe", args: ["D:\\"])`.
     Launch {
         exe: String,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
         args: Vec<String>,
     },
     /// `OpenUrl("https://...")` / `OpenUrl("mailto:foo@bar")` /
@@ -146,13 +344,22 @@ pub(crate) enum RawAction {
     OpenUrl(String),
     /// `Media(PlayPause)` / `Media(VolumeUp)` / ...
     Media(MediaKeyRef),
+    /// `LayerOn("nav")` etc. — switch a layer from `layers:`. See
+    /// [`crate::data::action::LayerOp`] for what each one does.
+    LayerOn(String),
+    LayerOff(String),
+    LayerToggle(String),
+    LayerMomentary(String),
+    LayerOneShot(String),
+    /// `Ref("save_all")` — the action of that name under `actions:`.
+    Ref(String),
     DoNothing,
 }
 
 /// On-disk representation of the runtime `MediaKey` enum. Same variants;
-/// kept separate so the runtime enum can live in `core::windows_actions`
+/// kept separate so the runtime enum can live in `data::action`
 /// without depending on serde.
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub(crate) enum MediaKeyRef {
     PlayPause,
     Stop,
@@ -166,7 +373,7 @@ pub(crate) enum MediaKeyRef {
 /// Either a symbolic `"WM_CLOSE"` (string) or a numeric `0x10` (integer).
 /// `untagged` because RON's grammar lets us put either kind of literal
 /// directly in the field — no `Name(...)` / `Code(...)` wrapper required.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub(crate) enum MessageRef {
     Code(u32),
@@ -175,8 +382,9 @@ pub(crate) enum MessageRef {
 
 /// One step inside a `Sequence(...)`. Direct enum-variant syntax in RON:
 /// `Window(Restore)`, `Delay(100)`, `Text("hi")`, `Key("Enter")`,
-/// `Combo("Ctrl+S")`, `Launch(...)`, `OpenUrl("...")`, `Media(PlayPause)`.
-#[derive(Debug, Deserialize)]
+/// `Combo("Ctrl+S")`, `Launch(...)`, `OpenUrl("...")`, `Media(PlayPause)`,
+/// `Ref("save_all")`.
+#[derive(Debug, Deserialize, Serialize)]
 pub(crate) enum RawStep {
     Window(RawWindowKind),
     Delay(u64),
@@ -185,14 +393,17 @@ pub(crate) enum RawStep {
     Combo(String),
     Launch {
         exe: String,
-        #[serde(default)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
         args: Vec<String>,
     },
     OpenUrl(String),
     Media(MediaKeyRef),
+    /// A named action from `actions:`, run inline: a `Sequence` runs all
+    /// its steps, `PressKey`/`Launch`/`OpenUrl`/`Media` their step form.
+    Ref(String),
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 pub(crate) enum RawWindowKind {
     Minimize,
     Maximize,
@@ -203,3 +414,15 @@ pub(crate) enum RawWindowKind {
 fn default_true() -> bool {
     true
 }
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
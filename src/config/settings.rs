@@ -0,0 +1,113 @@
+//! `settings:` — the defaults bindings fall back on, and how the daemon
+//! runs.
+//!
+//! The binding defaults are applied by the loader, so a reload that
+//! changes them rebuilds every binding that relies on them. The rest
+//! (`reload_debounce_ms`, `ipc`) are read by the daemon at startup and
+//! again after every reload.
+//!
+//! With `include:`, files are read in load order and a later file's value
+//! wins — the including file overrides what it includes.
+
+use std::fmt;
+use std::time::Duration;
+
+use super::schema::RawSettings;
+
+/// Resolved `settings:`, with the built-in defaults filled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub sequence_gap_ms: u64,
+    pub chord_window_ms: u64,
+    pub tap_term_ms: u64,
+    pub hold_term_ms: u64,
+    pub repeat: bool,
+    pub reload_debounce_ms: u64,
+    pub ipc: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sequence_gap_ms: 500,
+            chord_window_ms: 50,
+            tap_term_ms: 200,
+            hold_term_ms: 200,
+            repeat: true,
+            // Covers VS Code / Notepad++ / vim save patterns (write-temp,
+            // rename, set permissions, fsync) without being long enough for
+            // a user to notice the delay between Ctrl+S and the new bindings
+            // going live.
+            reload_debounce_ms: 250,
+            ipc: true,
+        }
+    }
+}
+
+impl Settings {
+    /// Overrides every value `raw` sets.
+    pub(super) fn apply(&mut self, raw: &RawSettings) {
+        let RawSettings {
+            sequence_gap_ms,
+            chord_window_ms,
+            tap_term_ms,
+            hold_term_ms,
+            repeat,
+            reload_debounce_ms,
+            ipc,
+        } = *raw;
+        self.sequence_gap_ms = sequence_gap_ms.unwrap_or(self.sequence_gap_ms);
+        self.chord_window_ms = chord_window_ms.unwrap_or(self.chord_window_ms);
+        self.tap_term_ms = tap_term_ms.unwrap_or(self.tap_term_ms);
+        self.hold_term_ms = hold_term_ms.unwrap_or(self.hold_term_ms);
+        self.repeat = repeat.unwrap_or(self.repeat);
+        self.reload_debounce_ms = reload_debounce_ms.unwrap_or(self.reload_debounce_ms);
+        self.ipc = ipc.unwrap_or(self.ipc);
+    }
+
+    pub fn reload_debounce(&self) -> Duration {
+        Duration::from_millis(self.reload_debounce_ms)
+    }
+
+    /// One `name: old -> new` line per setting that differs in `new`, for
+    /// the reload report.
+    pub fn changes(&self, new: &Settings) -> Vec<String> {
+        let mut changes = Vec::new();
+        let mut compare = |name: &str, old: &dyn fmt::Display, new: &dyn fmt::Display| {
+            let (old, new) = (old.to_string(), new.to_string());
+            if old != new {
+                changes.push(format!("{}: {} -> {}", name, old, new));
+            }
+        };
+        compare("sequence_gap_ms", &self.sequence_gap_ms, &new.sequence_gap_ms);
+        compare("chord_window_ms", &self.chord_window_ms, &new.chord_window_ms);
+        compare("tap_term_ms", &self.tap_term_ms, &new.tap_term_ms);
+        compare("hold_term_ms", &self.hold_term_ms, &new.hold_term_ms);
+        compare("repeat", &self.repeat, &new.repeat);
+        compare("reload_debounce_ms", &self.reload_debounce_ms, &new.reload_debounce_ms);
+        compare("ipc", &self.ipc, &new.ipc);
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_values_win_and_changes_are_listed() {
+        let mut settings = Settings::default();
+        let raw = RawSettings { sequence_gap_ms: Some(700), ipc: Some(false), ..RawSettings::default() };
+        settings.apply(&raw);
+        settings.apply(&RawSettings { sequence_gap_ms: Some(600), ..RawSettings::default() });
+        assert_eq!(
+            (settings.sequence_gap_ms, settings.ipc, settings.chord_window_ms),
+            (600, false, 50)
+        );
+        assert_eq!(
+            Settings::default().changes(&settings),
+            ["sequence_gap_ms: 500 -> 600", "ipc: true -> false"]
+        );
+        assert!(settings.changes(&settings).is_empty());
+    }
+}
@@ -0,0 +1,377 @@
+//! Source positions for semantic config errors.
+//!
+//! RON reports a position for syntax errors only: once the text has
+//! deserialized into `RawConfig`, where each value came from is gone. This
+//! module re-reads the (already well-formed) text with a small structural
+//! scanner and records the line and column every value starts at, so the
+//! loader can point an error at `config.ron:42:15` instead of "binding #37".
+//!
+//! The scanner only needs to know RON's brackets, strings, chars and
+//! comments; it never interprets values. It gives up (returns `None`) on
+//! anything it doesn't recognise, and the loader then falls back to
+//! position-less messages.
+
+/// One value in the source: where it starts, and what's inside it.
+#[derive(Debug)]
+pub(crate) struct Node {
+    pub line: usize,
+    pub column: usize,
+    /// Struct fields and map entries, keyed by name; tuple and list
+    /// elements, unkeyed, in order.
+    children: Vec<(Option<String>, Node)>,
+}
+
+impl Node {
+    /// The value of struct field or map entry `name`.
+    pub fn field(&self, name: &str) -> Option<&Node> {
+        self.children
+            .iter()
+            .find(|(key, _)| key.as_deref() == Some(name))
+            .map(|(_, node)| node)
+    }
+
+    /// Tuple or list element `index`, counting unkeyed children only.
+    pub fn item(&self, index: usize) -> Option<&Node> {
+        self.children
+            .iter()
+            .filter(|(key, _)| key.is_none())
+            .nth(index)
+            .map(|(_, node)| node)
+    }
+}
+
+/// A step from a value to one of its children.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Seg {
+    /// Struct field.
+    Field(&'static str),
+    /// Map entry, by key.
+    Key(String),
+    /// Tuple or list element.
+    Item(usize),
+}
+
+/// An error or warning about one binding or named action: the message, and
+/// the path from that entry down to the value it is about, which
+/// [`Node::locate`] turns into a line and column.
+#[derive(Debug, Clone)]
+pub(crate) struct Problem {
+    pub path: Vec<Seg>,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn at(seg: Seg, message: impl Into<String>) -> Self {
+        Self { path: vec![seg], message: message.into() }
+    }
+
+    /// The same problem, seen from the value that holds this one at `seg`.
+    pub fn under(mut self, seg: Seg) -> Self {
+        self.path.insert(0, seg);
+        self
+    }
+
+    /// Prefixes the message with `label: `.
+    pub fn labelled(mut self, label: impl std::fmt::Display) -> Self {
+        self.message = format!("{}: {}", label, self.message);
+        self
+    }
+}
+
+impl From<String> for Problem {
+    fn from(message: String) -> Self {
+        Self { path: Vec::new(), message }
+    }
+}
+
+impl Node {
+    /// Follows `path` as far as the tree allows and returns the deepest
+    /// node reached.
+    pub fn locate(&self, path: &[Seg]) -> &Node {
+        let mut node = self;
+        for seg in path {
+            let next = match seg {
+                Seg::Field(name) => node.field(name),
+                Seg::Key(key) => node.field(key),
+                Seg::Item(index) => node.item(*index),
+            };
+            match next {
+                Some(next) => node = next,
+                None => break,
+            }
+        }
+        node
+    }
+}
+
+/// Scans a whole config file. `None` if the text isn't shaped the way the
+/// scanner expects.
+pub(crate) fn scan(text: &str) -> Option<Node> {
+    let mut scanner = Scanner {
+        src: text,
+        pos: 0,
+        line_starts: std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect(),
+    };
+    scanner.skip_ws();
+    // Inner attributes: `#![enable(implicit_some)]`.
+    while scanner.rest().starts_with("#!") {
+        scanner.pos += 2;
+        scanner.skip_ws();
+        scanner.value()?;
+        scanner.skip_ws();
+    }
+    scanner.value()
+}
+
+struct Scanner<'a> {
+    src: &'a str,
+    pos: usize,
+    /// Byte offset of the start of every line.
+    line_starts: Vec<usize>,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// 1-based line and column (in chars) of byte offset `at`.
+    fn position(&self, at: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|&start| start <= at);
+        let start = self.line_starts[line - 1];
+        (line, self.src[start..at].chars().count() + 1)
+    }
+
+    /// Skips whitespace and `//` / nested `/* */` comments.
+    fn skip_ws(&mut self) {
+        loop {
+            let trimmed = self.rest().trim_start();
+            self.pos = self.src.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                let mut depth = 0usize;
+                while self.pos < self.src.len() {
+                    let rest = self.rest();
+                    if rest.starts_with("/*") {
+                        depth += 1;
+                        self.pos += 2;
+                    } else if rest.starts_with("*/") {
+                        depth -= 1;
+                        self.pos += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        self.pos += rest.chars().next().map_or(1, char::len_utf8);
+                    }
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<Node> {
+        self.skip_ws();
+        let (line, column) = self.position(self.pos);
+        let children = match self.peek()? {
+            '(' => self.group(')')?,
+            '[' => self.group(']')?,
+            '{' => self.map()?,
+            '"' | '\'' => {
+                self.quoted()?;
+                Vec::new()
+            }
+            _ if self.raw_string()? => Vec::new(),
+            _ => {
+                let word = self.word()?;
+                self.skip_ws();
+                if self.peek() != Some('(') {
+                    Vec::new()
+                } else if word == "Some" {
+                    // `Some(x)` written out despite `IMPLICIT_SOME`: look
+                    // through it, so paths don't depend on the spelling.
+                    let mut inner = self.group(')')?;
+                    if inner.len() != 1 || inner[0].0.is_some() {
+                        return None;
+                    }
+                    let node = inner.pop()?.1;
+                    return Some(Node { line, column, children: node.children });
+                } else {
+                    self.group(')')?
+                }
+            }
+        };
+        Some(Node { line, column, children })
+    }
+
+    /// `( ... )` or `[ ... ]`: comma-separated values, where `ident: value`
+    /// inside parentheses is a struct field.
+    fn group(&mut self, close: char) -> Option<Vec<(Option<String>, Node)>> {
+        self.pos += 1;
+        let mut children = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek()? == close {
+                self.pos += 1;
+                return Some(children);
+            }
+            let key = if close == ')' { self.field_name() } else { None };
+            children.push((key, self.value()?));
+            self.separator(close)?;
+        }
+    }
+
+    /// `{ key: value, ... }`. Keys are recorded unquoted.
+    fn map(&mut self) -> Option<Vec<(Option<String>, Node)>> {
+        self.pos += 1;
+        let mut children = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek()? == '}' {
+                self.pos += 1;
+                return Some(children);
+            }
+            let start = self.pos;
+            self.value()?;
+            let key = self.src[start..self.pos].trim_matches('"').to_string();
+            self.skip_ws();
+            if self.peek()? != ':' {
+                return None;
+            }
+            self.pos += 1;
+            children.push((Some(key), self.value()?));
+            self.separator('}')?;
+        }
+    }
+
+    /// After an element: a comma, or the closing bracket (left in place).
+    fn separator(&mut self, close: char) -> Option<()> {
+        self.skip_ws();
+        match self.peek()? {
+            ',' => self.pos += 1,
+            c if c == close => {}
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// `name:` at the cursor — consumed and returned — or nothing, with the
+    /// cursor untouched.
+    fn field_name(&mut self) -> Option<String> {
+        let start = self.pos;
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return None;
+        }
+        let name = self.src[start..start + len].to_string();
+        self.pos += len;
+        self.skip_ws();
+        if self.rest().starts_with(':') && !self.rest().starts_with("::") {
+            self.pos += 1;
+            Some(name)
+        } else {
+            self.pos = start;
+            None
+        }
+    }
+
+    /// An identifier, number, or other bare token.
+    fn word(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        let len = self
+            .rest()
+            .find(|c: char| c.is_whitespace() || ",:()[]{}\"'/".contains(c))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return None;
+        }
+        self.pos += len;
+        Some(&self.src[start..start + len])
+    }
+
+    /// A `"string"` or `'c'`, honouring backslash escapes.
+    fn quoted(&mut self) -> Option<()> {
+        let quote = self.peek()?;
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            if c == '\\' {
+                chars.next();
+            } else if c == quote {
+                self.pos += i + 1;
+                return Some(());
+            }
+        }
+        None
+    }
+
+    /// A raw string `r#"..."#` (or byte string `b"..."` / `br"..."`) at
+    /// the cursor. `Some(false)` if there isn't one.
+    fn raw_string(&mut self) -> Option<bool> {
+        let rest = self.rest();
+        let after_b = rest.strip_prefix('b').unwrap_or(rest);
+        if after_b.starts_with('"') {
+            self.pos += rest.len() - after_b.len();
+            self.quoted()?;
+            return Some(true);
+        }
+        let Some(after_r) = after_b.strip_prefix('r') else {
+            return Some(false);
+        };
+        let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+        let Some(body) = after_r[hashes..].strip_prefix('"') else {
+            return Some(false);
+        };
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let end = body.find(&terminator)?;
+        self.pos = self.src.len() - body.len() + end + terminator.len();
+        Some(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_fields_items_and_map_entries() {
+        let text = "#![enable(implicit_some)]\n(\n    // comment (with parens]\n    bindings: [\n        (keys: \"Ctrl+S\", action: Sequence([Key(\"a\"), Text(r#\"x\"y\"#)])),\n    ],\n    layers: { \"nav\": [(keys: \"h\", action: PressKey(\"Left\"))] },\n)";
+        let root = scan(text).expect("scans");
+        let binding = root.field("bindings").and_then(|b| b.item(0)).unwrap();
+        assert_eq!((binding.line, binding.column), (5, 9));
+        let keys = binding.field("keys").unwrap();
+        assert_eq!((keys.line, keys.column), (5, 16));
+        let step = binding.locate(&[Seg::Field("action"), Seg::Item(0), Seg::Item(1)]);
+        assert_eq!((step.line, step.column), (5, 54));
+        let nav = root.locate(&[Seg::Field("layers"), Seg::Key("nav".into()), Seg::Item(0)]);
+        assert_eq!((nav.line, nav.column), (7, 23));
+    }
+
+    #[test]
+    fn locate_stops_at_the_deepest_known_node() {
+        let root = scan("(bindings: [(keys: \"a\")])").unwrap();
+        let node = root.locate(&[Seg::Field("bindings"), Seg::Item(0), Seg::Field("action")]);
+        assert_eq!((node.line, node.column), (1, 13));
+    }
+
+    #[test]
+    fn written_out_some_is_transparent() {
+        let root = scan("(keys: Some(\"a\"), on_fail: Some(Action(DoNothing)))").unwrap();
+        assert!(root.locate(&[Seg::Field("on_fail"), Seg::Item(0)]).column > 30);
+    }
+
+    #[test]
+    fn unbalanced_text_gives_up() {
+        assert!(scan("(bindings: [").is_none());
+    }
+}
@@ -0,0 +1,144 @@
+//! `vars:` and the `${...}` substitutions in config strings.
+//!
+//! `Launch` / `OpenUrl` / `Text` strings may use `${name}` for a variable
+//! defined under `vars:` and `${env:NAME}` for an environment variable of
+//! the daemon. A variable's own value may use either. `$$` is a literal
+//! `$`; a `$` followed by anything else is left as it is.
+
+use std::collections::BTreeMap;
+
+/// The `vars:` of every loaded file, by name.
+pub(super) struct Vars<'a> {
+    /// Each value with the name of the file it was defined in.
+    defined: BTreeMap<&'a str, (&'a str, Option<&'a str>)>,
+    env: fn(&str) -> Option<String>,
+}
+
+impl Default for Vars<'_> {
+    fn default() -> Self {
+        Self { defined: BTreeMap::new(), env: |name| std::env::var(name).ok() }
+    }
+}
+
+impl<'a> Vars<'a> {
+    /// Adds a definition from the file called `file`. A duplicate name
+    /// keeps the first definition and fails with the file that one came
+    /// from.
+    pub fn insert(
+        &mut self,
+        name: &'a str,
+        value: &'a str,
+        file: Option<&'a str>,
+    ) -> Result<(), Option<&'a str>> {
+        if let Some(&(_, first)) = self.defined.get(name) {
+            return Err(first);
+        }
+        self.defined.insert(name, (value, file));
+        Ok(())
+    }
+
+    /// `text` with every variable substituted. A variable whose own value
+    /// doesn't expand is only named here; [`Vars::check`] reports why.
+    pub fn expand(&self, text: &str) -> Result<String, String> {
+        self.expand_in(text, &mut Vec::new())
+    }
+
+    /// The value of the variable `name`, expanded, or what is wrong with it.
+    pub fn check(&self, name: &'a str) -> Result<String, String> {
+        self.expand_in(self.defined[name].0, &mut vec![name])
+    }
+
+    /// `resolving` are the variables being expanded right now, outermost
+    /// first; one of them inside its own value is a cycle.
+    fn expand_in(&self, text: &str, resolving: &mut Vec<&'a str>) -> Result<String, String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(at) = rest.find('$') {
+            out.push_str(&rest[..at]);
+            rest = &rest[at + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                out.push('$');
+                rest = after;
+                continue;
+            }
+            let Some(after) = rest.strip_prefix('{') else {
+                out.push('$');
+                continue;
+            };
+            let end = after
+                .find('}')
+                .ok_or_else(|| format!("unclosed `${{` in {:?} (write `$$` for a literal `$`)", text))?;
+            out.push_str(&self.value(&after[..end], resolving)?);
+            rest = &after[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    fn value(&self, name: &str, resolving: &mut Vec<&'a str>) -> Result<String, String> {
+        if let Some(var) = name.strip_prefix("env:") {
+            return (self.env)(var)
+                .ok_or_else(|| format!("undefined environment variable `${{env:{}}}`", var));
+        }
+        let Some((&name, &(value, _))) = self.defined.get_key_value(name) else {
+            return Err(format!("undefined variable `${{{}}}` (not defined under `vars:`)", name));
+        };
+        if let Some(pos) = resolving.iter().position(|&n| n == name) {
+            let mut chain = resolving[pos..].to_vec();
+            chain.push(name);
+            return Err(format!("recursive variable reference {}", chain.join(" -> ")));
+        }
+        let outermost = resolving.is_empty();
+        resolving.push(name);
+        let result = self.expand_in(value, resolving);
+        resolving.pop();
+        result.map_err(|e| if outermost { format!("variable `${{{}}}` is invalid", name) } else { e })
+    }
+}
+
+/// `text` written so that [`Vars::expand`] gives it back unchanged.
+pub(super) fn literal(text: &str) -> String {
+    text.replace('$', "$$")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars<'a>(defined: &[(&'a str, &'a str)]) -> Vars<'a> {
+        let mut vars = Vars {
+            env: |name| (name == "HOME").then(|| "C:\\Users\\me".to_string()),
+            ..Vars::default()
+        };
+        for &(name, value) in defined {
+            vars.insert(name, value, None).unwrap();
+        }
+        vars
+    }
+
+    #[test]
+    fn substitutes_vars_env_and_escapes() {
+        let vars = vars(&[("proj", "${env:HOME}\\work"), ("repo", "${proj}\\key-switch")]);
+        assert_eq!(vars.expand("${repo}\\src").unwrap(), "C:\\Users\\me\\work\\key-switch\\src");
+        assert_eq!(vars.expand("$$5 and $5 and $${proj}").unwrap(), "$5 and $5 and ${proj}");
+        assert_eq!(vars.expand(&literal("$${x}$")).unwrap(), "$${x}$");
+        assert_eq!(vars.check("proj").unwrap(), "C:\\Users\\me\\work");
+    }
+
+    #[test]
+    fn undefined_recursive_and_broken_references_are_errors() {
+        let defined = vars(&[("a", "${b}"), ("b", "x${a}"), ("bad", "${env:NOPE}")]);
+        assert_eq!(
+            defined.expand("${nope}").unwrap_err(),
+            "undefined variable `${nope}` (not defined under `vars:`)"
+        );
+        assert_eq!(defined.expand("${env:NOPE}").unwrap_err(), "undefined environment variable `${env:NOPE}`");
+        assert!(defined.expand("${a").unwrap_err().starts_with("unclosed `${`"));
+        assert_eq!(defined.expand("${bad}").unwrap_err(), "variable `${bad}` is invalid");
+        assert_eq!(defined.check("bad").unwrap_err(), "undefined environment variable `${env:NOPE}`");
+        assert_eq!(defined.check("a").unwrap_err(), "recursive variable reference a -> b -> a");
+        let mut twice = vars(&[]);
+        assert_eq!(twice.insert("x", "1", Some("team.ron")), Ok(()));
+        assert_eq!(twice.insert("x", "2", Some("local.ron")), Err(Some("team.ron")));
+    }
+}
@@ -0,0 +1,587 @@
+//! Runtime bindings back to `config.ron` text — the loader run in reverse.
+//!
+//! [`to_ron_string`] rebuilds a `RawConfig` from converted bindings and
+//! prints it in one canonical form: keys spelled the way `vk_name` spells
+//! them, fields at their default left out, layer bindings under `layers:`,
+//! auto-blockers dropped (loading the output adds them back). Loading the
+//! result gives the same bindings back, which is what lets config
+//! migrations rewrite a user's file. [`to_string`] does the same in
+//! TOML or JSON.
+//!
+//! What the loader resolves away is not recovered: `Ref`s come back inlined,
+//! `${...}` variables substituted (and any `$` written `$$`), `include:`d
+//! files merged into one, `settings:` applied to every binding (values
+//! equal to the built-in defaults are left out), comments are gone. The one
+//! exception is a `Ref` to a `Sequence` used as a step — there is no inline
+//! spelling for a nested sequence, so those are written as named actions.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use ron::extensions::Extensions;
+use ron::ser::PrettyConfig;
+use serde::Serialize;
+
+use crate::data::action::{BindAction, LayerOp, MediaKey};
+use crate::data::binding::{Binding, BindingKind};
+use crate::data::condition::Condition;
+use crate::data::key_combination::MatchMode;
+use crate::data::on_fail::OnFail;
+use crate::data::sequence::{SequenceStep, WindowOp};
+use crate::data::trigger::{ModTapFlavor, Trigger};
+use crate::data::vk::Vk;
+use crate::data::vk_name::vk_name;
+
+use super::format::Format;
+use super::loader::ConfigError;
+use super::migrate::CURRENT_VERSION;
+use super::parsing::wm_name;
+use super::settings::Settings;
+use super::vars::literal;
+use super::schema::{
+    ChordSpec, MediaKeyRef, MessageRef, RawAction, RawBinding, RawCondition, RawConfig,
+    RawMatchMode, RawModTapFlavor, RawOnFail, RawSettings, RawStep, RawTrigger, RawWindowKind,
+    SequenceSpec,
+};
+
+/// Serializes `bindings` (as returned by `config::load`) to `config.ron`
+/// text in the current schema version, one binding per line. Bindings keep
+/// their order within the base list and within each layer.
+pub fn to_ron_string(bindings: &[Binding]) -> Result<String, ConfigError> {
+    ron_document(&Writer::default().config(bindings))
+}
+
+/// The layout behind [`to_ron_string`], for a `RawConfig` built some other
+/// way — by an importer, say.
+pub(super) fn ron_document(raw: &RawConfig) -> Result<String, ConfigError> {
+    let mut out = String::from("#![enable(implicit_some)]\n(\n");
+    out.push_str(&format!("    version: {},\n", CURRENT_VERSION));
+    if !raw.bindings.is_empty() {
+        out.push_str("    bindings: [\n");
+        for binding in &raw.bindings {
+            out.push_str(&format!("        {},\n", inline(binding)?));
+        }
+        out.push_str("    ],\n");
+    }
+    if !raw.layers.is_empty() {
+        out.push_str("    layers: {\n");
+        for (name, layer) in &raw.layers {
+            if layer.is_empty() {
+                out.push_str(&format!("        {}: [],\n", inline(name)?));
+                continue;
+            }
+            out.push_str(&format!("        {}: [\n", inline(name)?));
+            for binding in layer {
+                out.push_str(&format!("            {},\n", inline(binding)?));
+            }
+            out.push_str("        ],\n");
+        }
+        out.push_str("    },\n");
+    }
+    if !raw.actions.is_empty() {
+        out.push_str("    actions: {\n");
+        for (name, action) in &raw.actions {
+            out.push_str(&format!("        {}: {},\n", inline(name)?, inline(action)?));
+        }
+        out.push_str("    },\n");
+    }
+    out.push_str(")\n");
+    Ok(out)
+}
+
+/// Serializes `bindings` like [`to_ron_string`], in any config format.
+/// TOML is laid out the same way, one `[[bindings]]` table per binding
+/// with inline values; JSON comes out of `serde_json`'s pretty printer.
+/// Both use the encoding described in `format.rs`.
+pub fn to_string(bindings: &[Binding], format: Format) -> Result<String, ConfigError> {
+    let raw = Writer::default().config(bindings);
+    match format {
+        Format::Ron => ron_document(&raw),
+        Format::Toml => toml_document(&raw),
+        Format::Json => {
+            let document = JsonDocument {
+                version: CURRENT_VERSION,
+                bindings: &raw.bindings,
+                layers: &raw.layers,
+                actions: &raw.actions,
+            };
+            serde_json::to_string_pretty(&document)
+                .map(|text| text + "\n")
+                .map_err(serialize_error)
+        }
+    }
+}
+
+/// The top level of a JSON config.
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    version: u32,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    bindings: &'a [RawBinding],
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    layers: &'a BTreeMap<String, Vec<RawBinding>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    actions: &'a BTreeMap<String, RawAction>,
+}
+
+fn toml_document(raw: &RawConfig) -> Result<String, ConfigError> {
+    let mut out = format!("version = {}\n", CURRENT_VERSION);
+    for binding in &raw.bindings {
+        out.push_str("\n[[bindings]]\n");
+        out.push_str(&toml_fields(binding)?);
+    }
+    // Empty layers go in a plain `[layers]` table, ahead of the
+    // `[[layers.name]]` arrays of the others.
+    let (empty, layers): (Vec<_>, Vec<_>) = raw.layers.iter().partition(|(_, l)| l.is_empty());
+    if !empty.is_empty() {
+        out.push_str("\n[layers]\n");
+        for (name, _) in empty {
+            out.push_str(&format!("{} = []\n", toml_key(name)));
+        }
+    }
+    for (name, layer) in layers {
+        for binding in layer {
+            out.push_str(&format!("\n[[layers.{}]]\n", toml_key(name)));
+            out.push_str(&toml_fields(binding)?);
+        }
+    }
+    if !raw.actions.is_empty() {
+        out.push_str("\n[actions]\n");
+        for (name, action) in &raw.actions {
+            out.push_str(&format!("{} = {}\n", toml_key(name), toml_value(action)?));
+        }
+    }
+    Ok(out)
+}
+
+/// `field = value` lines for one binding, values inline, in schema order.
+fn toml_fields(binding: &RawBinding) -> Result<String, ConfigError> {
+    let toml::Value::Table(table) = toml_value(binding)? else {
+        return Err(serialize_error("a binding is not a table"));
+    };
+    Ok(table.iter().map(|(field, value)| format!("{} = {}\n", field, value)).collect())
+}
+
+/// `value` as a TOML value. Goes through JSON, which turns the integer
+/// keys of `holds:` into the strings TOML keys have to be; TOML's own
+/// value serializer rejects them.
+fn toml_value<T: Serialize>(value: &T) -> Result<toml::Value, ConfigError> {
+    let json = serde_json::to_value(value).map_err(serialize_error)?;
+    toml::Value::try_from(json).map_err(serialize_error)
+}
+
+/// A layer or action name as a TOML key: bare when it can be, else quoted.
+fn toml_key(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        name.to_string()
+    } else {
+        toml::Value::String(name.to_string()).to_string()
+    }
+}
+
+fn serialize_error(e: impl std::fmt::Display) -> ConfigError {
+    ConfigError::Other(format!("cannot serialize config: {}", e))
+}
+
+/// `value` as single-line RON, with `Some(...)` left implicit.
+fn inline<T: Serialize>(value: &T) -> Result<String, ConfigError> {
+    ron::Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .to_string_pretty(value, PrettyConfig::new().depth_limit(0))
+        .map_err(serialize_error)
+}
+
+/// Named actions written so far, for the nested sequences that need one.
+#[derive(Default)]
+struct Writer {
+    /// The built-in defaults: a value equal to one is left out.
+    defaults: Settings,
+    actions: BTreeMap<String, RawAction>,
+    /// Name given to each nested sequence, by `Arc` address: the loader
+    /// hands every `Ref` to one name the same `Arc`.
+    names: Vec<(*const Vec<SequenceStep>, String)>,
+}
+
+impl Writer {
+    fn config(mut self, bindings: &[Binding]) -> RawConfig {
+        let mut base = Vec::new();
+        let mut layers: BTreeMap<String, Vec<RawBinding>> = BTreeMap::new();
+        for binding in bindings.iter().filter(|b| !b.is_auto_blocker) {
+            // A layer with no bindings of its own still has to exist for
+            // the `Layer*` actions that name it.
+            for action in binding.actions() {
+                if let BindAction::Layer { layer, .. } = action {
+                    layers.entry(layer.to_string()).or_default();
+                }
+            }
+            let raw = self.binding(binding);
+            match &binding.layer {
+                Some(layer) => layers.entry(layer.to_string()).or_default().push(raw),
+                None => base.push(raw),
+            }
+        }
+        RawConfig {
+            include: Vec::new(),
+            bindings: base,
+            layers,
+            actions: self.actions,
+            vars: BTreeMap::new(),
+            settings: RawSettings::default(),
+        }
+    }
+
+    fn binding(&mut self, binding: &Binding) -> RawBinding {
+        let (mut keys, mut sequence, mut chord) = (None, None, None);
+        match &binding.kind {
+            BindingKind::Combo(combo) => keys = Some(combo_string(&combo.keys)),
+            BindingKind::Sequence { steps, max_gap } => {
+                let max_gap_ms = max_gap.as_millis() as u64;
+                sequence = Some(SequenceSpec {
+                    steps: steps.iter().map(|step| combo_string(&step.keys)).collect(),
+                    max_gap_ms: unless_default(max_gap_ms, self.defaults.sequence_gap_ms),
+                });
+            }
+            BindingKind::Chord { keys: chord_keys, window } => {
+                let window_ms = window.as_millis() as u64;
+                chord = Some(ChordSpec {
+                    keys: chord_keys.iter().map(|&vk| vk_name(vk)).collect(),
+                    window_ms: unless_default(window_ms, self.defaults.chord_window_ms),
+                });
+            }
+        }
+
+        // `ModTap` / `TapDance` carry their actions in the trigger; the
+        // binding's own action is derived from it.
+        let trigger = self.trigger(binding);
+        let action = match trigger {
+            RawTrigger::ModTap { .. } | RawTrigger::TapDance { .. } => None,
+            _ => Some(self.action(&binding.action)),
+        };
+
+        RawBinding {
+            id: binding.id.clone(),
+            name: binding.name.clone(),
+            description: binding.description.clone(),
+            keys,
+            sequence,
+            chord,
+            action,
+            block_default: binding.block_default,
+            block_original_combo: binding.block_original_combo,
+            when: condition(&binding.condition),
+            trigger,
+            repeat: match (&binding.kind, &binding.trigger) {
+                (BindingKind::Combo(_), Trigger::Immediate) => {
+                    unless_default(binding.repeat, self.defaults.repeat)
+                }
+                _ => None,
+            },
+            match_mode: match binding.combination().map(|combo| combo.mode) {
+                Some(MatchMode::Exact)   => RawMatchMode::Exact,
+                Some(MatchMode::Ordered) => RawMatchMode::Ordered,
+                Some(MatchMode::Subset) | None => RawMatchMode::Subset,
+            },
+            on_fail: match &binding.on_fail {
+                OnFail::Drop => RawOnFail::Drop,
+                OnFail::Replay => RawOnFail::Replay,
+                OnFail::Action(action) => RawOnFail::Action(self.action(action)),
+            },
+            tags: binding.tags.iter().map(|tag| tag.to_string()).collect(),
+            enabled: binding.enabled,
+        }
+    }
+
+    fn trigger(&mut self, binding: &Binding) -> RawTrigger {
+        match &binding.trigger {
+            Trigger::Immediate     => RawTrigger::Immediate,
+            Trigger::Tap(ms)       => RawTrigger::Tap(*ms),
+            Trigger::Hold(ms)      => RawTrigger::Hold(*ms),
+            Trigger::DoubleTap(ms) => RawTrigger::DoubleTap(*ms),
+            Trigger::TapDance { term_ms, steps } => RawTrigger::TapDance {
+                term_ms: unless_default(*term_ms, self.defaults.tap_term_ms),
+                // The loader only leaves `tap` empty on counts past the
+                // last tap that `holds:` added.
+                taps: steps
+                    .iter()
+                    .map_while(|step| step.tap.as_ref())
+                    .map(|tap| self.action(tap))
+                    .collect(),
+                holds: steps
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, step)| Some((i + 1, step.hold.as_ref()?)))
+                    .map(|(count, hold)| (count, self.action(hold)))
+                    .collect(),
+            },
+            Trigger::ModTap { hold, term_ms, flavor } => RawTrigger::ModTap {
+                tap: self.action(&binding.action),
+                hold: vk_name(*hold),
+                term_ms: unless_default(*term_ms, self.defaults.hold_term_ms),
+                flavor: match flavor {
+                    ModTapFlavor::HoldOnOtherKeyPress => RawModTapFlavor::HoldOnOtherKeyPress,
+                    ModTapFlavor::PermissiveHold      => RawModTapFlavor::PermissiveHold,
+                    ModTapFlavor::TapPreferred        => RawModTapFlavor::TapPreferred,
+                },
+            },
+        }
+    }
+
+    fn action(&mut self, action: &BindAction) -> RawAction {
+        match action {
+            BindAction::SwitchLanguage         => RawAction::SwitchLanguage,
+            BindAction::SwitchLanguageBackward => RawAction::SwitchLanguageBackward,
+            BindAction::ToggleCapsLock         => RawAction::ToggleCapsLock,
+            BindAction::DoNothing              => RawAction::DoNothing,
+
+            BindAction::PressKey(vk) => RawAction::PressKey(vk_name(*vk)),
+
+            BindAction::PostMessage { msg, wparam, lparam } => RawAction::PostMessage {
+                msg: match wm_name(*msg) {
+                    Some(name) => MessageRef::Name(name.to_string()),
+                    None => MessageRef::Code(*msg),
+                },
+                wparam: *wparam as u64,
+                lparam: *lparam as i64,
+            },
+
+            BindAction::Sequence(steps) => {
+                RawAction::Sequence(steps.iter().map(|step| self.step(step)).collect())
+            }
+
+            BindAction::Launch { exe, args } => RawAction::Launch {
+                exe: literal(exe),
+                args: args.iter().map(|arg| literal(arg)).collect(),
+            },
+
+            BindAction::OpenUrl(url) => RawAction::OpenUrl(literal(url)),
+
+            BindAction::Media(key) => RawAction::Media(media_key_to_ref(*key)),
+
+            BindAction::Layer { op, layer } => {
+                let name = layer.to_string();
+                match op {
+                    LayerOp::On        => RawAction::LayerOn(name),
+                    LayerOp::Off       => RawAction::LayerOff(name),
+                    LayerOp::Toggle    => RawAction::LayerToggle(name),
+                    LayerOp::Momentary => RawAction::LayerMomentary(name),
+                    LayerOp::OneShot   => RawAction::LayerOneShot(name),
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, step: &SequenceStep) -> RawStep {
+        match step {
+            SequenceStep::Delay(ms) => RawStep::Delay(*ms),
+            SequenceStep::TypeText(text) => RawStep::Text(literal(text)),
+            SequenceStep::PressKey(vk) => RawStep::Key(vk_name(*vk)),
+            SequenceStep::PressCombo(keys) => RawStep::Combo(combo_string(keys)),
+            SequenceStep::Window(op) => RawStep::Window(match op {
+                WindowOp::Minimize => RawWindowKind::Minimize,
+                WindowOp::Maximize => RawWindowKind::Maximize,
+                WindowOp::Restore  => RawWindowKind::Restore,
+                WindowOp::Close    => RawWindowKind::Close,
+            }),
+            SequenceStep::Launch { exe, args } => RawStep::Launch {
+                exe: literal(exe),
+                args: args.iter().map(|arg| literal(arg)).collect(),
+            },
+            SequenceStep::OpenUrl(url) => RawStep::OpenUrl(literal(url)),
+            SequenceStep::Media(key) => RawStep::Media(media_key_to_ref(*key)),
+            SequenceStep::Sequence(steps) => RawStep::Ref(self.named_sequence(steps)),
+        }
+    }
+
+    /// The `actions:` name a nested sequence is written under, adding it
+    /// the first time.
+    fn named_sequence(&mut self, steps: &Arc<Vec<SequenceStep>>) -> String {
+        let address = Arc::as_ptr(steps);
+        if let Some((_, name)) = self.names.iter().find(|(known, _)| *known == address) {
+            return name.clone();
+        }
+        let name = format!("sequence_{}", self.names.len() + 1);
+        self.names.push((address, name.clone()));
+        let raw = RawAction::Sequence(steps.iter().map(|step| self.step(step)).collect());
+        self.actions.insert(name.clone(), raw);
+        name
+    }
+}
+
+/// `value`, or `None` when it is what a missing field means anyway.
+fn unless_default<T: PartialEq>(value: T, default: T) -> Option<T> {
+    (value != default).then_some(value)
+}
+
+fn condition(condition: &Condition) -> RawCondition {
+    match condition {
+        Condition::Always => RawCondition::Always,
+        Condition::AppEquals(s) => RawCondition::AppEquals(s.clone()),
+        Condition::TitleContains(s) => RawCondition::TitleContains(s.clone()),
+        Condition::TitleEquals(s) => RawCondition::TitleEquals(s.clone()),
+        Condition::Not(inner) => RawCondition::Not(Box::new(self::condition(inner))),
+        Condition::And(parts) => RawCondition::And(parts.iter().map(self::condition).collect()),
+        Condition::Or(parts) => RawCondition::Or(parts.iter().map(self::condition).collect()),
+    }
+}
+
+fn media_key_to_ref(key: MediaKey) -> MediaKeyRef {
+    match key {
+        MediaKey::PlayPause  => MediaKeyRef::PlayPause,
+        MediaKey::Stop       => MediaKeyRef::Stop,
+        MediaKey::Next       => MediaKeyRef::Next,
+        MediaKey::Previous   => MediaKeyRef::Previous,
+        MediaKey::VolumeUp   => MediaKeyRef::VolumeUp,
+        MediaKey::VolumeDown => MediaKeyRef::VolumeDown,
+        MediaKey::VolumeMute => MediaKeyRef::VolumeMute,
+    }
+}
+
+/// `"Ctrl+Shift+S"`, in the combo's own key order.
+fn combo_string(keys: &[Vk]) -> String {
+    keys.iter().map(|&vk| vk_name(vk)).collect::<Vec<_>>().join("+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::loader::{from_ron_str, from_str_as};
+
+    /// Loads `text`, writes it back out, and loads that: both loads must
+    /// give the same bindings, auto-blockers included.
+    fn round_trip(text: &str) -> String {
+        let first = from_ron_str(text).expect("original loads");
+        let written = to_ron_string(&first).expect("serializes");
+        let second = from_ron_str(&written)
+            .unwrap_or_else(|e| panic!("written config loads: {}\n{}", e, written));
+        assert_eq!(format!("{:?}", first), format!("{:?}", second), "\n{}", written);
+        assert_eq!(to_ron_string(&second).unwrap(), written, "output is stable");
+        written
+    }
+
+    #[test]
+    fn default_config_round_trips() {
+        round_trip(include_str!("default_config.ron"));
+    }
+
+    #[test]
+    fn toml_and_json_round_trip() {
+        let text = r#"(
+            actions: { "inner": Sequence([Key("a")]) },
+            bindings: [
+                (keys: "Win+Space", action: SwitchLanguage, block_original_combo: true),
+                (keys: "F5", action: Sequence([Ref("inner"), Window(Restore), Text("x")]), when: Not(AppEquals("a.exe"))),
+                (keys: "CapsLock", trigger: TapDance(taps: [ToggleCapsLock], holds: { 2: LayerOn("nav") })),
+                (keys: "F", trigger: ModTap(tap: PressKey("f"), hold: "Ctrl")),
+                (chord: (keys: ["j", "k"]), action: PostMessage(msg: "WM_CLOSE", lparam: -1), on_fail: Replay),
+            ],
+            layers: { "nav": [(keys: "h", action: PressKey("Left"), trigger: Tap(150))] },
+        )"#;
+        let bindings = from_ron_str(text).unwrap();
+        for format in [Format::Toml, Format::Json] {
+            let written = to_string(&bindings, format).unwrap();
+            let loaded = from_str_as(&written, format)
+                .unwrap_or_else(|e| panic!("{:?} loads: {}\n{}", format, e, written));
+            assert_eq!(format!("{:?}", bindings), format!("{:?}", loaded), "\n{}", written);
+        }
+        let toml = to_string(&bindings, Format::Toml).unwrap();
+        assert!(toml.starts_with("version = 1\n"), "{}", toml);
+        assert!(toml.contains(r#"action = { Sequence = [{ Ref = "sequence_1" }, { Window = "Restore" }, { Text = "x" }] }"#), "{}", toml);
+        assert!(toml.contains("\n[[layers.nav]]\nkeys = \"H\"\n"), "{}", toml);
+        let json = to_string(&bindings, Format::Json).unwrap();
+        assert!(json.contains(r#""action": "SwitchLanguage""#), "{}", json);
+    }
+
+    #[test]
+    fn every_field_round_trips() {
+        let text = r#"
+            #![enable(implicit_some)]
+            (
+                vars: { "cost": "$$4" },
+                settings: (sequence_gap_ms: 600),
+                actions: {
+                    "save": Sequence([Combo("ctrl + s"), Delay(50)]),
+                },
+                bindings: [
+                    (keys: "Ctrl+Shift+Esc", action: PostMessage(msg: "wm_close", wparam: 1, lparam: -2), block_default: false),
+                    (keys: "Win+Space", action: SwitchLanguage, block_original_combo: true),
+                    (keys: "F1", action: PostMessage(msg: 0x1234)),
+                    (keys: "Alt+1", action: Sequence([
+                        Window(Restore), Text("hi \"there\"\n"), Key("return"), Ref("save"), Ref("save"),
+                        Launch(exe: "code.exe", args: ["D:\\"]), OpenUrl("https://example.com"), Media(PlayPause),
+                    ])),
+                    (keys: "Alt+2", action: Ref("save"), when: And([AppEquals("code.exe"), Not(Or([TitleContains("x"), TitleEquals("y")]))])),
+                    (keys: "CapsLock", trigger: TapDance(term_ms: 300, taps: [ToggleCapsLock, LayerToggle("nav")], holds: { 1: DoNothing, 4: Media(VolumeMute) })),
+                    (keys: "F", trigger: ModTap(tap: PressKey("f"), hold: "LCtrl", flavor: PermissiveHold)),
+                    (id: "notes", name: "Notes", description: "scratch pad", keys: "F2", action: Launch(exe: "notepad.exe"), trigger: Hold(250), tags: ["work"], enabled: false),
+                    (keys: "F3", action: OpenUrl("mailto:a@b"), trigger: DoubleTap(180)),
+                    (keys: "Shift+CapsLock", action: ToggleCapsLock, match: Ordered),
+                    (keys: "F4", action: Sequence([Text("costs $$5, not ${cost}")]), when: Always, repeat: false),
+                    (sequence: (steps: ["g", "Shift+g"], max_gap_ms: 700), action: LayerOneShot("empty"), on_fail: Replay),
+                    (sequence: (steps: ["g", "g"]), action: DoNothing),
+                    (chord: (keys: ["j", "k"], window_ms: 40), action: LayerMomentary("nav"), on_fail: Action(PressKey("Esc"))),
+                ],
+                layers: {
+                    "nav": [
+                        (keys: "h", action: PressKey("Left"), trigger: Tap(150)),
+                        (keys: "Alt+F4", action: LayerOff("nav"), block_original_combo: true),
+                    ],
+                    "empty": [],
+                },
+            )
+        "#;
+        let written = round_trip(text);
+        assert!(written.contains(r#"(keys: "Ctrl+Shift+Esc", action: PostMessage(msg: "WM_CLOSE", wparam: 1, lparam: -2), block_default: false)"#), "{}", written);
+        assert!(written.contains("msg: 4660"), "{}", written);
+        assert!(written.contains(r#"action: ToggleCapsLock, match: Ordered)"#), "{}", written);
+        assert!(written.contains(r#"Combo("Ctrl+S")"#), "{}", written);
+        assert!(written.contains(r#""empty": []"#), "{}", written);
+        assert!(written.contains(r#"Text("costs $$5, not $$4")"#), "{}", written);
+        assert!(written.contains(r#"(steps: ["G", "G"], max_gap_ms: 600)"#), "{}", written);
+    }
+
+    #[test]
+    fn defaults_and_auto_blockers_are_left_out() {
+        let bindings = from_ron_str(
+            r#"(bindings: [(keys: "Win+Space", action: SwitchLanguage, block_original_combo: true,
+                trigger: Immediate, on_fail: Drop, when: Always, block_default: true)])"#,
+        )
+        .unwrap();
+        assert_eq!(bindings.len(), 2, "auto-blocker added on load");
+        assert_eq!(
+            to_ron_string(&bindings).unwrap(),
+            "#![enable(implicit_some)]\n(\n    version: 1,\n    bindings: [\n        \
+             (keys: \"Win+Space\", action: SwitchLanguage, block_original_combo: true),\n    ],\n)\n"
+        );
+    }
+
+    #[test]
+    fn shared_nested_sequences_share_one_name() {
+        let text = r#"(
+            actions: { "inner": Sequence([Key("a")]), "outer": Sequence([Ref("inner"), Ref("inner")]) },
+            bindings: [
+                (keys: "F5", action: Sequence([Ref("outer"), Ref("inner")])),
+            ],
+        )"#;
+        let written = round_trip(text);
+        assert!(written.contains(r#"Sequence([Ref("sequence_1"), Ref("sequence_2")])"#), "{}", written);
+        assert!(written.contains(r#""sequence_1": Sequence([Ref("sequence_2"), Ref("sequence_2")])"#), "{}", written);
+    }
+
+    #[test]
+    fn settings_repeat_stays_off_bindings_it_does_not_apply_to() {
+        let written = round_trip(
+            r#"(
+                settings: (repeat: false),
+                bindings: [
+                    (keys: "F5", action: DoNothing),
+                    (keys: "F6", action: DoNothing, repeat: true),
+                    (keys: "F7", action: DoNothing, trigger: Hold(200)),
+                    (sequence: (steps: ["g", "g"]), action: DoNothing),
+                    (chord: (keys: ["j", "k"]), action: DoNothing),
+                ],
+            )"#,
+        );
+        assert_eq!(written.matches("repeat: ").count(), 1, "{}", written);
+        assert!(written.contains(r#"(keys: "F5", action: DoNothing, repeat: false)"#), "{}", written);
+    }
+}
@@ -10,6 +10,7 @@ use windows::{
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU32, Ordering};
 
+use crate::config::{ConfigSources, Settings};
 use crate::data::binding::Binding;
 use crate::hook::{config_watcher, ipc_server, keyboard_hook_callback};
 
@@ -22,6 +23,12 @@ pub struct App {
     /// without a restart. None disables hot-reload (e.g. when the user
     /// passes a config inline, or for tests).
     config_path: Option<PathBuf>,
+    /// Files the initial config was read from, so edits to included files
+    /// reload too.
+    config_sources: ConfigSources,
+    /// `settings:` of the initial config. A reload hands the watcher new
+    /// ones.
+    settings: Settings,
 }
 
 impl Default for App {
@@ -35,6 +42,8 @@ impl App {
         Self {
             pending_bindings: Vec::new(),
             config_path: None,
+            config_sources: ConfigSources::default(),
+            settings: Settings::default(),
         }
     }
 
@@ -47,10 +56,19 @@ impl App {
         self
     }
 
-    /// Enable hot-reload from a config file. The watcher is started just after
-    /// the keyboard hook is installed and torn down when `run` exits.
-    pub fn with_config_watcher(mut self, path: PathBuf) -> Self {
+    /// Enable hot-reload from a config file and the files it included
+    /// (`sources`, as returned by `config::load`). The watcher is started
+    /// just after the keyboard hook is installed and torn down when `run`
+    /// exits.
+    pub fn with_config_watcher(mut self, path: PathBuf, sources: ConfigSources) -> Self {
         self.config_path = Some(path);
+        self.config_sources = sources;
+        self
+    }
+
+    /// Apply the `settings:` the initial config was loaded with.
+    pub fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
         self
     }
 
@@ -91,8 +109,10 @@ impl App {
         hook.install()?;
         println!("✓ Hook installed successfully");
 
-        if ipc_server::spawn(hook) {
+        if ipc_server::set_enabled(hook, self.settings.ipc) {
             println!("✓ IPC server listening (use `swch on/off/exit`)\n");
+        } else if !self.settings.ipc {
+            println!("IPC server off (`settings: (ipc: false)`)\n");
         } else {
             println!();
         }
@@ -103,10 +123,25 @@ impl App {
         // debounce thread to exit). On error we keep running without
         // hot-reload — failing to start the watcher should not take down
         // the daemon.
+        let sources = std::mem::take(&mut self.config_sources);
+        let included = sources.files.len().saturating_sub(1);
         let _watcher = match self.config_path.take() {
-            Some(path) => match config_watcher::spawn_watcher(path.clone(), hook) {
+            Some(path) => match config_watcher::spawn_watcher(
+                path.clone(),
+                sources,
+                self.settings.clone(),
+                hook,
+            ) {
                 Ok(w) => {
-                    println!("✓ Watching {} for changes\n", path.display());
+                    if included > 0 {
+                        println!(
+                            "✓ Watching {} and {} included file(s) for changes\n",
+                            path.display(),
+                            included
+                        );
+                    } else {
+                        println!("✓ Watching {} for changes\n", path.display());
+                    }
                     Some(w)
                 }
                 Err(e) => {
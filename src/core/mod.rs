@@ -1,3 +1,6 @@
+#[cfg(windows)]
 pub mod app;
+#[cfg(windows)]
 pub mod constants;
+#[cfg(windows)]
 pub mod windows_actions;
\ No newline at end of file
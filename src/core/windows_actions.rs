@@ -1,4 +1,10 @@
+//! Carries out `BindAction`s and sequence steps on Windows: `SendInput`
+//! for keys and text, `PostMessageW` / `ShowWindow` for window operations,
+//! `ShellExecuteW` and process spawning for launches.
+
 use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use windows::{
     core::*,
@@ -13,94 +19,10 @@ use windows::{
 };
 
 use crate::core::constants::injected_sentinel;
-use crate::data::key_combination::KeyCombination;
-use crate::data::sequence::{spawn_sequence, SequenceStep};
-use crate::system::system_function::SystemFunction;
-
-#[allow(dead_code)] // Variants are part of the public surface; not every binary uses them all.
-#[derive(Debug, Clone)]
-pub enum BindAction {
-    SwitchLanguage,
-    SwitchLanguageBackward,
-    ToggleCapsLock,
-    PressKey(VIRTUAL_KEY),
-    PostMessage { msg: u32, wparam: usize, lparam: isize },
-    /// Macro-like body: list of steps executed in a spawned worker thread.
-    /// The hook callback must return fast, so it only kicks off the thread —
-    /// it never blocks waiting for delays or `SendInput` to drain.
-    ///
-    /// `Arc` makes cloning the binding (and therefore freezing the binding
-    /// list at install) cheap regardless of sequence length.
-    Sequence(Arc<Vec<SequenceStep>>),
-    /// Spawn a new process. Detached: child outlives the daemon, no console
-    /// window is attached to ours. Use absolute path or rely on PATH lookup.
-    Launch { exe: String, args: Vec<String> },
-    /// Hand a URL / mailto: / file path to the OS default handler via
-    /// `ShellExecuteW`. Works for `https://...`, `mailto:...`, document files,
-    /// even plain executables (equivalent to "open" verb).
-    OpenUrl(String),
-    /// Synthesize a media / volume / playback key. Maps to one of the
-    /// `VK_MEDIA_*` / `VK_VOLUME_*` virtual keys via `SendInput`.
-    Media(MediaKey),
-    DoNothing,
-}
-
-/// Media-control keys synthesized via `SendInput`. These map 1:1 to the
-/// virtual keys most physical multimedia keyboards already emit, so binding
-/// a hardware media key is the same code path as binding e.g. F13 to play.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum MediaKey {
-    PlayPause,
-    Stop,
-    Next,
-    Previous,
-    VolumeUp,
-    VolumeDown,
-    VolumeMute,
-}
-
-impl MediaKey {
-    pub fn as_vk(self) -> VIRTUAL_KEY {
-        match self {
-            MediaKey::PlayPause  => VK_MEDIA_PLAY_PAUSE,
-            MediaKey::Stop       => VK_MEDIA_STOP,
-            MediaKey::Next       => VK_MEDIA_NEXT_TRACK,
-            MediaKey::Previous   => VK_MEDIA_PREV_TRACK,
-            MediaKey::VolumeUp   => VK_VOLUME_UP,
-            MediaKey::VolumeDown => VK_VOLUME_DOWN,
-            MediaKey::VolumeMute => VK_VOLUME_MUTE,
-        }
-    }
-}
-
-// Hand-rolled `PartialEq` because the inner `Arc<Vec<…>>` makes the default
-// derive force structural equality on the Vec; we want pointer equality (two
-// bindings with the exact same `Sequence` Arc compare equal, distinct Arcs
-// compare unequal) which is the only case the rest of the program cares
-// about (auto-blocker dedup).
-impl PartialEq for BindAction {
-    fn eq(&self, other: &Self) -> bool {
-        use BindAction::*;
-        match (self, other) {
-            (SwitchLanguage, SwitchLanguage)
-            | (SwitchLanguageBackward, SwitchLanguageBackward)
-            | (ToggleCapsLock, ToggleCapsLock)
-            | (DoNothing, DoNothing) => true,
-            (PressKey(a), PressKey(b)) => a == b,
-            (
-                PostMessage { msg: m1, wparam: w1, lparam: l1 },
-                PostMessage { msg: m2, wparam: w2, lparam: l2 },
-            ) => m1 == m2 && w1 == w2 && l1 == l2,
-            (Sequence(a), Sequence(b)) => Arc::ptr_eq(a, b),
-            (Launch { exe: e1, args: a1 }, Launch { exe: e2, args: a2 }) => e1 == e2 && a1 == a2,
-            (OpenUrl(a), OpenUrl(b)) => a == b,
-            (Media(a), Media(b)) => a == b,
-            _ => false,
-        }
-    }
-}
-
-impl Eq for BindAction {}
+use crate::data::action::BindAction;
+use crate::data::sequence::{is_modifier, SequenceStep, WindowOp};
+use crate::data::vk::Vk;
+use crate::data::vk_name::{scan_code, ALT_GR, ANY_WIN, EXTENDED_SCAN};
 
 impl BindAction {
     pub fn execute(&self) {
@@ -123,56 +45,15 @@ impl BindAction {
             BindAction::Launch { exe, args } => launch_process(exe, args),
             BindAction::OpenUrl(url) => shell_open(url),
             BindAction::Media(key) => press_vk(key.as_vk()),
-            BindAction::DoNothing => {}
+            BindAction::Layer { .. } | BindAction::DoNothing => {}
         }
     }
-
-    pub fn to_system_function(&self) -> Option<SystemFunction> {
-        match self {
-            BindAction::SwitchLanguage => Some(SystemFunction::SwitchLanguage),
-            BindAction::SwitchLanguageBackward => Some(SystemFunction::SwitchLanguageBackward),
-            BindAction::ToggleCapsLock => Some(SystemFunction::ToggleCapsLock),
-            _ => None,
-        }
-    }
-
-    /// The system-default hotkey for this action's underlying OS function, if
-    /// any. Used to derive auto-blockers when `block_original_combo` is set.
-    pub fn get_system_combination(&self) -> Option<KeyCombination> {
-        self.to_system_function()
-            .and_then(|sf| sf.get_system_combination())
-    }
 }
 
 /// Synthesize a single key press (down + up). Shared between `PressKey`,
 /// `Media(...)`, and the sequence `Key(...)` step.
-pub(crate) fn press_vk(vk: VIRTUAL_KEY) {
-    let inputs = [
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: vk,
-                    wScan: 0,
-                    dwFlags: KEYBD_EVENT_FLAGS(0),
-                    time: 0,
-                    dwExtraInfo: injected_sentinel(),
-                },
-            },
-        },
-        INPUT {
-            r#type: INPUT_KEYBOARD,
-            Anonymous: INPUT_0 {
-                ki: KEYBDINPUT {
-                    wVk: vk,
-                    wScan: 0,
-                    dwFlags: KEYEVENTF_KEYUP,
-                    time: 0,
-                    dwExtraInfo: injected_sentinel(),
-                },
-            },
-        },
-    ];
+pub(crate) fn press_vk(vk: Vk) {
+    let inputs = [key_input(vk, 0, false), key_input(vk, 0, true)];
 
     // SAFETY: inputs is a valid stack array; SendInput reads via the size
     // we pass as cbSize.
@@ -187,6 +68,65 @@ pub(crate) fn press_vk(vk: VIRTUAL_KEY) {
     }
 }
 
+/// One injected key transition, marked with the injected sentinel. A
+/// positional key (`SC:0x..`) goes out as its scan code alone, so the
+/// active layout decides what it types; otherwise `scan`, if known, rides
+/// along with the VK so apps that read scan codes see the real one. `Win`
+/// presses the left Windows key, `AltGr` right Alt (Windows adds the fake
+/// Ctrl itself).
+pub(crate) fn key_input(vk: Vk, scan: u16, key_up: bool) -> INPUT {
+    let mut flags = if key_up { KEYEVENTF_KEYUP } else { KEYBD_EVENT_FLAGS(0) };
+    let (vk, scan) = match (vk, scan_code(vk)) {
+        (_, Some(scan)) => {
+            flags |= KEYEVENTF_SCANCODE;
+            (VIRTUAL_KEY(0), scan)
+        }
+        (ANY_WIN, None) => (VK_LWIN, scan),
+        (ALT_GR, None) => (VK_RMENU, EXTENDED_SCAN | 0x38),
+        (vk, None) => (VIRTUAL_KEY(vk.0), scan),
+    };
+    if scan & EXTENDED_SCAN != 0 {
+        flags |= KEYEVENTF_EXTENDEDKEY;
+    }
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                wScan: scan & 0xFF,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: injected_sentinel(),
+            },
+        },
+    }
+}
+
+/// Re-inject raw key transitions (`(vk, scan, key_up)`) in order, marked
+/// with the injected sentinel so the hook lets them through untouched. Used
+/// to hand back keys swallowed by a chord / sequence that failed with
+/// `on_fail: Replay`.
+pub(crate) fn send_key_events(events: &[(Vk, u16, bool)]) {
+    if events.is_empty() {
+        return;
+    }
+    let inputs: Vec<INPUT> = events
+        .iter()
+        .map(|&(vk, scan, key_up)| key_input(vk, scan, key_up))
+        .collect();
+
+    // SAFETY: inputs is a contiguous Vec of fully-initialised INPUTs;
+    // SendInput reads via the size we pass as cbSize.
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        eprintln!(
+            "SendInput dropped replayed key events: sent {} of {}",
+            sent,
+            inputs.len()
+        );
+    }
+}
+
 fn post_message_to_foreground(msg: u32, wparam: usize, lparam: isize) {
     // SAFETY: GetForegroundWindow / PostMessageW have no preconditions on
     // their inputs beyond what we already validate (HWND non-invalid).
@@ -202,7 +142,7 @@ fn post_message_to_foreground(msg: u32, wparam: usize, lparam: isize) {
     }
 }
 
-pub(crate) fn launch_process(exe: &str, args: &[String]) {
+fn launch_process(exe: &str, args: &[String]) {
     // `Command::spawn()` on Windows produces a child detached from this
     // process's lifetime — closing the daemon does not kill launched apps.
     // We deliberately do not pipe stdin/stdout/stderr; child runs with
@@ -218,7 +158,7 @@ pub(crate) fn launch_process(exe: &str, args: &[String]) {
     }
 }
 
-pub(crate) fn shell_open(target: &str) {
+fn shell_open(target: &str) {
     let target_wide: Vec<u16> = target.encode_utf16().chain(std::iter::once(0)).collect();
 
     // SAFETY: target_wide is null-terminated; the "open" verb literal lives
@@ -246,25 +186,172 @@ pub(crate) fn shell_open(target: &str) {
     }
 }
 
-impl std::fmt::Display for BindAction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            BindAction::SwitchLanguage => write!(f, "switch language"),
-            BindAction::SwitchLanguageBackward => write!(f, "switch language backward"),
-            BindAction::ToggleCapsLock => write!(f, "toggle CapsLock"),
-            BindAction::PressKey(vk) => write!(f, "press key {}", crate::data::vk_name::vk_name(*vk)),
-            BindAction::PostMessage { msg, .. } => write!(f, "post message {:#X}", msg),
-            BindAction::Sequence(steps) => write!(f, "sequence ({} steps)", steps.len()),
-            BindAction::Launch { exe, args } => {
-                if args.is_empty() {
-                    write!(f, "launch {}", exe)
-                } else {
-                    write!(f, "launch {} {}", exe, args.join(" "))
+/// Spawns a worker thread that executes the given steps in order against
+/// the foreground window captured *now*. Returns immediately — the caller
+/// (a hook callback) must not block.
+///
+/// `steps` is shared with the worker via `Arc` (no deep clone). `HWND` is
+/// `*mut c_void` and therefore not `Send`, so we ferry it across the thread
+/// boundary as an `isize` and rebuild it on arrival — equivalent to passing
+/// a window handle through any other integer-bearing channel (PostMessage's
+/// thread-message queue, registry, etc.).
+fn spawn_sequence(steps: Arc<Vec<SequenceStep>>) {
+    // SAFETY: GetForegroundWindow has no preconditions; an invalid/null HWND
+    // is handled by per-step checks in `apply_window_op`.
+    let target_raw: isize = unsafe { GetForegroundWindow().0 as isize };
+
+    thread::spawn(move || {
+        let target = HWND(target_raw as *mut std::ffi::c_void);
+        for step in steps.iter() {
+            execute_step(step, target);
+        }
+    });
+}
+
+fn execute_step(step: &SequenceStep, target: HWND) {
+    match step {
+        SequenceStep::TypeText(s) => type_text(s),
+        SequenceStep::PressKey(vk) => press_key(*vk),
+        SequenceStep::PressCombo(keys) => press_combo(keys),
+        SequenceStep::Delay(ms) => thread::sleep(Duration::from_millis(*ms)),
+        SequenceStep::Window(op) => apply_window_op(*op, target),
+        SequenceStep::Launch { exe, args } => launch_process(exe, args),
+        SequenceStep::OpenUrl(url) => shell_open(url),
+        SequenceStep::Media(key) => press_key(key.as_vk()),
+        SequenceStep::Sequence(steps) => {
+            for step in steps.iter() {
+                execute_step(step, target);
+            }
+        }
+    }
+}
+
+/// Emits each UTF-16 code unit as a Unicode key-down/key-up pair. Surrogate
+/// pairs (anything outside the BMP — most emoji, less common CJK) are sent
+/// as two separate code units; Windows recombines them into a single
+/// character when delivering to the target application.
+fn type_text(s: &str) {
+    // Two INPUTs per code unit (down + up). UTF-16 length is the right
+    // capacity bound because that's the unit SendInput's wScan field takes.
+    let cap = s.encode_utf16().count().saturating_mul(2);
+    if cap == 0 {
+        return;
+    }
+
+    let mut inputs: Vec<INPUT> = Vec::with_capacity(cap);
+    for code in s.encode_utf16() {
+        inputs.push(unicode_input(code, KEYBD_EVENT_FLAGS(0)));
+        inputs.push(unicode_input(code, KEYEVENTF_KEYUP));
+    }
+
+    // SAFETY: inputs is a contiguous stack-equivalent buffer; SendInput reads
+    // exactly `cbSize` bytes per entry.
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if (sent as usize) != inputs.len() {
+        eprintln!(
+            "[WARN] type_text: SendInput sent {} of {} inputs",
+            sent,
+            inputs.len()
+        );
+    }
+}
+
+fn unicode_input(scan: u16, extra_flags: KEYBD_EVENT_FLAGS) -> INPUT {
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(0), // 0 + KEYEVENTF_UNICODE → wScan is the codepoint
+                wScan: scan,
+                dwFlags: KEYEVENTF_UNICODE | extra_flags,
+                time: 0,
+                dwExtraInfo: injected_sentinel(),
+            },
+        },
+    }
+}
+
+fn press_key(vk: Vk) {
+    let inputs = [
+        key_input(vk, 0, false),
+        key_input(vk, 0, true),
+    ];
+    // SAFETY: see type_text.
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if (sent as usize) != inputs.len() {
+        eprintln!(
+            "[WARN] press_key: SendInput sent {} of {} for {:?}",
+            sent,
+            inputs.len(),
+            vk
+        );
+    }
+}
+
+/// Press a chord: modifiers go down first, payload key(s) tap, modifiers
+/// release last (reverse order). This matches the standard "hold Shift,
+/// press A, release Shift" sequence Windows expects from human input.
+fn press_combo(keys: &[Vk]) {
+    if keys.is_empty() {
+        return;
+    }
+
+    let (mods, payload): (Vec<Vk>, Vec<Vk>) =
+        keys.iter().copied().partition(|vk| is_modifier(*vk));
+
+    // 2 INPUTs per key for the modifiers (down + up) and 2 per payload key.
+    let mut inputs: Vec<INPUT> = Vec::with_capacity((mods.len() + payload.len()) * 2);
+
+    for vk in &mods {
+        inputs.push(key_input(*vk, 0, false));
+    }
+    for vk in &payload {
+        inputs.push(key_input(*vk, 0, false));
+    }
+    // Reverse-order release.
+    for vk in payload.iter().rev() {
+        inputs.push(key_input(*vk, 0, true));
+    }
+    for vk in mods.iter().rev() {
+        inputs.push(key_input(*vk, 0, true));
+    }
+
+    // SAFETY: see type_text.
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if (sent as usize) != inputs.len() {
+        eprintln!(
+            "[WARN] press_combo: SendInput sent {} of {} inputs",
+            sent,
+            inputs.len()
+        );
+    }
+}
+
+fn apply_window_op(op: WindowOp, target: HWND) {
+    if target.is_invalid() {
+        eprintln!("[WARN] window step skipped: no foreground HWND captured");
+        return;
+    }
+
+    // SAFETY: target is a non-invalid HWND obtained from GetForegroundWindow
+    // at sequence start; ShowWindow / PostMessageW both tolerate a stale
+    // HWND by returning an error / FALSE without UB.
+    unsafe {
+        match op {
+            WindowOp::Minimize => {
+                let _ = ShowWindow(target, SW_MINIMIZE);
+            }
+            WindowOp::Maximize => {
+                let _ = ShowWindow(target, SW_MAXIMIZE);
+            }
+            WindowOp::Restore => {
+                let _ = ShowWindow(target, SW_RESTORE);
+            }
+            WindowOp::Close => {
+                if let Err(e) = PostMessageW(Some(target), WM_CLOSE, WPARAM(0), LPARAM(0)) {
+                    eprintln!("[WARN] window close PostMessage failed: {:?}", e);
                 }
             }
-            BindAction::OpenUrl(url) => write!(f, "open {}", url),
-            BindAction::Media(key) => write!(f, "media {:?}", key),
-            BindAction::DoNothing => write!(f, "do nothing"),
         }
     }
 }
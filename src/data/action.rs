@@ -0,0 +1,156 @@
+//! What a binding does when it fires, as plain data. Carrying it out is
+//! the platform's job: `core::windows_actions` executes these on Windows.
+
+use std::sync::Arc;
+
+use crate::data::key_combination::KeyCombination;
+use crate::data::sequence::SequenceStep;
+use crate::data::vk::*;
+use crate::data::vk_name::vk_name;
+use crate::system::system_function::SystemFunction;
+
+#[allow(dead_code)] // Variants are part of the public surface; not every binary uses them all.
+#[derive(Debug, Clone)]
+pub enum BindAction {
+    SwitchLanguage,
+    SwitchLanguageBackward,
+    ToggleCapsLock,
+    PressKey(Vk),
+    PostMessage { msg: u32, wparam: usize, lparam: isize },
+    /// Macro-like body: list of steps executed in a spawned worker thread.
+    /// The hook callback must return fast, so it only kicks off the thread —
+    /// it never blocks waiting for delays or `SendInput` to drain.
+    ///
+    /// `Arc` makes cloning the binding (and therefore freezing the binding
+    /// list at install) cheap regardless of sequence length.
+    Sequence(Arc<Vec<SequenceStep>>),
+    /// Spawn a new process. Detached: child outlives the daemon, no console
+    /// window is attached to ours. Use absolute path or rely on PATH lookup.
+    Launch { exe: String, args: Vec<String> },
+    /// Hand a URL / mailto: / file path to the OS default handler via
+    /// `ShellExecuteW`. Works for `https://...`, `mailto:...`, document files,
+    /// even plain executables (equivalent to "open" verb).
+    OpenUrl(String),
+    /// Synthesize a media / volume / playback key. Maps to one of the
+    /// `VK_MEDIA_*` / `VK_VOLUME_*` virtual keys via `SendInput`.
+    Media(MediaKey),
+    /// Change which binding layers are active. Never executed here: the
+    /// engine applies it to its layer stack as soon as it fires.
+    Layer { op: LayerOp, layer: Arc<str> },
+    DoNothing,
+}
+
+/// What a `Layer` action does to its layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerOp {
+    /// Activate until a matching `Off` / `Toggle`.
+    On,
+    /// Deactivate, however it was activated.
+    Off,
+    /// `On` if inactive, `Off` otherwise.
+    Toggle,
+    /// Active while the key that fired the action is held.
+    Momentary,
+    /// Active for the next key press only.
+    OneShot,
+}
+
+/// Media-control keys synthesized via `SendInput`. These map 1:1 to the
+/// virtual keys most physical multimedia keyboards already emit, so binding
+/// a hardware media key is the same code path as binding e.g. F13 to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKey {
+    PlayPause,
+    Stop,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    VolumeMute,
+}
+
+impl MediaKey {
+    pub fn as_vk(self) -> Vk {
+        match self {
+            MediaKey::PlayPause  => VK_MEDIA_PLAY_PAUSE,
+            MediaKey::Stop       => VK_MEDIA_STOP,
+            MediaKey::Next       => VK_MEDIA_NEXT_TRACK,
+            MediaKey::Previous   => VK_MEDIA_PREV_TRACK,
+            MediaKey::VolumeUp   => VK_VOLUME_UP,
+            MediaKey::VolumeDown => VK_VOLUME_DOWN,
+            MediaKey::VolumeMute => VK_VOLUME_MUTE,
+        }
+    }
+}
+
+// Hand-rolled `PartialEq` because the inner `Arc<Vec<…>>` makes the default
+// derive force structural equality on the Vec; we want pointer equality (two
+// bindings with the exact same `Sequence` Arc compare equal, distinct Arcs
+// compare unequal) which is the only case the rest of the program cares
+// about (auto-blocker dedup).
+impl PartialEq for BindAction {
+    fn eq(&self, other: &Self) -> bool {
+        use BindAction::*;
+        match (self, other) {
+            (SwitchLanguage, SwitchLanguage)
+            | (SwitchLanguageBackward, SwitchLanguageBackward)
+            | (ToggleCapsLock, ToggleCapsLock)
+            | (DoNothing, DoNothing) => true,
+            (PressKey(a), PressKey(b)) => a == b,
+            (
+                PostMessage { msg: m1, wparam: w1, lparam: l1 },
+                PostMessage { msg: m2, wparam: w2, lparam: l2 },
+            ) => m1 == m2 && w1 == w2 && l1 == l2,
+            (Sequence(a), Sequence(b)) => Arc::ptr_eq(a, b),
+            (Launch { exe: e1, args: a1 }, Launch { exe: e2, args: a2 }) => e1 == e2 && a1 == a2,
+            (OpenUrl(a), OpenUrl(b)) => a == b,
+            (Media(a), Media(b)) => a == b,
+            (Layer { op: o1, layer: l1 }, Layer { op: o2, layer: l2 }) => o1 == o2 && l1 == l2,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for BindAction {}
+
+impl BindAction {
+    pub fn to_system_function(&self) -> Option<SystemFunction> {
+        match self {
+            BindAction::SwitchLanguage => Some(SystemFunction::SwitchLanguage),
+            BindAction::SwitchLanguageBackward => Some(SystemFunction::SwitchLanguageBackward),
+            BindAction::ToggleCapsLock => Some(SystemFunction::ToggleCapsLock),
+            _ => None,
+        }
+    }
+
+    /// The system-default hotkey for this action's underlying OS function, if
+    /// any. Used to derive auto-blockers when `block_original_combo` is set.
+    pub fn get_system_combination(&self) -> Option<KeyCombination> {
+        self.to_system_function()
+            .and_then(|sf| sf.get_system_combination())
+    }
+}
+
+impl std::fmt::Display for BindAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindAction::SwitchLanguage => write!(f, "switch language"),
+            BindAction::SwitchLanguageBackward => write!(f, "switch language backward"),
+            BindAction::ToggleCapsLock => write!(f, "toggle CapsLock"),
+            BindAction::PressKey(vk) => write!(f, "press key {}", vk_name(*vk)),
+            BindAction::PostMessage { msg, .. } => write!(f, "post message {:#X}", msg),
+            BindAction::Sequence(steps) => write!(f, "sequence ({} steps)", steps.len()),
+            BindAction::Launch { exe, args } => {
+                if args.is_empty() {
+                    write!(f, "launch {}", exe)
+                } else {
+                    write!(f, "launch {} {}", exe, args.join(" "))
+                }
+            }
+            BindAction::OpenUrl(url) => write!(f, "open {}", url),
+            BindAction::Media(key) => write!(f, "media {:?}", key),
+            BindAction::Layer { op, layer } => write!(f, "layer {:?} {}", op, layer),
+            BindAction::DoNothing => write!(f, "do nothing"),
+        }
+    }
+}
@@ -1,12 +1,13 @@
+use std::sync::Arc;
 use std::time::Duration;
 
-use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
-
 use super::condition::Condition;
-use super::key_combination::KeyCombination;
+use super::key_combination::{KeyCombination, MatchMode};
+use super::on_fail::OnFail;
 use super::trigger::Trigger;
-use super::vk_name::vk_name;
-use crate::core::windows_actions::BindAction;
+use super::vk::Vk;
+use super::vk_name::{is_sided_or_positional, vk_name};
+use crate::data::action::BindAction;
 
 /// What kind of input pattern triggers this binding.
 ///
@@ -15,8 +16,9 @@ use crate::core::windows_actions::BindAction;
 /// - `Sequence` is a Vim/Emacs leader-key flow: each step is its own combo,
 ///   pressed in order with at most `max_gap` between consecutive steps.
 ///   Keys involved in an in-progress sequence are suppressed from the
-///   foreground; if the sequence times out or is broken, those keys are
-///   *lost* (no replay). Bind only to keys not used in normal typing.
+///   foreground; if the sequence times out or is broken, the binding's
+///   `on_fail` decides whether they are lost, replayed, or replaced by a
+///   fallback action.
 /// - `Chord` requires the listed keys to all go down within `window` of
 ///   each other (the QMK "combo" concept — not to be confused with this
 ///   project's `Combo`). Order doesn't matter.
@@ -28,7 +30,7 @@ pub enum BindingKind {
         max_gap: Duration,
     },
     Chord {
-        keys: Vec<VIRTUAL_KEY>,
+        keys: Vec<Vk>,
         window: Duration,
     },
 }
@@ -46,6 +48,26 @@ impl BindingKind {
             BindingKind::Chord { keys, .. } => keys.len(),
         }
     }
+
+    /// The key bindings are ordered by, highest first, for the matching
+    /// loop: more keys, then more sided or positional ones.
+    /// `engine::sort_by_specificity` sorts by it, and the config conflict
+    /// analyser walks bindings in the same order.
+    pub fn specificity(&self) -> (usize, usize) {
+        (self.key_count(), self.narrow_key_count())
+    }
+
+    /// How many of the keys are sided or positional (see
+    /// [`is_sided_or_positional`]). Ranks `RCtrl+K` ahead of `Ctrl+K` and
+    /// `NumpadEnter` ahead of `Enter`, which the same keys also match.
+    pub fn narrow_key_count(&self) -> usize {
+        let keys: Vec<&Vk> = match self {
+            BindingKind::Combo(c) => c.keys.iter().collect(),
+            BindingKind::Sequence { steps, .. } => steps.iter().flat_map(|s| &s.keys).collect(),
+            BindingKind::Chord { keys, .. } => keys.iter().collect(),
+        };
+        keys.into_iter().filter(|&&k| is_sided_or_positional(k)).count()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -64,6 +86,27 @@ pub struct Binding {
     /// `BindingKind::Combo`; the loader rejects deferred triggers paired
     /// with `Sequence`/`Chord` (which have their own temporal semantics).
     pub trigger: Trigger,
+    /// Whether an `Immediate` combo fires again on every OS auto-repeat of
+    /// its key while held. With `false` the repeats are still swallowed
+    /// (if `block_default`), just not acted on.
+    pub repeat: bool,
+    /// What to do with swallowed keys when a `Sequence`/`Chord` gesture
+    /// fails to complete. Only meaningful for those kinds; the loader
+    /// rejects anything but the default `Drop` on a `Combo`.
+    pub on_fail: OnFail,
+    /// Layer this binding belongs to. `None` (top-level `bindings:`) is
+    /// always live; `Some(name)` only while that layer is active.
+    pub layer: Option<Arc<str>>,
+    /// `id:` from the config — unique, and what reload reports and error
+    /// messages name the binding by.
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Groups switched on and off together at runtime (`swch enable-tag`).
+    pub tags: Vec<Arc<str>>,
+    /// Whether the binding is live while none of its tags has been switched
+    /// at runtime. See [`Binding::is_live`].
+    pub enabled: bool,
     pub(crate) is_auto_blocker: bool,
 }
 
@@ -82,6 +125,14 @@ impl Binding {
             block_original_combo: false,
             condition: Condition::Always,
             trigger: Trigger::Immediate,
+            repeat: true,
+            on_fail: OnFail::Drop,
+            layer: None,
+            id: None,
+            name: None,
+            description: None,
+            tags: Vec::new(),
+            enabled: true,
             is_auto_blocker: false,
         }
     }
@@ -94,6 +145,14 @@ impl Binding {
             block_original_combo: false,
             condition: Condition::Always,
             trigger: Trigger::Immediate,
+            repeat: true,
+            on_fail: OnFail::Drop,
+            layer: None,
+            id: None,
+            name: None,
+            description: None,
+            tags: Vec::new(),
+            enabled: true,
             is_auto_blocker: true,
         }
     }
@@ -108,6 +167,26 @@ impl Binding {
         }
     }
 
+    /// Every action this binding can fire: its own action, its `on_fail`
+    /// fallback, and the steps of a `TapDance` trigger.
+    pub fn actions(&self) -> Vec<&BindAction> {
+        let mut actions = vec![&self.action];
+        if let OnFail::Action(action) = &self.on_fail {
+            actions.push(action);
+        }
+        if let Trigger::TapDance { steps, .. } = &self.trigger {
+            for step in steps.iter() {
+                actions.extend(step.tap.iter().chain(step.hold.iter()));
+            }
+        }
+        actions
+    }
+
+    /// True if `action` is something this binding can fire.
+    pub fn can_fire(&self, action: &BindAction) -> bool {
+        self.actions().contains(&action)
+    }
+
     #[allow(dead_code)] // Part of the public builder API.
     pub fn with_block_default(mut self, block: bool) -> Self {
         self.block_default = block;
@@ -131,8 +210,60 @@ impl Binding {
         self
     }
 
-    pub fn execute(&self) {
-        self.action.execute();
+    #[allow(dead_code)] // Part of the public builder API.
+    pub fn with_repeat(mut self, repeat: bool) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn with_on_fail(mut self, on_fail: OnFail) -> Self {
+        self.on_fail = on_fail;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: Option<Arc<str>>) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_id(mut self, id: Option<String>) -> Self {
+        self.id = id;
+        self
+    }
+
+    pub fn with_name(mut self, name: Option<String>) -> Self {
+        self.name = name;
+        self
+    }
+
+    pub fn with_description(mut self, description: Option<String>) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn with_tags(mut self, tags: Vec<Arc<str>>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Whether the binding takes part in matching, given the tags switched
+    /// at runtime: a disabled tag turns it off, else an enabled one turns
+    /// it on, else its own `enabled` decides.
+    pub fn is_live(&self, switched_tags: &[(Arc<str>, bool)]) -> bool {
+        let mut live = self.enabled;
+        for tag in &self.tags {
+            match switched_tags.iter().find(|(t, _)| t == tag) {
+                Some((_, false)) => return false,
+                Some((_, true)) => live = true,
+                None => {}
+            }
+        }
+        live
     }
 }
 
@@ -145,16 +276,63 @@ impl std::fmt::Display for Binding {
         } else {
             format!("  [{:?}]", self.condition)
         };
-        let trigger_suffix = if self.trigger.is_immediate() {
+        let mut trigger_suffix = if self.trigger.is_immediate() {
             String::new()
         } else {
             format!("  @{}", self.trigger)
         };
+        if !self.repeat {
+            trigger_suffix.push_str("  (no repeat)");
+        }
+        match self.combination().map(|c| c.mode) {
+            Some(MatchMode::Exact) => trigger_suffix.push_str("  (exact)"),
+            Some(MatchMode::Ordered) => trigger_suffix.push_str("  (ordered)"),
+            _ => {}
+        }
+        let on_fail_suffix = if self.on_fail.is_drop() {
+            String::new()
+        } else {
+            format!("  on_fail: {}", self.on_fail)
+        };
+        let layer_suffix = match &self.layer {
+            Some(layer) => format!("  (layer {})", layer),
+            None => String::new(),
+        };
+        let mut meta_suffix = String::new();
+        match (&self.name, &self.id) {
+            (Some(name), Some(id)) => meta_suffix.push_str(&format!("  {:?} ({})", name, id)),
+            (Some(name), None) => meta_suffix.push_str(&format!("  {:?}", name)),
+            (None, Some(id)) => meta_suffix.push_str(&format!("  ({})", id)),
+            (None, None) => {}
+        }
+        if let Some(description) = &self.description {
+            meta_suffix.push_str(&format!(" — {}", description));
+        }
+        for tag in &self.tags {
+            meta_suffix.push_str(&format!("  #{}", tag));
+        }
+        if !self.enabled {
+            meta_suffix.push_str("  (disabled)");
+        }
 
         if self.is_auto_blocker {
-            write!(f, "[AUTO-BLOCK] {:<24} -> (blocked){}{}", keys_str, cond_suffix, trigger_suffix)
+            write!(
+                f,
+                "[AUTO-BLOCK] {:<24} -> (blocked){}{}{}{}",
+                keys_str, cond_suffix, trigger_suffix, layer_suffix, meta_suffix
+            )
         } else {
-            write!(f, "{:<34} -> {}{}{}", keys_str, self.action, cond_suffix, trigger_suffix)
+            write!(
+                f,
+                "{:<34} -> {}{}{}{}{}{}",
+                keys_str,
+                self.action,
+                cond_suffix,
+                trigger_suffix,
+                on_fail_suffix,
+                layer_suffix,
+                meta_suffix
+            )
         }
     }
 }
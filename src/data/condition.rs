@@ -10,17 +10,6 @@
 //! names case-insensitively, and humans don't reliably remember the casing
 //! of window titles either.
 
-use std::ffi::c_void;
-
-use windows::Win32::{
-    Foundation::*,
-    System::{
-        ProcessStatus::GetModuleBaseNameW,
-        Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION},
-    },
-    UI::WindowsAndMessaging::{GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId},
-};
-
 #[derive(Debug, Clone, Default)]
 pub enum Condition {
     /// Always true — the binding fires whenever the combo matches. Default
@@ -54,6 +43,48 @@ impl Condition {
         matches!(self, Condition::Always)
     }
 
+    /// Static check: is `self` true in every context where `other` is?
+    /// Conservative — `false` means "not provably", never "provably not".
+    /// Used by the config analyser to find bindings that can never fire.
+    pub fn covers(&self, other: &Condition) -> bool {
+        match (self, other) {
+            (Condition::Always, _) => true,
+            (_, Condition::And(parts)) if parts.iter().any(|p| self.covers(p)) => true,
+            (_, Condition::Or(parts)) => parts.iter().all(|p| self.covers(p)),
+            (Condition::And(parts), _) => parts.iter().all(|p| p.covers(other)),
+            (Condition::Or(parts), _) => parts.iter().any(|p| p.covers(other)),
+            (Condition::Not(inner), _) => inner.excludes(other),
+            (Condition::AppEquals(a), Condition::AppEquals(b))
+            | (Condition::TitleEquals(a), Condition::TitleEquals(b)) => a.eq_ignore_ascii_case(b),
+            (
+                Condition::TitleContains(needle),
+                Condition::TitleContains(title) | Condition::TitleEquals(title),
+            ) => title.to_lowercase().contains(&needle.to_lowercase()),
+            _ => false,
+        }
+    }
+
+    /// Static check: can `self` and `other` never both be true? Conservative
+    /// like [`Condition::covers`]: `false` means they might overlap.
+    pub fn excludes(&self, other: &Condition) -> bool {
+        match (self, other) {
+            (Condition::Not(inner), _) => inner.covers(other),
+            (_, Condition::Not(inner)) => inner.covers(self),
+            (Condition::Always, _) | (_, Condition::Always) => false,
+            (Condition::And(parts), _) => parts.iter().any(|p| p.excludes(other)),
+            (_, Condition::And(parts)) => parts.iter().any(|p| self.excludes(p)),
+            (Condition::Or(parts), _) => parts.iter().all(|p| p.excludes(other)),
+            (_, Condition::Or(parts)) => parts.iter().all(|p| self.excludes(p)),
+            (Condition::AppEquals(a), Condition::AppEquals(b))
+            | (Condition::TitleEquals(a), Condition::TitleEquals(b)) => !a.eq_ignore_ascii_case(b),
+            (Condition::TitleEquals(title), Condition::TitleContains(needle))
+            | (Condition::TitleContains(needle), Condition::TitleEquals(title)) => {
+                !title.to_lowercase().contains(&needle.to_lowercase())
+            }
+            _ => false,
+        }
+    }
+
     pub fn evaluate(&self, ctx: &ForegroundContext) -> bool {
         match self {
             Condition::Always => true,
@@ -78,35 +109,51 @@ impl Condition {
     }
 }
 
+/// Where a lazily filled [`ForegroundContext`] looks the focused window up.
+/// The hook adapter implements it over the live window handle.
+pub trait ForegroundWindow {
+    /// Base name of the window's executable.
+    fn app(&self) -> Option<String>;
+    /// Title bar text of the window.
+    fn title(&self) -> Option<String>;
+}
+
 /// One-shot snapshot of the focused window's identifying info. Populated
-/// lazily — `app()` and `title()` each issue their Win32 calls on first use,
-/// then cache the result so a callback with N condition-bearing bindings
-/// pays for the lookups exactly once.
+/// lazily — `app()` and `title()` each ask the window on first use, then
+/// cache the result so a callback with N condition-bearing bindings pays
+/// for the lookups exactly once.
 pub struct ForegroundContext {
-    hwnd: HWND,
+    window: Option<Box<dyn ForegroundWindow>>,
     app: std::cell::OnceCell<Option<String>>,
     title: std::cell::OnceCell<Option<String>>,
 }
 
 impl ForegroundContext {
-    /// Capture the foreground window. Safe to call any time — never returns
-    /// an invalid context, but `app()` / `title()` will yield `None` if the
-    /// OS reports no foreground window (e.g. during secure-desktop transitions).
-    pub fn capture() -> Self {
-        // SAFETY: GetForegroundWindow has no preconditions.
-        let hwnd = unsafe { GetForegroundWindow() };
+    /// Context backed by `window`, looked up on first use.
+    pub fn lazy(window: Box<dyn ForegroundWindow>) -> Self {
         Self {
-            hwnd,
+            window: Some(window),
             app: std::cell::OnceCell::new(),
             title: std::cell::OnceCell::new(),
         }
     }
 
+    /// Context with pre-filled values and no live window behind it. Used by
+    /// the engine tests and trace replay, where the foreground app is part
+    /// of the recorded input rather than something to ask the OS about.
+    pub fn snapshot(app: Option<String>, title: Option<String>) -> Self {
+        Self {
+            window: None,
+            app: std::cell::OnceCell::from(app),
+            title: std::cell::OnceCell::from(title),
+        }
+    }
+
     /// Base name of the focused window's executable (e.g. `"chrome.exe"`),
     /// or `None` if the window/process is gone or inaccessible.
     pub fn app(&self) -> Option<&str> {
         self.app
-            .get_or_init(|| self.fetch_app())
+            .get_or_init(|| self.window.as_ref().and_then(|w| w.app()))
             .as_deref()
     }
 
@@ -114,75 +161,11 @@ impl ForegroundContext {
     /// gone or has no title (rare).
     pub fn title(&self) -> Option<&str> {
         self.title
-            .get_or_init(|| self.fetch_title())
+            .get_or_init(|| self.window.as_ref().and_then(|w| w.title()))
             .as_deref()
     }
-
-    fn fetch_app(&self) -> Option<String> {
-        if self.hwnd.is_invalid() {
-            return None;
-        }
-        // SAFETY: hwnd is the result of GetForegroundWindow and validated
-        // non-invalid above. pid out-pointer is a valid stack local.
-        let mut pid: u32 = 0;
-        let _thread_id = unsafe { GetWindowThreadProcessId(self.hwnd, Some(&mut pid)) };
-        if pid == 0 {
-            return None;
-        }
-
-        // PROCESS_QUERY_LIMITED_INFORMATION is enough for GetModuleBaseNameW
-        // and is permitted against most processes including elevated ones,
-        // which PROCESS_QUERY_INFORMATION would deny.
-        //
-        // SAFETY: OpenProcess returns an owned handle that we close on drop
-        // via the RAII wrapper below.
-        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) }.ok()?;
-        let _guard = ProcessHandleGuard(handle);
-
-        let mut buf = [0u16; 260]; // MAX_PATH; exe names are short.
-        // SAFETY: handle is valid; buf is sized and writable.
-        let len = unsafe { GetModuleBaseNameW(handle, None, &mut buf) };
-        if len == 0 {
-            return None;
-        }
-        Some(String::from_utf16_lossy(&buf[..len as usize]))
-    }
-
-    fn fetch_title(&self) -> Option<String> {
-        if self.hwnd.is_invalid() {
-            return None;
-        }
-        let mut buf = [0u16; 512];
-        // SAFETY: hwnd validated above; buf sized and writable.
-        let len = unsafe { GetWindowTextW(self.hwnd, &mut buf) };
-        if len == 0 {
-            return None;
-        }
-        Some(String::from_utf16_lossy(&buf[..len as usize]))
-    }
 }
 
-/// Closes the process handle when dropped. Local RAII because the Win32
-/// `CloseHandle` requires the raw handle and panics from inside `fetch_app`
-/// would otherwise leak.
-struct ProcessHandleGuard(HANDLE);
-
-impl Drop for ProcessHandleGuard {
-    fn drop(&mut self) {
-        if !self.0.is_invalid() {
-            // SAFETY: handle came from OpenProcess above and hasn't been
-            // closed by anyone else.
-            unsafe {
-                let _ = windows::Win32::Foundation::CloseHandle(self.0);
-            }
-        }
-    }
-}
-
-// Silence "unused" on the c_void import which is pulled in transitively by
-// the windows crate's ProcessHandleGuard semantics but not directly named.
-const _: Option<*mut c_void> = None;
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -190,10 +173,35 @@ mod tests {
     /// `Always` short-circuits and never touches the context.
     #[test]
     fn always_is_true() {
-        let ctx = ForegroundContext::capture();
+        let ctx = ForegroundContext::snapshot(None, None);
         assert!(Condition::Always.evaluate(&ctx));
     }
 
+    #[test]
+    fn lazy_context_asks_the_window_once() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct Counting(Rc<Cell<u32>>);
+        impl ForegroundWindow for Counting {
+            fn app(&self) -> Option<String> {
+                self.0.set(self.0.get() + 1);
+                Some("Code.exe".into())
+            }
+            fn title(&self) -> Option<String> {
+                None
+            }
+        }
+
+        let lookups = Rc::new(Cell::new(0));
+        let ctx = ForegroundContext::lazy(Box::new(Counting(Rc::clone(&lookups))));
+        assert_eq!(lookups.get(), 0);
+        assert!(Condition::AppEquals("code.exe".into()).evaluate(&ctx));
+        assert!(!Condition::AppEquals("vim.exe".into()).evaluate(&ctx));
+        assert!(!Condition::TitleContains("x".into()).evaluate(&ctx));
+        assert_eq!(lookups.get(), 1);
+    }
+
     #[test]
     fn is_always_helper() {
         assert!(Condition::Always.is_always());
@@ -203,7 +211,7 @@ mod tests {
 
     #[test]
     fn not_inverts() {
-        let ctx = ForegroundContext::capture();
+        let ctx = ForegroundContext::snapshot(None, None);
         let always_true = Condition::Always;
         let always_false = Condition::Not(Box::new(always_true.clone()));
         assert!(always_true.evaluate(&ctx));
@@ -212,7 +220,7 @@ mod tests {
 
     #[test]
     fn and_short_circuits_on_first_false() {
-        let ctx = ForegroundContext::capture();
+        let ctx = ForegroundContext::snapshot(None, None);
         let cond = Condition::And(vec![
             Condition::Not(Box::new(Condition::Always)), // false
             Condition::Always,                            // not evaluated, but must not panic
@@ -222,7 +230,7 @@ mod tests {
 
     #[test]
     fn or_short_circuits_on_first_true() {
-        let ctx = ForegroundContext::capture();
+        let ctx = ForegroundContext::snapshot(None, None);
         let cond = Condition::Or(vec![
             Condition::Always,
             Condition::Not(Box::new(Condition::Always)),
@@ -230,9 +238,30 @@ mod tests {
         assert!(cond.evaluate(&ctx));
     }
 
+    #[test]
+    fn covers_and_excludes() {
+        let app = |s: &str| Condition::AppEquals(s.into());
+        let code = app("code.exe");
+        assert!(Condition::Always.covers(&code));
+        assert!(!code.covers(&Condition::Always));
+        assert!(app("Code.EXE").covers(&code));
+        assert!(code.covers(&Condition::And(vec![code.clone(), Condition::TitleContains("x".into())])));
+        assert!(Condition::Or(vec![code.clone(), app("vim.exe")]).covers(&code));
+        assert!(!code.covers(&Condition::Or(vec![code.clone(), app("vim.exe")])));
+        assert!(Condition::TitleContains("rust".into())
+            .covers(&Condition::TitleEquals("The Rust Book".into())));
+        assert!(Condition::Not(Box::new(app("vim.exe"))).covers(&code));
+
+        assert!(code.excludes(&app("vim.exe")));
+        assert!(!code.excludes(&Condition::TitleContains("x".into())));
+        assert!(code.excludes(&Condition::Not(Box::new(code.clone()))));
+        assert!(!Condition::Always.excludes(&code));
+        assert!(Condition::Or(vec![app("a.exe"), app("b.exe")]).excludes(&code));
+    }
+
     #[test]
     fn nested_and_or_evaluates_correctly() {
-        let ctx = ForegroundContext::capture();
+        let ctx = ForegroundContext::snapshot(None, None);
         // (true AND (false OR true)) = true
         let cond = Condition::And(vec![
             Condition::Always,
@@ -1,10 +1,26 @@
-use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
+use super::vk::*;
+use super::vk_name::{ALT_GR, ANY_WIN};
+
+/// How a combo's keys must be held for it to match (`match:` in the config).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MatchMode {
+    /// All the keys are held; anything else may be held too.
+    #[default]
+    Subset,
+    /// All the keys are held, and no modifier outside them.
+    Exact,
+    /// All the keys are held, and went down in the listed order.
+    Ordered,
+}
 
 #[derive(Debug, Clone)]
 pub struct KeyCombination {
-    pub keys: Vec<VIRTUAL_KEY>,
+    pub keys: Vec<Vk>,
+    pub mode: MatchMode,
 }
 
+/// Key-set equality: the mode is ignored, as two combos on the same keys
+/// overlap whatever their modes.
 impl PartialEq for KeyCombination {
     fn eq(&self, other: &Self) -> bool {
         if self.keys.len() != other.keys.len() {
@@ -24,37 +40,85 @@ impl PartialEq for KeyCombination {
 impl Eq for KeyCombination {}
 
 impl KeyCombination {
-    pub fn new(key: VIRTUAL_KEY) -> Self {
-        Self { keys: vec![key] }
+    pub fn new(key: Vk) -> Self {
+        Self { keys: vec![key], mode: MatchMode::Subset }
     }
 
     #[allow(dead_code)] // Part of the public builder API; also used by tests.
-    pub fn with(mut self, key: VIRTUAL_KEY) -> Self {
+    pub fn with(mut self, key: Vk) -> Self {
         if !self.keys.contains(&key) {
             self.keys.push(key);
         }
         self
     }
 
-    pub fn from_keys(keys: Vec<VIRTUAL_KEY>) -> Self {
-        let mut deduped: Vec<VIRTUAL_KEY> = Vec::with_capacity(keys.len());
+    pub fn from_keys(keys: Vec<Vk>) -> Self {
+        let mut deduped: Vec<Vk> = Vec::with_capacity(keys.len());
         for k in keys {
             if !deduped.contains(&k) {
                 deduped.push(k);
             }
         }
-        Self { keys: deduped }
+        Self { keys: deduped, mode: MatchMode::Subset }
     }
 
-    pub fn matches(&self, pressed_keys: &[VIRTUAL_KEY]) -> bool {
-        self.keys.iter().all(|key| pressed_keys.contains(key))
+    pub fn with_mode(mut self, mode: MatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// `pressed_keys` must be in the order the keys went down; only
+    /// [`MatchMode::Ordered`] looks at it.
+    pub fn matches(&self, pressed_keys: &[Vk]) -> bool {
+        match self.mode {
+            MatchMode::Subset => self.keys.iter().all(|key| pressed_keys.contains(key)),
+            MatchMode::Exact => {
+                self.keys.iter().all(|key| pressed_keys.contains(key))
+                    && pressed_keys.iter().all(|&held| self.expects(held))
+            }
+            MatchMode::Ordered => {
+                let mut last = None;
+                self.keys.iter().all(|key| {
+                    let at = pressed_keys.iter().position(|k| k == key);
+                    let in_order = at.is_some() && at > last;
+                    last = at;
+                    in_order
+                })
+            }
+        }
+    }
+
+    /// Whether `Exact` lets `held` be down. Any non-modifier may be. A
+    /// modifier must be named by the combo, or its unsided form must be:
+    /// `Ctrl` takes either Ctrl key, `RCtrl` only the right one. The unsided
+    /// VK Windows holds alongside either side is expected whenever the
+    /// combo names any form of it.
+    fn expects(&self, held: Vk) -> bool {
+        let Some(family) = modifier_family(held) else {
+            return true;
+        };
+        self.keys.iter().any(|&key| {
+            key == held || key == family || (held == family && modifier_family(key) == Some(family))
+        })
+    }
+}
+
+/// The unsided modifier `vk` is a form of, or `None` for other keys: both
+/// Ctrl keys are `Ctrl`.
+fn modifier_family(vk: Vk) -> Option<Vk> {
+    match vk {
+        VK_SHIFT | VK_LSHIFT | VK_RSHIFT => Some(VK_SHIFT),
+        VK_CONTROL | VK_LCONTROL | VK_RCONTROL => Some(VK_CONTROL),
+        VK_MENU | VK_LMENU | VK_RMENU => Some(VK_MENU),
+        ANY_WIN | VK_LWIN | VK_RWIN => Some(ANY_WIN),
+        ALT_GR => Some(ALT_GR),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
 
     #[test]
     fn test_single_key() {
@@ -78,4 +142,33 @@ mod tests {
         let combo = KeyCombination::new(VK_CAPITAL);
         assert!(combo.matches(&[VK_CAPITAL, VK_SHIFT]));
     }
+
+    #[test]
+    fn exact_rejects_extra_modifiers_only() {
+        let combo = KeyCombination::from_keys(vec![VK_CONTROL, VK_K]).with_mode(MatchMode::Exact);
+        assert!(combo.matches(&[VK_LCONTROL, VK_CONTROL, VK_K]));
+        assert!(!combo.matches(&[VK_LCONTROL, VK_CONTROL, VK_LSHIFT, VK_SHIFT, VK_K]));
+        assert!(!combo.matches(&[VK_CONTROL, VK_LWIN, ANY_WIN, VK_K]));
+        // Other held keys are not modifiers and don't count.
+        assert!(combo.matches(&[VK_CONTROL, VK_J, VK_K]));
+        assert!(!combo.matches(&[VK_K]));
+
+        // A sided name covers the generic form held alongside it.
+        let sided = KeyCombination::from_keys(vec![VK_RCONTROL, VK_K]).with_mode(MatchMode::Exact);
+        assert!(sided.matches(&[VK_RCONTROL, VK_CONTROL, VK_K]));
+        assert!(!sided.matches(&[VK_RCONTROL, VK_CONTROL, ALT_GR, VK_K]));
+        assert!(!sided.matches(&[VK_LCONTROL, VK_RCONTROL, VK_CONTROL, VK_K]));
+    }
+
+    #[test]
+    fn ordered_needs_the_listed_press_order() {
+        let combo = KeyCombination::from_keys(vec![VK_SHIFT, VK_CAPITAL]).with_mode(MatchMode::Ordered);
+        assert!(combo.matches(&[VK_LSHIFT, VK_SHIFT, VK_CAPITAL]));
+        assert!(combo.matches(&[VK_A, VK_SHIFT, VK_B, VK_CAPITAL]));
+        assert!(!combo.matches(&[VK_CAPITAL, VK_LSHIFT, VK_SHIFT]));
+        assert!(!combo.matches(&[VK_SHIFT]));
+        // The default mode takes either order.
+        let any_order = KeyCombination::from_keys(vec![VK_SHIFT, VK_CAPITAL]);
+        assert!(any_order.matches(&[VK_CAPITAL, VK_SHIFT]));
+    }
 }
@@ -1,6 +1,9 @@
+pub mod action;
 pub mod binding;
 pub mod condition;
 pub mod key_combination;
+pub mod on_fail;
 pub mod sequence;
 pub mod trigger;
+pub mod vk;
 pub mod vk_name;
\ No newline at end of file
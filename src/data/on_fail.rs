@@ -0,0 +1,56 @@
+//! What happens to the keys a `Sequence` / `Chord` binding swallowed when
+//! the gesture does not complete.
+//!
+//! While a sequence or chord is in flight its key-downs are suppressed from
+//! the foreground — otherwise the first half of `j+k` would already have
+//! typed a `j`. `OnFail` decides what to do with them once the gesture is
+//! abandoned (window/gap expired, or a key that doesn't belong arrived):
+//! lose them (`Drop`, the original behaviour), re-inject them in order
+//! (`Replay`), or fire a fallback action instead (`Action`).
+
+use crate::data::action::BindAction;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OnFail {
+    /// Default. The swallowed key-downs are lost.
+    #[default]
+    Drop,
+    /// Re-inject the swallowed keys in the order they were pressed, marked
+    /// with the injected sentinel so the hook doesn't see them again. This
+    /// is what makes chords on ordinary typing keys usable.
+    Replay,
+    /// Fire this action instead, e.g. a leader key pressed alone sends
+    /// `Escape`. The swallowed keys are not re-injected.
+    Action(BindAction),
+}
+
+impl OnFail {
+    /// True for the original "keys are lost" behaviour. The engine only
+    /// tracks failure (wake-ups, interrupting keys) for the other variants.
+    pub fn is_drop(&self) -> bool {
+        matches!(self, OnFail::Drop)
+    }
+}
+
+impl std::fmt::Display for OnFail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OnFail::Drop => write!(f, "Drop"),
+            OnFail::Replay => write!(f, "Replay"),
+            OnFail::Action(action) => write!(f, "Action({})", action),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_drop() {
+        assert_eq!(OnFail::default(), OnFail::Drop);
+        assert!(OnFail::Drop.is_drop());
+        assert!(!OnFail::Replay.is_drop());
+        assert!(!OnFail::Action(BindAction::DoNothing).is_drop());
+    }
+}
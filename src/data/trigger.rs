@@ -10,8 +10,24 @@
 //! e.g. one `Tap(200)` binding and one `Hold(200)` binding on `CapsLock`
 //! arm both gestures on key-down and the appropriate one resolves on
 //! key-up or timer expiry.
+//!
+//! `TapDance` carries its own actions too — one per tap count, optionally
+//! with a hold action per count — so "CapsLock x1 / x2 / x3" is a single
+//! binding instead of several that race each other.
+//!
+//! `ModTap` is the other exception to "one trigger, one action": it owns its key
+//! outright (home-row modifiers, "CapsLock = Escape on tap, Ctrl on hold")
+//! and is resolved by `crate::hook::mod_tap_state`, because the hold side
+//! has to keep a real modifier down and decide early when other keys are
+//! pressed.
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+use std::sync::Arc;
+
+use crate::data::action::BindAction;
+use crate::data::vk::Vk;
+use crate::data::vk_name::vk_name;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum Trigger {
     /// Default. The action fires synchronously on key-down (the existing
     /// behaviour of every binding before triggers existed).
@@ -28,12 +44,57 @@ pub enum Trigger {
     /// The action fires on the *second* key-down within `<ms>` of the
     /// first. A single press within the window emits nothing.
     DoubleTap(u64),
+    /// Count taps that follow each other within `term_ms`; when the pause
+    /// after the last one exceeds `term_ms`, `steps[count - 1]` fires. The
+    /// last press held past `term_ms` fires that step's hold action
+    /// instead. Ignores the binding's own action.
+    TapDance {
+        term_ms: u64,
+        steps: Arc<[TapDanceStep]>,
+    },
+    /// Tap-hold on a single key. Released before `term_ms` → the binding's
+    /// action fires (the tap). Held past `term_ms` — or interrupted by
+    /// another key, as `flavor` decides — → `hold` (a modifier) is pressed
+    /// for real and kept down until the key is released.
+    ModTap {
+        hold: Vk,
+        term_ms: u64,
+        flavor: ModTapFlavor,
+    },
+}
+
+/// What one tap count of a `TapDance` fires. Either side may be empty: a
+/// count with no `tap` does nothing when released, a count with no `hold`
+/// treats a long last press like a tap.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TapDanceStep {
+    pub tap: Option<BindAction>,
+    pub hold: Option<BindAction>,
+}
+
+/// How a pending `ModTap` reacts to other keys pressed before its term
+/// elapses. Keys pressed while it is undecided are held back and delivered,
+/// in order, once it resolves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModTapFlavor {
+    /// Any other key-down decides hold immediately. Best for keys that are
+    /// mostly used as modifiers (CapsLock → Ctrl).
+    HoldOnOtherKeyPress,
+    /// Default. Another key pressed *and released* while the mod-tap key is
+    /// still down decides hold. Rolling over (`a` down, `s` down, `a` up)
+    /// stays a tap, which is what home-row modifiers need. ZMK calls this
+    /// flavor "balanced".
+    #[default]
+    PermissiveHold,
+    /// Only the term decides: released before it → tap, held past it →
+    /// hold, no matter what else was pressed in between.
+    TapPreferred,
 }
 
 impl Trigger {
     /// True for the simple "execute now" path. Used by the hot path to
     /// skip the deferred-gesture machinery entirely when no binding needs it.
-    pub fn is_immediate(self) -> bool {
+    pub fn is_immediate(&self) -> bool {
         matches!(self, Trigger::Immediate)
     }
 }
@@ -45,6 +106,23 @@ impl std::fmt::Display for Trigger {
             Trigger::Tap(ms)      => write!(f, "Tap({}ms)", ms),
             Trigger::Hold(ms)     => write!(f, "Hold({}ms)", ms),
             Trigger::DoubleTap(ms) => write!(f, "DoubleTap({}ms)", ms),
+            Trigger::TapDance { term_ms, steps } => {
+                write!(f, "TapDance({}ms", term_ms)?;
+                for (i, step) in steps.iter().enumerate() {
+                    let sep = if i == 0 { ": " } else { " | " };
+                    match &step.tap {
+                        Some(action) => write!(f, "{}{}", sep, action)?,
+                        None => write!(f, "{}-", sep)?,
+                    }
+                    if let Some(hold) = &step.hold {
+                        write!(f, " (hold: {})", hold)?;
+                    }
+                }
+                write!(f, ")")
+            }
+            Trigger::ModTap { hold, term_ms, flavor } => {
+                write!(f, "ModTap(hold {}, {}ms, {:?})", vk_name(*hold), term_ms, flavor)
+            }
         }
     }
 }
@@ -61,4 +139,36 @@ mod tests {
         assert!(!Trigger::Hold(200).is_immediate());
         assert!(!Trigger::DoubleTap(200).is_immediate());
     }
+
+    #[test]
+    fn tap_dance_display_lists_steps() {
+        let trigger = Trigger::TapDance {
+            term_ms: 200,
+            steps: Arc::from(vec![
+                TapDanceStep { tap: Some(BindAction::SwitchLanguage), hold: None },
+                TapDanceStep { tap: None, hold: Some(BindAction::ToggleCapsLock) },
+            ]),
+        };
+        assert!(!trigger.is_immediate());
+        assert_eq!(
+            trigger.to_string(),
+            format!(
+                "TapDance(200ms: {} | - (hold: {}))",
+                BindAction::SwitchLanguage,
+                BindAction::ToggleCapsLock
+            )
+        );
+    }
+
+    #[test]
+    fn mod_tap_display_names_the_modifier() {
+        use crate::data::vk::VK_CONTROL;
+        let trigger = Trigger::ModTap {
+            hold: VK_CONTROL,
+            term_ms: 200,
+            flavor: ModTapFlavor::default(),
+        };
+        assert!(!trigger.is_immediate());
+        assert_eq!(trigger.to_string(), "ModTap(hold Ctrl, 200ms, PermissiveHold)");
+    }
 }
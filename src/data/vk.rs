@@ -0,0 +1,185 @@
+//! Virtual-key codes, as a crate-local type so the engine, the binding
+//! data and the config layer build and test without the Win32 bindings.
+//! The values are Windows' own (`WinUser.h`); the hook adapter converts
+//! at the boundary.
+
+/// A Windows virtual-key code, or one of the pseudo keys past `0xFF` that
+/// [`crate::data::vk_name`] defines (positional keys, `Win`, `AltGr`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Vk(pub u16);
+
+pub const VK_LBUTTON: Vk = Vk(0x01);
+pub const VK_RBUTTON: Vk = Vk(0x02);
+pub const VK_CANCEL: Vk = Vk(0x03);
+pub const VK_MBUTTON: Vk = Vk(0x04);
+pub const VK_XBUTTON1: Vk = Vk(0x05);
+pub const VK_XBUTTON2: Vk = Vk(0x06);
+pub const VK_BACK: Vk = Vk(0x08);
+pub const VK_TAB: Vk = Vk(0x09);
+pub const VK_CLEAR: Vk = Vk(0x0C);
+pub const VK_RETURN: Vk = Vk(0x0D);
+pub const VK_SHIFT: Vk = Vk(0x10);
+pub const VK_CONTROL: Vk = Vk(0x11);
+pub const VK_MENU: Vk = Vk(0x12);
+pub const VK_PAUSE: Vk = Vk(0x13);
+pub const VK_CAPITAL: Vk = Vk(0x14);
+pub const VK_HANGEUL: Vk = Vk(0x15);
+pub const VK_HANGUL: Vk = Vk(0x15);
+pub const VK_KANA: Vk = Vk(0x15);
+pub const VK_IME_ON: Vk = Vk(0x16);
+pub const VK_JUNJA: Vk = Vk(0x17);
+pub const VK_FINAL: Vk = Vk(0x18);
+pub const VK_HANJA: Vk = Vk(0x19);
+pub const VK_KANJI: Vk = Vk(0x19);
+pub const VK_IME_OFF: Vk = Vk(0x1A);
+pub const VK_ESCAPE: Vk = Vk(0x1B);
+pub const VK_CONVERT: Vk = Vk(0x1C);
+pub const VK_NONCONVERT: Vk = Vk(0x1D);
+pub const VK_ACCEPT: Vk = Vk(0x1E);
+pub const VK_MODECHANGE: Vk = Vk(0x1F);
+pub const VK_SPACE: Vk = Vk(0x20);
+pub const VK_PRIOR: Vk = Vk(0x21);
+pub const VK_NEXT: Vk = Vk(0x22);
+pub const VK_END: Vk = Vk(0x23);
+pub const VK_HOME: Vk = Vk(0x24);
+pub const VK_LEFT: Vk = Vk(0x25);
+pub const VK_UP: Vk = Vk(0x26);
+pub const VK_RIGHT: Vk = Vk(0x27);
+pub const VK_DOWN: Vk = Vk(0x28);
+pub const VK_SELECT: Vk = Vk(0x29);
+pub const VK_PRINT: Vk = Vk(0x2A);
+pub const VK_EXECUTE: Vk = Vk(0x2B);
+pub const VK_SNAPSHOT: Vk = Vk(0x2C);
+pub const VK_INSERT: Vk = Vk(0x2D);
+pub const VK_DELETE: Vk = Vk(0x2E);
+pub const VK_HELP: Vk = Vk(0x2F);
+pub const VK_0: Vk = Vk(0x30);
+pub const VK_1: Vk = Vk(0x31);
+pub const VK_2: Vk = Vk(0x32);
+pub const VK_3: Vk = Vk(0x33);
+pub const VK_4: Vk = Vk(0x34);
+pub const VK_5: Vk = Vk(0x35);
+pub const VK_6: Vk = Vk(0x36);
+pub const VK_7: Vk = Vk(0x37);
+pub const VK_8: Vk = Vk(0x38);
+pub const VK_9: Vk = Vk(0x39);
+pub const VK_A: Vk = Vk(0x41);
+pub const VK_B: Vk = Vk(0x42);
+pub const VK_C: Vk = Vk(0x43);
+pub const VK_D: Vk = Vk(0x44);
+pub const VK_E: Vk = Vk(0x45);
+pub const VK_F: Vk = Vk(0x46);
+pub const VK_G: Vk = Vk(0x47);
+pub const VK_H: Vk = Vk(0x48);
+pub const VK_I: Vk = Vk(0x49);
+pub const VK_J: Vk = Vk(0x4A);
+pub const VK_K: Vk = Vk(0x4B);
+pub const VK_L: Vk = Vk(0x4C);
+pub const VK_M: Vk = Vk(0x4D);
+pub const VK_N: Vk = Vk(0x4E);
+pub const VK_O: Vk = Vk(0x4F);
+pub const VK_P: Vk = Vk(0x50);
+pub const VK_Q: Vk = Vk(0x51);
+pub const VK_R: Vk = Vk(0x52);
+pub const VK_S: Vk = Vk(0x53);
+pub const VK_T: Vk = Vk(0x54);
+pub const VK_U: Vk = Vk(0x55);
+pub const VK_V: Vk = Vk(0x56);
+pub const VK_W: Vk = Vk(0x57);
+pub const VK_X: Vk = Vk(0x58);
+pub const VK_Y: Vk = Vk(0x59);
+pub const VK_Z: Vk = Vk(0x5A);
+pub const VK_LWIN: Vk = Vk(0x5B);
+pub const VK_RWIN: Vk = Vk(0x5C);
+pub const VK_APPS: Vk = Vk(0x5D);
+pub const VK_SLEEP: Vk = Vk(0x5F);
+pub const VK_NUMPAD0: Vk = Vk(0x60);
+pub const VK_NUMPAD1: Vk = Vk(0x61);
+pub const VK_NUMPAD2: Vk = Vk(0x62);
+pub const VK_NUMPAD3: Vk = Vk(0x63);
+pub const VK_NUMPAD4: Vk = Vk(0x64);
+pub const VK_NUMPAD5: Vk = Vk(0x65);
+pub const VK_NUMPAD6: Vk = Vk(0x66);
+pub const VK_NUMPAD7: Vk = Vk(0x67);
+pub const VK_NUMPAD8: Vk = Vk(0x68);
+pub const VK_NUMPAD9: Vk = Vk(0x69);
+pub const VK_MULTIPLY: Vk = Vk(0x6A);
+pub const VK_ADD: Vk = Vk(0x6B);
+pub const VK_SEPARATOR: Vk = Vk(0x6C);
+pub const VK_SUBTRACT: Vk = Vk(0x6D);
+pub const VK_DECIMAL: Vk = Vk(0x6E);
+pub const VK_DIVIDE: Vk = Vk(0x6F);
+pub const VK_F1: Vk = Vk(0x70);
+pub const VK_F2: Vk = Vk(0x71);
+pub const VK_F3: Vk = Vk(0x72);
+pub const VK_F4: Vk = Vk(0x73);
+pub const VK_F5: Vk = Vk(0x74);
+pub const VK_F6: Vk = Vk(0x75);
+pub const VK_F7: Vk = Vk(0x76);
+pub const VK_F8: Vk = Vk(0x77);
+pub const VK_F9: Vk = Vk(0x78);
+pub const VK_F10: Vk = Vk(0x79);
+pub const VK_F11: Vk = Vk(0x7A);
+pub const VK_F12: Vk = Vk(0x7B);
+pub const VK_F13: Vk = Vk(0x7C);
+pub const VK_F14: Vk = Vk(0x7D);
+pub const VK_F15: Vk = Vk(0x7E);
+pub const VK_F16: Vk = Vk(0x7F);
+pub const VK_F17: Vk = Vk(0x80);
+pub const VK_F18: Vk = Vk(0x81);
+pub const VK_F19: Vk = Vk(0x82);
+pub const VK_F20: Vk = Vk(0x83);
+pub const VK_F21: Vk = Vk(0x84);
+pub const VK_F22: Vk = Vk(0x85);
+pub const VK_F23: Vk = Vk(0x86);
+pub const VK_F24: Vk = Vk(0x87);
+pub const VK_NUMLOCK: Vk = Vk(0x90);
+pub const VK_SCROLL: Vk = Vk(0x91);
+pub const VK_LSHIFT: Vk = Vk(0xA0);
+pub const VK_RSHIFT: Vk = Vk(0xA1);
+pub const VK_LCONTROL: Vk = Vk(0xA2);
+pub const VK_RCONTROL: Vk = Vk(0xA3);
+pub const VK_LMENU: Vk = Vk(0xA4);
+pub const VK_RMENU: Vk = Vk(0xA5);
+pub const VK_BROWSER_BACK: Vk = Vk(0xA6);
+pub const VK_BROWSER_FORWARD: Vk = Vk(0xA7);
+pub const VK_BROWSER_REFRESH: Vk = Vk(0xA8);
+pub const VK_BROWSER_STOP: Vk = Vk(0xA9);
+pub const VK_BROWSER_SEARCH: Vk = Vk(0xAA);
+pub const VK_BROWSER_FAVORITES: Vk = Vk(0xAB);
+pub const VK_BROWSER_HOME: Vk = Vk(0xAC);
+pub const VK_VOLUME_MUTE: Vk = Vk(0xAD);
+pub const VK_VOLUME_DOWN: Vk = Vk(0xAE);
+pub const VK_VOLUME_UP: Vk = Vk(0xAF);
+pub const VK_MEDIA_NEXT_TRACK: Vk = Vk(0xB0);
+pub const VK_MEDIA_PREV_TRACK: Vk = Vk(0xB1);
+pub const VK_MEDIA_STOP: Vk = Vk(0xB2);
+pub const VK_MEDIA_PLAY_PAUSE: Vk = Vk(0xB3);
+pub const VK_LAUNCH_MAIL: Vk = Vk(0xB4);
+pub const VK_LAUNCH_MEDIA_SELECT: Vk = Vk(0xB5);
+pub const VK_LAUNCH_APP1: Vk = Vk(0xB6);
+pub const VK_LAUNCH_APP2: Vk = Vk(0xB7);
+pub const VK_OEM_1: Vk = Vk(0xBA);
+pub const VK_OEM_PLUS: Vk = Vk(0xBB);
+pub const VK_OEM_COMMA: Vk = Vk(0xBC);
+pub const VK_OEM_MINUS: Vk = Vk(0xBD);
+pub const VK_OEM_PERIOD: Vk = Vk(0xBE);
+pub const VK_OEM_2: Vk = Vk(0xBF);
+pub const VK_OEM_3: Vk = Vk(0xC0);
+pub const VK_OEM_4: Vk = Vk(0xDB);
+pub const VK_OEM_5: Vk = Vk(0xDC);
+pub const VK_OEM_6: Vk = Vk(0xDD);
+pub const VK_OEM_7: Vk = Vk(0xDE);
+pub const VK_OEM_8: Vk = Vk(0xDF);
+pub const VK_OEM_102: Vk = Vk(0xE2);
+pub const VK_PROCESSKEY: Vk = Vk(0xE5);
+pub const VK_PACKET: Vk = Vk(0xE7);
+pub const VK_ATTN: Vk = Vk(0xF6);
+pub const VK_CRSEL: Vk = Vk(0xF7);
+pub const VK_EXSEL: Vk = Vk(0xF8);
+pub const VK_EREOF: Vk = Vk(0xF9);
+pub const VK_PLAY: Vk = Vk(0xFA);
+pub const VK_ZOOM: Vk = Vk(0xFB);
+pub const VK_NONAME: Vk = Vk(0xFC);
+pub const VK_PA1: Vk = Vk(0xFD);
+pub const VK_OEM_CLEAR: Vk = Vk(0xFE);
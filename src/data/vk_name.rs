@@ -1,10 +1,199 @@
-use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use crate::data::vk::*;
+
+/// Set in a scan value for keys whose scan code carries the `0xE0` prefix
+/// (`LLKHF_EXTENDED` in the hook): `0xE01C` is numpad Enter, `0x1C` the
+/// main Enter.
+pub const EXTENDED_SCAN: u16 = 0xE000;
+
+/// Positional keys — a physical key position, whatever the layout maps it
+/// to — are encoded as `Vk`s past the real VK range: `0x100 + scan` for
+/// plain scan codes, `0x200 + scan` for extended ones. Keys Windows has no
+/// VK for follow from `0x300`. Real VKs stay below `0x100`, so a key table
+/// needs [`KEY_SLOTS`] entries to hold them all.
+const POSITIONAL_BASE: u16 = 0x100;
+const EXTENDED_BASE: u16 = 0x200;
+pub const KEY_SLOTS: usize = 0x340;
+
+/// Numpad Enter: the extended twin of Enter's scan code `0x1C`.
+pub const NUMPAD_ENTER: Vk = Vk(EXTENDED_BASE + 0x1C);
+
+/// Either Windows key. Windows has a generic VK for Shift, Ctrl and Alt,
+/// but not for Win.
+pub const ANY_WIN: Vk = Vk(0x300);
+
+/// AltGr: right Alt on a layout that has AltGr, where Windows reports it as
+/// a fake left Ctrl plus right Alt. Counts as neither Ctrl nor Alt.
+pub const ALT_GR: Vk = Vk(0x301);
+
+/// True for the keys that match a subset of what a broader name does: the
+/// sided modifiers (`LCtrl` against `Ctrl`) and positional keys
+/// (`NumpadEnter` against `Enter`).
+pub fn is_sided_or_positional(vk: Vk) -> bool {
+    scan_code(vk).is_some()
+        || matches!(
+            vk,
+            VK_LSHIFT | VK_RSHIFT | VK_LCONTROL | VK_RCONTROL | VK_LMENU | VK_RMENU | VK_LWIN | VK_RWIN
+        )
+}
+
+/// The positional key for a scan value (see [`EXTENDED_SCAN`]), or `None`
+/// for a zero or malformed scan code.
+pub fn positional_key(scan: u16) -> Option<Vk> {
+    let code = scan & 0xFF;
+    match scan & !0xFF {
+        _ if code == 0 => None,
+        0 => Some(Vk(POSITIONAL_BASE + code)),
+        EXTENDED_SCAN => Some(Vk(EXTENDED_BASE + code)),
+        _ => None,
+    }
+}
+
+/// The scan value a positional key stands for; `None` for real VKs.
+pub fn scan_code(vk: Vk) -> Option<u16> {
+    match vk.0 {
+        0x101..=0x1FF => Some(vk.0 - POSITIONAL_BASE),
+        0x201..=0x2FF => Some(EXTENDED_SCAN | (vk.0 - EXTENDED_BASE)),
+        _ => None,
+    }
+}
+
+/// Every key with a name of its own: the name [`vk_name`] renders, then the
+/// other spellings [`parse_vk`] accepts (compared ignoring case, so the
+/// AutoHotkey spellings `AppsKey`, `NumpadMult`, ... carry over). Letters,
+/// digits, F-keys, numpad digits and OEM punctuation are ranges handled in
+/// code. Mouse buttons, the reserved and OEM-specific codes, and the
+/// gamepad and UI-navigation keys have no name: `VK(0x..)` reaches them.
+const NAMED_KEYS: &[(Vk, &str, &[&str])] = &[
+    // Modifiers
+    (VK_SHIFT,    "Shift",  &[]),
+    (VK_LSHIFT,   "LShift", &["LeftShift"]),
+    (VK_RSHIFT,   "RShift", &["RightShift"]),
+    (VK_CONTROL,  "Ctrl",   &["Control"]),
+    (VK_LCONTROL, "LCtrl",  &["LeftCtrl", "LeftControl"]),
+    (VK_RCONTROL, "RCtrl",  &["RightCtrl", "RightControl"]),
+    (VK_MENU,     "Alt",    &["Menu"]),
+    (VK_LMENU,    "LAlt",   &["LeftAlt"]),
+    (VK_RMENU,    "RAlt",   &["RightAlt"]),
+    (ALT_GR,      "AltGr",  &[]),
+    (ANY_WIN,     "Win",    &["Super"]),
+    (VK_LWIN,     "LWin",   &["LeftWin"]),
+    (VK_RWIN,     "RWin",   &["RightWin"]),
+    // Editing and navigation
+    (VK_CAPITAL,  "CapsLock",   &["Caps"]),
+    (VK_TAB,      "Tab",        &[]),
+    (VK_ESCAPE,   "Esc",        &["Escape"]),
+    (VK_RETURN,   "Enter",      &["Return"]),
+    (VK_SPACE,    "Space",      &["Spacebar"]),
+    (VK_BACK,     "Backspace",  &["Back"]),
+    (VK_DELETE,   "Delete",     &["Del"]),
+    (VK_INSERT,   "Insert",     &["Ins"]),
+    (VK_HOME,     "Home",       &[]),
+    (VK_END,      "End",        &[]),
+    (VK_PRIOR,    "PageUp",     &["PgUp"]),
+    (VK_NEXT,     "PageDown",   &["PgDn"]),
+    (VK_UP,       "Up",         &["UpArrow"]),
+    (VK_DOWN,     "Down",       &["DownArrow"]),
+    (VK_LEFT,     "Left",       &["LeftArrow"]),
+    (VK_RIGHT,    "Right",      &["RightArrow"]),
+    (VK_CLEAR,    "Clear",      &["NumpadClear"]),
+    (VK_APPS,     "Apps",       &["AppsKey", "ContextMenu"]),
+    // Locks and system keys
+    (VK_NUMLOCK,  "NumLock",    &[]),
+    (VK_SCROLL,   "ScrollLock", &["Scroll"]),
+    (VK_PAUSE,    "Pause",      &["Break"]),
+    (VK_CANCEL,   "Cancel",     &["CtrlBreak"]),
+    (VK_SNAPSHOT, "PrtSc",      &["PrintScreen", "PrtScn"]),
+    (VK_SLEEP,    "Sleep",      &[]),
+    (VK_SELECT,   "Select",     &[]),
+    (VK_PRINT,    "Print",      &[]),
+    (VK_EXECUTE,  "Execute",    &[]),
+    (VK_HELP,     "Help",       &[]),
+    // Numpad operators
+    (NUMPAD_ENTER, "NumpadEnter",     &["NumEnter"]),
+    (VK_MULTIPLY,  "NumpadMultiply",  &["NumpadMult", "NumMultiply"]),
+    (VK_ADD,       "NumpadAdd",       &["NumAdd", "NumpadPlus"]),
+    (VK_SEPARATOR, "NumpadSeparator", &["NumpadSep"]),
+    (VK_SUBTRACT,  "NumpadSubtract",  &["NumpadSub", "NumSubtract", "NumpadMinus"]),
+    (VK_DECIMAL,   "NumpadDecimal",   &["NumpadDot", "NumDecimal"]),
+    (VK_DIVIDE,    "NumpadDivide",    &["NumpadDiv", "NumDivide"]),
+    // Media and volume
+    (VK_MEDIA_PLAY_PAUSE, "MediaPlayPause", &["PlayPause"]),
+    (VK_MEDIA_STOP,       "MediaStop",      &[]),
+    (VK_MEDIA_NEXT_TRACK, "MediaNext",      &["NextTrack"]),
+    (VK_MEDIA_PREV_TRACK, "MediaPrev",      &["MediaPrevious", "PrevTrack"]),
+    (VK_VOLUME_UP,        "VolumeUp",       &["VolUp"]),
+    (VK_VOLUME_DOWN,      "VolumeDown",     &["VolDown"]),
+    (VK_VOLUME_MUTE,      "VolumeMute",     &["VolMute", "Mute"]),
+    // Browser and launch keys
+    (VK_BROWSER_BACK,        "BrowserBack",      &[]),
+    (VK_BROWSER_FORWARD,     "BrowserForward",   &[]),
+    (VK_BROWSER_REFRESH,     "BrowserRefresh",   &[]),
+    (VK_BROWSER_STOP,        "BrowserStop",      &[]),
+    (VK_BROWSER_SEARCH,      "BrowserSearch",    &[]),
+    (VK_BROWSER_FAVORITES,   "BrowserFavorites", &[]),
+    (VK_BROWSER_HOME,        "BrowserHome",      &[]),
+    (VK_LAUNCH_MAIL,         "LaunchMail",       &[]),
+    (VK_LAUNCH_MEDIA_SELECT, "LaunchMedia",      &["LaunchMediaSelect"]),
+    (VK_LAUNCH_APP1,         "LaunchApp1",       &[]),
+    (VK_LAUNCH_APP2,         "LaunchApp2",       &[]),
+    // IME keys
+    (VK_KANA,       "Kana",       &["Hangul", "Hangeul"]),
+    (VK_IME_ON,     "ImeOn",      &[]),
+    (VK_JUNJA,      "Junja",      &[]),
+    (VK_FINAL,      "Final",      &[]),
+    (VK_KANJI,      "Kanji",      &["Hanja"]),
+    (VK_IME_OFF,    "ImeOff",     &[]),
+    (VK_CONVERT,    "Convert",    &[]),
+    (VK_NONCONVERT, "NonConvert", &[]),
+    (VK_ACCEPT,     "Accept",     &[]),
+    (VK_MODECHANGE, "ModeChange", &[]),
+    (VK_PROCESSKEY, "ProcessKey", &[]),
+    // OEM keys without a fixed character, and legacy terminal keys
+    (VK_OEM_8,     "Oem8",     &[]),
+    (VK_OEM_102,   "Oem102",   &[]),
+    (VK_OEM_CLEAR, "OemClear", &[]),
+    (VK_PACKET,    "Packet",   &[]),
+    (VK_ATTN,      "Attn",     &[]),
+    (VK_CRSEL,     "CrSel",    &[]),
+    (VK_EXSEL,     "ExSel",    &[]),
+    (VK_EREOF,     "ErEof",    &[]),
+    (VK_PLAY,      "Play",     &[]),
+    (VK_ZOOM,      "Zoom",     &[]),
+    (VK_PA1,       "Pa1",      &[]),
+];
+
+/// OEM punctuation, named by the character it types on a US layout.
+const PUNCTUATION: [(char, Vk); 11] = [
+    ('`', VK_OEM_3),
+    ('-', VK_OEM_MINUS),
+    ('=', VK_OEM_PLUS),
+    (',', VK_OEM_COMMA),
+    ('.', VK_OEM_PERIOD),
+    (';', VK_OEM_1),
+    ('/', VK_OEM_2),
+    ('[', VK_OEM_4),
+    (']', VK_OEM_6),
+    ('\\', VK_OEM_5),
+    ('\'', VK_OEM_7),
+];
 
 /// Renders a virtual-key code as a short human-readable name (e.g. "Shift",
-/// "F5", "A", "CapsLock"). Falls back to "VK(<raw>)" for unmapped codes.
-pub fn vk_name(vk: VIRTUAL_KEY) -> String {
+/// "F5", "A", "CapsLock"). Positional keys render as "SC:0x29" (or
+/// "NumpadEnter"). Falls back to "VK(0xE2)" for codes without a name,
+/// which [`parse_vk`] reads back.
+pub fn vk_name(vk: Vk) -> String {
     let raw = vk.0;
 
+    if let Some((_, name, _)) = NAMED_KEYS.iter().find(|(key, _, _)| *key == vk) {
+        return name.to_string();
+    }
+    if let Some(scan) = scan_code(vk) {
+        return format!("SC:{:#04X}", scan);
+    }
+    if let Some((ch, _)) = PUNCTUATION.iter().find(|(_, key)| *key == vk) {
+        return ch.to_string();
+    }
+
     if (VK_A.0..=VK_Z.0).contains(&raw) {
         return ((b'A' + (raw - VK_A.0) as u8) as char).to_string();
     }
@@ -17,62 +206,10 @@ pub fn vk_name(vk: VIRTUAL_KEY) -> String {
     if (VK_NUMPAD0.0..=VK_NUMPAD9.0).contains(&raw) {
         return format!("Num{}", raw - VK_NUMPAD0.0);
     }
-
-    match vk {
-        VK_SHIFT    => "Shift",
-        VK_LSHIFT   => "LShift",
-        VK_RSHIFT   => "RShift",
-        VK_CONTROL  => "Ctrl",
-        VK_LCONTROL => "LCtrl",
-        VK_RCONTROL => "RCtrl",
-        VK_MENU     => "Alt",
-        VK_LMENU    => "LAlt",
-        VK_RMENU    => "RAlt",
-        VK_LWIN     => "Win",
-        VK_RWIN     => "RWin",
-        VK_CAPITAL  => "CapsLock",
-        VK_TAB      => "Tab",
-        VK_ESCAPE   => "Esc",
-        VK_RETURN   => "Enter",
-        VK_SPACE    => "Space",
-        VK_BACK     => "Backspace",
-        VK_DELETE   => "Delete",
-        VK_INSERT   => "Insert",
-        VK_HOME     => "Home",
-        VK_END      => "End",
-        VK_PRIOR    => "PageUp",
-        VK_NEXT     => "PageDown",
-        VK_UP       => "Up",
-        VK_DOWN     => "Down",
-        VK_LEFT     => "Left",
-        VK_RIGHT    => "Right",
-        VK_OEM_3    => "`",
-        VK_OEM_MINUS => "-",
-        VK_OEM_PLUS  => "=",
-        VK_OEM_COMMA => ",",
-        VK_OEM_PERIOD => ".",
-        VK_OEM_1    => ";",
-        VK_OEM_2    => "/",
-        VK_OEM_4    => "[",
-        VK_OEM_5    => "\\",
-        VK_OEM_6    => "]",
-        VK_OEM_7    => "'",
-        VK_NUMLOCK  => "NumLock",
-        VK_SCROLL   => "ScrollLock",
-        VK_PAUSE    => "Pause",
-        VK_SNAPSHOT => "PrtSc",
-        VK_MEDIA_PLAY_PAUSE => "MediaPlayPause",
-        VK_MEDIA_STOP       => "MediaStop",
-        VK_MEDIA_NEXT_TRACK => "MediaNext",
-        VK_MEDIA_PREV_TRACK => "MediaPrev",
-        VK_VOLUME_UP        => "VolumeUp",
-        VK_VOLUME_DOWN      => "VolumeDown",
-        VK_VOLUME_MUTE      => "VolumeMute",
-        _ => return format!("VK({})", raw),
-    }.to_string()
+    format!("VK({:#04X})", raw)
 }
 
-/// Parses a single human-readable key name back into a `VIRTUAL_KEY`. Matching
+/// Parses a single human-readable key name back into a `Vk`. Matching
 /// is case-insensitive and tolerates internal whitespace so users can write
 /// `"Caps Lock"`, `"CAPSLOCK"`, or `"capslock"` interchangeably.
 ///
@@ -81,14 +218,17 @@ pub fn vk_name(vk: VIRTUAL_KEY) -> String {
 ///   - Digits `0`–`9`
 ///   - Function keys `F1`–`F24`
 ///   - Numpad digits `Num0`–`Num9` / `Numpad0`–`Numpad9`
-///   - Modifier aliases (`Ctrl`/`Control`, `Alt`/`Menu`, `Win`/`Super`/`LWin`, ...)
-///   - Named keys (`Esc`/`Escape`, `Del`/`Delete`, `Enter`/`Return`, ...)
+///   - Positional keys by scan code, `SC:0x29` (`SC:0xE01C` for extended ones)
+///   - Every name in the named-key table: modifiers and their sided forms,
+///     `AltGr`, navigation, numpad operators, media, browser, launch and
+///     IME keys, each with its aliases (`Esc`/`Escape`, `Apps`/`AppsKey`, ...)
 ///   - OEM punctuation as a single character (`` ` ``, `-`, `=`, `,`, `.`,
 ///     `;`, `/`, `[`, `]`, `\`, `'`)
+///   - Any virtual-key code as `VK(0x01)`–`VK(0xFE)`
 ///
-/// Returns `None` for anything not in the table above. Callers should surface
-/// the failing name to the user — never silently drop unparseable bindings.
-pub fn parse_vk(name: &str) -> Option<VIRTUAL_KEY> {
+/// Returns `None` for anything else. Callers should surface the failing
+/// name to the user — never silently drop unparseable bindings.
+pub fn parse_vk(name: &str) -> Option<Vk> {
     // Normalize: strip ASCII whitespace, fold to lowercase. Length grows by at
     // most the input length, so a String is fine — config parsing is not on a
     // hot path.
@@ -115,7 +255,22 @@ pub fn parse_vk(name: &str) -> Option<VIRTUAL_KEY> {
         && let Ok(n) = rest.parse::<u16>()
         && (1..=24).contains(&n)
     {
-        return Some(VIRTUAL_KEY(VK_F1.0 + n - 1));
+        return Some(Vk(VK_F1.0 + n - 1));
+    }
+
+    // Scan codes: `sc:0x29`, `sc:0xe01c`.
+    if let Some(rest) = normalized.strip_prefix("sc:") {
+        let hex = rest.strip_prefix("0x")?;
+        return u16::from_str_radix(hex, 16).ok().and_then(positional_key);
+    }
+
+    // Raw codes: `vk(0xe2)`. 0xFF and up aren't keys.
+    if let Some(rest) = normalized.strip_prefix("vk(") {
+        let hex = rest.strip_suffix(')')?.strip_prefix("0x")?;
+        return u8::from_str_radix(hex, 16)
+            .ok()
+            .filter(|code| (0x01..=0xFE).contains(code))
+            .map(|code| Vk(code.into()));
     }
 
     // Numpad digits: accept `num0`..`num9` and `numpad0`..`numpad9`.
@@ -124,78 +279,101 @@ pub fn parse_vk(name: &str) -> Option<VIRTUAL_KEY> {
             && let Ok(n) = rest.parse::<u16>()
             && (0..=9).contains(&n)
         {
-            return Some(VIRTUAL_KEY(VK_NUMPAD0.0 + n));
+            return Some(Vk(VK_NUMPAD0.0 + n));
         }
     }
 
-    // Named keys & modifier aliases.
-    match normalized.as_str() {
-        "shift"                                 => Some(VK_SHIFT),
-        "lshift" | "leftshift"                  => Some(VK_LSHIFT),
-        "rshift" | "rightshift"                 => Some(VK_RSHIFT),
-        "ctrl" | "control"                      => Some(VK_CONTROL),
-        "lctrl" | "leftctrl" | "leftcontrol"    => Some(VK_LCONTROL),
-        "rctrl" | "rightctrl" | "rightcontrol"  => Some(VK_RCONTROL),
-        "alt" | "menu"                          => Some(VK_MENU),
-        "lalt" | "leftalt"                      => Some(VK_LMENU),
-        "ralt" | "rightalt"                     => Some(VK_RMENU),
-        "win" | "super" | "lwin" | "leftwin"    => Some(VK_LWIN),
-        "rwin" | "rightwin"                     => Some(VK_RWIN),
-        "capslock" | "caps"                     => Some(VK_CAPITAL),
-        "tab"                                   => Some(VK_TAB),
-        "esc" | "escape"                        => Some(VK_ESCAPE),
-        "enter" | "return"                      => Some(VK_RETURN),
-        "space" | "spacebar"                    => Some(VK_SPACE),
-        "backspace" | "back"                    => Some(VK_BACK),
-        "del" | "delete"                        => Some(VK_DELETE),
-        "ins" | "insert"                        => Some(VK_INSERT),
-        "home"                                  => Some(VK_HOME),
-        "end"                                   => Some(VK_END),
-        "pageup" | "pgup"                       => Some(VK_PRIOR),
-        "pagedown" | "pgdn"                     => Some(VK_NEXT),
-        "up" | "uparrow"                        => Some(VK_UP),
-        "down" | "downarrow"                    => Some(VK_DOWN),
-        "left" | "leftarrow"                    => Some(VK_LEFT),
-        "right" | "rightarrow"                  => Some(VK_RIGHT),
-        "numlock"                               => Some(VK_NUMLOCK),
-        "scrolllock" | "scroll"                 => Some(VK_SCROLL),
-        "pause" | "break"                       => Some(VK_PAUSE),
-        "prtsc" | "printscreen" | "prtscn"      => Some(VK_SNAPSHOT),
-        // Media / volume keys. Recognised so hardware multimedia buttons can
-        // be used as combo triggers.
-        "mediaplaypause" | "playpause"          => Some(VK_MEDIA_PLAY_PAUSE),
-        "mediastop"                             => Some(VK_MEDIA_STOP),
-        "medianext" | "nexttrack"               => Some(VK_MEDIA_NEXT_TRACK),
-        "mediaprev" | "mediaprevious" | "prevtrack" => Some(VK_MEDIA_PREV_TRACK),
-        "volumeup" | "volup"                    => Some(VK_VOLUME_UP),
-        "volumedown" | "voldown"                => Some(VK_VOLUME_DOWN),
-        "volumemute" | "volmute" | "mute"       => Some(VK_VOLUME_MUTE),
-        _ => None,
-    }
+    NAMED_KEYS
+        .iter()
+        .find(|(_, name, aliases)| {
+            name.eq_ignore_ascii_case(&normalized)
+                || aliases.iter().any(|alias| alias.eq_ignore_ascii_case(&normalized))
+        })
+        .map(|(vk, _, _)| *vk)
 }
 
-fn single_char_vk(ch: char) -> Option<VIRTUAL_KEY> {
+fn single_char_vk(ch: char) -> Option<Vk> {
     if ch.is_ascii_alphabetic() {
         let upper = ch.to_ascii_uppercase() as u16;
-        return Some(VIRTUAL_KEY(VK_A.0 + (upper - b'A' as u16)));
+        return Some(Vk(VK_A.0 + (upper - b'A' as u16)));
     }
     if ch.is_ascii_digit() {
-        return Some(VIRTUAL_KEY(VK_0.0 + (ch as u16 - b'0' as u16)));
-    }
-    match ch {
-        '`' => Some(VK_OEM_3),
-        '-' => Some(VK_OEM_MINUS),
-        '=' => Some(VK_OEM_PLUS),
-        ',' => Some(VK_OEM_COMMA),
-        '.' => Some(VK_OEM_PERIOD),
-        ';' => Some(VK_OEM_1),
-        '/' => Some(VK_OEM_2),
-        '[' => Some(VK_OEM_4),
-        ']' => Some(VK_OEM_6),
-        '\\' => Some(VK_OEM_5),
-        '\'' => Some(VK_OEM_7),
-        _ => None,
+        return Some(Vk(VK_0.0 + (ch as u16 - b'0' as u16)));
     }
+    PUNCTUATION.iter().find(|(c, _)| *c == ch).map(|(_, vk)| *vk)
+}
+
+/// Every name [`parse_vk`] accepts, for `swch keys`: one line per key, the
+/// name [`vk_name`] renders first and its aliases after it, then the
+/// ranges and escapes.
+pub fn key_name_list() -> Vec<String> {
+    let mut lines = vec![
+        "A … Z".to_string(),
+        "0 … 9".to_string(),
+        "F1 … F24".to_string(),
+        "Num0 … Num9, Numpad0 … Numpad9".to_string(),
+    ];
+    lines.extend(NAMED_KEYS.iter().map(|(_, name, aliases)| {
+        std::iter::once(name).chain(aliases.iter()).copied().collect::<Vec<_>>().join(", ")
+    }));
+    lines.push(PUNCTUATION.iter().map(|(ch, _)| ch.to_string()).collect::<Vec<_>>().join(" "));
+    lines.push("SC:0x01 … SC:0xFF, SC:0xE001 … SC:0xE0FF  (a key position by scan code)".into());
+    lines.push("VK(0x01) … VK(0xFE)  (any virtual-key code)".into());
+    lines
+}
+
+/// Spellings offered by [`suggest_vk_name`] besides every key's own name.
+const EXTRA_SUGGESTIONS: &[&str] = &["Escape", "PrintScreen"];
+
+/// The known key name closest to `name`, which [`parse_vk`] rejected, if it
+/// is close enough to be a likely typo: `"Ctlr"` → `"Ctrl"`. Names shorter
+/// than three characters get no suggestion — everything is close to them.
+pub fn suggest_vk_name(name: &str) -> Option<&'static str> {
+    let name: Vec<char> = name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    if name.len() < 3 {
+        return None;
+    }
+    let max_distance = if name.len() >= 6 { 2 } else { 1 };
+    NAMED_KEYS
+        .iter()
+        .map(|(_, name, _)| name)
+        .chain(EXTRA_SUGGESTIONS)
+        .map(|candidate| {
+            let lower: Vec<char> = candidate.chars().flat_map(|c| c.to_lowercase()).collect();
+            (edit_distance(&name, &lower), *candidate)
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Optimal-string-alignment distance: insertions, deletions, substitutions
+/// and swaps of adjacent characters each cost 1.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
 }
 
 #[cfg(test)]
@@ -226,13 +404,52 @@ mod tests {
         assert_eq!(vk_name(VK_SHIFT), "Shift");
         assert_eq!(vk_name(VK_CONTROL), "Ctrl");
         assert_eq!(vk_name(VK_MENU), "Alt");
-        assert_eq!(vk_name(VK_LWIN), "Win");
+        assert_eq!(vk_name(ANY_WIN), "Win");
+        assert_eq!(vk_name(VK_LWIN), "LWin");
+        assert_eq!(vk_name(ALT_GR), "AltGr");
         assert_eq!(vk_name(VK_CAPITAL), "CapsLock");
     }
 
     #[test]
-    fn unknown_falls_back() {
-        assert_eq!(vk_name(VIRTUAL_KEY(0xFE)), "VK(254)");
+    fn unnamed_codes_use_the_vk_escape() {
+        assert_eq!(vk_name(Vk(0x88)), "VK(0x88)");
+        assert_eq!(parse_vk("VK(0x88)"), Some(Vk(0x88)));
+        assert_eq!(parse_vk("vk(0xe2)"), Some(VK_OEM_102));
+        for bad in ["VK(0x00)", "VK(0xFF)", "VK(0x100)", "VK(226)", "VK(0xE2"] {
+            assert_eq!(parse_vk(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn every_code_round_trips_through_its_name() {
+        for code in 0x01..=0xFE {
+            let vk = Vk(code);
+            let name = vk_name(vk);
+            assert_eq!(parse_vk(&name), Some(vk), "{:#04X} rendered as {}", code, name);
+        }
+    }
+
+    #[test]
+    fn named_keys_beyond_the_basics() {
+        assert_eq!(parse_vk("BrowserBack"), Some(VK_BROWSER_BACK));
+        assert_eq!(parse_vk("LaunchMail"), Some(VK_LAUNCH_MAIL));
+        assert_eq!(parse_vk("Oem102"), Some(VK_OEM_102));
+        assert_eq!(parse_vk("Kanji"), Some(VK_KANJI));
+        assert_eq!(parse_vk("Non Convert"), Some(VK_NONCONVERT));
+        assert_eq!(parse_vk("AppsKey"), Some(VK_APPS));
+        assert_eq!(parse_vk("Sleep"), Some(VK_SLEEP));
+        assert_eq!(parse_vk("NumpadMult"), Some(VK_MULTIPLY));
+        assert_eq!(vk_name(VK_DIVIDE), "NumpadDivide");
+        assert_eq!(vk_name(VK_HANGUL), "Kana");
+    }
+
+    #[test]
+    fn every_spelling_parses_to_its_own_key() {
+        for (vk, name, aliases) in NAMED_KEYS {
+            for spelling in std::iter::once(name).chain(aliases.iter()) {
+                assert_eq!(parse_vk(spelling), Some(*vk), "{} parses to another key", spelling);
+            }
+        }
     }
 
     #[test]
@@ -269,9 +486,12 @@ mod tests {
         assert_eq!(parse_vk("Control"), Some(VK_CONTROL));
         assert_eq!(parse_vk("Alt"), Some(VK_MENU));
         assert_eq!(parse_vk("Menu"), Some(VK_MENU));
-        assert_eq!(parse_vk("Win"), Some(VK_LWIN));
-        assert_eq!(parse_vk("Super"), Some(VK_LWIN));
+        assert_eq!(parse_vk("Win"), Some(ANY_WIN));
+        assert_eq!(parse_vk("Super"), Some(ANY_WIN));
+        assert_eq!(parse_vk("LWin"), Some(VK_LWIN));
         assert_eq!(parse_vk("RWin"), Some(VK_RWIN));
+        assert_eq!(parse_vk("RCtrl"), Some(VK_RCONTROL));
+        assert_eq!(parse_vk("AltGr"), Some(ALT_GR));
     }
 
     #[test]
@@ -305,7 +525,8 @@ mod tests {
     fn vk_name_roundtrip_for_common_keys() {
         for vk in [
             VK_A, VK_Z, VK_0, VK_9, VK_F1, VK_F24, VK_SHIFT, VK_CONTROL,
-            VK_MENU, VK_LWIN, VK_CAPITAL, VK_TAB, VK_ESCAPE, VK_RETURN,
+            VK_MENU, ANY_WIN, VK_LWIN, VK_RWIN, VK_LSHIFT, VK_RCONTROL, VK_LMENU,
+            ALT_GR, VK_CAPITAL, VK_TAB, VK_ESCAPE, VK_RETURN,
             VK_SPACE, VK_DELETE, VK_INSERT, VK_HOME, VK_END, VK_UP, VK_DOWN,
             VK_LEFT, VK_RIGHT, VK_NUMPAD0, VK_NUMPAD9,
         ] {
@@ -318,4 +539,37 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn positional_keys_by_scan_code() {
+        assert_eq!(parse_vk("SC:0x29"), positional_key(0x29));
+        assert_eq!(parse_vk("sc:0xe01c"), Some(NUMPAD_ENTER));
+        assert_eq!(parse_vk("NumpadEnter"), Some(NUMPAD_ENTER));
+        assert_ne!(parse_vk("NumpadEnter"), parse_vk("Enter"));
+        assert_eq!(scan_code(NUMPAD_ENTER), Some(0xE01C));
+        assert_eq!(scan_code(VK_RETURN), None);
+        assert_eq!(vk_name(parse_vk("SC:0x29").unwrap()), "SC:0x29");
+        assert_eq!(vk_name(NUMPAD_ENTER), "NumpadEnter");
+        assert_eq!(vk_name(positional_key(0xE05B).unwrap()), "SC:0xE05B");
+        for bad in ["SC:0x0", "SC:0x1E01", "SC:29", "SC:0xZZ"] {
+            assert_eq!(parse_vk(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn key_list_shows_each_name_with_its_aliases() {
+        let list = key_name_list();
+        assert!(list.contains(&"Apps, AppsKey, ContextMenu".to_string()), "{:#?}", list);
+        assert!(list.contains(&"Oem102".to_string()));
+        assert!(list.iter().any(|line| line.starts_with("VK(0x01)")));
+    }
+
+    #[test]
+    fn suggests_close_key_names() {
+        assert_eq!(suggest_vk_name("Ctlr"), Some("Ctrl"));
+        assert_eq!(suggest_vk_name("Escpae"), Some("Escape"));
+        assert_eq!(suggest_vk_name("capslokc"), Some("CapsLock"));
+        assert_eq!(suggest_vk_name("Hyper"), None);
+        assert_eq!(suggest_vk_name("Fo"), None);
+    }
 }
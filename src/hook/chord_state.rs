@@ -1,52 +1,75 @@
 //! State machine for `BindingKind::Chord` — "press these keys within `window`
 //! of each other to fire the action".
 //!
-//! Hot path calls `handle_keydown` once per Chord-kind binding per key-down
+//! The engine calls `handle_keydown` once per Chord-kind binding per key-down
 //! event. The chord is identified by its sorted key set; a pending entry
 //! accumulates seen keys until either the full set is in (fire action) or
 //! the window expires (drop entry).
 //!
-//! Mutex serializes access; no atomic gates needed (single critical section
-//! per event).
+//! Time comes in as an engine-clock millisecond (`now`) rather than being
+//! read from the OS, so the state machine is fully deterministic.
 //!
 //! **Suppression**: every key-down event for a key that is part of a chord
 //! binding suppresses the foreground from seeing it. When the chord
-//! completes, the key-up of every chord key is also suppressed (via the
-//! existing `blocked_keys` bitmap). If the chord fails to complete within
-//! the window, the suppressed key-downs are simply lost — there is no
-//! replay. Pick chord keys that are not part of normal typing.
-
-use std::sync::atomic::Ordering;
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+//! completes, the key-up of every chord key is also suppressed (the engine
+//! marks them in its blocked-keys bitmap). If the chord fails to complete,
+//! the binding's [`OnFail`] decides what happens to the swallowed keys:
+//! with the default `Drop` they are simply lost, so pick chord keys that are
+//! not part of normal typing; `Replay` hands them back to the engine for
+//! re-injection, which makes chords on letters usable.
+//!
+//! A chord with a non-`Drop` `on_fail` fails when its window expires
+//! ([`ChordState::expire`]) or as soon as a key outside the chord goes down
+//! ([`ChordState::interrupt`]) — waiting out the window would let the
+//! interrupting key reach the app before the replayed ones.
 
-use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
+use std::time::Duration;
 
-use crate::core::windows_actions::BindAction;
+use crate::data::action::BindAction;
+use crate::data::on_fail::OnFail;
+use crate::data::vk::Vk;
+use crate::hook::engine::FailedGesture;
 
 struct PendingChord {
     /// Canonical (sorted) VK list this chord expects.
     chord_keys: Vec<u16>,
     /// Canonical (sorted) VKs seen so far in the current window.
     seen_keys: Vec<u16>,
+    /// VKs in the order they were pressed — what `Replay` re-injects.
+    pressed: Vec<u16>,
     action: BindAction,
-    expires_at: Instant,
+    on_fail: OnFail,
+    /// Engine-clock millisecond after which the entry is stale.
+    expires_at: u64,
+}
+
+impl PendingChord {
+    fn into_failed(self) -> FailedGesture {
+        FailedGesture {
+            keys: self.pressed,
+            on_fail: self.on_fail,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct ChordState {
-    pending: Arc<Mutex<Vec<PendingChord>>>,
+    pending: Vec<PendingChord>,
 }
 
-/// What the hot path should do after consulting the chord state machine.
+/// What the engine should do after consulting the chord state machine.
+#[derive(Debug)]
 pub enum ChordOutcome {
     /// VK is not part of this binding's chord — caller proceeds with
     /// normal handling.
     NotInChord,
-    /// VK is part of a pending chord; caller must suppress the key-down.
-    /// If `Some`, the chord just completed: caller must mark every listed
-    /// VK as blocked so the matching key-ups get suppressed too.
-    Suppress { completed_keys: Option<Vec<u16>> },
+    /// VK is part of a pending chord that is not complete yet; caller must
+    /// suppress the key-down. `expires_at` is when the chord gives up.
+    Pending { expires_at: u64 },
+    /// The chord just completed. Caller must suppress the key-down, fire
+    /// `action`, and mark every listed VK as blocked so the matching
+    /// key-ups get suppressed too.
+    Completed { keys: Vec<u16>, action: BindAction },
 }
 
 impl ChordState {
@@ -54,16 +77,40 @@ impl ChordState {
         Self::default()
     }
 
+    /// Remove every chord whose window has passed by `now`, returning the
+    /// ones whose `on_fail` wants handling.
+    pub fn expire(&mut self, now: u64) -> Vec<FailedGesture> {
+        let (expired, live) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| p.expires_at <= now);
+        self.pending = live;
+        failures(expired)
+    }
+
+    /// A key went down: abandon every non-`Drop` chord that neither `vk`
+    /// nor its `positional` key is part of. `Drop` chords keep waiting out
+    /// their window, as before `on_fail` existed.
+    pub fn interrupt(&mut self, vk: Vk, positional: Option<Vk>) -> Vec<FailedGesture> {
+        let part_of = |p: &PendingChord| {
+            p.chord_keys.contains(&vk.0) || positional.is_some_and(|k| p.chord_keys.contains(&k.0))
+        };
+        let (broken, live) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| !p.on_fail.is_drop() && !part_of(p));
+        self.pending = live;
+        failures(broken)
+    }
+
     /// Per-binding feed: called once for each Chord-kind binding on every
-    /// raw key-down. Returns whether the key-down should be suppressed and
-    /// the list of keys whose key-up must also be suppressed if the chord
-    /// just completed.
+    /// raw key-down at engine time `now`.
     pub fn handle_keydown(
-        &self,
-        vk: VIRTUAL_KEY,
+        &mut self,
+        vk: Vk,
         action: &BindAction,
-        chord_keys: &[VIRTUAL_KEY],
+        on_fail: &OnFail,
+        chord_keys: &[Vk],
         window: Duration,
+        now: u64,
     ) -> ChordOutcome {
         // Canonicalize the chord's key set so two bindings that list the
         // same keys in different orders share the same pending entry.
@@ -74,154 +121,198 @@ impl ChordState {
             return ChordOutcome::NotInChord;
         }
 
-        let now = Instant::now();
-        let mut pending = self.pending.lock().expect("chord mutex poisoned");
-
         // Drop expired entries. Cheap enough to do every event; keeps the
         // vec bounded by the number of currently-in-progress chords (≤ user
         // fingers).
-        pending.retain(|p| p.expires_at > now);
+        self.pending.retain(|p| p.expires_at > now);
 
         // Look for a pending entry matching this chord shape.
-        if let Some(idx) = pending.iter().position(|p| p.chord_keys == canonical) {
-            let entry = &mut pending[idx];
+        if let Some(idx) = self.pending.iter().position(|p| p.chord_keys == canonical) {
+            let entry = &mut self.pending[idx];
             if !entry.seen_keys.contains(&vk.0) {
                 entry.seen_keys.push(vk.0);
                 entry.seen_keys.sort_unstable();
             }
+            // Every press, repeats of a seen key included, is typing that
+            // `Replay` owes the app.
+            entry.pressed.push(vk.0);
 
             // Completed?
             if entry.seen_keys == entry.chord_keys {
-                let fired_action = entry.action.clone();
-                let fired_keys = entry.chord_keys.clone();
-                pending.remove(idx);
-                drop(pending); // release lock before executing user action
-
-                fired_action.execute();
-                return ChordOutcome::Suppress {
-                    completed_keys: Some(fired_keys),
+                let entry = self.pending.remove(idx);
+                return ChordOutcome::Completed {
+                    keys: entry.chord_keys,
+                    action: entry.action,
                 };
             }
 
-            return ChordOutcome::Suppress { completed_keys: None };
+            return ChordOutcome::Pending { expires_at: entry.expires_at };
         }
 
         // No pending entry — start a fresh one with this VK as the first
         // seen key. Lifetime = the chord window from this moment.
-        pending.push(PendingChord {
+        let expires_at = now + window.as_millis() as u64;
+        self.pending.push(PendingChord {
             chord_keys: canonical,
             seen_keys: vec![vk.0],
+            pressed: vec![vk.0],
             action: action.clone(),
-            expires_at: now + window,
+            on_fail: on_fail.clone(),
+            expires_at,
         });
 
-        ChordOutcome::Suppress { completed_keys: None }
+        ChordOutcome::Pending { expires_at }
     }
 }
 
-/// Apply the "completed chord" side effect: mark all chord keys in the
-/// blocked-keys bitmap so the corresponding key-ups also get suppressed.
-/// Kept separate from `ChordOutcome` so the hot path stays the source of
-/// truth for the bitmap layout.
-pub fn mark_completed_keys_blocked(blocked: &[std::sync::atomic::AtomicU64; 4], keys: &[u16]) {
-    for k in keys {
-        let idx = (*k as usize) / 64;
-        let bit = 1u64 << ((*k as usize) % 64);
-        if idx < blocked.len() {
-            blocked[idx].fetch_or(bit, Ordering::AcqRel);
-        }
-    }
+fn failures(entries: Vec<PendingChord>) -> Vec<FailedGesture> {
+    entries
+        .into_iter()
+        .filter(|p| !p.on_fail.is_drop())
+        .map(PendingChord::into_failed)
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use crate::data::vk::*;
+
+    const WINDOW: Duration = Duration::from_millis(50);
 
     #[test]
     fn vk_not_in_chord_returns_not_in_chord() {
-        let state = ChordState::new();
+        let mut state = ChordState::new();
         let outcome = state.handle_keydown(
             VK_A,
             &BindAction::DoNothing,
+            &OnFail::Drop,
             &[VK_J, VK_K],
-            Duration::from_millis(50),
+            WINDOW,
+            0,
         );
         assert!(matches!(outcome, ChordOutcome::NotInChord));
     }
 
     #[test]
     fn first_chord_key_arms_pending() {
-        let state = ChordState::new();
+        let mut state = ChordState::new();
         let outcome = state.handle_keydown(
             VK_J,
             &BindAction::DoNothing,
+            &OnFail::Drop,
             &[VK_J, VK_K],
-            Duration::from_millis(10_000),
+            WINDOW,
+            0,
         );
-        match outcome {
-            ChordOutcome::Suppress { completed_keys: None } => {}
-            other => panic!("expected Suppress without completion, got {:?}",
-                match other { ChordOutcome::NotInChord => "NotInChord",
-                              ChordOutcome::Suppress { completed_keys } => {
-                                  if completed_keys.is_some() { "Completed" } else { "Suppress" }
-                              }}),
-        }
+        assert!(matches!(outcome, ChordOutcome::Pending { .. }), "got {:?}", outcome);
     }
 
     #[test]
     fn second_chord_key_within_window_completes() {
-        let state = ChordState::new();
-        state.handle_keydown(VK_J, &BindAction::DoNothing, &[VK_J, VK_K],
-                             Duration::from_millis(10_000));
+        let mut state = ChordState::new();
+        state.handle_keydown(VK_J, &BindAction::DoNothing, &OnFail::Drop, &[VK_J, VK_K], WINDOW, 0);
         let outcome = state.handle_keydown(
             VK_K,
             &BindAction::DoNothing,
+            &OnFail::Drop,
             &[VK_J, VK_K],
-            Duration::from_millis(10_000),
+            WINDOW,
+            20,
         );
         match outcome {
-            ChordOutcome::Suppress { completed_keys: Some(keys) } => {
+            ChordOutcome::Completed { keys, action } => {
                 assert_eq!(keys, vec![VK_J.0, VK_K.0]);
+                assert_eq!(action, BindAction::DoNothing);
             }
-            _ => panic!("expected completion"),
+            other => panic!("expected completion, got {:?}", other),
         }
     }
 
+    #[test]
+    fn second_chord_key_after_window_starts_over() {
+        let mut state = ChordState::new();
+        state.handle_keydown(VK_J, &BindAction::DoNothing, &OnFail::Drop, &[VK_J, VK_K], WINDOW, 0);
+        let outcome = state.handle_keydown(
+            VK_K,
+            &BindAction::DoNothing,
+            &OnFail::Drop,
+            &[VK_J, VK_K],
+            WINDOW,
+            80,
+        );
+        assert!(matches!(outcome, ChordOutcome::Pending { .. }));
+    }
+
     #[test]
     fn third_chord_key_starts_fresh_after_completion() {
-        let state = ChordState::new();
-        state.handle_keydown(VK_J, &BindAction::DoNothing, &[VK_J, VK_K],
-                             Duration::from_millis(10_000));
-        state.handle_keydown(VK_K, &BindAction::DoNothing, &[VK_J, VK_K],
-                             Duration::from_millis(10_000));
+        let mut state = ChordState::new();
+        state.handle_keydown(VK_J, &BindAction::DoNothing, &OnFail::Drop, &[VK_J, VK_K], WINDOW, 0);
+        state.handle_keydown(VK_K, &BindAction::DoNothing, &OnFail::Drop, &[VK_J, VK_K], WINDOW, 10);
         // Both chord keys consumed; a third press of J should re-arm a
         // fresh pending entry — chord state must not be sticky.
         let outcome = state.handle_keydown(
             VK_J,
             &BindAction::DoNothing,
+            &OnFail::Drop,
             &[VK_J, VK_K],
-            Duration::from_millis(10_000),
+            WINDOW,
+            20,
         );
-        assert!(matches!(outcome, ChordOutcome::Suppress { completed_keys: None }));
+        assert!(matches!(outcome, ChordOutcome::Pending { .. }));
     }
 
     #[test]
     fn canonical_order_independent() {
         // Chord listed as [K, J] should match the same pending entry as one
         // listed as [J, K] — order in the binding's `keys` shouldn't matter.
-        let state = ChordState::new();
-        state.handle_keydown(VK_J, &BindAction::DoNothing, &[VK_K, VK_J],
-                             Duration::from_millis(10_000));
+        let mut state = ChordState::new();
+        state.handle_keydown(VK_J, &BindAction::DoNothing, &OnFail::Drop, &[VK_K, VK_J], WINDOW, 0);
         let outcome = state.handle_keydown(
             VK_K,
             &BindAction::DoNothing,
+            &OnFail::Drop,
             &[VK_J, VK_K], // reversed order
-            Duration::from_millis(10_000),
+            WINDOW,
+            10,
         );
-        assert!(matches!(
-            outcome,
-            ChordOutcome::Suppress { completed_keys: Some(_) }
-        ));
+        assert!(matches!(outcome, ChordOutcome::Completed { .. }));
+    }
+
+    #[test]
+    fn expire_reports_only_non_drop_chords() {
+        let mut state = ChordState::new();
+        state.handle_keydown(VK_J, &BindAction::DoNothing, &OnFail::Drop, &[VK_J, VK_K], WINDOW, 0);
+        state.handle_keydown(VK_D, &BindAction::DoNothing, &OnFail::Replay, &[VK_D, VK_F], WINDOW, 0);
+        assert!(state.expire(49).is_empty());
+        let failed = state.expire(50);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].keys, vec![VK_D.0]);
+        assert_eq!(failed[0].on_fail, OnFail::Replay);
+        assert!(state.pending.is_empty());
+    }
+
+    #[test]
+    fn interrupt_breaks_non_drop_chords_without_the_key() {
+        let mut state = ChordState::new();
+        state.handle_keydown(VK_J, &BindAction::DoNothing, &OnFail::Drop, &[VK_J, VK_K], WINDOW, 0);
+        state.handle_keydown(VK_D, &BindAction::DoNothing, &OnFail::Replay, &[VK_D, VK_F], WINDOW, 0);
+        // F belongs to the D+F chord; nothing breaks.
+        assert!(state.interrupt(VK_F, None).is_empty());
+        // X belongs to neither: the Replay chord fails, the Drop one waits.
+        let failed = state.interrupt(VK_X, None);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].keys, vec![VK_D.0]);
+        assert_eq!(state.pending.len(), 1);
+    }
+
+    #[test]
+    fn pressed_order_is_preserved_for_replay() {
+        let mut state = ChordState::new();
+        let keys = [VK_A, VK_S, VK_D];
+        state.handle_keydown(VK_S, &BindAction::DoNothing, &OnFail::Replay, &keys, WINDOW, 0);
+        state.handle_keydown(VK_A, &BindAction::DoNothing, &OnFail::Replay, &keys, WINDOW, 10);
+        let failed = state.expire(100);
+        assert_eq!(failed[0].keys, vec![VK_S.0, VK_A.0]);
     }
 }
@@ -1,50 +1,110 @@
-//! File-watcher driven hot-reload for `config.ron`.
+//! File-watcher driven hot-reload for `config.ron` (or its TOML / JSON
+//! counterpart) and the files it includes.
 //!
 //! Lifecycle:
 //!   1. Caller (`App::run`) starts the watcher after the keyboard hook is
-//!      installed; the returned `RecommendedWatcher` is kept alive for the
+//!      installed; the returned `ConfigWatcher` is kept alive for the
 //!      duration of the program.
-//!   2. `notify` delivers filesystem events to an internal channel.
+//!   2. `notify` delivers filesystem events for the config, its included
+//!      files, and new files matching an include glob to an internal channel.
 //!   3. A worker thread debounces those events (editors fire several per
 //!      save) and reloads the config, atomically swapping the binding list
 //!      via `KeyboardHook::update_bindings`.
 //!   4. Parse errors during reload are logged; the previously-running
 //!      binding list stays live, so a broken save never bricks the daemon.
+//!   5. Either way the outcome — on success, which bindings were added,
+//!      removed or changed ([`ReloadDiff`]) and which `settings:` changed —
+//!      is logged and sent to `swch watch` subscribers.
+//!   6. Changed settings take effect right away: binding defaults through
+//!      the reloaded bindings, `ipc` through the IPC server, and
+//!      `reload_debounce_ms` from the next save on.
 
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::thread;
-use std::time::Duration;
 
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 
-use crate::config;
+use crate::config::{self, ConfigSources, IncludeGlob, Settings};
+use crate::hook::ipc_server;
 use crate::hook::keyboard_hook::KeyboardHook;
+use crate::hook::reload_diff::ReloadDiff;
 
-/// Coalescing window: any save burst that completes within this many ms is
-/// reduced to a single reload. 250 ms covers VS Code / Notepad++ / vim save
-/// patterns (write-temp, rename, set permissions, fsync) without being long
-/// enough for a user to notice the delay between Ctrl+S and the new bindings
-/// going live.
-const DEBOUNCE_MS: u64 = 250;
+/// Handle to a running watcher. Keep it alive for as long as hot-reload
+/// should work; dropping it stops the notify backend, which in turn ends
+/// the debounce thread.
+pub struct ConfigWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+}
+
+/// The files a reload is triggered by: `config.ron`, every file it
+/// included, and anything new that matches an include glob. Rebuilt after
+/// every successful reload, since editing `include:` changes it.
+struct WatchSet {
+    files: Vec<PathBuf>,
+    globs: Vec<IncludeGlob>,
+}
+
+impl WatchSet {
+    fn new(config_path: &Path, sources: ConfigSources) -> Self {
+        let mut files = sources.files;
+        if !files.iter().any(|f| f == config_path) {
+            files.insert(0, config_path.to_path_buf());
+        }
+        Self { files, globs: sources.globs }
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.files.iter().any(|f| f == path) || self.globs.iter().any(|g| g.matches(path))
+    }
 
-/// Install a watcher on the parent directory of `config_path` and spawn the
-/// debounce + reload worker. Returns the watcher handle — caller MUST keep it
-/// alive (drop = stop watching, debounce thread exits cleanly).
+    /// Directories to watch, and whether each needs a recursive watch.
+    ///
+    /// Directories, not the files themselves: editors that save by writing
+    /// a temp file and renaming it (VS Code, vim, Sublime) delete the
+    /// watched inode, after which a file-level watcher stops receiving
+    /// events. A directory watch is robust to that.
+    fn dirs(&self) -> BTreeMap<PathBuf, bool> {
+        let mut dirs = BTreeMap::new();
+        for file in &self.files {
+            let parent = file
+                .parent()
+                .expect("config_path must have a parent directory")
+                .to_path_buf();
+            dirs.entry(parent).or_insert(false);
+        }
+        for glob in &self.globs {
+            *dirs.entry(glob.dir.clone()).or_insert(false) |= glob.is_nested();
+        }
+        dirs
+    }
+}
+
+/// Install a watcher on the directories of `config_path` and the files in
+/// `sources` (as returned by the initial `config::load`), and spawn the
+/// debounce + reload worker. `settings` are the ones loaded with them.
+/// Returns the watcher handle — caller MUST keep it alive (drop = stop
+/// watching, debounce thread exits cleanly).
 pub fn spawn_watcher(
     config_path: PathBuf,
+    sources: ConfigSources,
+    settings: Settings,
     hook: &'static KeyboardHook,
-) -> notify::Result<RecommendedWatcher> {
+) -> notify::Result<ConfigWatcher> {
     let (tx, rx) = channel::<()>();
+    let watch_set = Arc::new(RwLock::new(WatchSet::new(&config_path, sources)));
 
-    // Filter callback: only forward events that touch our target file and
+    // Filter callback: only forward events that touch one of our files and
     // represent a meaningful change. Atime updates from us reading the file
     // would otherwise loop us.
-    let target_path = config_path.clone();
+    let filter_set = Arc::clone(&watch_set);
     let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
         let Ok(event) = res else { return };
 
-        if !event.paths.iter().any(|p| p == &target_path) {
+        let set = filter_set.read().unwrap_or_else(|e| e.into_inner());
+        if !event.paths.iter().any(|p| set.matches(p)) {
             return;
         }
 
@@ -58,27 +118,83 @@ pub fn spawn_watcher(
         }
     })?;
 
-    // Watch the parent directory, not the file itself: editors that save by
-    // writing a temp file and renaming it (VS Code, vim, Sublime) delete the
-    // watched inode, after which a file-level watcher stops receiving
-    // events. A non-recursive directory watch is robust to that.
-    let parent = config_path
+    // The root config's directory must be watchable; the rest is best
+    // effort (a glob's directory may not exist yet).
+    let watched = watch_set.read().unwrap_or_else(|e| e.into_inner()).dirs();
+    let root_dir = config_path
         .parent()
-        .expect("config_path must have a parent directory")
-        .to_path_buf();
-    watcher.watch(&parent, RecursiveMode::NonRecursive)?;
+        .expect("config_path must have a parent directory");
+    for (dir, &recursive) in &watched {
+        let result = watcher.watch(dir, recursive_mode(recursive));
+        if dir == root_dir {
+            result?;
+        }
+    }
 
+    let watcher = Arc::new(Mutex::new(watcher));
+    let worker = Reloader {
+        config_path,
+        watch_set,
+        watched,
+        watcher: Arc::downgrade(&watcher),
+        settings,
+    };
     thread::Builder::new()
         .name("config-watcher".into())
-        .spawn(move || debounce_loop(rx, config_path, hook))
+        .spawn(move || debounce_loop(rx, worker, hook))
         .expect("spawn config-watcher thread");
 
-    Ok(watcher)
+    Ok(ConfigWatcher { _watcher: watcher })
 }
 
-fn debounce_loop(rx: Receiver<()>, config_path: PathBuf, hook: &'static KeyboardHook) {
-    let debounce = Duration::from_millis(DEBOUNCE_MS);
+fn recursive_mode(recursive: bool) -> RecursiveMode {
+    if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    }
+}
+
+/// State the debounce thread needs to reload and re-target the watcher.
+struct Reloader {
+    config_path: PathBuf,
+    watch_set: Arc<RwLock<WatchSet>>,
+    /// Directories currently watched, and whether recursively.
+    watched: BTreeMap<PathBuf, bool>,
+    /// Weak so the thread doesn't keep the watcher (and with it the event
+    /// channel) alive after the [`ConfigWatcher`] handle is dropped.
+    watcher: Weak<Mutex<RecommendedWatcher>>,
+    /// Settings of the config currently live.
+    settings: Settings,
+}
+
+impl Reloader {
+    /// Point the watcher at a new set of files, watching directories that
+    /// are new and unwatching those no longer needed.
+    fn retarget(&mut self, set: WatchSet) {
+        let dirs = set.dirs();
+        *self.watch_set.write().unwrap_or_else(|e| e.into_inner()) = set;
+        if dirs == self.watched {
+            return;
+        }
+        let Some(watcher) = self.watcher.upgrade() else { return };
+        let mut watcher = watcher.lock().unwrap_or_else(|e| e.into_inner());
+        for (dir, recursive) in &self.watched {
+            if dirs.get(dir) != Some(recursive) {
+                let _ = watcher.unwatch(dir);
+            }
+        }
+        for (dir, &recursive) in &dirs {
+            if self.watched.get(dir) != Some(&recursive) {
+                // Best effort, as at startup.
+                let _ = watcher.watch(dir, recursive_mode(recursive));
+            }
+        }
+        self.watched = dirs;
+    }
+}
 
+fn debounce_loop(rx: Receiver<()>, mut reloader: Reloader, hook: &'static KeyboardHook) {
     loop {
         // Block until first event of a burst.
         if rx.recv().is_err() {
@@ -88,7 +204,9 @@ fn debounce_loop(rx: Receiver<()>, config_path: PathBuf, hook: &'static Keyboard
 
         // Drain extra events arriving inside the debounce window. Reset the
         // timer on each one so a sequence of fast-fire events still
-        // produces one reload, not many.
+        // produces one reload, not many. Any save burst that completes
+        // within `reload_debounce_ms` is reduced to a single reload.
+        let debounce = reloader.settings.reload_debounce();
         loop {
             match rx.recv_timeout(debounce) {
                 Ok(()) => continue,
@@ -97,15 +215,51 @@ fn debounce_loop(rx: Receiver<()>, config_path: PathBuf, hook: &'static Keyboard
             }
         }
 
-        match config::load(&config_path) {
-            Ok(new_bindings) => {
-                let count = new_bindings.len();
-                hook.update_bindings(new_bindings);
-                println!("[INFO] config reloaded ({} bindings live)", count);
+        match config::load(&reloader.config_path) {
+            Ok(loaded) => {
+                let count = loaded.bindings.len();
+                let diff = ReloadDiff::between(&hook.bindings(), &loaded.bindings);
+                hook.update_bindings(loaded.bindings);
+                let set = WatchSet::new(&reloader.config_path, loaded.sources);
+                reloader.retarget(set);
+                let changed_settings = reloader.settings.changes(&loaded.settings);
+                let summary = match (diff.is_empty(), changed_settings.len()) {
+                    (true, 0) => format!("config reloaded ({} bindings live, no changes)", count),
+                    (true, n) => format!(
+                        "config reloaded ({} bindings live): {} setting(s) changed",
+                        count, n
+                    ),
+                    (false, 0) => {
+                        format!("config reloaded ({} bindings live): {}", count, diff.summary())
+                    }
+                    (false, n) => format!(
+                        "config reloaded ({} bindings live): {}, {} setting(s) changed",
+                        count,
+                        diff.summary(),
+                        n
+                    ),
+                };
+                let mut report = diff.to_string();
+                for change in &changed_settings {
+                    report.push_str(&format!("settings: {}\n", change));
+                }
+                println!("[INFO] {}", summary);
+                for line in report.lines() {
+                    println!("       {}", line);
+                }
+                ipc_server::publish(&format!("{}\n{}", summary, report));
+                if loaded.settings.ipc != reloader.settings.ipc {
+                    ipc_server::set_enabled(hook, loaded.settings.ipc);
+                }
+                reloader.settings = loaded.settings;
             }
             Err(e) => {
                 eprintln!("[ERROR] config reload failed:\n{}", e);
                 eprintln!("[INFO] keeping previous bindings live");
+                ipc_server::publish(&format!(
+                    "config reload failed, previous bindings kept live:\n{}",
+                    e
+                ));
             }
         }
     }
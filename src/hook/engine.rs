@@ -0,0 +1,1754 @@
+//! Platform-independent input engine: the binding-matching core of the hook.
+//!
+//! `Engine` consumes abstract, timestamped [`KeyEvent`]s plus a
+//! [`ForegroundContext`] snapshot and answers with a [`Decision`] — whether
+//! to swallow the event, what to run or inject, and when it needs to be
+//! woken up again. It never calls Win32, never reads the clock and never
+//! spawns threads; all of that lives in the thin adapter in
+//! `keyboard_hook_callback`. That split is what lets the Tap / Hold /
+//! DoubleTap, chord and sequence interactions be driven deterministically
+//! from tests, on any platform:
+//!
+//! ```ignore
+//! let mut engine = Engine::new(bindings);
+//! let ctx = ForegroundContext::snapshot(None, None);
+//! let d = engine.handle(KeyEvent::down(VK_CAPITAL, 0), &ctx);
+//! assert!(d.suppress);
+//! let d = engine.tick(250, &ctx); // a pending Hold(200) fires here
+//! ```
+//!
+//! Time is a plain millisecond counter with an arbitrary epoch. The only
+//! requirement is that it is monotonic across the events and ticks fed to
+//! one engine.
+
+use std::sync::Arc;
+
+use crate::data::action::BindAction;
+use crate::data::binding::{Binding, BindingKind};
+use crate::data::condition::ForegroundContext;
+use crate::data::on_fail::OnFail;
+use crate::data::vk_name::{positional_key, scan_code, ALT_GR, ANY_WIN, KEY_SLOTS};
+use crate::data::trigger::{ModTapFlavor, Trigger};
+use crate::data::vk::*;
+use crate::hook::chord_state::{ChordOutcome, ChordState};
+use crate::hook::layer_stack::LayerStack;
+use crate::hook::mod_tap_state::{ModTapState, ModTapStep};
+use crate::hook::sequence_state::{SequenceOutcome, SequenceState};
+use crate::hook::tap_state::{TapDanceUp, TapState};
+
+/// Bounded buffer of active keys, used per-keystroke to evaluate bindings
+/// without allocating on the hot path. A held key counts up to three times
+/// (its VK, the unsided modifier and its positional key), so 32 entries
+/// cover any realistic combination. Past the cap the latest presses are
+/// silently dropped, which is acceptable because we sort bindings by
+/// specificity (longer combos match first) and no real binding uses more
+/// than a handful of keys.
+const ACTIVE_KEYS_CAP: usize = 32;
+
+struct ActiveKeys {
+    keys: [Vk; ACTIVE_KEYS_CAP],
+    len: usize,
+}
+
+impl ActiveKeys {
+    fn new() -> Self {
+        Self {
+            keys: [Vk(0); ACTIVE_KEYS_CAP],
+            len: 0,
+        }
+    }
+
+    fn push_unique(&mut self, vk: Vk) {
+        if self.len >= ACTIVE_KEYS_CAP {
+            return;
+        }
+        for i in 0..self.len {
+            if self.keys[i] == vk {
+                return;
+            }
+        }
+        self.keys[self.len] = vk;
+        self.len += 1;
+    }
+
+    fn as_slice(&self) -> &[Vk] {
+        &self.keys[..self.len]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEventKind {
+    Down,
+    Up,
+}
+
+/// One physical key transition as seen by the engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub vk: Vk,
+    pub kind: KeyEventKind,
+    /// Engine-clock timestamp in milliseconds.
+    pub time_ms: u64,
+    /// Hardware scan code, with [`EXTENDED_SCAN`] set for `0xE0`-prefixed
+    /// keys. 0 when unknown; such an event matches no positional key.
+    ///
+    /// [`EXTENDED_SCAN`]: crate::data::vk_name::EXTENDED_SCAN
+    pub scan: u16,
+}
+
+impl KeyEvent {
+    pub fn down(vk: Vk, time_ms: u64) -> Self {
+        Self { vk, kind: KeyEventKind::Down, time_ms, scan: 0 }
+    }
+
+    pub fn up(vk: Vk, time_ms: u64) -> Self {
+        Self { vk, kind: KeyEventKind::Up, time_ms, scan: 0 }
+    }
+
+    pub fn with_scan(mut self, scan: u16) -> Self {
+        self.scan = scan;
+        self
+    }
+
+    /// The positional key (`SC:0x..`) this event's physical key stands for.
+    pub fn positional(&self) -> Option<Vk> {
+        positional_key(self.scan)
+    }
+}
+
+/// One piece of engine output. Order matters: a `ModTap` tap action must
+/// reach the app before the keys typed behind it, a replayed `on_fail` key
+/// before the action of the key that broke the gesture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// Run a binding action. The engine never executes it itself.
+    Action(BindAction),
+    /// Inject a key event. The caller must mark it with the injected
+    /// sentinel so it bypasses the engine. Used for the swallowed keys of a
+    /// failed `on_fail: Replay` gesture, `ModTap` modifiers, and events held
+    /// back behind either of them.
+    Key(KeyEvent),
+}
+
+/// The engine's answer to an event or a tick.
+#[derive(Debug, Default, Clone)]
+pub struct Decision {
+    /// Swallow the event instead of passing it on to the foreground app.
+    /// Always `false` for decisions returned by [`Engine::tick`].
+    pub suppress: bool,
+    /// Actions to run and keys to inject, in order.
+    pub outputs: Vec<Output>,
+    /// Engine-clock deadlines at which the caller must call
+    /// [`Engine::tick`] so timer-driven gestures (`Hold`, `ModTap`, and
+    /// chords or sequences with an `on_fail`) resolve even if no further
+    /// key event arrives.
+    pub wake_at: Vec<u64>,
+    /// Keys replayed for failed gestures so far, one entry per press.
+    replayed: Vec<Vk>,
+    /// The event itself must not overtake `outputs`: set when the event
+    /// ended someone else's gesture early (a tap dance it interrupted).
+    hold_back: bool,
+}
+
+impl Decision {
+    /// The actions among `outputs`, in order.
+    pub fn actions(&self) -> Vec<BindAction> {
+        self.outputs
+            .iter()
+            .filter_map(|o| match o {
+                Output::Action(action) => Some(action.clone()),
+                Output::Key(_) => None,
+            })
+            .collect()
+    }
+
+    /// The injected key events among `outputs`, in order.
+    pub fn injected(&self) -> Vec<KeyEvent> {
+        self.outputs
+            .iter()
+            .filter_map(|o| match o {
+                Output::Key(event) => Some(*event),
+                Output::Action(_) => None,
+            })
+            .collect()
+    }
+
+    fn push_action(&mut self, action: BindAction) {
+        self.outputs.push(Output::Action(action));
+    }
+
+    fn push_key(&mut self, event: KeyEvent) {
+        self.outputs.push(Output::Key(event));
+    }
+
+    fn has_injected(&self) -> bool {
+        self.outputs.iter().any(|o| matches!(o, Output::Key(_)))
+    }
+}
+
+/// A chord or sequence that gave up with a non-`Drop` `on_fail`: the keys
+/// it swallowed, in press order, and what to do about them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedGesture {
+    pub keys: Vec<u16>,
+    pub on_fail: OnFail,
+}
+
+pub struct Engine {
+    /// Sorted by specificity (see [`sort_by_specificity`]).
+    bindings: Arc<Vec<Binding>>,
+    /// Raw (un-normalized) key-down state, one slot per virtual-key code
+    /// and one per positional key. A held key sets both of its slots.
+    active_keys: [bool; KEY_SLOTS],
+    /// When each slot last went down, counted in presses: what `match:
+    /// Ordered` combos check the order against.
+    pressed_at: [u64; KEY_SLOTS],
+    presses: u64,
+    /// Keys whose key-down was blocked: the matching key-up must also be
+    /// swallowed. One bit per slot of `active_keys`.
+    blocked_keys: [u64; BLOCKED_WORDS],
+    /// The VK last seen for each positional key, so a swallowed positional
+    /// chord key can be replayed as the key the layout produced.
+    positional_vks: [Vk; KEY_SLOTS],
+    /// AltGr's fake left Ctrl is down: right Alt counts as [`ALT_GR`], and
+    /// neither counts as Ctrl or Alt.
+    alt_gr: bool,
+    /// Pending Tap / Hold / DoubleTap gestures. See [`TapState`].
+    tap_state: TapState,
+    /// Pending simultaneous-chord gestures (`BindingKind::Chord`).
+    chord_state: ChordState,
+    /// Pending leader-sequence gestures (`BindingKind::Sequence`).
+    sequence_state: SequenceState,
+    /// Undecided and held `Trigger::ModTap` keys.
+    mod_tap_state: ModTapState,
+    /// Active binding layers. See [`LayerStack`].
+    layers: LayerStack,
+    /// Tags switched on (`true`) or off at runtime, in the order they were
+    /// switched. Kept across hot-reloads, like the layers.
+    switched_tags: Vec<(Arc<str>, bool)>,
+}
+
+const BLOCKED_WORDS: usize = KEY_SLOTS / 64;
+
+/// The scan code Windows gives the fake left Ctrl it sends ahead of right
+/// Alt on a layout with AltGr.
+const ALT_GR_CTRL_SCAN: u16 = 0x21D;
+
+/// Sort so more specific combinations (more keys) are checked first in the
+/// matching loop; among those, sided and positional keys (`RCtrl`,
+/// `NumpadEnter`) go before the names they narrow (`Ctrl`, `Enter`).
+/// Stable, so equally specific bindings keep config order.
+pub fn sort_by_specificity(bindings: &mut [Binding]) {
+    bindings.sort_by_key(|b| std::cmp::Reverse(b.kind.specificity()));
+}
+
+impl Engine {
+    /// Build an engine over `bindings`, sorting them by specificity.
+    pub fn new(mut bindings: Vec<Binding>) -> Self {
+        sort_by_specificity(&mut bindings);
+        Self::with_sorted_bindings(Arc::new(bindings))
+    }
+
+    /// Build an engine over an already-sorted, shared binding list.
+    pub fn with_sorted_bindings(bindings: Arc<Vec<Binding>>) -> Self {
+        Self {
+            bindings,
+            active_keys: [false; KEY_SLOTS],
+            pressed_at: [0; KEY_SLOTS],
+            presses: 0,
+            blocked_keys: [0; BLOCKED_WORDS],
+            positional_vks: [Vk(0); KEY_SLOTS],
+            alt_gr: false,
+            tap_state: TapState::new(),
+            chord_state: ChordState::new(),
+            sequence_state: SequenceState::new(),
+            mod_tap_state: ModTapState::new(),
+            layers: LayerStack::new(),
+            switched_tags: Vec::new(),
+        }
+    }
+
+    /// Swap the binding list (hot-reload). In-flight gestures keep the
+    /// actions they were armed with.
+    pub fn set_sorted_bindings(&mut self, bindings: Arc<Vec<Binding>>) {
+        self.bindings = bindings;
+    }
+
+    pub fn bindings(&self) -> &Arc<Vec<Binding>> {
+        &self.bindings
+    }
+
+    /// True if at least one binding gates on the foreground window, i.e.
+    /// the caller should capture a real [`ForegroundContext`] before
+    /// calling [`Engine::handle`]. The common case — no `when:` anywhere —
+    /// lets the adapter skip the Win32 lookups entirely.
+    pub fn needs_foreground(&self) -> bool {
+        self.bindings.iter().any(|b| !b.condition.is_always())
+    }
+
+    /// Overwrite the tracked down/up state of `vk` without running any
+    /// matching. Used by the adapter to reconcile modifiers with the OS
+    /// after focus transitions that swallowed key-ups. Releasing a VK also
+    /// releases the positional keys last seen producing it.
+    pub fn sync_key(&mut self, vk: Vk, down: bool) {
+        if down {
+            self.hold(vk.0 as usize);
+            return;
+        }
+        let was_down = match self.active_keys.get_mut(vk.0 as usize) {
+            Some(slot) => std::mem::replace(slot, false),
+            None => false,
+        };
+        if vk == VK_LCONTROL {
+            self.alt_gr = false;
+        }
+        if was_down {
+            for (slot, produced) in self.active_keys.iter_mut().zip(&self.positional_vks) {
+                if *produced == vk {
+                    *slot = false;
+                }
+            }
+        }
+    }
+
+    /// Mark `slot` held, stamping it with the press order if it was not.
+    /// Returns whether it already was.
+    fn hold(&mut self, slot: usize) -> bool {
+        let Some(held) = self.active_keys.get_mut(slot) else {
+            return false;
+        };
+        if std::mem::replace(held, true) {
+            return true;
+        }
+        self.presses += 1;
+        self.pressed_at[slot] = self.presses;
+        false
+    }
+
+    /// Names of the active layers, top of the stack first.
+    pub fn active_layers(&self) -> Vec<String> {
+        self.layers.active_top_down().map(|l| l.to_string()).collect()
+    }
+
+    /// Switch every binding tagged `tag` on or off, whatever its `enabled:`
+    /// says. Returns how many bindings carry the tag; with none, nothing is
+    /// switched.
+    pub fn switch_tag(&mut self, tag: &str, on: bool) -> usize {
+        let tagged = self.bindings.iter().filter(|b| b.tags.iter().any(|t| &**t == tag)).count();
+        if tagged > 0 {
+            self.switched_tags.retain(|(t, _)| &**t != tag);
+            self.switched_tags.push((Arc::from(tag), on));
+        }
+        tagged
+    }
+
+    /// Tags switched at runtime, with whether they are on.
+    pub fn switched_tags(&self) -> &[(Arc<str>, bool)] {
+        &self.switched_tags
+    }
+
+    /// True if `binding` currently takes part in matching.
+    pub fn is_live(&self, binding: &Binding) -> bool {
+        binding.is_live(&self.switched_tags)
+    }
+
+    /// True if the engine currently believes `vk` is held.
+    pub fn is_key_down(&self, vk: Vk) -> bool {
+        self.active_keys.get(vk.0 as usize).copied().unwrap_or(false)
+    }
+
+    /// Forget every blocked key-up. Bits whose key-up we never observed
+    /// (because focus was elsewhere) would otherwise suppress the next
+    /// legitimate release.
+    pub fn clear_blocked(&mut self) {
+        clear_all_blocked(&mut self.blocked_keys);
+    }
+
+    /// End the momentary layers whose key `is_down` reports released. A
+    /// key-up swallowed by a higher-integrity window would otherwise leave
+    /// the layer on until the key is pressed again.
+    pub fn sync_momentary_layers(&mut self, is_down: impl Fn(Vk) -> bool) {
+        for key in self.layers.momentary_keys() {
+            if !is_down(key) {
+                self.sync_key(key, false);
+                self.layers.release(key);
+            }
+        }
+    }
+
+    /// Resolve timer-driven gestures due at or before `now`: fire due
+    /// holds, fail chords / sequences whose window or gap has run out, and
+    /// turn an undecided `ModTap` past its term into a hold. `ctx` is used
+    /// for the key events that were held back behind that `ModTap`.
+    pub fn tick(&mut self, now: u64, ctx: &ForegroundContext) -> Decision {
+        let mut decision = Decision::default();
+        for (vk, action) in self.tap_state.tick(now) {
+            self.fire(vk, action, &mut decision);
+        }
+        let mut failed = self.chord_state.expire(now);
+        failed.extend(self.sequence_state.expire(now));
+        self.resolve_failures(failed, now, &mut decision);
+        // Delivering held-back events can arm another mod-tap whose term
+        // has also run out already.
+        while let Some((modifier, buffered)) = self.mod_tap_state.tick(now) {
+            self.press_modifier(modifier, now, &mut decision);
+            self.deliver(buffered, ctx, &mut decision);
+        }
+        decision
+    }
+
+    /// Feed one key event. Gestures due at or before the event's timestamp
+    /// resolve first, so their output precedes the event's own in
+    /// `Decision::outputs`.
+    pub fn handle(&mut self, event: KeyEvent, ctx: &ForegroundContext) -> Decision {
+        let mut decision = self.tick(event.time_ms, ctx);
+        decision.suppress = self.process(event, ctx, &mut decision);
+        // Injected keys reach the app only after the hook returns, so an
+        // event passed through now would overtake them. Swallow it and
+        // re-inject it behind them instead.
+        if (decision.hold_back || decision.has_injected()) && !decision.suppress {
+            decision.suppress = true;
+            decision.push_key(event);
+        }
+        decision
+    }
+
+    /// Route one event through `ModTap` first, then the binding matching.
+    /// Returns whether the event is swallowed.
+    fn process(&mut self, event: KeyEvent, ctx: &ForegroundContext, decision: &mut Decision) -> bool {
+        match self.mod_tap_state.on_event(event) {
+            ModTapStep::Pass => {}
+            ModTapStep::Consumed => return true,
+            ModTapStep::Tap { action, buffered } => {
+                self.fire(event.vk, action, decision);
+                self.deliver(buffered, ctx, decision);
+                return true;
+            }
+            ModTapStep::Hold { modifier, buffered } => {
+                self.press_modifier(modifier, event.time_ms, decision);
+                self.deliver(buffered, ctx, decision);
+                // Delivery may have armed another mod-tap this event
+                // must queue behind, so start over rather than fall through.
+                return self.process(event, ctx, decision);
+            }
+            ModTapStep::Release(modifier) => {
+                self.sync_key(modifier, false);
+                decision.push_key(KeyEvent::up(modifier, event.time_ms));
+                return true;
+            }
+        }
+
+        if event.kind == KeyEventKind::Down
+            && let Some((tap, hold, term_ms, flavor)) = self.find_mod_tap(event, ctx)
+        {
+            let deadline =
+                self.mod_tap_state.arm(event.vk, tap, hold, flavor, term_ms, event.time_ms);
+            decision.wake_at.push(deadline);
+            return true;
+        }
+
+        match event.kind {
+            KeyEventKind::Down => self.key_down(event, ctx, decision),
+            KeyEventKind::Up => self.key_up(event, decision),
+        }
+    }
+
+    /// Run events held back behind an undecided `ModTap` through the engine
+    /// as if they arrived now; whatever passes is re-injected in order.
+    fn deliver(&mut self, events: Vec<KeyEvent>, ctx: &ForegroundContext, decision: &mut Decision) {
+        for event in events {
+            if !self.process(event, ctx, decision) {
+                decision.push_key(event);
+            }
+        }
+    }
+
+    /// Press a `ModTap` hold modifier for real and count it as held, so
+    /// combos such as `Ctrl+C` match while the mod-tap key is down.
+    fn press_modifier(&mut self, modifier: Vk, now: u64, decision: &mut Decision) {
+        self.sync_key(modifier, true);
+        decision.push_key(KeyEvent::down(modifier, now));
+    }
+
+    /// Queue `action`, fired on behalf of `key`. `Layer` actions also take
+    /// effect on the layer stack right away, so the very next event already
+    /// matches against the new layers; they stay in the output (where they
+    /// execute as no-ops) so traces show them.
+    fn fire(&mut self, key: Vk, action: BindAction, decision: &mut Decision) {
+        if let BindAction::Layer { op, layer } = &action {
+            let held = self.is_key_down(key);
+            self.layers.apply(*op, layer, key, held);
+        }
+        decision.push_action(action);
+    }
+
+    /// The `ModTap` binding that owns the event's key, if any is active in
+    /// `ctx`.
+    fn find_mod_tap(
+        &self,
+        event: KeyEvent,
+        ctx: &ForegroundContext,
+    ) -> Option<(BindAction, Vk, u64, ModTapFlavor)> {
+        let active_layers: Vec<Arc<str>> = self.layers.active_top_down().cloned().collect();
+        in_layer_order(&self.bindings, &active_layers, &self.switched_tags).find_map(|binding| {
+            let Trigger::ModTap { hold, term_ms, flavor } = binding.trigger else {
+                return None;
+            };
+            let combo = binding.combination()?;
+            let vk = event.vk;
+            let owns_key = matches!(
+                combo.keys.as_slice(),
+                [k] if *k == vk || *k == normalize(vk) || Some(*k) == event.positional()
+            );
+            if !owns_key || !(binding.condition.is_always() || binding.condition.evaluate(ctx)) {
+                return None;
+            }
+            Some((binding.action.clone(), hold, term_ms, flavor))
+        })
+    }
+
+    /// Turn failed gestures into decision output: `Replay` re-injects the
+    /// swallowed keys (once each, even if several gestures held the same
+    /// key), `Action` queues the fallback.
+    fn resolve_failures(&mut self, failed: Vec<FailedGesture>, now: u64, decision: &mut Decision) {
+        for gesture in failed {
+            match gesture.on_fail {
+                OnFail::Drop => {}
+                OnFail::Action(action) => {
+                    let key = gesture.keys.last().map_or(Vk(0), |&k| Vk(k));
+                    self.fire(key, action, decision);
+                }
+                OnFail::Replay => {
+                    // Gestures that fail together may have swallowed the
+                    // same press (a chord and a sequence both starting on
+                    // `j`): the n-th press of a key is replayed once, by
+                    // whichever gesture gets to it first.
+                    let mut own: Vec<Vk> = Vec::new();
+                    for (i, &key) in gesture.keys.iter().enumerate() {
+                        let pressed_again = gesture.keys[i + 1..].contains(&key);
+                        let key = Vk(key);
+                        // A positional key goes back out as the VK it
+                        // produced, with its scan code.
+                        let (vk, scan) = match scan_code(key) {
+                            Some(scan) => (self.positional_vks[key.0 as usize], scan),
+                            None => (key, 0),
+                        };
+                        own.push(vk);
+                        let nth = own.iter().filter(|&&k| k == vk).count();
+                        if decision.replayed.iter().filter(|&&k| k == vk).count() >= nth {
+                            continue;
+                        }
+                        decision.replayed.push(vk);
+                        // The key-up is no longer ours to swallow: if the
+                        // key is still held, its real release must follow
+                        // the replayed press; if it was already released
+                        // (and swallowed), replay the release too. A key
+                        // pressed again later in the gesture was released
+                        // in between.
+                        take_blocked(&mut self.blocked_keys, key.0);
+                        decision.push_key(KeyEvent::down(vk, now).with_scan(scan));
+                        if pressed_again || !self.is_key_down(key) {
+                            decision.push_key(KeyEvent::up(vk, now).with_scan(scan));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn key_down(&mut self, event: KeyEvent, ctx: &ForegroundContext, decision: &mut Decision) -> bool {
+        let (vk_code, now) = (event.vk, event.time_ms);
+        let vk_index = vk_code.0 as usize;
+        let positional = event.positional();
+
+        // Detect OS auto-repeat (key was already down before this event).
+        // Sequence/Chord state machines must ignore repeats — they expect
+        // deliberate presses. Combo-kind bindings fire on every repeat unless
+        // they set `repeat: false` (or `settings: (repeat: false)` does):
+        // typing-style PressKey actions rely on it, a Launch rarely wants it.
+        let was_repeat = self.hold(vk_index);
+        if let Some(key) = positional {
+            self.hold(key.0 as usize);
+            self.positional_vks[key.0 as usize] = vk_code;
+        }
+        if vk_code == VK_LCONTROL && event.scan == ALT_GR_CTRL_SCAN {
+            self.alt_gr = true;
+        }
+
+        let active_keys = collect_active_keys(&self.active_keys, &self.pressed_at, self.alt_gr);
+
+        // A key outside an in-flight `on_fail` chord breaks it right away,
+        // and a fresh press of another key ends any pending tap dance.
+        if !was_repeat {
+            let failed = self.chord_state.interrupt(vk_code, positional);
+            self.resolve_failures(failed, now, decision);
+            for (key, action) in self.tap_state.interrupt_tap_dances(vk_code) {
+                self.fire(key, action, decision);
+                decision.hold_back = true;
+            }
+        }
+        let mut failed = Vec::new();
+
+        // A fresh press of a non-modifier key uses up one-shot layers. They
+        // stay active while this press is matched.
+        let one_shots = if was_repeat || is_modifier(vk_code) {
+            Vec::new()
+        } else {
+            self.layers.take_one_shots()
+        };
+
+        // Hold our own handle on the list so a `set_sorted_bindings` from
+        // the outside can never pull it out from under the loop.
+        let bindings = Arc::clone(&self.bindings);
+        let active_layers: Vec<Arc<str>> = self.layers.active_top_down().cloned().collect();
+        let switched_tags = self.switched_tags.clone();
+
+        let mut suppress = false;
+        let mut immediate_fired = false;
+
+        for binding in in_layer_order(&bindings, &active_layers, &switched_tags) {
+            if !binding.condition.is_always() && !binding.condition.evaluate(ctx) {
+                continue;
+            }
+
+            match &binding.kind {
+                BindingKind::Combo(combo) => {
+                    if !combo.matches(active_keys.as_slice()) {
+                        continue;
+                    }
+                    match binding.trigger {
+                        Trigger::Immediate => {
+                            if !immediate_fired && (binding.repeat || !was_repeat) {
+                                self.fire(vk_code, binding.action.clone(), decision);
+                                immediate_fired = true;
+                            }
+                            if binding.block_default {
+                                suppress = true;
+                            }
+                        }
+                        Trigger::Tap(term_ms) => {
+                            if was_repeat { continue; }
+                            self.tap_state.arm_tap(vk_code, binding.action.clone(), term_ms, now);
+                            suppress = true;
+                        }
+                        Trigger::Hold(term_ms) => {
+                            if was_repeat { continue; }
+                            if let Some(deadline) =
+                                self.tap_state.arm_hold(vk_code, binding.action.clone(), term_ms, now)
+                            {
+                                decision.wake_at.push(deadline);
+                            }
+                            suppress = true;
+                        }
+                        Trigger::DoubleTap(term_ms) => {
+                            if was_repeat { continue; }
+                            if let Some(action) = self.tap_state.handle_double_tap(
+                                vk_code,
+                                binding.action.clone(),
+                                term_ms,
+                                now,
+                            ) {
+                                self.fire(vk_code, action, decision);
+                            }
+                            suppress = true;
+                        }
+                        Trigger::TapDance { term_ms, ref steps } => {
+                            if was_repeat { continue; }
+                            if let Some(deadline) =
+                                self.tap_state.tap_dance_down(vk_code, steps, term_ms, now)
+                            {
+                                decision.wake_at.push(deadline);
+                            }
+                            suppress = true;
+                        }
+                        // Owns its key outright; resolved in `process`
+                        // before matching ever runs.
+                        Trigger::ModTap { .. } => {}
+                    }
+                }
+                BindingKind::Sequence { steps, max_gap } => {
+                    if was_repeat { continue; }
+                    match self.sequence_state.handle_keydown(
+                        vk_code,
+                        active_keys.as_slice(),
+                        &binding.action,
+                        &binding.on_fail,
+                        steps,
+                        *max_gap,
+                        now,
+                        &mut failed,
+                    ) {
+                        SequenceOutcome::NotMatching => {}
+                        SequenceOutcome::Advanced { expires_at } => {
+                            suppress = true;
+                            if !binding.on_fail.is_drop() {
+                                decision.wake_at.push(expires_at);
+                            }
+                        }
+                        SequenceOutcome::Completed { last_step_keys, action } => {
+                            suppress = true;
+                            self.fire(vk_code, action, decision);
+                            for k in last_step_keys {
+                                mark_blocked(&mut self.blocked_keys, k);
+                            }
+                        }
+                    }
+                }
+                BindingKind::Chord { keys, window } => {
+                    if was_repeat { continue; }
+                    // A chord may name the key by position, by side or
+                    // not; feed it the identity it uses.
+                    let chord_key = [positional, Some(vk_code), Some(normalize(vk_code))]
+                        .into_iter()
+                        .flatten()
+                        .find(|k| keys.contains(k))
+                        .unwrap_or(vk_code);
+                    match self.chord_state.handle_keydown(
+                        chord_key,
+                        &binding.action,
+                        &binding.on_fail,
+                        keys,
+                        *window,
+                        now,
+                    ) {
+                        ChordOutcome::NotInChord => {}
+                        ChordOutcome::Pending { expires_at } => {
+                            suppress = true;
+                            if !binding.on_fail.is_drop() {
+                                decision.wake_at.push(expires_at);
+                            }
+                        }
+                        ChordOutcome::Completed { keys, action } => {
+                            suppress = true;
+                            self.fire(vk_code, action, decision);
+                            for k in keys {
+                                mark_blocked(&mut self.blocked_keys, k);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Sequences broken by this key. Resolved after the loop so the
+        // replay of the broken sequence's keys is complete before this key
+        // (if not claimed by another binding) is queued behind them.
+        self.resolve_failures(failed, now, decision);
+        self.layers.finish_one_shots(one_shots);
+
+        if suppress {
+            mark_blocked(&mut self.blocked_keys, vk_code.0);
+        }
+
+        suppress
+    }
+
+    fn key_up(&mut self, event: KeyEvent, decision: &mut Decision) -> bool {
+        let (vk_code, now) = (event.vk, event.time_ms);
+        self.sync_key(vk_code, false);
+        if let Some(key) = event.positional() {
+            self.active_keys[key.0 as usize] = false;
+        }
+        // A chord or sequence that named the positional key blocked that
+        // slot rather than the VK's.
+        let positional_blocked = event
+            .positional()
+            .is_some_and(|key| take_blocked(&mut self.blocked_keys, key.0));
+        self.layers.release(vk_code);
+
+        // Resolve every deferred gesture pending for this key. Tap fires its
+        // action here (if still within window); Hold simply cancels (key
+        // released too early — a due hold already fired in the pre-event
+        // tick). Both gestures can be pending simultaneously on the same
+        // key — they were armed independently and are resolved
+        // independently.
+        let tap_action = self.tap_state.resolve_tap_on_keyup(vk_code, now);
+        let tap_fired = tap_action.is_some();
+        if let Some(action) = tap_action {
+            self.fire(vk_code, action, decision);
+        }
+        let hold_cancelled = self.tap_state.cancel_hold_on_keyup(vk_code);
+        let dance = self.tap_state.tap_dance_up(vk_code, now);
+        let dancing = dance.is_some();
+        match dance {
+            Some(TapDanceUp::Finished(Some(action))) => self.fire(vk_code, action, decision),
+            Some(TapDanceUp::Waiting { deadline }) => decision.wake_at.push(deadline),
+            Some(TapDanceUp::Finished(None)) | None => {}
+        }
+
+        if tap_fired || hold_cancelled || dancing {
+            // Gesture-bound key: suppress the key-up too. Clear any blocked
+            // bit set on the corresponding key-down so it doesn't leak.
+            let _ = take_blocked(&mut self.blocked_keys, vk_code.0);
+            return true;
+        }
+
+        take_blocked(&mut self.blocked_keys, vk_code.0) | positional_blocked
+    }
+}
+
+fn mark_blocked(blocked: &mut [u64; BLOCKED_WORDS], vk: u16) {
+    let idx = (vk as usize) / 64;
+    let bit = 1u64 << ((vk as usize) % 64);
+    if idx < blocked.len() {
+        blocked[idx] |= bit;
+    }
+}
+
+/// Clears the `vk` bit and reports whether it was previously set.
+fn take_blocked(blocked: &mut [u64; BLOCKED_WORDS], vk: u16) -> bool {
+    let idx = (vk as usize) / 64;
+    let bit = 1u64 << ((vk as usize) % 64);
+    if idx >= blocked.len() {
+        return false;
+    }
+    let prev = blocked[idx];
+    blocked[idx] &= !bit;
+    (prev & bit) != 0
+}
+
+fn clear_all_blocked(blocked: &mut [u64; BLOCKED_WORDS]) {
+    *blocked = [0; BLOCKED_WORDS];
+}
+
+/// Every held key in press order, plus the unsided form of each held sided
+/// modifier: a combo on `Ctrl` matches either Ctrl key, one on `RCtrl` only
+/// the right. With `alt_gr`, right Alt is [`ALT_GR`] and Ctrl / Alt come
+/// only from keys that really are them.
+fn collect_active_keys(
+    mask: &[bool; KEY_SLOTS],
+    pressed_at: &[u64; KEY_SLOTS],
+    alt_gr: bool,
+) -> ActiveKeys {
+    // The earliest presses, sorted by press time. Each one pushes at most
+    // as many keys as it takes, so `keys` fills up before this runs out.
+    let mut held = [(0u64, 0u16); ACTIVE_KEYS_CAP];
+    let mut count = 0;
+    for (slot, _) in mask.iter().enumerate().filter(|&(_, &down)| down) {
+        let press = (pressed_at[slot], slot as u16);
+        let at = held[..count].partition_point(|&earlier| earlier < press);
+        if at == ACTIVE_KEYS_CAP {
+            continue;
+        }
+        count = (count + 1).min(ACTIVE_KEYS_CAP);
+        held.copy_within(at..count - 1, at + 1);
+        held[at] = press;
+    }
+
+    let mut keys = ActiveKeys::new();
+    for &(_, vk) in &held[..count] {
+        let vk = Vk(vk);
+        if alt_gr {
+            match vk {
+                // The generic slots are synced from the OS, which counts
+                // AltGr as both.
+                VK_CONTROL | VK_LCONTROL | VK_MENU => continue,
+                VK_RMENU => {
+                    keys.push_unique(ALT_GR);
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        keys.push_unique(vk);
+        keys.push_unique(normalize(vk));
+    }
+
+    keys
+}
+
+/// The live `bindings` in matching order for the given active layers (top
+/// first): every active layer's bindings, then the base bindings. Each
+/// group keeps its specificity order.
+fn in_layer_order<'a>(
+    bindings: &'a [Binding],
+    active_layers: &'a [Arc<str>],
+    switched_tags: &'a [(Arc<str>, bool)],
+) -> impl Iterator<Item = &'a Binding> {
+    active_layers
+        .iter()
+        .map(|l| Some(&**l))
+        .chain(std::iter::once(None))
+        .flat_map(move |layer| bindings.iter().filter(move |b| b.layer.as_deref() == layer))
+        .filter(move |b| b.is_live(switched_tags))
+}
+
+fn is_modifier(vk: Vk) -> bool {
+    matches!(normalize(vk), VK_SHIFT | VK_CONTROL | VK_MENU | ANY_WIN)
+}
+
+/// Map sided modifiers onto the generic codes bindings are written with.
+fn normalize(vk: Vk) -> Vk {
+    match vk {
+        VK_LSHIFT | VK_RSHIFT => VK_SHIFT,
+        VK_LCONTROL | VK_RCONTROL => VK_CONTROL,
+        VK_LMENU | VK_RMENU => VK_MENU,
+        VK_LWIN | VK_RWIN => ANY_WIN,
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::condition::Condition;
+    use crate::data::key_combination::{KeyCombination, MatchMode};
+    use crate::data::action::LayerOp;
+    use crate::data::trigger::TapDanceStep;
+    use std::time::Duration;
+
+    fn no_ctx() -> ForegroundContext {
+        ForegroundContext::snapshot(None, None)
+    }
+
+    fn combo(keys: &[Vk]) -> KeyCombination {
+        KeyCombination::from_keys(keys.to_vec())
+    }
+
+    #[test]
+    fn active_keys_dedup() {
+        let mut buf = ActiveKeys::new();
+        buf.push_unique(VK_SHIFT);
+        buf.push_unique(VK_CONTROL);
+        buf.push_unique(VK_SHIFT);
+        assert_eq!(buf.as_slice(), &[VK_SHIFT, VK_CONTROL]);
+    }
+
+    #[test]
+    fn active_keys_capacity_bound() {
+        let mut buf = ActiveKeys::new();
+        for i in 0..(ACTIVE_KEYS_CAP as u16 + 4) {
+            buf.push_unique(Vk(i));
+        }
+        assert_eq!(buf.as_slice().len(), ACTIVE_KEYS_CAP);
+    }
+
+    #[test]
+    fn active_keys_past_the_cap_keep_the_earliest_presses() {
+        let mut mask = [false; KEY_SLOTS];
+        let mut pressed_at = [0; KEY_SLOTS];
+        // Forty keys, each pressed before the one in the slot below it.
+        for i in 0..40u16 {
+            mask[(VK_0.0 + i) as usize] = true;
+            pressed_at[(VK_0.0 + i) as usize] = 100 - u64::from(i);
+        }
+        let keys = collect_active_keys(&mask, &pressed_at, false);
+        let expected: Vec<Vk> = (8..40u16).rev().map(|i| Vk(VK_0.0 + i)).collect();
+        assert_eq!(keys.as_slice(), expected.as_slice());
+    }
+
+    #[test]
+    fn blocked_bitmap_independent_bits() {
+        let mut blocked = [0u64; BLOCKED_WORDS];
+        mark_blocked(&mut blocked, 20);   // VK_CAPITAL, slot 0
+        mark_blocked(&mut blocked, 16);   // VK_SHIFT,  slot 0
+        mark_blocked(&mut blocked, 200);  // slot 3
+        mark_blocked(&mut blocked, 0x21C); // NumpadEnter, slot 8
+
+        // Releasing VK_CAPITAL must not touch VK_SHIFT or 200.
+        assert!(take_blocked(&mut blocked, 20));
+        assert!(!take_blocked(&mut blocked, 20)); // second take is a no-op
+        assert!(take_blocked(&mut blocked, 16));
+        assert!(take_blocked(&mut blocked, 200));
+        assert!(take_blocked(&mut blocked, 0x21C));
+    }
+
+    #[test]
+    fn clear_all_resets_every_slot() {
+        let mut blocked = [0u64; BLOCKED_WORDS];
+        mark_blocked(&mut blocked, 20);
+        mark_blocked(&mut blocked, 200);
+        clear_all_blocked(&mut blocked);
+        assert!(!take_blocked(&mut blocked, 20));
+        assert!(!take_blocked(&mut blocked, 200));
+    }
+
+    #[test]
+    fn immediate_combo_fires_and_blocks_key_up() {
+        let mut engine = Engine::new(vec![Binding::new(
+            combo(&[VK_CAPITAL]),
+            BindAction::SwitchLanguage,
+        )]);
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::SwitchLanguage]);
+
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 50), &no_ctx());
+        assert!(d.suppress);
+        assert!(d.actions().is_empty());
+
+        // Unrelated key passes through untouched.
+        let d = engine.handle(KeyEvent::down(VK_A, 60), &no_ctx());
+        assert!(!d.suppress);
+        assert!(d.actions().is_empty());
+    }
+
+    #[test]
+    fn more_specific_combo_wins() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::SwitchLanguage),
+            Binding::new(combo(&[VK_SHIFT, VK_CAPITAL]), BindAction::ToggleCapsLock),
+        ]);
+        engine.handle(KeyEvent::down(VK_LSHIFT, 0), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 10), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+    }
+
+    #[test]
+    fn block_default_false_passes_event_through() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_F13]), BindAction::DoNothing).with_block_default(false),
+        ]);
+        let d = engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx());
+        assert!(!d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::DoNothing]);
+        assert!(!engine.handle(KeyEvent::up(VK_F13, 10), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn tap_and_hold_resolve_by_timing() {
+        let bindings = vec![
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::SwitchLanguage)
+                .with_trigger(Trigger::Tap(200)),
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::ToggleCapsLock)
+                .with_trigger(Trigger::Hold(200)),
+        ];
+
+        // Quick press → tap.
+        let mut engine = Engine::new(bindings.clone());
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert!(d.suppress);
+        assert!(d.actions().is_empty());
+        assert_eq!(d.wake_at, vec![200]);
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 120), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::SwitchLanguage]);
+        assert!(engine.tick(500, &no_ctx()).actions().is_empty());
+
+        // Long press → hold fires on the wake-up tick, release is silent.
+        let mut engine = Engine::new(bindings);
+        engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert!(engine.tick(199, &no_ctx()).actions().is_empty());
+        assert_eq!(engine.tick(200, &no_ctx()).actions(), vec![BindAction::ToggleCapsLock]);
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 400), &no_ctx());
+        assert!(d.suppress);
+        assert!(d.actions().is_empty());
+    }
+
+    #[test]
+    fn late_wake_up_still_fires_hold_before_key_up() {
+        // The timer thread may run late; a key-up past the deadline must
+        // not cancel a hold that was already due.
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::ToggleCapsLock)
+                .with_trigger(Trigger::Hold(200)),
+        ]);
+        engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 250), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+    }
+
+    #[test]
+    fn auto_repeat_does_not_rearm_gestures() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::ToggleCapsLock)
+                .with_trigger(Trigger::Hold(200)),
+        ]);
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert_eq!(d.wake_at, vec![200]);
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 30), &no_ctx());
+        assert!(d.wake_at.is_empty());
+        assert_eq!(engine.tick(200, &no_ctx()).actions(), vec![BindAction::ToggleCapsLock]);
+    }
+
+    #[test]
+    fn combos_without_repeat_swallow_repeats_silently() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_F13]), BindAction::ToggleCapsLock).with_repeat(false),
+            Binding::new(combo(&[VK_F14]), BindAction::SwitchLanguage),
+        ]);
+        assert_eq!(engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx()).actions().len(), 1);
+        let d = engine.handle(KeyEvent::down(VK_F13, 30), &no_ctx());
+        assert!(d.suppress);
+        assert!(d.actions().is_empty());
+        engine.handle(KeyEvent::up(VK_F13, 40), &no_ctx());
+        assert_eq!(engine.handle(KeyEvent::down(VK_F13, 50), &no_ctx()).actions().len(), 1);
+
+        engine.handle(KeyEvent::down(VK_F14, 60), &no_ctx());
+        assert_eq!(engine.handle(KeyEvent::down(VK_F14, 90), &no_ctx()).actions().len(), 1);
+    }
+
+    #[test]
+    fn double_tap_fires_on_second_press() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_F13]), BindAction::ToggleCapsLock)
+                .with_trigger(Trigger::DoubleTap(250)),
+        ]);
+        let d = engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx());
+        assert!(d.suppress);
+        assert!(d.actions().is_empty());
+        engine.handle(KeyEvent::up(VK_F13, 40), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_F13, 120), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+    }
+
+    #[test]
+    fn chord_completes_within_window() {
+        let mut engine = Engine::new(vec![Binding::with_kind(
+            BindingKind::Chord {
+                keys: vec![VK_J, VK_K],
+                window: Duration::from_millis(50),
+            },
+            BindAction::PressKey(VK_ESCAPE),
+        )]);
+        let d = engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        assert!(d.suppress);
+        assert!(d.actions().is_empty());
+        let d = engine.handle(KeyEvent::down(VK_K, 20), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_ESCAPE)]);
+        // Both key-ups are swallowed.
+        assert!(engine.handle(KeyEvent::up(VK_J, 60), &no_ctx()).suppress);
+        assert!(engine.handle(KeyEvent::up(VK_K, 70), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn chord_outside_window_does_not_fire() {
+        let mut engine = Engine::new(vec![Binding::with_kind(
+            BindingKind::Chord {
+                keys: vec![VK_J, VK_K],
+                window: Duration::from_millis(50),
+            },
+            BindAction::PressKey(VK_ESCAPE),
+        )]);
+        engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_J, 10), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_K, 100), &no_ctx());
+        assert!(d.actions().is_empty());
+    }
+
+    #[test]
+    fn sequence_fires_on_last_step_and_ignores_repeats() {
+        let mut engine = Engine::new(vec![Binding::with_kind(
+            BindingKind::Sequence {
+                steps: vec![combo(&[VK_F13]), combo(&[VK_F14])],
+                max_gap: Duration::from_millis(500),
+            },
+            BindAction::PressKey(VK_HOME),
+        )]);
+        assert!(engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx()).suppress);
+        // Auto-repeat of the first step neither advances nor aborts.
+        assert!(engine.handle(KeyEvent::down(VK_F13, 30), &no_ctx()).actions().is_empty());
+        engine.handle(KeyEvent::up(VK_F13, 60), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_F14, 300), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_HOME)]);
+        assert!(engine.handle(KeyEvent::up(VK_F14, 350), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn condition_gates_on_snapshot() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_PAUSE]), BindAction::DoNothing)
+                .with_condition(Condition::AppEquals("spotify.exe".into())),
+        ]);
+        assert!(engine.needs_foreground());
+
+        let other = ForegroundContext::snapshot(Some("code.exe".into()), None);
+        let d = engine.handle(KeyEvent::down(VK_PAUSE, 0), &other);
+        assert!(!d.suppress);
+        engine.handle(KeyEvent::up(VK_PAUSE, 10), &other);
+
+        let spotify = ForegroundContext::snapshot(Some("Spotify.exe".into()), None);
+        let d = engine.handle(KeyEvent::down(VK_PAUSE, 20), &spotify);
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::DoNothing]);
+    }
+
+    #[test]
+    fn sync_key_and_clear_blocked_repair_state() {
+        let mut engine = Engine::new(vec![Binding::new(
+            combo(&[VK_CONTROL, VK_K]),
+            BindAction::DoNothing,
+        )]);
+        // Ctrl is reported held by the OS without us ever seeing its key-down.
+        engine.sync_key(VK_LCONTROL, true);
+        assert!(engine.is_key_down(VK_LCONTROL));
+        let d = engine.handle(KeyEvent::down(VK_K, 0), &no_ctx());
+        assert!(d.suppress);
+
+        // Focus moved away before K's key-up: the blocked bit is dropped.
+        engine.clear_blocked();
+        assert!(!engine.handle(KeyEvent::up(VK_K, 10), &no_ctx()).suppress);
+    }
+
+    fn jk_chord(on_fail: OnFail) -> Engine {
+        Engine::new(vec![
+            Binding::with_kind(
+                BindingKind::Chord {
+                    keys: vec![VK_J, VK_K],
+                    window: Duration::from_millis(50),
+                },
+                BindAction::PressKey(VK_ESCAPE),
+            )
+            .with_on_fail(on_fail),
+        ])
+    }
+
+    #[test]
+    fn chord_replay_on_interrupting_key_keeps_typing_order() {
+        let mut engine = jk_chord(OnFail::Replay);
+        let d = engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.wake_at, vec![50]);
+
+        // "x" arrives while J is still held: J is replayed, and X is
+        // swallowed and queued behind it so the app sees "jx".
+        let d = engine.handle(KeyEvent::down(VK_X, 10), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.injected(), vec![KeyEvent::down(VK_J, 10), KeyEvent::down(VK_X, 10)]);
+        assert!(d.actions().is_empty());
+
+        // J's real release is no longer swallowed.
+        assert!(!engine.handle(KeyEvent::up(VK_J, 30), &no_ctx()).suppress);
+        assert!(!engine.handle(KeyEvent::up(VK_X, 40), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn chord_replay_on_timeout_includes_swallowed_release() {
+        let mut engine = jk_chord(OnFail::Replay);
+        engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        // Quick tap: the release is swallowed while the chord is pending.
+        assert!(engine.handle(KeyEvent::up(VK_J, 20), &no_ctx()).suppress);
+        assert!(engine.tick(49, &no_ctx()).injected().is_empty());
+        let d = engine.tick(50, &no_ctx());
+        assert_eq!(d.injected(), vec![KeyEvent::down(VK_J, 50), KeyEvent::up(VK_J, 50)]);
+    }
+
+    #[test]
+    fn chord_replay_includes_a_key_pressed_twice() {
+        let mut engine = jk_chord(OnFail::Replay);
+        engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_J, 10), &no_ctx());
+        engine.handle(KeyEvent::down(VK_J, 20), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_X, 30), &no_ctx());
+        assert_eq!(
+            d.injected(),
+            vec![
+                KeyEvent::down(VK_J, 30),
+                KeyEvent::up(VK_J, 30),
+                KeyEvent::down(VK_J, 30),
+                KeyEvent::down(VK_X, 30),
+            ]
+        );
+    }
+
+    #[test]
+    fn chord_completion_replays_nothing() {
+        let mut engine = jk_chord(OnFail::Replay);
+        engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_K, 20), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_ESCAPE)]);
+        assert!(engine.tick(100, &no_ctx()).injected().is_empty());
+    }
+
+    #[test]
+    fn drop_chord_keeps_original_behaviour() {
+        let mut engine = jk_chord(OnFail::Drop);
+        let d = engine.handle(KeyEvent::down(VK_J, 0), &no_ctx());
+        assert!(d.wake_at.is_empty());
+        let d = engine.handle(KeyEvent::down(VK_X, 10), &no_ctx());
+        assert!(!d.suppress);
+        assert!(d.injected().is_empty());
+        assert!(engine.tick(100, &no_ctx()).injected().is_empty());
+    }
+
+    #[test]
+    fn sequence_fallback_action_when_leader_pressed_alone() {
+        let mut engine = Engine::new(vec![
+            Binding::with_kind(
+                BindingKind::Sequence {
+                    steps: vec![combo(&[VK_SPACE]), combo(&[VK_F])],
+                    max_gap: Duration::from_millis(300),
+                },
+                BindAction::PressKey(VK_HOME),
+            )
+            .with_on_fail(OnFail::Action(BindAction::PressKey(VK_ESCAPE))),
+        ]);
+        let d = engine.handle(KeyEvent::down(VK_SPACE, 0), &no_ctx());
+        assert_eq!(d.wake_at, vec![300]);
+        engine.handle(KeyEvent::up(VK_SPACE, 50), &no_ctx());
+        let d = engine.tick(300, &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_ESCAPE)]);
+        assert!(d.injected().is_empty());
+    }
+
+    #[test]
+    fn sequence_replay_on_wrong_key() {
+        let mut engine = Engine::new(vec![
+            Binding::with_kind(
+                BindingKind::Sequence {
+                    steps: vec![combo(&[VK_G]), combo(&[VK_G])],
+                    max_gap: Duration::from_millis(300),
+                },
+                BindAction::PressKey(VK_HOME),
+            )
+            .with_on_fail(OnFail::Replay),
+        ]);
+        engine.handle(KeyEvent::down(VK_G, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_G, 40), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_O, 100), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(
+            d.injected(),
+            vec![
+                KeyEvent::down(VK_G, 100),
+                KeyEvent::up(VK_G, 100),
+                KeyEvent::down(VK_O, 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn sequence_replay_keeps_repeated_keys() {
+        let gg = |last, action| {
+            Binding::with_kind(
+                BindingKind::Sequence {
+                    steps: vec![combo(&[VK_G]), combo(&[VK_G]), combo(&[last])],
+                    max_gap: Duration::from_millis(300),
+                },
+                action,
+            )
+            .with_on_fail(OnFail::Replay)
+        };
+        // Two sequences swallow the same two presses of G; each press goes
+        // back out once.
+        let mut engine = Engine::new(vec![
+            gg(VK_X, BindAction::PressKey(VK_HOME)),
+            gg(VK_Z, BindAction::PressKey(VK_END)),
+        ]);
+        engine.handle(KeyEvent::down(VK_G, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_G, 10), &no_ctx());
+        engine.handle(KeyEvent::down(VK_G, 20), &no_ctx());
+        engine.handle(KeyEvent::up(VK_G, 30), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_Y, 40), &no_ctx());
+        assert_eq!(
+            d.injected(),
+            vec![
+                KeyEvent::down(VK_G, 40),
+                KeyEvent::up(VK_G, 40),
+                KeyEvent::down(VK_G, 40),
+                KeyEvent::up(VK_G, 40),
+                KeyEvent::down(VK_Y, 40),
+            ]
+        );
+    }
+
+    fn caps_mod_tap(flavor: ModTapFlavor) -> Binding {
+        Binding::new(combo(&[VK_CAPITAL]), BindAction::PressKey(VK_ESCAPE))
+            .with_trigger(Trigger::ModTap { hold: VK_CONTROL, term_ms: 200, flavor })
+    }
+
+    #[test]
+    fn mod_tap_quick_release_fires_tap_action() {
+        let mut engine = Engine::new(vec![caps_mod_tap(ModTapFlavor::HoldOnOtherKeyPress)]);
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.wake_at, vec![200]);
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 80), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.outputs, vec![Output::Action(BindAction::PressKey(VK_ESCAPE))]);
+        assert!(engine.tick(200, &no_ctx()).outputs.is_empty());
+    }
+
+    #[test]
+    fn mod_tap_other_key_press_holds_modifier_until_release() {
+        let mut engine = Engine::new(vec![caps_mod_tap(ModTapFlavor::HoldOnOtherKeyPress)]);
+        engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        // C is swallowed and re-injected behind the real Ctrl press.
+        let d = engine.handle(KeyEvent::down(VK_C, 30), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.injected(), vec![KeyEvent::down(VK_CONTROL, 30), KeyEvent::down(VK_C, 30)]);
+        assert!(engine.is_key_down(VK_CONTROL));
+        assert!(!engine.handle(KeyEvent::up(VK_C, 60), &no_ctx()).suppress);
+        // Auto-repeat of the held key is swallowed; release lets go of Ctrl.
+        assert!(engine.handle(KeyEvent::down(VK_CAPITAL, 90), &no_ctx()).suppress);
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 120), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.injected(), vec![KeyEvent::up(VK_CONTROL, 120)]);
+        assert!(!engine.is_key_down(VK_CONTROL));
+    }
+
+    #[test]
+    fn mod_tap_hold_at_term_lets_buffered_keys_match_modifier_combos() {
+        let mut engine = Engine::new(vec![
+            caps_mod_tap(ModTapFlavor::TapPreferred),
+            Binding::new(combo(&[VK_CONTROL, VK_C]), BindAction::ToggleCapsLock),
+        ]);
+        engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert!(engine.handle(KeyEvent::down(VK_C, 50), &no_ctx()).suppress);
+        let d = engine.tick(200, &no_ctx());
+        assert_eq!(
+            d.outputs,
+            vec![
+                Output::Key(KeyEvent::down(VK_CONTROL, 200)),
+                Output::Action(BindAction::ToggleCapsLock),
+            ]
+        );
+        // C's key-down was claimed by the Ctrl+C binding, so its release is too.
+        assert!(engine.handle(KeyEvent::up(VK_C, 250), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn mod_tap_rollover_stays_a_tap_in_typing_order() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_A]), BindAction::PressKey(VK_A))
+                .with_trigger(Trigger::ModTap {
+                    hold: VK_SHIFT,
+                    term_ms: 200,
+                    flavor: ModTapFlavor::default(),
+                }),
+        ]);
+        engine.handle(KeyEvent::down(VK_A, 0), &no_ctx());
+        engine.handle(KeyEvent::down(VK_S, 40), &no_ctx());
+        let d = engine.handle(KeyEvent::up(VK_A, 70), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(
+            d.outputs,
+            vec![
+                Output::Action(BindAction::PressKey(VK_A)),
+                Output::Key(KeyEvent::down(VK_S, 40)),
+            ]
+        );
+        assert!(!engine.handle(KeyEvent::up(VK_S, 90), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn mod_tap_default_flavor_decides_before_the_term() {
+        let mut engine = Engine::new(vec![
+            caps_mod_tap(ModTapFlavor::default()),
+            Binding::new(combo(&[VK_CONTROL, VK_C]), BindAction::ToggleCapsLock),
+        ]);
+        engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert!(engine.handle(KeyEvent::down(VK_C, 30), &no_ctx()).suppress);
+        // C tapped inside the hold: Ctrl goes down right away, not at 200.
+        let d = engine.handle(KeyEvent::up(VK_C, 60), &no_ctx());
+        assert_eq!(
+            d.outputs,
+            vec![
+                Output::Key(KeyEvent::down(VK_CONTROL, 60)),
+                Output::Action(BindAction::ToggleCapsLock),
+            ]
+        );
+    }
+
+    fn caps_tap_dance() -> Engine {
+        let steps = [
+            BindAction::SwitchLanguage,
+            BindAction::SwitchLanguageBackward,
+            BindAction::ToggleCapsLock,
+        ]
+        .into_iter()
+        .map(|a| TapDanceStep { tap: Some(a), hold: None })
+        .collect::<Vec<_>>();
+        Engine::new(vec![
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::SwitchLanguage)
+                .with_trigger(Trigger::TapDance { term_ms: 200, steps: Arc::from(steps) }),
+        ])
+    }
+
+    #[test]
+    fn tap_dance_counts_taps() {
+        // x2: the dance ends one term after the second release.
+        let mut engine = caps_tap_dance();
+        assert_eq!(engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx()).wake_at, vec![200]);
+        let d = engine.handle(KeyEvent::up(VK_CAPITAL, 50), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.wake_at, vec![250]);
+        engine.handle(KeyEvent::down(VK_CAPITAL, 120), &no_ctx());
+        engine.handle(KeyEvent::up(VK_CAPITAL, 160), &no_ctx());
+        assert!(engine.tick(250, &no_ctx()).actions().is_empty());
+        assert_eq!(
+            engine.tick(360, &no_ctx()).actions(),
+            vec![BindAction::SwitchLanguageBackward]
+        );
+
+        // x3 is the last count: fires on the third release, no waiting.
+        let mut engine = caps_tap_dance();
+        for t in [0, 100, 200] {
+            engine.handle(KeyEvent::down(VK_CAPITAL, t), &no_ctx());
+            let d = engine.handle(KeyEvent::up(VK_CAPITAL, t + 40), &no_ctx());
+            if t == 200 {
+                assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+            }
+        }
+    }
+
+    #[test]
+    fn tap_dance_interrupted_fires_before_the_interrupting_key() {
+        let mut engine = caps_tap_dance();
+        engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_CAPITAL, 40), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_A, 90), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(
+            d.outputs,
+            vec![
+                Output::Action(BindAction::SwitchLanguage),
+                Output::Key(KeyEvent::down(VK_A, 90)),
+            ]
+        );
+        assert!(engine.tick(300, &no_ctx()).outputs.is_empty());
+    }
+
+    fn nav_layer(op: LayerOp) -> Engine {
+        let nav: Arc<str> = Arc::from("nav");
+        Engine::new(vec![
+            Binding::new(combo(&[VK_F13]), BindAction::Layer { op, layer: Arc::clone(&nav) }),
+            Binding::new(combo(&[VK_H]), BindAction::PressKey(VK_LEFT)).with_layer(Some(nav)),
+        ])
+    }
+
+    #[test]
+    fn momentary_layer_lives_while_its_key_is_held() {
+        let mut engine = nav_layer(LayerOp::Momentary);
+        engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx());
+        assert_eq!(engine.active_layers(), vec!["nav".to_string()]);
+        let d = engine.handle(KeyEvent::down(VK_H, 10), &no_ctx());
+        assert!(d.suppress);
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_LEFT)]);
+        engine.handle(KeyEvent::up(VK_H, 30), &no_ctx());
+        engine.handle(KeyEvent::up(VK_F13, 50), &no_ctx());
+        assert!(engine.active_layers().is_empty());
+
+        let d = engine.handle(KeyEvent::down(VK_H, 60), &no_ctx());
+        assert!(!d.suppress);
+        assert!(d.actions().is_empty());
+    }
+
+    #[test]
+    fn momentary_layer_ends_when_its_missed_key_up_is_resynced() {
+        let mut engine = nav_layer(LayerOp::Momentary);
+        engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx());
+        engine.sync_momentary_layers(|vk| vk == VK_F13);
+        assert_eq!(engine.active_layers(), vec!["nav".to_string()]);
+
+        engine.sync_momentary_layers(|_| false);
+        assert!(engine.active_layers().is_empty());
+        let d = engine.handle(KeyEvent::down(VK_H, 60), &no_ctx());
+        assert!(!d.suppress);
+    }
+
+    #[test]
+    fn toggled_layer_stays_until_toggled_again() {
+        let mut engine = nav_layer(LayerOp::Toggle);
+        engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_F13, 20), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_H, 40), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_LEFT)]);
+        engine.handle(KeyEvent::up(VK_H, 60), &no_ctx());
+
+        engine.handle(KeyEvent::down(VK_F13, 80), &no_ctx());
+        engine.handle(KeyEvent::up(VK_F13, 100), &no_ctx());
+        assert!(engine.handle(KeyEvent::down(VK_H, 120), &no_ctx()).actions().is_empty());
+    }
+
+    #[test]
+    fn one_shot_layer_covers_the_next_press_only() {
+        let mut engine = nav_layer(LayerOp::OneShot);
+        engine.handle(KeyEvent::down(VK_F13, 0), &no_ctx());
+        engine.handle(KeyEvent::up(VK_F13, 20), &no_ctx());
+        assert_eq!(engine.active_layers(), vec!["nav".to_string()]);
+        let d = engine.handle(KeyEvent::down(VK_H, 40), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_LEFT)]);
+        assert!(engine.active_layers().is_empty());
+        engine.handle(KeyEvent::up(VK_H, 60), &no_ctx());
+        assert!(engine.handle(KeyEvent::down(VK_H, 80), &no_ctx()).actions().is_empty());
+    }
+
+    #[test]
+    fn active_layer_shadows_base_bindings() {
+        let nav: Arc<str> = Arc::from("nav");
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_F13]), BindAction::Layer {
+                op: LayerOp::On,
+                layer: Arc::clone(&nav),
+            }),
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::SwitchLanguage),
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::ToggleCapsLock)
+                .with_layer(Some(nav)),
+        ]);
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 0), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::SwitchLanguage]);
+        engine.handle(KeyEvent::up(VK_CAPITAL, 20), &no_ctx());
+
+        engine.handle(KeyEvent::down(VK_F13, 40), &no_ctx());
+        engine.handle(KeyEvent::up(VK_F13, 60), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 80), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+    }
+
+    #[test]
+    fn switched_tags_turn_bindings_on_and_off() {
+        let gaming: Arc<str> = Arc::from("gaming");
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CAPITAL]), BindAction::SwitchLanguage)
+                .with_tags(vec![Arc::clone(&gaming)]),
+            Binding::new(combo(&[VK_F13]), BindAction::ToggleCapsLock)
+                .with_tags(vec![Arc::clone(&gaming)])
+                .with_enabled(false),
+        ]);
+        let press = |engine: &mut Engine, vk, at| {
+            let actions = engine.handle(KeyEvent::down(vk, at), &no_ctx()).actions();
+            engine.handle(KeyEvent::up(vk, at + 10), &no_ctx());
+            actions
+        };
+        assert_eq!(press(&mut engine, VK_CAPITAL, 0), vec![BindAction::SwitchLanguage]);
+        assert!(press(&mut engine, VK_F13, 20).is_empty());
+
+        assert_eq!(engine.switch_tag("gaming", true), 2);
+        assert_eq!(press(&mut engine, VK_F13, 40), vec![BindAction::ToggleCapsLock]);
+        assert_eq!(engine.switch_tag("gaming", false), 2);
+        assert!(press(&mut engine, VK_CAPITAL, 60).is_empty());
+        assert!(press(&mut engine, VK_F13, 80).is_empty());
+
+        assert_eq!(engine.switch_tag("work", false), 0);
+        assert_eq!(engine.switched_tags(), [(gaming, false)]);
+    }
+
+    #[test]
+    fn numpad_enter_is_told_apart_from_enter() {
+        use crate::data::vk_name::NUMPAD_ENTER;
+        // Config order puts Enter first; the positional key still wins.
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_RETURN]), BindAction::SwitchLanguage),
+            Binding::new(combo(&[NUMPAD_ENTER]), BindAction::ToggleCapsLock),
+        ]);
+        let numpad = KeyEvent::down(VK_RETURN, 0).with_scan(0xE01C);
+        assert_eq!(engine.handle(numpad, &no_ctx()).actions(), vec![BindAction::ToggleCapsLock]);
+        assert!(engine.handle(KeyEvent::up(VK_RETURN, 10).with_scan(0xE01C), &no_ctx()).suppress);
+        let main = KeyEvent::down(VK_RETURN, 20).with_scan(0x1C);
+        assert_eq!(engine.handle(main, &no_ctx()).actions(), vec![BindAction::SwitchLanguage]);
+    }
+
+    #[test]
+    fn positional_keys_match_whatever_the_layout_types() {
+        // SC:0x29 is the key left of 1: ` on US, Ё on Russian, ^ on German.
+        let grave = positional_key(0x29).unwrap();
+        let mut engine = Engine::new(vec![Binding::new(
+            combo(&[VK_CONTROL, grave]),
+            BindAction::ToggleCapsLock,
+        )]);
+        engine.handle(KeyEvent::down(VK_LCONTROL, 0).with_scan(0x1D), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_OEM_5, 10).with_scan(0x29), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+        engine.handle(KeyEvent::up(VK_OEM_5, 20).with_scan(0x29), &no_ctx());
+        // Same VK from another position, or with no scan code, does not match.
+        let d = engine.handle(KeyEvent::down(VK_OEM_5, 30).with_scan(0x2B), &no_ctx());
+        assert!(d.actions().is_empty());
+        engine.handle(KeyEvent::up(VK_OEM_5, 40).with_scan(0x2B), &no_ctx());
+        assert!(engine.handle(KeyEvent::down(VK_OEM_5, 50), &no_ctx()).actions().is_empty());
+    }
+
+    #[test]
+    fn positional_chord_replays_the_vk_it_swallowed() {
+        let j = positional_key(0x24).unwrap();
+        let mut engine = Engine::new(vec![
+            Binding::with_kind(
+                BindingKind::Chord { keys: vec![j, VK_K], window: Duration::from_millis(50) },
+                BindAction::PressKey(VK_ESCAPE),
+            )
+            .with_on_fail(OnFail::Replay),
+        ]);
+        // Whatever VK the layout gives the J position is what goes back.
+        assert!(engine.handle(KeyEvent::down(VK_OEM_1, 0).with_scan(0x24), &no_ctx()).suppress);
+        assert!(engine.handle(KeyEvent::up(VK_OEM_1, 20).with_scan(0x24), &no_ctx()).suppress);
+        let d = engine.tick(50, &no_ctx());
+        assert_eq!(
+            d.injected(),
+            vec![
+                KeyEvent::down(VK_OEM_1, 50).with_scan(0x24),
+                KeyEvent::up(VK_OEM_1, 50).with_scan(0x24),
+            ]
+        );
+
+        // Completed by position: both key-ups are swallowed.
+        engine.handle(KeyEvent::down(VK_OEM_1, 100).with_scan(0x24), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_K, 110).with_scan(0x25), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::PressKey(VK_ESCAPE)]);
+        assert!(engine.handle(KeyEvent::up(VK_OEM_1, 120).with_scan(0x24), &no_ctx()).suppress);
+        assert!(engine.handle(KeyEvent::up(VK_K, 130).with_scan(0x25), &no_ctx()).suppress);
+    }
+
+    #[test]
+    fn sided_modifiers_match_their_own_side_only() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CONTROL, VK_K]), BindAction::SwitchLanguage),
+            Binding::new(combo(&[VK_RCONTROL, VK_K]), BindAction::ToggleCapsLock),
+            Binding::new(combo(&[ANY_WIN, VK_J]), BindAction::DoNothing),
+        ]);
+        let chord = |engine: &mut Engine, modifier, key, at| {
+            engine.handle(KeyEvent::down(modifier, at), &no_ctx());
+            let actions = engine.handle(KeyEvent::down(key, at + 1), &no_ctx()).actions();
+            engine.handle(KeyEvent::up(key, at + 2), &no_ctx());
+            engine.handle(KeyEvent::up(modifier, at + 3), &no_ctx());
+            actions
+        };
+        assert_eq!(chord(&mut engine, VK_RCONTROL, VK_K, 0), vec![BindAction::ToggleCapsLock]);
+        assert_eq!(chord(&mut engine, VK_LCONTROL, VK_K, 10), vec![BindAction::SwitchLanguage]);
+        assert_eq!(chord(&mut engine, VK_RWIN, VK_J, 20), vec![BindAction::DoNothing]);
+        assert_eq!(chord(&mut engine, VK_LWIN, VK_J, 30), vec![BindAction::DoNothing]);
+    }
+
+    #[test]
+    fn alt_gr_is_neither_ctrl_nor_alt() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CONTROL, VK_MENU, VK_E]), BindAction::SwitchLanguage),
+            Binding::new(combo(&[ALT_GR, VK_E]), BindAction::ToggleCapsLock),
+        ]);
+        // What Windows sends for AltGr+E on a German layout.
+        engine.handle(KeyEvent::down(VK_LCONTROL, 0).with_scan(ALT_GR_CTRL_SCAN), &no_ctx());
+        engine.handle(KeyEvent::down(VK_RMENU, 0).with_scan(0xE038), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_E, 10).with_scan(0x12), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+        engine.handle(KeyEvent::up(VK_E, 20).with_scan(0x12), &no_ctx());
+        engine.handle(KeyEvent::up(VK_LCONTROL, 30).with_scan(ALT_GR_CTRL_SCAN), &no_ctx());
+        engine.handle(KeyEvent::up(VK_RMENU, 30).with_scan(0xE038), &no_ctx());
+
+        // Real Ctrl+Alt is still Ctrl+Alt.
+        engine.handle(KeyEvent::down(VK_LCONTROL, 40).with_scan(0x1D), &no_ctx());
+        engine.handle(KeyEvent::down(VK_LMENU, 40).with_scan(0x38), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_E, 50).with_scan(0x12), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::SwitchLanguage]);
+    }
+
+    #[test]
+    fn exact_and_ordered_combos_check_what_else_is_held() {
+        let mut engine = Engine::new(vec![
+            Binding::new(combo(&[VK_CONTROL, VK_K]).with_mode(MatchMode::Exact), BindAction::DoNothing),
+            Binding::new(
+                combo(&[VK_SHIFT, VK_CAPITAL]).with_mode(MatchMode::Ordered),
+                BindAction::ToggleCapsLock,
+            ),
+        ]);
+        engine.handle(KeyEvent::down(VK_LCONTROL, 0), &no_ctx());
+        engine.handle(KeyEvent::down(VK_LSHIFT, 0), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_K, 10), &no_ctx());
+        assert!(d.actions().is_empty() && !d.suppress);
+        engine.handle(KeyEvent::up(VK_K, 20), &no_ctx());
+        engine.handle(KeyEvent::up(VK_LSHIFT, 20), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_K, 30), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::DoNothing]);
+        engine.handle(KeyEvent::up(VK_K, 40), &no_ctx());
+        engine.handle(KeyEvent::up(VK_LCONTROL, 40), &no_ctx());
+
+        engine.handle(KeyEvent::down(VK_CAPITAL, 50), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_RSHIFT, 60), &no_ctx());
+        assert!(d.actions().is_empty());
+        engine.handle(KeyEvent::up(VK_CAPITAL, 70), &no_ctx());
+        let d = engine.handle(KeyEvent::down(VK_CAPITAL, 80), &no_ctx());
+        assert_eq!(d.actions(), vec![BindAction::ToggleCapsLock]);
+    }
+}
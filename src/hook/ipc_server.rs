@@ -7,10 +7,19 @@
 //!
 //! Each connection handles exactly one request/response then closes. This
 //! is the simplest reliable protocol for a CLI driver where commands are
-//! independent — no session state to carry across requests.
+//! independent — no session state to carry across requests. `subscribe` is
+//! the exception: its connection stays open and [`publish`] writes every
+//! reload report to it.
+//!
+//! `settings: (ipc: false)` keeps the listener from starting. Switched off
+//! by a reload once it runs, the listener stays up — the pipe cannot be
+//! closed under a blocking accept — but turns every request away and drops
+//! the subscribers; switched back on, it serves again.
 
 use std::io::{BufRead, BufReader};
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Mutex;
 use std::thread;
 
 use interprocess::local_socket::traits::{ListenerExt, Stream as StreamTrait};
@@ -32,10 +41,42 @@ pub fn set_main_thread_id(id: u32) {
     MAIN_THREAD_ID.store(id, Ordering::Release);
 }
 
-/// Spawn the listener thread. Returns immediately; the thread runs for the
-/// lifetime of the daemon. Bind errors are logged and the daemon continues
-/// without IPC (the user just won't be able to use `swch on/off/exit`).
+/// `settings: (ipc: ...)` as of the last load.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Whether the listener thread is running.
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Applies `settings: (ipc: ...)`: starts the listener if it is wanted and
+/// not yet running. Returns whether a listener is serving requests.
+pub fn set_enabled(hook: &'static KeyboardHook, enabled: bool) -> bool {
+    ENABLED.store(enabled, Ordering::Release);
+    if !enabled {
+        SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        return false;
+    }
+    spawn(hook)
+}
+
+/// One sender per `subscribe` connection; its worker thread writes what
+/// arrives to the pipe.
+static SUBSCRIBERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+
+/// Send `report` (one or more lines) to every subscribed client. A client
+/// that has gone away is noticed and dropped on the report after.
+pub fn publish(report: &str) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner());
+    subscribers.retain(|tx| tx.send(report.to_string()).is_ok());
+}
+
+/// Spawn the listener thread, unless it is already running. Returns
+/// immediately; the thread runs for the lifetime of the daemon. Bind errors
+/// are logged and the daemon continues without IPC (the user just won't be
+/// able to use `swch on/off/exit`).
 pub fn spawn(hook: &'static KeyboardHook) -> bool {
+    if LISTENING.load(Ordering::Acquire) {
+        return true;
+    }
     let name = match ipc::PIPE_NAME.to_ns_name::<GenericNamespaced>() {
         Ok(n) => n,
         Err(e) => {
@@ -79,6 +120,7 @@ pub fn spawn(hook: &'static KeyboardHook) -> bool {
         })
         .expect("spawn ipc-server thread");
 
+    LISTENING.store(true, Ordering::Release);
     true
 }
 
@@ -99,6 +141,34 @@ where
 
     let mut stream = reader.into_inner();
 
+    if !ENABLED.load(Ordering::Acquire) {
+        let _ = stream.write_all(
+            format_err("IPC is switched off by `settings: (ipc: false)` in the config").as_bytes(),
+        );
+        let _ = stream.flush();
+        return;
+    }
+
+    if let Ok(Command::Subscribe) = Command::parse(&line) {
+        let (tx, rx) = channel();
+        SUBSCRIBERS.lock().unwrap_or_else(|e| e.into_inner()).push(tx);
+        let mut message = format_ok("subscribed to config reloads");
+        // Ends when the client closes the pipe and a write fails, which
+        // also drops `rx` so `publish` forgets the sender.
+        loop {
+            if !message.ends_with('\n') {
+                message.push('\n');
+            }
+            if stream.write_all(message.as_bytes()).is_err() || stream.flush().is_err() {
+                return;
+            }
+            match rx.recv() {
+                Ok(next) => message = next,
+                Err(_) => return,
+            }
+        }
+    }
+
     let response = match Command::parse(&line) {
         Ok(cmd) => dispatch(cmd, hook),
         Err(e) => format_err(&e),
@@ -126,9 +196,50 @@ fn dispatch(cmd: Command, hook: &'static KeyboardHook) -> String {
         },
         Command::Status => {
             let state = if hook.is_installed() { "running" } else { "disabled" };
-            let count = hook.bindings().len();
-            format_ok(&format!("{} ({} bindings live)", state, count))
+            let bindings = hook.bindings();
+            let engine = hook.engine();
+            let live = bindings.iter().filter(|b| engine.is_live(b)).count();
+            let off = if live < bindings.len() {
+                format!(", {} off", bindings.len() - live)
+            } else {
+                String::new()
+            };
+            let recording = if hook.is_recording() { ", recording" } else { "" };
+            let layers = engine.active_layers();
+            let layers = if layers.is_empty() {
+                String::new()
+            } else {
+                format!(", layers: {}", layers.join(", "))
+            };
+            let tags: Vec<String> = engine
+                .switched_tags()
+                .iter()
+                .map(|(tag, on)| format!("{}{}", if *on { "+" } else { "-" }, tag))
+                .collect();
+            let tags = if tags.is_empty() {
+                String::new()
+            } else {
+                format!(", tags: {}", tags.join(" "))
+            };
+            format_ok(&format!(
+                "{} ({} bindings live{}{}{}{})",
+                state, live, off, recording, layers, tags
+            ))
         }
+        Command::Subscribe => unreachable!("handled by handle_connection"),
+        Command::EnableTag(tag) => switch_tag(hook, &tag, true),
+        Command::DisableTag(tag) => switch_tag(hook, &tag, false),
+        Command::Record(path) => match hook.start_recording(&path) {
+            Ok(()) => format_ok(&format!("recording key events to {}", path.display())),
+            Err(e) => format_err(&format!("cannot record to {}: {}", path.display(), e)),
+        },
+        Command::RecordStop => match hook.stop_recording() {
+            Some(Ok((path, count))) => {
+                format_ok(&format!("wrote {} events to {}", count, path.display()))
+            }
+            Some(Err(e)) => format_err(&format!("trace write failed: {}", e)),
+            None => format_err("not recording"),
+        },
         Command::Exit => {
             let main_tid = MAIN_THREAD_ID.load(Ordering::Acquire);
             if main_tid == 0 {
@@ -146,3 +257,16 @@ fn dispatch(cmd: Command, hook: &'static KeyboardHook) -> String {
         }
     }
 }
+
+fn switch_tag(hook: &KeyboardHook, tag: &str, on: bool) -> String {
+    match hook.engine().switch_tag(tag, on) {
+        0 => format_err(&format!("no binding is tagged {:?}", tag)),
+        count => format_ok(&format!(
+            "tag {:?} {} ({} binding{})",
+            tag,
+            if on { "enabled" } else { "disabled" },
+            count,
+            if count == 1 { "" } else { "s" }
+        )),
+    }
+}
@@ -1,5 +1,7 @@
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 
 use arc_swap::{ArcSwap, Guard};
 use windows::{
@@ -11,56 +13,49 @@ use windows::{
 };
 
 use crate::data::binding::Binding;
-use crate::hook::chord_state::ChordState;
+use crate::hook::engine::{self, Engine};
 use crate::hook::keyboard_hook_callback;
-use crate::hook::sequence_state::SequenceState;
-use crate::hook::tap_state::TapState;
-
-#[allow(clippy::declare_interior_mutable_const)]
-const ATOMIC_FALSE: AtomicBool = AtomicBool::new(false);
+use crate::hook::trace::{TraceEvent, TraceRecorder};
 
 pub struct KeyboardHook {
-    /// Bindings list. Hot path reads via `ArcSwap::load()` which is
-    /// effectively lock-free (no atomic CAS on the read side). Hot-reload
-    /// updates the list with `update_bindings`, which atomically swaps the
-    /// inner `Arc`. Any sequence currently executing keeps its own `Arc`
-    /// reference until it finishes, so a swap mid-sequence never breaks an
-    /// in-flight macro.
+    /// Bindings list. Readers outside the hook (IPC `status`) go through
+    /// `ArcSwap::load()`, which is effectively lock-free. Hot-reload updates
+    /// the list with `update_bindings`, which atomically swaps the inner
+    /// `Arc` and hands the same `Arc` to the engine. Any sequence currently
+    /// executing keeps its own `Arc` reference until it finishes, so a swap
+    /// mid-sequence never breaks an in-flight macro.
     bindings: ArcSwap<Vec<Binding>>,
     pub hook_handle: AtomicPtr<std::ffi::c_void>,
     pub foreground_hook: AtomicPtr<std::ffi::c_void>,
-    /// Bitmap of keys whose key-down was blocked: the matching key-up must also
-    /// be swallowed. One bit per virtual-key code (0..256), packed into four
-    /// 64-bit atomics. Replaces an earlier single-key field that lost track of
-    /// every-but-the-most-recent block.
-    pub blocked_keys: [AtomicU64; 4],
-    pub active_keys: [AtomicBool; 256],
-    /// Pending Tap / Hold / DoubleTap gestures. See [`TapState`].
-    pub tap_state: TapState,
-    /// Pending simultaneous-chord gestures (`BindingKind::Chord`).
-    pub chord_state: ChordState,
-    /// Pending leader-sequence gestures (`BindingKind::Sequence`).
-    pub sequence_state: SequenceState,
+    /// Matching state: active and blocked keys plus every pending
+    /// Tap / Hold / DoubleTap, chord and sequence gesture. Locked by the hook
+    /// callback, the foreground-change callback and hold wake-up timers;
+    /// never held while an action executes.
+    engine: Mutex<Engine>,
+    /// The engine's [`Engine::needs_foreground`] for the current bindings,
+    /// cached at swap time so the callback can decide whether to capture
+    /// the foreground window without taking the engine lock.
+    needs_foreground: AtomicBool,
+    /// Active trace recording, if any (`swch record`). `recording` mirrors
+    /// `recorder.is_some()` so the callback can skip the lock — and the
+    /// foreground lookups a trace line needs — when nothing is recording.
+    recorder: Mutex<Option<TraceRecorder>>,
+    recording: AtomicBool,
 }
 
 impl KeyboardHook {
     /// Constructs an empty hook. Not `const` because `ArcSwap::from_pointee`
     /// requires a heap allocation; use a `LazyLock` for static initialization.
     pub fn new() -> Self {
+        let bindings = Arc::new(Vec::new());
         Self {
-            bindings: ArcSwap::from_pointee(Vec::new()),
+            bindings: ArcSwap::new(Arc::clone(&bindings)),
             hook_handle: AtomicPtr::new(std::ptr::null_mut()),
             foreground_hook: AtomicPtr::new(std::ptr::null_mut()),
-            blocked_keys: [
-                AtomicU64::new(0),
-                AtomicU64::new(0),
-                AtomicU64::new(0),
-                AtomicU64::new(0),
-            ],
-            active_keys: [ATOMIC_FALSE; 256],
-            tap_state: TapState::new(),
-            chord_state: ChordState::new(),
-            sequence_state: SequenceState::new(),
+            engine: Mutex::new(Engine::with_sorted_bindings(bindings)),
+            needs_foreground: AtomicBool::new(false),
+            recorder: Mutex::new(None),
+            recording: AtomicBool::new(false),
         }
     }
 
@@ -69,8 +64,63 @@ impl KeyboardHook {
     /// key-count descending so more specific combinations are checked first
     /// in the callback.
     pub fn update_bindings(&self, mut bindings: Vec<Binding>) {
-        bindings.sort_by(|a, b| b.kind.key_count().cmp(&a.kind.key_count()));
-        self.bindings.store(Arc::new(bindings));
+        engine::sort_by_specificity(&mut bindings);
+        let bindings = Arc::new(bindings);
+        self.bindings.store(Arc::clone(&bindings));
+        let mut engine = self.engine();
+        engine.set_sorted_bindings(bindings);
+        self.needs_foreground.store(engine.needs_foreground(), Ordering::Release);
+    }
+
+    /// True if some binding gates on the foreground window, so the callback
+    /// must capture it before matching.
+    pub fn needs_foreground(&self) -> bool {
+        self.needs_foreground.load(Ordering::Acquire)
+    }
+
+    /// Lock the engine. A panic while the lock was held leaves the engine
+    /// in a consistent-enough state (worst case a stale pending gesture),
+    /// so poisoning is ignored rather than taking the hook down with it.
+    pub fn engine(&self) -> MutexGuard<'_, Engine> {
+        self.engine.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Start writing every non-injected key event to `path`. Fails if a
+    /// recording is already running or the file cannot be created.
+    pub fn start_recording(&self, path: &Path) -> io::Result<()> {
+        let mut slot = self.recorder.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(active) = slot.as_ref() {
+            return Err(io::Error::other(format!(
+                "already recording to {}",
+                active.path().display()
+            )));
+        }
+        *slot = Some(TraceRecorder::start(path, keyboard_hook_callback::now_ms())?);
+        self.recording.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Stop the running recording, flush it, and report where it went and
+    /// how many events it holds. `None` if nothing was recording.
+    pub fn stop_recording(&self) -> Option<io::Result<(PathBuf, usize)>> {
+        let recorder = {
+            let mut slot = self.recorder.lock().unwrap_or_else(|e| e.into_inner());
+            self.recording.store(false, Ordering::Release);
+            slot.take()?
+        };
+        let path = recorder.path().to_path_buf();
+        Some(recorder.finish().map(|count| (path, count)))
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Acquire)
+    }
+
+    /// Append one event to the running recording, if any.
+    pub(crate) fn record(&self, event: TraceEvent) {
+        if let Some(recorder) = self.recorder.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            recorder.record(event);
+        }
     }
 
     /// Snapshot of the current binding list. The returned `Guard` derefs to
@@ -193,10 +243,10 @@ impl Drop for KeyboardHook {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::windows_actions::BindAction;
+    use crate::data::action::BindAction;
     use crate::data::binding::Binding;
     use crate::data::key_combination::KeyCombination;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use crate::data::vk::*;
 
     fn three_bindings() -> Vec<Binding> {
         vec![
@@ -0,0 +1,185 @@
+//! Which binding layers are active right now.
+//!
+//! Bindings from the top-level `bindings:` list belong to no layer and are
+//! always live. Bindings under `layers: { name: [...] }` are live only while
+//! their layer is on the stack. The stack is ordered: when several layers
+//! are active, the most recently activated one is matched first, so it
+//! shadows the layers below it and the base bindings.
+//!
+//! Pure state, driven by the engine: `Layer` actions are applied here the
+//! moment they fire, key releases end momentary layers, and key presses
+//! use up one-shot layers.
+
+use std::sync::Arc;
+
+use crate::data::action::LayerOp;
+use crate::data::vk::Vk;
+
+#[derive(Default)]
+pub struct LayerStack {
+    /// Active layers, bottom to top. A name appears at most once.
+    active: Vec<Arc<str>>,
+    /// Momentary layers and the key holding each of them.
+    momentary: Vec<(u16, Arc<str>)>,
+    /// One-shot layers waiting for their key press.
+    one_shot: Vec<Arc<str>>,
+}
+
+impl LayerStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_active(&self, layer: &str) -> bool {
+        self.active.iter().any(|l| &**l == layer)
+    }
+
+    /// Active layers, top of the stack first.
+    pub fn active_top_down(&self) -> impl Iterator<Item = &Arc<str>> {
+        self.active.iter().rev()
+    }
+
+    /// Apply a `Layer` action fired by `key`. `key_held` says whether that
+    /// key is still down: a momentary layer fired on release (a `Tap`
+    /// trigger, say) has nothing to hold it and acts as a one-shot instead.
+    pub fn apply(&mut self, op: LayerOp, layer: &Arc<str>, key: Vk, key_held: bool) {
+        match op {
+            LayerOp::On => self.push(layer),
+            LayerOp::Off => self.remove(layer),
+            LayerOp::Toggle if self.is_active(layer) => self.remove(layer),
+            LayerOp::Toggle => self.push(layer),
+            LayerOp::Momentary if key_held => {
+                self.push(layer);
+                self.momentary.push((key.0, Arc::clone(layer)));
+            }
+            LayerOp::Momentary | LayerOp::OneShot => {
+                self.push(layer);
+                if !self.one_shot.contains(layer) {
+                    self.one_shot.push(Arc::clone(layer));
+                }
+            }
+        }
+    }
+
+    /// `key` was released: drop the momentary layers it was holding, unless
+    /// another held key is holding the same layer.
+    pub fn release(&mut self, key: Vk) {
+        let (released, kept): (Vec<_>, Vec<_>) =
+            self.momentary.drain(..).partition(|(k, _)| *k == key.0);
+        self.momentary = kept;
+        for (_, layer) in released {
+            if !self.momentary.iter().any(|(_, l)| *l == layer) {
+                self.active.retain(|l| *l != layer);
+            }
+        }
+    }
+
+    /// The keys holding momentary layers.
+    pub fn momentary_keys(&self) -> Vec<Vk> {
+        let mut keys: Vec<Vk> = Vec::new();
+        for &(key, _) in &self.momentary {
+            if !keys.contains(&Vk(key)) {
+                keys.push(Vk(key));
+            }
+        }
+        keys
+    }
+
+    /// A key press is about to be matched. Returns the one-shot layers it
+    /// uses up; they stay active for this press and the caller ends them
+    /// with [`LayerStack::finish_one_shots`] afterwards.
+    pub fn take_one_shots(&mut self) -> Vec<Arc<str>> {
+        std::mem::take(&mut self.one_shot)
+    }
+
+    /// End one-shot layers taken by [`LayerStack::take_one_shots`], except
+    /// those the press itself turned on again in the meantime.
+    pub fn finish_one_shots(&mut self, used: Vec<Arc<str>>) {
+        for layer in used {
+            let rearmed = self.one_shot.contains(&layer)
+                || self.momentary.iter().any(|(_, l)| *l == layer);
+            if !rearmed {
+                self.active.retain(|l| *l != layer);
+            }
+        }
+    }
+
+    fn push(&mut self, layer: &Arc<str>) {
+        self.active.retain(|l| l != layer);
+        self.active.push(Arc::clone(layer));
+    }
+
+    fn remove(&mut self, layer: &Arc<str>) {
+        self.active.retain(|l| l != layer);
+        self.momentary.retain(|(_, l)| l != layer);
+        self.one_shot.retain(|l| l != layer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::vk::*;
+
+    fn name(s: &str) -> Arc<str> {
+        Arc::from(s)
+    }
+
+    #[test]
+    fn on_off_toggle() {
+        let mut stack = LayerStack::new();
+        let nav = name("nav");
+        stack.apply(LayerOp::On, &nav, VK_F13, true);
+        assert!(stack.is_active("nav"));
+        stack.apply(LayerOp::Toggle, &nav, VK_F13, true);
+        assert!(!stack.is_active("nav"));
+        stack.apply(LayerOp::Toggle, &nav, VK_F13, true);
+        assert!(stack.is_active("nav"));
+        stack.apply(LayerOp::Off, &nav, VK_F13, true);
+        assert!(!stack.is_active("nav"));
+    }
+
+    #[test]
+    fn latest_layer_is_on_top() {
+        let mut stack = LayerStack::new();
+        stack.apply(LayerOp::On, &name("nav"), VK_F13, true);
+        stack.apply(LayerOp::On, &name("num"), VK_F14, true);
+        stack.apply(LayerOp::On, &name("nav"), VK_F13, true);
+        let order: Vec<&str> = stack.active_top_down().map(|l| &**l).collect();
+        assert_eq!(order, vec!["nav", "num"]);
+    }
+
+    #[test]
+    fn momentary_ends_when_last_holding_key_is_released() {
+        let mut stack = LayerStack::new();
+        let nav = name("nav");
+        stack.apply(LayerOp::Momentary, &nav, VK_F13, true);
+        stack.apply(LayerOp::Momentary, &nav, VK_F14, true);
+        stack.release(VK_F13);
+        assert!(stack.is_active("nav"));
+        stack.release(VK_F14);
+        assert!(!stack.is_active("nav"));
+    }
+
+    #[test]
+    fn one_shot_lasts_for_one_press() {
+        let mut stack = LayerStack::new();
+        let nav = name("nav");
+        stack.apply(LayerOp::OneShot, &nav, VK_F13, true);
+        let used = stack.take_one_shots();
+        assert!(stack.is_active("nav"));
+        stack.finish_one_shots(used);
+        assert!(!stack.is_active("nav"));
+    }
+
+    #[test]
+    fn momentary_fired_on_release_acts_as_one_shot() {
+        let mut stack = LayerStack::new();
+        stack.apply(LayerOp::Momentary, &name("nav"), VK_F13, false);
+        stack.release(VK_F13);
+        assert!(stack.is_active("nav"));
+        let used = stack.take_one_shots();
+        stack.finish_one_shots(used);
+        assert!(!stack.is_active("nav"));
+    }
+}
@@ -1,7 +1,16 @@
 pub mod chord_state;
+#[cfg(windows)]
 pub mod config_watcher;
+pub mod engine;
+#[cfg(windows)]
 pub mod ipc_server;
+#[cfg(windows)]
 pub mod keyboard_hook;
+#[cfg(windows)]
 pub mod keyboard_hook_callback;
+pub mod layer_stack;
+pub mod mod_tap_state;
+pub mod reload_diff;
 pub mod sequence_state;
-pub mod tap_state;
\ No newline at end of file
+pub mod tap_state;
+pub mod trace;
@@ -0,0 +1,248 @@
+//! Undecided / held state for `Trigger::ModTap` keys.
+//!
+//! A mod-tap key goes down suppressed and *undecided*. From then on every
+//! other key event is held back in a buffer until the key resolves:
+//!
+//! * **tap** — the mod-tap key is released first (before its term). The
+//!   caller fires the tap action, then delivers the buffered events.
+//! * **hold** — the term elapses, or the [`ModTapFlavor`] says another key
+//!   settled it early. The caller presses the modifier for real, then
+//!   delivers the buffered events, so `CapsLock`+`C` reaches the app as
+//!   `Ctrl`+`C`. The modifier stays down until the mod-tap key is released.
+//!
+//! Only one key is undecided at a time. A second mod-tap key pressed while
+//! the first is pending is just another buffered event; it is armed in turn
+//! when the buffer is delivered. Like the other gesture state machines this
+//! module never reads the clock: `now` comes from the engine.
+
+use std::collections::HashMap;
+
+use crate::data::action::BindAction;
+use crate::data::trigger::ModTapFlavor;
+use crate::data::vk::Vk;
+use crate::hook::engine::{KeyEvent, KeyEventKind};
+
+struct PendingModTap {
+    key: u16,
+    tap: BindAction,
+    hold: Vk,
+    flavor: ModTapFlavor,
+    /// Engine-clock millisecond at which the key resolves as hold.
+    deadline: u64,
+    /// Events that arrived while undecided, in arrival order.
+    buffered: Vec<KeyEvent>,
+}
+
+/// How an event interacted with the mod-tap state.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ModTapStep {
+    /// Not a mod-tap matter: run the normal binding matching.
+    Pass,
+    /// Swallowed — buffered behind an undecided key, or an auto-repeat of a
+    /// mod-tap key.
+    Consumed,
+    /// The undecided key was released in time. Fire `action`, then deliver
+    /// `buffered`. The release itself is consumed.
+    Tap {
+        action: BindAction,
+        buffered: Vec<KeyEvent>,
+    },
+    /// The undecided key resolved as hold because of this event. Press
+    /// `modifier`, deliver `buffered`, then handle this event normally.
+    Hold {
+        modifier: Vk,
+        buffered: Vec<KeyEvent>,
+    },
+    /// A held mod-tap key was released: release `modifier`. The key-up
+    /// itself is consumed.
+    Release(Vk),
+}
+
+#[derive(Default)]
+pub struct ModTapState {
+    pending: Option<PendingModTap>,
+    /// Mod-tap keys resolved as hold, mapped to the modifier each keeps
+    /// down until its release.
+    held: HashMap<u16, Vk>,
+}
+
+impl ModTapState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// True while a mod-tap key is down and neither tap nor hold yet.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Start an undecided mod-tap on `vk`. Returns the deadline at which
+    /// [`ModTapState::tick`] resolves it as hold. Callers only arm when
+    /// [`ModTapState::on_event`] returned `Pass`, so nothing is pending.
+    pub fn arm(
+        &mut self,
+        vk: Vk,
+        tap: BindAction,
+        hold: Vk,
+        flavor: ModTapFlavor,
+        term_ms: u64,
+        now: u64,
+    ) -> u64 {
+        let deadline = now + term_ms;
+        self.pending = Some(PendingModTap {
+            key: vk.0,
+            tap,
+            hold,
+            flavor,
+            deadline,
+            buffered: Vec::new(),
+        });
+        deadline
+    }
+
+    /// Resolve the undecided key as hold if its term has run out. Returns
+    /// the modifier to press and the events to deliver after it.
+    pub fn tick(&mut self, now: u64) -> Option<(Vk, Vec<KeyEvent>)> {
+        if self.pending.as_ref().is_some_and(|p| p.deadline <= now) {
+            return Some(self.resolve_hold());
+        }
+        None
+    }
+
+    /// Route one key event through the mod-tap state. Callers must
+    /// [`ModTapState::tick`] first so a key held past its term is already
+    /// a hold when its release arrives.
+    pub fn on_event(&mut self, event: KeyEvent) -> ModTapStep {
+        if let Some(pending) = self.pending.as_mut() {
+            if event.vk.0 == pending.key {
+                return match event.kind {
+                    KeyEventKind::Down => ModTapStep::Consumed,
+                    KeyEventKind::Up => {
+                        let pending = self.pending.take().expect("checked above");
+                        ModTapStep::Tap {
+                            action: pending.tap,
+                            buffered: pending.buffered,
+                        }
+                    }
+                };
+            }
+            let decides_hold = match (pending.flavor, event.kind) {
+                (ModTapFlavor::HoldOnOtherKeyPress, KeyEventKind::Down) => true,
+                (ModTapFlavor::PermissiveHold, KeyEventKind::Up) => pending
+                    .buffered
+                    .iter()
+                    .any(|e| e.vk == event.vk && e.kind == KeyEventKind::Down),
+                _ => false,
+            };
+            if !decides_hold {
+                pending.buffered.push(event);
+                return ModTapStep::Consumed;
+            }
+            let (modifier, buffered) = self.resolve_hold();
+            return ModTapStep::Hold { modifier, buffered };
+        }
+
+        if let Some(&modifier) = self.held.get(&event.vk.0) {
+            return match event.kind {
+                KeyEventKind::Down => ModTapStep::Consumed,
+                KeyEventKind::Up => {
+                    self.held.remove(&event.vk.0);
+                    ModTapStep::Release(modifier)
+                }
+            };
+        }
+
+        ModTapStep::Pass
+    }
+
+    fn resolve_hold(&mut self) -> (Vk, Vec<KeyEvent>) {
+        let pending = self.pending.take().expect("resolve_hold without a pending mod-tap");
+        self.held.insert(pending.key, pending.hold);
+        (pending.hold, pending.buffered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::vk::*;
+
+    fn armed(flavor: ModTapFlavor) -> ModTapState {
+        let mut state = ModTapState::new();
+        let deadline = state.arm(
+            VK_CAPITAL,
+            BindAction::PressKey(VK_ESCAPE),
+            VK_CONTROL,
+            flavor,
+            200,
+            0,
+        );
+        assert_eq!(deadline, 200);
+        state
+    }
+
+    #[test]
+    fn release_before_term_is_a_tap_with_buffered_keys() {
+        let mut state = armed(ModTapFlavor::TapPreferred);
+        assert_eq!(state.on_event(KeyEvent::down(VK_A, 10)), ModTapStep::Consumed);
+        assert_eq!(
+            state.on_event(KeyEvent::up(VK_CAPITAL, 50)),
+            ModTapStep::Tap {
+                action: BindAction::PressKey(VK_ESCAPE),
+                buffered: vec![KeyEvent::down(VK_A, 10)],
+            }
+        );
+        assert!(!state.is_pending());
+        assert_eq!(state.on_event(KeyEvent::up(VK_A, 60)), ModTapStep::Pass);
+    }
+
+    #[test]
+    fn term_resolves_hold_and_release_lets_go_of_the_modifier() {
+        let mut state = armed(ModTapFlavor::TapPreferred);
+        assert_eq!(state.on_event(KeyEvent::down(VK_CAPITAL, 30)), ModTapStep::Consumed);
+        assert!(state.tick(199).is_none());
+        assert_eq!(state.tick(200), Some((VK_CONTROL, Vec::new())));
+        // Auto-repeat while held stays swallowed.
+        assert_eq!(state.on_event(KeyEvent::down(VK_CAPITAL, 230)), ModTapStep::Consumed);
+        assert_eq!(
+            state.on_event(KeyEvent::up(VK_CAPITAL, 400)),
+            ModTapStep::Release(VK_CONTROL)
+        );
+        assert_eq!(state.on_event(KeyEvent::down(VK_CAPITAL, 500)), ModTapStep::Pass);
+    }
+
+    #[test]
+    fn hold_on_other_key_press_decides_on_key_down() {
+        let mut state = armed(ModTapFlavor::HoldOnOtherKeyPress);
+        assert_eq!(
+            state.on_event(KeyEvent::down(VK_C, 20)),
+            ModTapStep::Hold { modifier: VK_CONTROL, buffered: Vec::new() }
+        );
+    }
+
+    #[test]
+    fn permissive_hold_needs_a_nested_tap() {
+        let mut state = armed(ModTapFlavor::PermissiveHold);
+        // Release of a key pressed before the mod-tap doesn't count.
+        assert_eq!(state.on_event(KeyEvent::up(VK_S, 5)), ModTapStep::Consumed);
+        assert_eq!(state.on_event(KeyEvent::down(VK_C, 20)), ModTapStep::Consumed);
+        assert_eq!(
+            state.on_event(KeyEvent::up(VK_C, 60)),
+            ModTapStep::Hold {
+                modifier: VK_CONTROL,
+                buffered: vec![KeyEvent::up(VK_S, 5), KeyEvent::down(VK_C, 20)],
+            }
+        );
+    }
+
+    #[test]
+    fn tap_preferred_ignores_other_keys_until_term() {
+        let mut state = armed(ModTapFlavor::TapPreferred);
+        assert_eq!(state.on_event(KeyEvent::down(VK_C, 20)), ModTapStep::Consumed);
+        assert_eq!(state.on_event(KeyEvent::up(VK_C, 60)), ModTapStep::Consumed);
+        assert!(state.is_pending());
+        let (modifier, buffered) = state.tick(200).expect("term elapsed");
+        assert_eq!(modifier, VK_CONTROL);
+        assert_eq!(buffered, vec![KeyEvent::down(VK_C, 20), KeyEvent::up(VK_C, 60)]);
+    }
+}
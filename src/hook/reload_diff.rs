@@ -0,0 +1,152 @@
+//! What a hot-reload changed: the old and new binding lists compared, for
+//! the `config reloaded` log line and `swch watch` subscribers.
+//!
+//! Bindings are paired by `id:` when they have one, else by what triggers
+//! them — layer, keys, `when:` and the kind of trigger. Several bindings on
+//! the same trigger pair up in order. A pair that differs in
+//! any other field is reported as changed; what is left over on either side
+//! was added or removed. Auto-blockers follow their binding and are not
+//! reported.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::data::binding::Binding;
+
+/// Bindings a reload added, removed and changed, each shown as in the
+/// startup listing.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReloadDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Old and new form of each changed binding.
+    pub changed: Vec<(String, String)>,
+}
+
+impl ReloadDiff {
+    pub fn between(old: &[Binding], new: &[Binding]) -> Self {
+        // Indices into `old` not yet paired, by pairing key, in order.
+        let mut unpaired: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (index, binding) in old.iter().enumerate().filter(|(_, b)| !b.is_auto_blocker) {
+            unpaired.entry(pairing_key(binding)).or_default().push(index);
+        }
+
+        let mut diff = Self::default();
+        for binding in new.iter().filter(|b| !b.is_auto_blocker) {
+            let same = unpaired
+                .get_mut(&pairing_key(binding))
+                .filter(|indices| !indices.is_empty())
+                .map(|indices| &old[indices.remove(0)]);
+            match same {
+                None => diff.added.push(binding.to_string()),
+                // `BindAction` compares sequences by pointer, and a reload
+                // rebuilds all of them; the debug form compares contents.
+                Some(before) if format!("{:?}", before) != format!("{:?}", binding) => {
+                    diff.changed.push((before.to_string(), binding.to_string()));
+                }
+                Some(_) => {}
+            }
+        }
+
+        let mut removed: Vec<usize> = unpaired.into_values().flatten().collect();
+        removed.sort_unstable();
+        diff.removed = removed.into_iter().map(|index| old[index].to_string()).collect();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// `2 added, 1 removed, 0 changed`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} added, {} removed, {} changed",
+            self.added.len(),
+            self.removed.len(),
+            self.changed.len()
+        )
+    }
+}
+
+/// One line per binding: `+ added`, `- removed`, and `~ new` followed by
+/// `  was old` for a changed one.
+impl fmt::Display for ReloadDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for binding in &self.removed {
+            writeln!(f, "- {}", binding)?;
+        }
+        for binding in &self.added {
+            writeln!(f, "+ {}", binding)?;
+        }
+        for (before, after) in &self.changed {
+            writeln!(f, "~ {}", after)?;
+            writeln!(f, "  was {}", before)?;
+        }
+        Ok(())
+    }
+}
+
+/// What pairs a binding with its other version: the `id:`, or the layer,
+/// keys, condition and trigger variant (a `Tap(200)` that became `Tap(150)`
+/// changed; a `Tap` and a `Hold` on one key are two bindings).
+fn pairing_key(binding: &Binding) -> String {
+    match &binding.id {
+        Some(id) => format!("id {}", id),
+        None => format!(
+            "{:?} {:?} {:?} {:?}",
+            binding.layer,
+            binding.kind,
+            binding.condition,
+            std::mem::discriminant(&binding.trigger)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::action::BindAction;
+    use crate::data::key_combination::KeyCombination;
+    use crate::data::trigger::Trigger;
+    use crate::data::vk::*;
+
+    fn bind(vk: Vk, action: BindAction) -> Binding {
+        Binding::new(KeyCombination::new(vk), action)
+    }
+
+    #[test]
+    fn pairs_by_trigger_and_reports_what_moved() {
+        let old = vec![
+            bind(VK_F13, BindAction::SwitchLanguage),
+            bind(VK_CAPITAL, BindAction::ToggleCapsLock).with_trigger(Trigger::Tap(200)),
+            bind(VK_CAPITAL, BindAction::DoNothing).with_trigger(Trigger::Hold(200)),
+            bind(VK_F14, BindAction::DoNothing),
+        ];
+        let new = vec![
+            bind(VK_F13, BindAction::SwitchLanguage),
+            bind(VK_CAPITAL, BindAction::PressKey(VK_ESCAPE)).with_trigger(Trigger::Hold(200)),
+            bind(VK_CAPITAL, BindAction::ToggleCapsLock).with_trigger(Trigger::Tap(200)),
+            bind(VK_F15, BindAction::DoNothing),
+        ];
+        let diff = ReloadDiff::between(&old, &new);
+        assert_eq!(diff.removed, [old[3].to_string()]);
+        assert_eq!(diff.added, [new[3].to_string()]);
+        assert_eq!(diff.changed, [(old[2].to_string(), new[1].to_string())]);
+        assert_eq!(diff.summary(), "1 added, 1 removed, 1 changed");
+        assert_eq!(diff.to_string().lines().count(), 4);
+        assert!(ReloadDiff::between(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn ids_pair_bindings_whose_keys_changed() {
+        let old = vec![bind(VK_F13, BindAction::DoNothing).with_id(Some("x".into()))];
+        let new = vec![
+            bind(VK_F14, BindAction::DoNothing).with_id(Some("x".into())),
+            Binding::new_auto_blocker(KeyCombination::new(VK_F15)),
+        ];
+        let diff = ReloadDiff::between(&old, &new);
+        assert_eq!(diff.changed, [(old[0].to_string(), new[0].to_string())]);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+    }
+}
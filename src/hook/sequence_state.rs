@@ -2,47 +2,65 @@
 //! flow. Steps are pressed in order, each step is itself a combo, and no
 //! more than `max_gap` may elapse between consecutive steps.
 //!
-//! Hot path calls `handle_keydown` once per Sequence-kind binding per
-//! non-auto-repeat key-down event. The state machine identifies a pending
-//! sequence by its steps shape; multiple sequences with the same shape but
-//! different actions share the same pending slot (first binding's action
-//! wins on completion).
+//! The engine calls `handle_keydown` once per Sequence-kind binding per
+//! non-auto-repeat key-down event, passing the engine-clock time `now`.
+//! The state machine identifies a pending sequence by its steps shape;
+//! multiple sequences with the same shape but different actions share the
+//! same pending slot (first binding's action wins on completion).
 //!
 //! **Suppression rules** mirror chord_state: every key-down that advances
 //! a sequence is suppressed; the key-up of the final completing key is
-//! also suppressed (via the blocked-keys bitmap). If a sequence times out
-//! mid-flight, the suppressed key-downs are lost — no replay. Don't bind
-//! sequences to letters that appear in normal typing.
+//! also suppressed (via the engine's blocked-keys bitmap). If a sequence
+//! times out or is broken by a wrong key mid-flight, the binding's
+//! [`OnFail`] decides the fate of the suppressed key-downs — lost with the
+//! default `Drop` (so don't bind letters that appear in normal typing),
+//! handed back for re-injection with `Replay`.
 
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
-
-use crate::core::windows_actions::BindAction;
+use crate::data::action::BindAction;
 use crate::data::key_combination::KeyCombination;
+use crate::data::on_fail::OnFail;
+use crate::data::vk::Vk;
+use crate::hook::engine::FailedGesture;
 
 struct PendingSequence {
     steps: Vec<KeyCombination>,
     next_step: usize,
+    /// VKs whose key-downs advanced the sequence, in order — what `Replay`
+    /// re-injects.
+    pressed: Vec<u16>,
     action: BindAction,
-    expires_at: Instant,
+    on_fail: OnFail,
+    /// Engine-clock millisecond after which the entry is stale.
+    expires_at: u64,
+}
+
+impl PendingSequence {
+    fn into_failed(self) -> Option<FailedGesture> {
+        (!self.on_fail.is_drop()).then_some(FailedGesture {
+            keys: self.pressed,
+            on_fail: self.on_fail,
+        })
+    }
 }
 
 #[derive(Default)]
 pub struct SequenceState {
-    pending: Arc<Mutex<Vec<PendingSequence>>>,
+    pending: Vec<PendingSequence>,
 }
 
+#[derive(Debug)]
 pub enum SequenceOutcome {
     /// This key-down does not match the binding's first (or current) step.
     NotMatching,
-    /// Sequence advanced; key-down should be suppressed.
-    Advanced,
-    /// Sequence completed; action has been executed. The caller should
-    /// suppress this key-down AND mark the listed VKs in `blocked_keys` so
-    /// their key-ups are suppressed too.
-    Completed { last_step_keys: Vec<u16> },
+    /// Sequence advanced; key-down should be suppressed. `expires_at` is
+    /// when the sequence gives up waiting for the next step.
+    Advanced { expires_at: u64 },
+    /// Sequence completed. The caller should fire `action`, suppress this
+    /// key-down AND mark the listed VKs as blocked so their key-ups are
+    /// suppressed too.
+    Completed { last_step_keys: Vec<u16>, action: BindAction },
 }
 
 impl SequenceState {
@@ -50,49 +68,69 @@ impl SequenceState {
         Self::default()
     }
 
+    /// Remove every sequence whose gap has run out by `now`, returning the
+    /// ones whose `on_fail` wants handling.
+    pub fn expire(&mut self, now: u64) -> Vec<FailedGesture> {
+        let (expired, live): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|p| p.expires_at <= now);
+        self.pending = live;
+        expired.into_iter().filter_map(PendingSequence::into_failed).collect()
+    }
+
     /// Feed one Sequence binding's spec against the current input state.
-    /// Called once per binding per (non-auto-repeat) key-down event.
+    /// Called once per binding per (non-auto-repeat) key-down event; `vk`
+    /// is the key that just went down, `active_keys` everything held.
+    /// Failures (a wrong key arriving mid-sequence) are appended to
+    /// `failed` for non-`Drop` bindings.
+    #[allow(clippy::too_many_arguments)]
     pub fn handle_keydown(
-        &self,
-        active_keys: &[VIRTUAL_KEY],
+        &mut self,
+        vk: Vk,
+        active_keys: &[Vk],
         action: &BindAction,
+        on_fail: &OnFail,
         steps: &[KeyCombination],
         max_gap: Duration,
+        now: u64,
+        failed: &mut Vec<FailedGesture>,
     ) -> SequenceOutcome {
         if steps.is_empty() {
             return SequenceOutcome::NotMatching;
         }
 
-        let now = Instant::now();
-        let mut pending = self.pending.lock().expect("sequence mutex poisoned");
+        let gap_ms = max_gap.as_millis() as u64;
 
         // Drop expired entries first — keeps the vec short and lets a fresh
         // start match cleanly even if a stale pending entry was hanging.
-        pending.retain(|p| p.expires_at > now);
+        self.pending.retain(|p| p.expires_at > now);
 
         // Look for an existing pending entry for this sequence shape.
-        if let Some(idx) = pending.iter().position(|p| p.steps == steps) {
-            let next_step_idx = pending[idx].next_step;
+        if let Some(idx) = self.pending.iter().position(|p| p.steps == steps) {
+            let next_step_idx = self.pending[idx].next_step;
             if next_step_idx < steps.len() && steps[next_step_idx].matches(active_keys) {
-                pending[idx].next_step += 1;
-                pending[idx].expires_at = now + max_gap;
+                self.pending[idx].next_step += 1;
+                self.pending[idx].expires_at = now + gap_ms;
+                self.pending[idx].pressed.push(vk.0);
 
-                if pending[idx].next_step == steps.len() {
-                    let act = pending[idx].action.clone();
+                if self.pending[idx].next_step == steps.len() {
+                    let entry = self.pending.remove(idx);
                     let last_keys: Vec<u16> =
                         steps[steps.len() - 1].keys.iter().map(|k| k.0).collect();
-                    pending.remove(idx);
-                    drop(pending);
-                    act.execute();
-                    return SequenceOutcome::Completed { last_step_keys: last_keys };
+                    return SequenceOutcome::Completed {
+                        last_step_keys: last_keys,
+                        action: entry.action,
+                    };
                 }
-                return SequenceOutcome::Advanced;
+                return SequenceOutcome::Advanced {
+                    expires_at: self.pending[idx].expires_at,
+                };
             }
             // Mismatch — abort this pending. We do NOT immediately try to
             // restart at step 0: that would silently classify a wrong key
             // as the start of a fresh attempt, which is more confusing
             // than just resetting and letting the user try again.
-            pending.remove(idx);
+            failed.extend(self.pending.remove(idx).into_failed());
         }
 
         // Fresh start: does this key-down match step 0?
@@ -100,20 +138,23 @@ impl SequenceState {
             // Length-1 sequence (degenerate; schema enforces ≥ 2 anyway):
             // fire immediately rather than hanging a useless pending entry.
             if steps.len() == 1 {
-                let act = action.clone();
                 let last_keys: Vec<u16> = steps[0].keys.iter().map(|k| k.0).collect();
-                drop(pending);
-                act.execute();
-                return SequenceOutcome::Completed { last_step_keys: last_keys };
+                return SequenceOutcome::Completed {
+                    last_step_keys: last_keys,
+                    action: action.clone(),
+                };
             }
 
-            pending.push(PendingSequence {
+            let expires_at = now + gap_ms;
+            self.pending.push(PendingSequence {
                 steps: steps.to_vec(),
                 next_step: 1,
+                pressed: vec![vk.0],
                 action: action.clone(),
-                expires_at: now + max_gap,
+                on_fail: on_fail.clone(),
+                expires_at,
             });
-            return SequenceOutcome::Advanced;
+            return SequenceOutcome::Advanced { expires_at };
         }
 
         SequenceOutcome::NotMatching
@@ -123,60 +164,59 @@ impl SequenceState {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use crate::data::vk::*;
 
-    fn combo(keys: &[VIRTUAL_KEY]) -> KeyCombination {
+    const GAP: Duration = Duration::from_millis(500);
+
+    fn combo(keys: &[Vk]) -> KeyCombination {
         KeyCombination::from_keys(keys.to_vec())
     }
 
     #[test]
     fn first_step_match_advances() {
-        let state = SequenceState::new();
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
         let steps = vec![combo(&[VK_G]), combo(&[VK_G])];
-        let outcome = state.handle_keydown(
-            &[VK_G],
-            &BindAction::DoNothing,
-            &steps,
-            Duration::from_millis(10_000),
-        );
-        assert!(matches!(outcome, SequenceOutcome::Advanced));
+        let outcome = state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 0, &mut failed);
+        assert!(matches!(outcome, SequenceOutcome::Advanced { .. }));
     }
 
     #[test]
     fn second_step_completes_sequence() {
-        let state = SequenceState::new();
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
         let steps = vec![combo(&[VK_G]), combo(&[VK_G])];
-        state.handle_keydown(&[VK_G], &BindAction::DoNothing, &steps,
-                              Duration::from_millis(10_000));
+        state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 0, &mut failed);
         // simulating release between presses — active_keys empty in real
         // flow, but second key-down delivers active=[G] again.
-        let outcome = state.handle_keydown(
-            &[VK_G],
-            &BindAction::DoNothing,
-            &steps,
-            Duration::from_millis(10_000),
-        );
+        let outcome = state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 200, &mut failed);
         match outcome {
-            SequenceOutcome::Completed { last_step_keys } => {
+            SequenceOutcome::Completed { last_step_keys, .. } => {
                 assert_eq!(last_step_keys, vec![VK_G.0]);
             }
             _ => panic!("expected completion"),
         }
     }
 
+    #[test]
+    fn second_step_after_gap_restarts() {
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
+        let steps = vec![combo(&[VK_G]), combo(&[VK_G])];
+        state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 0, &mut failed);
+        // Past the gap the first entry is stale; this press is a fresh step 0.
+        let outcome = state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 600, &mut failed);
+        assert!(matches!(outcome, SequenceOutcome::Advanced { .. }));
+    }
+
     #[test]
     fn mismatched_intermediate_aborts() {
-        let state = SequenceState::new();
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
         let steps = vec![combo(&[VK_G]), combo(&[VK_H])];
-        state.handle_keydown(&[VK_G], &BindAction::DoNothing, &steps,
-                              Duration::from_millis(10_000));
+        state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 0, &mut failed);
         // Wrong key: expecting H, got A.
-        let outcome = state.handle_keydown(
-            &[VK_A],
-            &BindAction::DoNothing,
-            &steps,
-            Duration::from_millis(10_000),
-        );
+        let outcome = state.handle_keydown(VK_A, &[VK_A], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 100, &mut failed);
         // No match against this sequence; pending dropped.
         assert!(matches!(outcome, SequenceOutcome::NotMatching));
     }
@@ -185,26 +225,64 @@ mod tests {
     fn ctrl_x_ctrl_s_sequence() {
         // Emacs-style "Ctrl+X Ctrl+S" save. Both steps use modifier; modifier
         // stays held across the gap.
-        let state = SequenceState::new();
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
         let steps = vec![
             KeyCombination::from_keys(vec![VK_CONTROL, VK_X]),
             KeyCombination::from_keys(vec![VK_CONTROL, VK_S]),
         ];
 
         // First step: active=[Ctrl, X]
-        let o1 = state.handle_keydown(&[VK_CONTROL, VK_X], &BindAction::DoNothing,
-                                       &steps, Duration::from_millis(10_000));
-        assert!(matches!(o1, SequenceOutcome::Advanced));
+        let o1 = state.handle_keydown(VK_X, &[VK_CONTROL, VK_X], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 0, &mut failed);
+        assert!(matches!(o1, SequenceOutcome::Advanced { .. }));
 
         // Second step: active=[Ctrl, S]
-        let o2 = state.handle_keydown(&[VK_CONTROL, VK_S], &BindAction::DoNothing,
-                                       &steps, Duration::from_millis(10_000));
+        let o2 = state.handle_keydown(VK_S, &[VK_CONTROL, VK_S], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 300, &mut failed);
         match o2 {
-            SequenceOutcome::Completed { last_step_keys } => {
+            SequenceOutcome::Completed { last_step_keys, .. } => {
                 assert!(last_step_keys.contains(&VK_CONTROL.0));
                 assert!(last_step_keys.contains(&VK_S.0));
             }
             _ => panic!("expected completion"),
         }
     }
+
+    #[test]
+    fn mismatch_reports_failure_for_non_drop() {
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
+        let steps = vec![combo(&[VK_G]), combo(&[VK_H])];
+        let fallback = OnFail::Action(BindAction::PressKey(VK_ESCAPE));
+        state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &fallback, &steps, GAP, 0, &mut failed);
+        assert!(failed.is_empty());
+        state.handle_keydown(VK_A, &[VK_A], &BindAction::DoNothing, &fallback, &steps, GAP, 100, &mut failed);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].keys, vec![VK_G.0]);
+        assert_eq!(failed[0].on_fail, fallback);
+    }
+
+    #[test]
+    fn expire_reports_pressed_keys_in_order() {
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
+        let steps = vec![combo(&[VK_F13]), combo(&[VK_G]), combo(&[VK_H])];
+        state.handle_keydown(VK_F13, &[VK_F13], &BindAction::DoNothing, &OnFail::Replay, &steps, GAP, 0, &mut failed);
+        state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Replay, &steps, GAP, 100, &mut failed);
+        // Each advance pushes the deadline out by another gap.
+        assert!(state.expire(500).is_empty());
+        let expired = state.expire(600);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].keys, vec![VK_F13.0, VK_G.0]);
+        assert!(failed.is_empty());
+    }
+
+    #[test]
+    fn drop_sequences_expire_silently() {
+        let mut state = SequenceState::new();
+        let mut failed = Vec::new();
+        let steps = vec![combo(&[VK_G]), combo(&[VK_H])];
+        state.handle_keydown(VK_G, &[VK_G], &BindAction::DoNothing, &OnFail::Drop, &steps, GAP, 0, &mut failed);
+        assert!(state.expire(1_000).is_empty());
+        assert!(state.pending.is_empty());
+    }
 }
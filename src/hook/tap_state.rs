@@ -1,36 +1,81 @@
-//! Deferred-gesture state for `Trigger::Tap`, `Trigger::Hold`, `Trigger::DoubleTap`.
+//! Deferred-gesture state for `Trigger::Tap`, `Trigger::Hold`,
+//! `Trigger::DoubleTap` and `Trigger::TapDance`.
 //!
-//! Each gesture has its own `HashMap` of pending entries keyed by VK. The
-//! resolution protocol is Mutex-serialized: whichever side (timer thread or
-//! key-event handler) acquires the lock first and successfully `remove()`s
-//! the entry gets to fire its action. The other side observes `None` and
-//! no-ops. No atomic gate needed — `Mutex` provides the linearization.
+//! Each gesture has its own `HashMap` of pending entries keyed by VK, and
+//! every entry carries its deadline on the engine clock (milliseconds, see
+//! [`crate::hook::engine`]). Nothing here reads the wall clock or spawns
+//! threads: callers pass `now` explicitly, which is what makes the gesture
+//! logic replayable in tests.
 //!
-//! All three maps are owned by `Arc<Mutex<...>>` so spawned timer threads
-//! can share them without needing a `'static` reference into `KeyboardHook`.
+//! Expired `Tap` / `DoubleTap` entries are treated as absent and lazily
+//! garbage-collected by [`TapState::tick`]. `Hold` entries are the only
+//! ones that fire *without* a key event, so `tick` is also where due holds
+//! resolve; the Win32 adapter schedules a wake-up at each hold deadline.
+//! Tap dances resolve the same way: each press and release pushes the
+//! deadline out by the term, and `tick` ends the dance once it passes.
 
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::sync::Arc;
 
-use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
+use crate::data::action::BindAction;
+use crate::data::trigger::TapDanceStep;
+use crate::data::vk::Vk;
 
-use crate::core::windows_actions::BindAction;
+struct PendingGesture {
+    action: BindAction,
+    /// Engine-clock millisecond at which the gesture's term elapses.
+    deadline: u64,
+}
 
 #[derive(Default)]
 pub struct TapState {
     /// `Tap` triggers: action stored here until either key-up arrives in
-    /// time (→ fire) or the timer GC's the entry (→ tap window expired,
-    /// no fire — this was actually a hold).
-    pending_taps: Arc<Mutex<HashMap<u16, BindAction>>>,
-    /// `Hold` triggers: action stored here until either the timer fires
-    /// (→ key held long enough) or key-up cancels (→ user released too
-    /// early).
-    pending_holds: Arc<Mutex<HashMap<u16, BindAction>>>,
+    /// time (→ fire) or the deadline passes (→ tap window expired, no
+    /// fire — this was actually a hold).
+    pending_taps: HashMap<u16, PendingGesture>,
+    /// `Hold` triggers: action stored here until either the deadline
+    /// passes (→ key held long enough, fired by `tick`) or key-up cancels
+    /// (→ user released too early).
+    pending_holds: HashMap<u16, PendingGesture>,
     /// `DoubleTap` triggers: first key-down arms an entry, second key-down
-    /// within term consumes it (→ fire). Otherwise the timer GC's it.
-    pending_double_taps: Arc<Mutex<HashMap<u16, BindAction>>>,
+    /// within term consumes it (→ fire). Otherwise the entry expires.
+    pending_double_taps: HashMap<u16, PendingGesture>,
+    /// `TapDance` triggers: counts presses until the key stays up (→ tap
+    /// action for the count) or down (→ hold action) for a full term.
+    pending_dances: HashMap<u16, PendingDance>,
+}
+
+struct PendingDance {
+    steps: Arc<[TapDanceStep]>,
+    term_ms: u64,
+    /// Presses so far, starting at 1.
+    count: usize,
+    /// The key is currently held.
+    down: bool,
+    /// Engine-clock millisecond at which the dance ends.
+    deadline: u64,
+}
+
+impl PendingDance {
+    /// The action the dance ends with right now: the hold action if the
+    /// key is still down and the count has one, the tap action otherwise.
+    fn finish(self) -> Option<BindAction> {
+        let step = self.steps.get(self.count - 1)?;
+        if self.down && step.hold.is_some() {
+            return step.hold.clone();
+        }
+        step.tap.clone()
+    }
+}
+
+/// What a key-up did to a pending tap dance.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TapDanceUp {
+    /// No higher count exists, so the dance ended right away; fire the
+    /// action, if the count has one.
+    Finished(Option<BindAction>),
+    /// Waiting for another tap until `deadline`.
+    Waiting { deadline: u64 },
 }
 
 impl TapState {
@@ -38,185 +83,262 @@ impl TapState {
         Self::default()
     }
 
-    // ---- Tap ----
+    /// Fire every hold and end every tap dance whose deadline is at or
+    /// before `now`, and drop any expired tap / double-tap entries.
+    /// Returned actions, each with the key it belongs to, are ordered by
+    /// deadline (ties broken by VK) so replays are deterministic despite
+    /// the `HashMap` storage.
+    pub fn tick(&mut self, now: u64) -> Vec<(Vk, BindAction)> {
+        self.pending_taps.retain(|_, p| p.deadline > now);
+        self.pending_double_taps.retain(|_, p| p.deadline > now);
 
-    /// Arm a tap gesture. The action will fire if `resolve_tap_on_keyup` is
-    /// called with this VK before `term_ms` elapses; otherwise the timer
-    /// GC's the entry silently.
-    pub fn arm_tap(&self, vk: VIRTUAL_KEY, action: BindAction, term_ms: u64) {
-        {
-            let mut map = self.pending_taps.lock().expect("tap mutex poisoned");
-            // Auto-repeat protection: don't stack pendings on the same key
-            // if user holds it down and OS re-fires key-down. First wins.
-            if map.contains_key(&vk.0) {
-                return;
+        let mut due: Vec<(u64, u16, BindAction)> = Vec::new();
+        let holds: Vec<u16> = self
+            .pending_holds
+            .iter()
+            .filter(|(_, p)| p.deadline <= now)
+            .map(|(vk, _)| *vk)
+            .collect();
+        for vk in holds {
+            if let Some(p) = self.pending_holds.remove(&vk) {
+                due.push((p.deadline, vk, p.action));
             }
-            map.insert(vk.0, action);
         }
-
-        // Timer thread is purely GC — if the window expires without a
-        // matching key-up, drop the entry so the next press starts fresh.
-        let map_arc = Arc::clone(&self.pending_taps);
-        let vk_u16 = vk.0;
-        thread::Builder::new()
-            .name("tap-gc".into())
-            .spawn(move || {
-                thread::sleep(Duration::from_millis(term_ms));
-                map_arc.lock().expect("tap mutex poisoned").remove(&vk_u16);
-            })
-            .expect("spawn tap-gc thread");
-    }
-
-    /// Called on key-up. Returns `true` if there was a pending tap (and the
-    /// caller should suppress the key-up itself); the tap action, if any,
-    /// is executed inline before returning. `false` means this key wasn't
-    /// part of any pending tap gesture.
-    pub fn resolve_tap_on_keyup(&self, vk: VIRTUAL_KEY) -> bool {
-        let action = self
-            .pending_taps
-            .lock()
-            .expect("tap mutex poisoned")
-            .remove(&vk.0);
-
-        match action {
-            Some(action) => {
-                action.execute();
-                true
+        let dances: Vec<u16> = self
+            .pending_dances
+            .iter()
+            .filter(|(_, d)| d.deadline <= now)
+            .map(|(vk, _)| *vk)
+            .collect();
+        for vk in dances {
+            if let Some(dance) = self.pending_dances.remove(&vk) {
+                let deadline = dance.deadline;
+                if let Some(action) = dance.finish() {
+                    due.push((deadline, vk, action));
+                }
             }
-            None => false,
         }
+        due.sort_by_key(|(deadline, vk, _)| (*deadline, *vk));
+
+        due.into_iter()
+            .map(|(_, vk, action)| (Vk(vk), action))
+            .collect()
     }
 
-    // ---- Hold ----
+    // ---- Tap ----
 
-    /// Arm a hold gesture. The action fires from the timer thread after
-    /// `term_ms` unless `cancel_hold_on_keyup` removes the entry first.
-    pub fn arm_hold(&self, vk: VIRTUAL_KEY, action: BindAction, term_ms: u64) {
-        {
-            let mut map = self.pending_holds.lock().expect("hold mutex poisoned");
-            if map.contains_key(&vk.0) {
-                return;
-            }
-            map.insert(vk.0, action);
+    /// Arm a tap gesture. The action will fire if `resolve_tap_on_keyup` is
+    /// called with this VK before `term_ms` elapses; otherwise the entry
+    /// expires silently.
+    pub fn arm_tap(&mut self, vk: Vk, action: BindAction, term_ms: u64, now: u64) {
+        // Auto-repeat protection: don't stack pendings on the same key if
+        // user holds it down and OS re-fires key-down. First wins.
+        if self.pending_taps.get(&vk.0).is_some_and(|p| p.deadline > now) {
+            return;
         }
+        self.pending_taps.insert(vk.0, PendingGesture {
+            action,
+            deadline: now + term_ms,
+        });
+    }
 
-        let map_arc = Arc::clone(&self.pending_holds);
-        let vk_u16 = vk.0;
-        thread::Builder::new()
-            .name("hold-timer".into())
-            .spawn(move || {
-                thread::sleep(Duration::from_millis(term_ms));
-                // Lock + remove + fire under the same critical section so
-                // we don't race with cancel_hold_on_keyup.
-                let action = map_arc
-                    .lock()
-                    .expect("hold mutex poisoned")
-                    .remove(&vk_u16);
-                if let Some(action) = action {
-                    action.execute();
-                }
-            })
-            .expect("spawn hold-timer thread");
+    /// Called on key-up. Returns the tap action if there was a live pending
+    /// tap for this VK — the caller executes it and suppresses the key-up.
+    /// `None` means this key wasn't part of any pending tap gesture (or the
+    /// tap window already expired).
+    pub fn resolve_tap_on_keyup(&mut self, vk: Vk, now: u64) -> Option<BindAction> {
+        self.pending_taps
+            .remove(&vk.0)
+            .filter(|p| p.deadline > now)
+            .map(|p| p.action)
+    }
+
+    // ---- Hold ----
+
+    /// Arm a hold gesture. Returns the deadline at which `tick` must run to
+    /// fire it, or `None` if a hold is already pending on this key (OS
+    /// auto-repeat — first wins, no new wake-up needed).
+    pub fn arm_hold(
+        &mut self,
+        vk: Vk,
+        action: BindAction,
+        term_ms: u64,
+        now: u64,
+    ) -> Option<u64> {
+        if self.pending_holds.contains_key(&vk.0) {
+            return None;
+        }
+        let deadline = now + term_ms;
+        self.pending_holds.insert(vk.0, PendingGesture { action, deadline });
+        Some(deadline)
     }
 
     /// Called on key-up. Returns `true` if there was a pending hold (caller
     /// suppresses the key-up); the gesture is simply cancelled — nothing
     /// fires because the key was released before the hold window elapsed.
-    pub fn cancel_hold_on_keyup(&self, vk: VIRTUAL_KEY) -> bool {
-        self.pending_holds
-            .lock()
-            .expect("hold mutex poisoned")
-            .remove(&vk.0)
-            .is_some()
+    ///
+    /// Callers must `tick(now)` first so a hold whose deadline has already
+    /// passed fires instead of being cancelled.
+    pub fn cancel_hold_on_keyup(&mut self, vk: Vk) -> bool {
+        self.pending_holds.remove(&vk.0).is_some()
     }
 
     // ---- DoubleTap ----
 
-    /// Called on key-down. If there is a pending double-tap entry for this
-    /// VK (i.e. the user pressed this key recently enough), the entry is
-    /// consumed and the action fires. Otherwise a fresh entry is armed and
-    /// the timer will GC it after `term_ms`. Returns `true` either way —
-    /// the caller should suppress the key event from the foreground.
-    pub fn handle_double_tap(&self, vk: VIRTUAL_KEY, action: BindAction, term_ms: u64) -> bool {
+    /// Called on key-down. If there is a live double-tap entry for this VK
+    /// (i.e. the user pressed this key recently enough), the entry is
+    /// consumed and its action returned for the caller to fire. Otherwise
+    /// a fresh entry is armed and `None` is returned. The caller should
+    /// suppress the key event from the foreground either way.
+    pub fn handle_double_tap(
+        &mut self,
+        vk: Vk,
+        action: BindAction,
+        term_ms: u64,
+        now: u64,
+    ) -> Option<BindAction> {
+        if let Some(existing) = self.pending_double_taps.remove(&vk.0)
+            && existing.deadline > now
         {
-            let mut map = self
-                .pending_double_taps
-                .lock()
-                .expect("double-tap mutex poisoned");
-            if let Some(existing_action) = map.remove(&vk.0) {
-                // Second press inside window → fire double action.
-                existing_action.execute();
-                return true;
+            // Second press inside window → fire double action.
+            return Some(existing.action);
+        }
+        // First press (or the previous one expired): arm.
+        self.pending_double_taps.insert(vk.0, PendingGesture {
+            action,
+            deadline: now + term_ms,
+        });
+        None
+    }
+
+    // ---- TapDance ----
+
+    /// Called on a (non-repeat) key-down. Starts a dance or counts one more
+    /// tap, and returns the deadline at which `tick` must run to end it.
+    /// `None` if the key is already counted as down (a second `TapDance`
+    /// binding matching the same press).
+    pub fn tap_dance_down(
+        &mut self,
+        vk: Vk,
+        steps: &Arc<[TapDanceStep]>,
+        term_ms: u64,
+        now: u64,
+    ) -> Option<u64> {
+        let deadline = now + term_ms;
+        match self.pending_dances.get_mut(&vk.0) {
+            Some(dance) if dance.down => None,
+            Some(dance) => {
+                dance.count += 1;
+                dance.down = true;
+                dance.deadline = deadline;
+                Some(deadline)
+            }
+            None => {
+                self.pending_dances.insert(vk.0, PendingDance {
+                    steps: Arc::clone(steps),
+                    term_ms,
+                    count: 1,
+                    down: true,
+                    deadline,
+                });
+                Some(deadline)
             }
-            // First press: arm.
-            map.insert(vk.0, action);
         }
+    }
 
-        // GC thread — drop the entry if no second press arrives in time.
-        let map_arc = Arc::clone(&self.pending_double_taps);
-        let vk_u16 = vk.0;
-        thread::Builder::new()
-            .name("double-tap-gc".into())
-            .spawn(move || {
-                thread::sleep(Duration::from_millis(term_ms));
-                map_arc
-                    .lock()
-                    .expect("double-tap mutex poisoned")
-                    .remove(&vk_u16);
-            })
-            .expect("spawn double-tap-gc thread");
+    /// Called on key-up. `None` means no dance is pending on this key (it
+    /// already ended through a hold or an interruption). Callers must
+    /// `tick(now)` first so a held press past its term ends as a hold.
+    pub fn tap_dance_up(&mut self, vk: Vk, now: u64) -> Option<TapDanceUp> {
+        let dance = self.pending_dances.get_mut(&vk.0)?;
+        if dance.count >= dance.steps.len() {
+            let mut dance = self.pending_dances.remove(&vk.0)?;
+            dance.down = false;
+            return Some(TapDanceUp::Finished(dance.finish()));
+        }
+        dance.down = false;
+        dance.deadline = now + dance.term_ms;
+        Some(TapDanceUp::Waiting { deadline: dance.deadline })
+    }
 
-        true
+    /// Called on a (non-repeat) key-down of `vk`: every dance on another
+    /// key ends right away, as if its term had run out. Returned actions,
+    /// each with its dance key, are ordered by VK.
+    pub fn interrupt_tap_dances(&mut self, vk: Vk) -> Vec<(Vk, BindAction)> {
+        let mut others: Vec<u16> = self
+            .pending_dances
+            .keys()
+            .copied()
+            .filter(|&k| k != vk.0)
+            .collect();
+        others.sort_unstable();
+        others
+            .into_iter()
+            .filter_map(|k| {
+                let action = self.pending_dances.remove(&k)?.finish()?;
+                Some((Vk(k), action))
+            })
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use windows::Win32::UI::Input::KeyboardAndMouse::*;
+    use crate::data::vk::*;
 
-    fn tap_pending(state: &TapState, vk: VIRTUAL_KEY) -> bool {
-        state
-            .pending_taps
-            .lock()
-            .unwrap()
-            .contains_key(&vk.0)
+    fn tap_pending(state: &TapState, vk: Vk) -> bool {
+        state.pending_taps.contains_key(&vk.0)
     }
-    fn hold_pending(state: &TapState, vk: VIRTUAL_KEY) -> bool {
-        state
-            .pending_holds
-            .lock()
-            .unwrap()
-            .contains_key(&vk.0)
+    fn hold_pending(state: &TapState, vk: Vk) -> bool {
+        state.pending_holds.contains_key(&vk.0)
+    }
+    fn double_tap_pending(state: &TapState, vk: Vk) -> bool {
+        state.pending_double_taps.contains_key(&vk.0)
     }
-    fn double_tap_pending(state: &TapState, vk: VIRTUAL_KEY) -> bool {
-        state
-            .pending_double_taps
-            .lock()
-            .unwrap()
-            .contains_key(&vk.0)
+    fn dance_pending(state: &TapState, vk: Vk) -> bool {
+        state.pending_dances.contains_key(&vk.0)
+    }
+
+    /// x1 = switch language, x2 = toggle CapsLock (hold: do nothing).
+    fn two_step_dance() -> Arc<[TapDanceStep]> {
+        Arc::from(vec![
+            TapDanceStep { tap: Some(BindAction::SwitchLanguage), hold: None },
+            TapDanceStep {
+                tap: Some(BindAction::ToggleCapsLock),
+                hold: Some(BindAction::DoNothing),
+            },
+        ])
     }
 
     #[test]
     fn arm_tap_inserts_then_resolve_removes() {
-        let state = TapState::new();
+        let mut state = TapState::new();
         assert!(!tap_pending(&state, VK_F13));
-        state.arm_tap(VK_F13, BindAction::DoNothing, 10_000);
+        state.arm_tap(VK_F13, BindAction::DoNothing, 200, 0);
         assert!(tap_pending(&state, VK_F13));
-        assert!(state.resolve_tap_on_keyup(VK_F13));
+        assert_eq!(state.resolve_tap_on_keyup(VK_F13, 100), Some(BindAction::DoNothing));
         assert!(!tap_pending(&state, VK_F13));
     }
 
     #[test]
-    fn resolve_tap_with_no_pending_returns_false() {
-        let state = TapState::new();
-        assert!(!state.resolve_tap_on_keyup(VK_F13));
+    fn resolve_tap_with_no_pending_returns_none() {
+        let mut state = TapState::new();
+        assert_eq!(state.resolve_tap_on_keyup(VK_F13, 0), None);
+    }
+
+    #[test]
+    fn resolve_tap_after_term_does_not_fire() {
+        let mut state = TapState::new();
+        state.arm_tap(VK_F13, BindAction::DoNothing, 200, 0);
+        assert_eq!(state.resolve_tap_on_keyup(VK_F13, 250), None);
+        assert!(!tap_pending(&state, VK_F13));
     }
 
     #[test]
     fn arm_hold_inserts_then_cancel_removes() {
-        let state = TapState::new();
-        state.arm_hold(VK_F13, BindAction::DoNothing, 10_000);
+        let mut state = TapState::new();
+        assert_eq!(state.arm_hold(VK_F13, BindAction::DoNothing, 200, 0), Some(200));
         assert!(hold_pending(&state, VK_F13));
         assert!(state.cancel_hold_on_keyup(VK_F13));
         assert!(!hold_pending(&state, VK_F13));
@@ -224,16 +346,31 @@ mod tests {
 
     #[test]
     fn cancel_hold_with_no_pending_returns_false() {
-        let state = TapState::new();
+        let mut state = TapState::new();
         assert!(!state.cancel_hold_on_keyup(VK_F13));
     }
 
+    #[test]
+    fn tick_fires_due_holds_in_deadline_order() {
+        let mut state = TapState::new();
+        state.arm_hold(VK_F14, BindAction::ToggleCapsLock, 300, 0);
+        state.arm_hold(VK_F13, BindAction::SwitchLanguage, 200, 0);
+        assert!(state.tick(199).is_empty());
+        assert_eq!(state.tick(200), vec![(VK_F13, BindAction::SwitchLanguage)]);
+        assert_eq!(state.tick(1_000), vec![(VK_F14, BindAction::ToggleCapsLock)]);
+        assert!(!hold_pending(&state, VK_F13));
+        assert!(!hold_pending(&state, VK_F14));
+    }
+
     #[test]
     fn double_tap_first_press_arms_second_press_fires() {
-        let state = TapState::new();
-        assert!(state.handle_double_tap(VK_F13, BindAction::DoNothing, 10_000));
+        let mut state = TapState::new();
+        assert_eq!(state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 0), None);
         assert!(double_tap_pending(&state, VK_F13));
-        assert!(state.handle_double_tap(VK_F13, BindAction::DoNothing, 10_000));
+        assert_eq!(
+            state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 100),
+            Some(BindAction::DoNothing),
+        );
         // Pending entry was consumed by second press.
         assert!(!double_tap_pending(&state, VK_F13));
     }
@@ -241,10 +378,18 @@ mod tests {
     #[test]
     fn double_tap_third_press_arms_fresh_pending() {
         // Tap, tap (consumed), tap → third is a "first tap" again.
-        let state = TapState::new();
-        state.handle_double_tap(VK_F13, BindAction::DoNothing, 10_000);
-        state.handle_double_tap(VK_F13, BindAction::DoNothing, 10_000);
-        state.handle_double_tap(VK_F13, BindAction::DoNothing, 10_000);
+        let mut state = TapState::new();
+        state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 0);
+        state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 50);
+        state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 100);
+        assert!(double_tap_pending(&state, VK_F13));
+    }
+
+    #[test]
+    fn double_tap_second_press_after_term_rearms() {
+        let mut state = TapState::new();
+        state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 0);
+        assert_eq!(state.handle_double_tap(VK_F13, BindAction::DoNothing, 250, 400), None);
         assert!(double_tap_pending(&state, VK_F13));
     }
 
@@ -252,14 +397,14 @@ mod tests {
     fn tap_and_hold_pending_simultaneously_on_same_key() {
         // The whole point of B-style: one key can have both Tap and Hold
         // pending at the same time. Resolution is independent.
-        let state = TapState::new();
-        state.arm_tap(VK_CAPITAL, BindAction::DoNothing, 10_000);
-        state.arm_hold(VK_CAPITAL, BindAction::DoNothing, 10_000);
+        let mut state = TapState::new();
+        state.arm_tap(VK_CAPITAL, BindAction::DoNothing, 200, 0);
+        state.arm_hold(VK_CAPITAL, BindAction::DoNothing, 200, 0);
         assert!(tap_pending(&state, VK_CAPITAL));
         assert!(hold_pending(&state, VK_CAPITAL));
 
         // Resolve tap on key-up — only the tap entry should clear.
-        state.resolve_tap_on_keyup(VK_CAPITAL);
+        state.resolve_tap_on_keyup(VK_CAPITAL, 50);
         assert!(!tap_pending(&state, VK_CAPITAL));
         assert!(hold_pending(&state, VK_CAPITAL));
 
@@ -271,14 +416,85 @@ mod tests {
     #[test]
     fn auto_repeat_keydown_does_not_stack() {
         // Holding the key down causes OS to fire repeated key-downs.
-        // arm_tap must NOT replace the existing entry or spawn extra timer
-        // threads — first one wins.
-        let state = TapState::new();
-        state.arm_tap(VK_F13, BindAction::DoNothing, 10_000);
-        state.arm_tap(VK_F13, BindAction::SwitchLanguage, 10_000); // would-be replacement
-        assert!(tap_pending(&state, VK_F13));
+        // arm_tap must NOT replace the existing entry — first one wins.
+        let mut state = TapState::new();
+        state.arm_tap(VK_F13, BindAction::DoNothing, 200, 0);
+        state.arm_tap(VK_F13, BindAction::SwitchLanguage, 200, 30); // would-be replacement
+        assert_eq!(state.resolve_tap_on_keyup(VK_F13, 60), Some(BindAction::DoNothing));
         // After resolve, no second entry should remain.
-        state.resolve_tap_on_keyup(VK_F13);
         assert!(!tap_pending(&state, VK_F13));
+
+        // Same rule for holds: the repeat arms nothing and asks for no wake-up.
+        assert!(state.arm_hold(VK_F13, BindAction::DoNothing, 200, 0).is_some());
+        assert!(state.arm_hold(VK_F13, BindAction::DoNothing, 200, 30).is_none());
+    }
+
+    #[test]
+    fn tick_garbage_collects_expired_taps() {
+        let mut state = TapState::new();
+        state.arm_tap(VK_F13, BindAction::DoNothing, 200, 0);
+        state.handle_double_tap(VK_F14, BindAction::DoNothing, 200, 0);
+        assert!(state.tick(300).is_empty());
+        assert!(!tap_pending(&state, VK_F13));
+        assert!(!double_tap_pending(&state, VK_F14));
+    }
+
+    #[test]
+    fn tap_dance_single_tap_fires_after_term() {
+        let mut state = TapState::new();
+        let steps = two_step_dance();
+        assert_eq!(state.tap_dance_down(VK_CAPITAL, &steps, 200, 0), Some(200));
+        assert_eq!(
+            state.tap_dance_up(VK_CAPITAL, 50),
+            Some(TapDanceUp::Waiting { deadline: 250 })
+        );
+        assert!(state.tick(249).is_empty());
+        assert_eq!(state.tick(250), vec![(VK_CAPITAL, BindAction::SwitchLanguage)]);
+        assert!(!dance_pending(&state, VK_CAPITAL));
+    }
+
+    #[test]
+    fn tap_dance_last_count_finishes_on_release() {
+        let mut state = TapState::new();
+        let steps = two_step_dance();
+        state.tap_dance_down(VK_CAPITAL, &steps, 200, 0);
+        state.tap_dance_up(VK_CAPITAL, 50);
+        assert_eq!(state.tap_dance_down(VK_CAPITAL, &steps, 200, 120), Some(320));
+        // Repeat / second binding on the same press counts nothing.
+        assert_eq!(state.tap_dance_down(VK_CAPITAL, &steps, 200, 130), None);
+        assert_eq!(
+            state.tap_dance_up(VK_CAPITAL, 160),
+            Some(TapDanceUp::Finished(Some(BindAction::ToggleCapsLock)))
+        );
+        assert!(!dance_pending(&state, VK_CAPITAL));
+    }
+
+    #[test]
+    fn tap_dance_held_last_press_fires_hold_or_tap() {
+        let mut state = TapState::new();
+        let steps = two_step_dance();
+        // Count 1 has no hold action: a long press is still a tap.
+        state.tap_dance_down(VK_CAPITAL, &steps, 200, 0);
+        assert_eq!(state.tick(200), vec![(VK_CAPITAL, BindAction::SwitchLanguage)]);
+        assert_eq!(state.tap_dance_up(VK_CAPITAL, 300), None);
+
+        state.tap_dance_down(VK_CAPITAL, &steps, 200, 1_000);
+        state.tap_dance_up(VK_CAPITAL, 1_050);
+        state.tap_dance_down(VK_CAPITAL, &steps, 200, 1_100);
+        assert_eq!(state.tick(1_300), vec![(VK_CAPITAL, BindAction::DoNothing)]);
+    }
+
+    #[test]
+    fn tap_dance_interrupted_by_other_key() {
+        let mut state = TapState::new();
+        let steps = two_step_dance();
+        state.tap_dance_down(VK_CAPITAL, &steps, 200, 0);
+        state.tap_dance_up(VK_CAPITAL, 40);
+        assert!(state.interrupt_tap_dances(VK_CAPITAL).is_empty());
+        assert_eq!(
+            state.interrupt_tap_dances(VK_A),
+            vec![(VK_CAPITAL, BindAction::SwitchLanguage)]
+        );
+        assert!(!dance_pending(&state, VK_CAPITAL));
     }
 }
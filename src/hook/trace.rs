@@ -0,0 +1,512 @@
+//! Key-event traces: a plain-text recording of what the hook saw, and an
+//! offline replay of such a recording through the [`Engine`].
+//!
+//! One event per line, `#` starts a comment:
+//!
+//! ```text
+//! # key-switch-rs trace v1
+//! 0    down CapsLock app="code.exe" title="main.rs - key-switch-rs"
+//! 96   up   CapsLock app="code.exe" title="main.rs - key-switch-rs"
+//! 410  down 0xFF
+//! 520  down Enter sc=0xE01C
+//! ```
+//!
+//! Fields are `<ms> <down|up> <key> [sc=0x..] [app="..."] [title="..."]`.
+//! Times are milliseconds since recording started and must not decrease.
+//! Keys use the same names as the config (`vk_name`, `VK(0x..)` for a code
+//! without a name), or a raw `0x..` code for those a config can't hold.
+//! `sc` is the hardware scan code, `0xE0..` for extended keys; absent
+//! means unknown, and such an event matches no positional (`SC:0x..`)
+//! binding. `app` / `title` describe the foreground window at the time of
+//! the event and feed `when:` conditions on replay; absent means unknown.
+//!
+//! Recording runs on the daemon (`swch record <file>`): the hook callback
+//! pushes events onto a channel and a writer thread owns the file, so disk
+//! I/O never runs inside the low-level hook. Replay (`swch replay`) is pure
+//! and needs no daemon.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+
+use crate::data::action::BindAction;
+use crate::data::binding::Binding;
+use crate::data::condition::ForegroundContext;
+use crate::data::vk::Vk;
+use crate::data::vk_name::{parse_vk, vk_name};
+use crate::hook::engine::{Engine, KeyEvent, KeyEventKind};
+
+/// Header written at the top of every recorded trace.
+const TRACE_HEADER: &str = "# key-switch-rs trace v1";
+
+/// One line of a trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub event: KeyEvent,
+    pub app: Option<String>,
+    pub title: Option<String>,
+}
+
+impl TraceEvent {
+    fn context(&self) -> ForegroundContext {
+        ForegroundContext::snapshot(self.app.clone(), self.title.clone())
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.event.kind {
+            KeyEventKind::Down => "down",
+            KeyEventKind::Up => "up",
+        };
+        write!(f, "{:<6} {:<4} {}", self.event.time_ms, kind, format_key(self.event.vk))?;
+        if self.event.scan != 0 {
+            write!(f, " sc={:#04X}", self.event.scan)?;
+        }
+        if let Some(app) = &self.app {
+            write!(f, " app={}", quote(app))?;
+        }
+        if let Some(title) = &self.title {
+            write!(f, " title={}", quote(title))?;
+        }
+        Ok(())
+    }
+}
+
+/// Key name as written to a trace: the config name if it parses back to
+/// the same code, otherwise the raw code in hex.
+fn format_key(vk: Vk) -> String {
+    let name = vk_name(vk);
+    if parse_vk(&name) == Some(vk) {
+        name
+    } else {
+        format!("{:#04X}", vk.0)
+    }
+}
+
+fn parse_key(token: &str) -> Option<Vk> {
+    if let Some(hex) = token.strip_prefix("0x").or_else(|| token.strip_prefix("0X")) {
+        return u8::from_str_radix(hex, 16).ok().map(|v| Vk(v as u16));
+    }
+    parse_vk(token)
+}
+
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            other => out.push(other),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Split a line into whitespace-separated tokens, where `key="..."` values
+/// may contain spaces and the escapes written by [`quote`].
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            return Ok(tokens);
+        }
+        let mut token = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+            if c != '"' {
+                token.push(c);
+                continue;
+            }
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => token.push('\n'),
+                        Some(escaped) => token.push(escaped),
+                        None => return Err("unterminated escape".into()),
+                    },
+                    Some(other) => token.push(other),
+                    None => return Err("unterminated quoted value".into()),
+                }
+            }
+        }
+        tokens.push(token);
+    }
+}
+
+/// Parse a whole trace. Errors carry the 1-based line number.
+pub fn parse_trace(text: &str) -> Result<Vec<TraceEvent>, String> {
+    let mut events = Vec::new();
+    let mut last_time = 0;
+
+    for (i, raw_line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {}", line_no, msg);
+
+        let tokens = tokenize(line).map_err(err)?;
+        let [time, kind, key, rest @ ..] = tokens.as_slice() else {
+            return Err(err(format!(
+                "expected `<ms> <down|up> <key>`, got {:?}",
+                line
+            )));
+        };
+
+        let time_ms: u64 = time
+            .parse()
+            .map_err(|_| err(format!("invalid timestamp {:?}", time)))?;
+        if time_ms < last_time {
+            return Err(err(format!(
+                "timestamp {} goes backwards (previous event at {})",
+                time_ms, last_time
+            )));
+        }
+        last_time = time_ms;
+
+        let kind = match kind.to_ascii_lowercase().as_str() {
+            "down" => KeyEventKind::Down,
+            "up" => KeyEventKind::Up,
+            other => return Err(err(format!("expected `down` or `up`, got {:?}", other))),
+        };
+        let vk = parse_key(key).ok_or_else(|| err(format!("unknown key {:?}", key)))?;
+
+        let mut scan = 0;
+        let mut app = None;
+        let mut title = None;
+        for field in rest {
+            match field.split_once('=') {
+                Some(("sc", value)) => {
+                    scan = value
+                        .strip_prefix("0x")
+                        .and_then(|hex| u16::from_str_radix(hex, 16).ok())
+                        .ok_or_else(|| err(format!("invalid scan code {:?}", value)))?;
+                }
+                Some(("app", value)) => app = Some(value.to_string()),
+                Some(("title", value)) => title = Some(value.to_string()),
+                _ => return Err(err(format!("unknown field {:?}", field))),
+            }
+        }
+
+        events.push(TraceEvent {
+            event: KeyEvent { vk, kind, time_ms, scan },
+            app,
+            title,
+        });
+    }
+
+    Ok(events)
+}
+
+// ---- Replay ----
+
+/// One thing that happened during a replay: either a trace event or a
+/// timer tick in between.
+#[derive(Debug)]
+pub struct ReplayStep {
+    pub time_ms: u64,
+    /// `None` for a timer tick (a `Hold` term or an `on_fail` window elapsing).
+    pub event: Option<TraceEvent>,
+    pub suppressed: bool,
+    pub fired: Vec<Fired>,
+    /// Keys handed back by a failed `on_fail: Replay` gesture.
+    pub replayed: Vec<KeyEvent>,
+}
+
+#[derive(Debug)]
+pub struct Fired {
+    pub action: BindAction,
+    /// The binding the action came from, if it could be identified.
+    pub binding: Option<String>,
+}
+
+impl fmt::Display for ReplayStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.event {
+            Some(ev) => {
+                let kind = match ev.event.kind {
+                    KeyEventKind::Down => "down",
+                    KeyEventKind::Up => "up",
+                };
+                write!(f, "{:>7} ms  {:<4} {:<14}", self.time_ms, kind, format_key(ev.event.vk))?;
+                write!(f, "{}", if self.suppressed { "suppressed" } else { "passed" })?;
+            }
+            None => write!(f, "{:>7} ms  (timer)", self.time_ms)?,
+        }
+        if !self.replayed.is_empty() {
+            let keys: Vec<String> = self
+                .replayed
+                .iter()
+                .map(|e| {
+                    let kind = if e.kind == KeyEventKind::Up { "up" } else { "down" };
+                    format!("{} {}", format_key(e.vk), kind)
+                })
+                .collect();
+            write!(f, "\n             re-injected: {}", keys.join(", "))?;
+        }
+        for fired in &self.fired {
+            match &fired.binding {
+                Some(b) => write!(f, "\n             fired: {}", b.trim())?,
+                None => write!(f, "\n             fired: {}", fired.action)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run `events` through a fresh engine over `bindings` and report what
+/// happened at each step. Timer-driven resolutions (`Hold`, `on_fail`
+/// expiry) get their ticks at the exact deadline, including after the last
+/// event.
+pub fn replay(events: &[TraceEvent], bindings: Vec<Binding>) -> Vec<ReplayStep> {
+    let mut engine = Engine::new(bindings);
+    let mut wake_at: Vec<u64> = Vec::new();
+    let mut steps = Vec::new();
+    // Ticks see the foreground of the latest event, like the live timer
+    // thread would.
+    let mut ctx = ForegroundContext::snapshot(None, None);
+
+    for ev in events {
+        run_due_ticks(&mut engine, &ctx, &mut wake_at, Some(ev.event.time_ms), &mut steps);
+        ctx = ev.context();
+        let decision = engine.handle(ev.event, &ctx);
+        wake_at.extend(decision.wake_at.iter().copied());
+        steps.push(ReplayStep {
+            time_ms: ev.event.time_ms,
+            event: Some(ev.clone()),
+            suppressed: decision.suppress,
+            fired: attribute(&engine, decision.actions()),
+            replayed: decision.injected(),
+        });
+    }
+    run_due_ticks(&mut engine, &ctx, &mut wake_at, None, &mut steps);
+
+    steps
+}
+
+/// Tick the engine at every pending deadline up to and including `until`
+/// (all of them if `None`), recording ticks that fired something. A tick
+/// may ask for further wake-ups; those are honoured in the same pass.
+fn run_due_ticks(
+    engine: &mut Engine,
+    ctx: &ForegroundContext,
+    wake_at: &mut Vec<u64>,
+    until: Option<u64>,
+    steps: &mut Vec<ReplayStep>,
+) {
+    loop {
+        wake_at.sort_unstable();
+        wake_at.dedup();
+        let Some(&deadline) = wake_at.first().filter(|&&t| until.is_none_or(|u| t <= u)) else {
+            return;
+        };
+        wake_at.remove(0);
+        let decision = engine.tick(deadline, ctx);
+        wake_at.extend(decision.wake_at.iter().copied());
+        if !decision.outputs.is_empty() {
+            steps.push(ReplayStep {
+                time_ms: deadline,
+                event: None,
+                suppressed: false,
+                fired: attribute(engine, decision.actions()),
+                replayed: decision.injected(),
+            });
+        }
+    }
+}
+
+/// Pair each fired action with the binding it most likely came from. The
+/// engine hands back actions, not bindings; auto-blockers share the
+/// `DoNothing` action with user bindings, so those are skipped unless
+/// nothing else matches.
+fn attribute(engine: &Engine, actions: Vec<BindAction>) -> Vec<Fired> {
+    actions
+        .into_iter()
+        .map(|action| {
+            let bindings = engine.bindings();
+            let binding = bindings
+                .iter()
+                .find(|b| !b.is_auto_blocker && b.can_fire(&action))
+                .or_else(|| bindings.iter().find(|b| b.can_fire(&action)))
+                .map(|b| b.to_string());
+            Fired { action, binding }
+        })
+        .collect()
+}
+
+// ---- Recording ----
+
+/// A running recording: the hook side of the channel plus the writer
+/// thread that owns the file.
+pub struct TraceRecorder {
+    path: PathBuf,
+    start_ms: u64,
+    tx: Sender<TraceEvent>,
+    writer: JoinHandle<io::Result<usize>>,
+}
+
+impl TraceRecorder {
+    /// Create (truncate) `path` and start the writer thread. `now_ms` is the
+    /// engine-clock time that becomes 0 in the trace.
+    pub fn start(path: &Path, now_ms: u64) -> io::Result<Self> {
+        let file = File::create(path)?;
+        let (tx, rx) = mpsc::channel::<TraceEvent>();
+        let writer = std::thread::Builder::new()
+            .name("trace-writer".into())
+            .spawn(move || {
+                let mut out = BufWriter::new(file);
+                writeln!(out, "{}", TRACE_HEADER)?;
+                let mut count = 0;
+                for ev in rx {
+                    writeln!(out, "{}", ev)?;
+                    count += 1;
+                }
+                out.flush()?;
+                Ok(count)
+            })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            start_ms: now_ms,
+            tx,
+            writer,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queue one event. Never blocks on disk I/O.
+    pub fn record(&self, mut event: TraceEvent) {
+        event.event.time_ms = event.event.time_ms.saturating_sub(self.start_ms);
+        // A send error means the writer already failed; `finish` reports it.
+        let _ = self.tx.send(event);
+    }
+
+    /// Close the channel, wait for the writer to flush and return how many
+    /// events were written.
+    pub fn finish(self) -> io::Result<usize> {
+        drop(self.tx);
+        self.writer
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("trace writer thread panicked")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::condition::Condition;
+    use crate::data::key_combination::KeyCombination;
+    use crate::data::trigger::Trigger;
+    use crate::data::vk::*;
+
+    #[test]
+    fn format_parse_roundtrip() {
+        let events = vec![
+            TraceEvent {
+                event: KeyEvent::down(VK_CAPITAL, 0),
+                app: Some("code.exe".into()),
+                title: Some("say \"hi\" \\ there".into()),
+            },
+            TraceEvent { event: KeyEvent::up(VK_CAPITAL, 96), app: None, title: None },
+            TraceEvent { event: KeyEvent::down(Vk(0xFF), 410), app: None, title: None },
+            TraceEvent {
+                event: KeyEvent::down(VK_RETURN, 520).with_scan(0xE01C),
+                app: Some("code.exe".into()),
+                title: None,
+            },
+        ];
+        let text: String = events.iter().map(|e| format!("{}\n", e)).collect();
+        assert_eq!(parse_trace(&text).unwrap(), events);
+    }
+
+    #[test]
+    fn parse_skips_comments_and_blank_lines() {
+        let text = "# key-switch-rs trace v1\n\n  0 down A\n# note\n10 UP a\n";
+        let events = parse_trace(text).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].event, KeyEvent::up(VK_A, 10));
+    }
+
+    #[test]
+    fn parse_errors_carry_line_number() {
+        let err = parse_trace("0 down A\n5 sideways A\n").unwrap_err();
+        assert!(err.starts_with("line 2:"), "{}", err);
+
+        let err = parse_trace("0 down Ctlr\n").unwrap_err();
+        assert!(err.contains("unknown key"), "{}", err);
+
+        let err = parse_trace("10 down A\n5 up A\n").unwrap_err();
+        assert!(err.contains("backwards"), "{}", err);
+
+        let err = parse_trace("0 down A app=\"code.exe\n").unwrap_err();
+        assert!(err.contains("unterminated"), "{}", err);
+
+        let err = parse_trace("0 down Enter sc=1C\n").unwrap_err();
+        assert!(err.contains("invalid scan code"), "{}", err);
+    }
+
+    #[test]
+    fn replay_reports_fired_and_suppressed() {
+        let bindings = vec![
+            Binding::new(KeyCombination::new(VK_CAPITAL), BindAction::SwitchLanguage),
+        ];
+        let events = parse_trace("0 down CapsLock\n80 up CapsLock\n100 down A\n").unwrap();
+        let steps = replay(&events, bindings);
+
+        assert_eq!(steps.len(), 3);
+        assert!(steps[0].suppressed);
+        assert_eq!(steps[0].fired.len(), 1);
+        assert!(steps[0].fired[0].binding.as_deref().unwrap().contains("CapsLock"));
+        assert!(steps[1].suppressed);
+        assert!(steps[1].fired.is_empty());
+        assert!(!steps[2].suppressed);
+    }
+
+    #[test]
+    fn replay_ticks_hold_at_deadline() {
+        let bindings = vec![
+            Binding::new(KeyCombination::new(VK_CAPITAL), BindAction::ToggleCapsLock)
+                .with_trigger(Trigger::Hold(200)),
+        ];
+        // Held past the term: the hold fires on a timer step at 200 ms,
+        // before the key-up at 500 ms.
+        let events = parse_trace("0 down CapsLock\n500 up CapsLock\n").unwrap();
+        let steps = replay(&events, bindings.clone());
+        assert_eq!(steps.len(), 3);
+        assert!(steps[1].event.is_none());
+        assert_eq!(steps[1].time_ms, 200);
+        assert_eq!(steps[1].fired[0].action, BindAction::ToggleCapsLock);
+
+        // Trace ends while the key is still held: the trailing tick still runs.
+        let events = parse_trace("0 down CapsLock\n").unwrap();
+        let steps = replay(&events, bindings);
+        assert_eq!(steps.last().unwrap().time_ms, 200);
+    }
+
+    #[test]
+    fn replay_uses_recorded_foreground() {
+        let bindings = vec![
+            Binding::new(KeyCombination::new(VK_PAUSE), BindAction::DoNothing)
+                .with_condition(Condition::AppEquals("spotify.exe".into())),
+        ];
+        let events = parse_trace(
+            "0 down Pause app=\"code.exe\"\n10 up Pause\n20 down Pause app=\"Spotify.exe\"\n",
+        )
+        .unwrap();
+        let steps = replay(&events, bindings);
+        assert!(!steps[0].suppressed);
+        assert!(steps[2].suppressed);
+    }
+}
@@ -7,10 +7,15 @@
 //! daemon → client: "OK: enabled\n"
 //! ```
 //!
+//! The one exception is `subscribe`: after its `OK:` line the connection
+//! stays open and the daemon writes a few plain lines per config reload.
+//!
 //! Transport is a Windows named pipe via the `interprocess` crate's
 //! cross-platform `local_socket` API (on Windows this is a named pipe;
 //! on Unix it would be a Unix domain socket).
 
+use std::path::PathBuf;
+
 /// Local-socket name used by both sides. On Windows the OS-mapped form
 /// resolves to `\\.\pipe\key-switch-rs.sock`.
 pub const PIPE_NAME: &str = "key-switch-rs.sock";
@@ -28,28 +33,60 @@ pub enum Command {
     /// Health check / "is daemon up". Daemon answers with a short status
     /// string (running/disabled).
     Status,
+    /// Start recording key events to a trace file on the daemon side. The
+    /// path is taken verbatim — the CLI makes it absolute before sending
+    /// because the daemon's working directory is not the user's.
+    Record(PathBuf),
+    /// Stop the running trace recording and flush it.
+    RecordStop,
+    /// Switch on every binding tagged with this tag, including ones loaded
+    /// with `enabled: false`. Lasts until the daemon exits, across reloads.
+    EnableTag(String),
+    /// Switch off every binding tagged with this tag.
+    DisableTag(String),
+    /// Keep the connection open and receive a report of every config
+    /// reload, as lines, until either side closes it (`swch watch`).
+    Subscribe,
 }
 
 impl Command {
     /// Parses a line received over the wire. Lines are trimmed before
-    /// matching so terminal newlines / whitespace don't matter.
+    /// matching so terminal newlines / whitespace don't matter. The command
+    /// word is case-insensitive; its argument (if any) is kept verbatim.
     pub fn parse(line: &str) -> Result<Self, String> {
-        match line.trim().to_ascii_lowercase().as_str() {
-            "on" => Ok(Command::On),
-            "off" => Ok(Command::Off),
-            "exit" | "quit" | "shutdown" => Ok(Command::Exit),
-            "status" | "ping" => Ok(Command::Status),
-            other => Err(format!("unknown command {:?}", other)),
+        let line = line.trim();
+        let (word, arg) = match line.split_once(char::is_whitespace) {
+            Some((word, arg)) => (word, arg.trim()),
+            None => (line, ""),
+        };
+        match (word.to_ascii_lowercase().as_str(), arg) {
+            ("on", "") => Ok(Command::On),
+            ("off", "") => Ok(Command::Off),
+            ("exit" | "quit" | "shutdown", "") => Ok(Command::Exit),
+            ("status" | "ping", "") => Ok(Command::Status),
+            ("record", "") => Err("record needs a trace file path".into()),
+            ("record-stop", "") => Ok(Command::RecordStop),
+            ("subscribe" | "watch", "") => Ok(Command::Subscribe),
+            ("record", path) => Ok(Command::Record(PathBuf::from(path))),
+            ("enable-tag" | "disable-tag", "") => Err(format!("{} needs a tag", word)),
+            ("enable-tag", tag) => Ok(Command::EnableTag(tag.to_string())),
+            ("disable-tag", tag) => Ok(Command::DisableTag(tag.to_string())),
+            _ => Err(format!("unknown command {:?}", line.to_ascii_lowercase())),
         }
     }
 
     /// Wire-format for sending. Always newline-terminated.
-    pub fn as_wire(&self) -> &'static str {
+    pub fn as_wire(&self) -> String {
         match self {
-            Command::On => "on\n",
-            Command::Off => "off\n",
-            Command::Exit => "exit\n",
-            Command::Status => "status\n",
+            Command::On => "on\n".into(),
+            Command::Off => "off\n".into(),
+            Command::Exit => "exit\n".into(),
+            Command::Status => "status\n".into(),
+            Command::Record(path) => format!("record {}\n", path.display()),
+            Command::RecordStop => "record-stop\n".into(),
+            Command::EnableTag(tag) => format!("enable-tag {}\n", tag),
+            Command::DisableTag(tag) => format!("disable-tag {}\n", tag),
+            Command::Subscribe => "subscribe\n".into(),
         }
     }
 }
@@ -84,11 +121,32 @@ mod tests {
 
     #[test]
     fn command_parse_roundtrip() {
-        for cmd in [Command::On, Command::Off, Command::Exit, Command::Status] {
-            assert_eq!(Command::parse(cmd.as_wire()).unwrap(), cmd);
+        for cmd in [
+            Command::On,
+            Command::Off,
+            Command::Exit,
+            Command::Status,
+            Command::Record(PathBuf::from(r"C:\Users\Me\My Traces\caps.trace")),
+            Command::RecordStop,
+            Command::EnableTag("gaming".into()),
+            Command::DisableTag("Work Laptop".into()),
+            Command::Subscribe,
+        ] {
+            assert_eq!(Command::parse(&cmd.as_wire()).unwrap(), cmd);
         }
     }
 
+    #[test]
+    fn command_parse_record_keeps_path_case() {
+        assert_eq!(
+            Command::parse("RECORD D:\\Traces\\Bug.trace\n").unwrap(),
+            Command::Record(PathBuf::from("D:\\Traces\\Bug.trace")),
+        );
+        assert!(Command::parse("record").is_err());
+        assert!(Command::parse("disable-tag").is_err());
+        assert!(Command::parse("on now").is_err());
+    }
+
     #[test]
     fn command_parse_case_insensitive() {
         assert_eq!(Command::parse("ON").unwrap(), Command::On);
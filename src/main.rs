@@ -1,7 +1,9 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
 use std::process::ExitCode;
 
-use key_switch_rs::config;
+use key_switch_rs::config::{self, LoadedConfig};
+#[cfg(windows)]
 use key_switch_rs::core::app::App;
 
 fn main() -> ExitCode {
@@ -21,21 +23,51 @@ fn main() -> ExitCode {
 }
 
 fn run() -> Result<(), Box<dyn std::error::Error>> {
-    // Resolution order for the config path:
-    //   1. First positional CLI argument, if provided.
-    //   2. <exe_dir>/config.ron (portable layout: drop the .exe anywhere and
-    //      its config lives beside it).
-    let config_path: PathBuf = match std::env::args_os().nth(1) {
-        Some(arg) => PathBuf::from(arg),
-        None => config::default_config_path()?,
-    };
+    // `--config <path>` (or a bare path, as before the flag existed) wins;
+    // otherwise `config::locate_config` walks the rest of the chain.
+    let flag = config_flag(std::env::args_os().skip(1))?;
+    let location = config::locate_config(flag.as_deref())?;
+    println!("[INFO] config location: {}", location);
+    let config_path = location.path;
 
-    let bindings = config::load(&config_path)?;
+    let loaded = config::load(&config_path)?;
+    run_daemon(config_path, loaded)
+}
 
-    let mut app = App::new().with_config_watcher(config_path);
-    for b in bindings {
+#[cfg(windows)]
+fn run_daemon(config_path: PathBuf, loaded: LoadedConfig) -> Result<(), Box<dyn std::error::Error>> {
+    let mut app = App::new()
+        .with_config_watcher(config_path, loaded.sources)
+        .with_settings(loaded.settings);
+    for b in loaded.bindings {
         app = app.add_binding(b);
     }
     app.run()?;
     Ok(())
 }
+
+/// The config still loads (and reports its errors) elsewhere; only the
+/// keyboard hook needs Windows.
+#[cfg(not(windows))]
+fn run_daemon(_config_path: PathBuf, _loaded: LoadedConfig) -> Result<(), Box<dyn std::error::Error>> {
+    Err("the keyboard hook runs only on Windows".into())
+}
+
+/// The config path given on the command line: `--config <path>`,
+/// `--config=<path>`, or a lone positional path.
+fn config_flag(mut args: impl Iterator<Item = OsString>) -> Result<Option<PathBuf>, String> {
+    let Some(arg) = args.next() else {
+        return Ok(None);
+    };
+    let path = if arg == "--config" {
+        args.next().ok_or("--config needs a path")?
+    } else if let Some(path) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+        OsString::from(path)
+    } else {
+        arg
+    };
+    match args.next() {
+        Some(extra) => Err(format!("unexpected argument {:?}", extra)),
+        None => Ok(Some(PathBuf::from(path))),
+    }
+}
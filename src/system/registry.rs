@@ -1,18 +1,16 @@
 
 
-use windows::{
-    core::*,
-    Win32::{
-        System::Registry::*,
-        UI::Input::KeyboardAndMouse::*,
-    },
-};
+#[cfg(windows)]
+use windows::{core::*, Win32::System::Registry::*};
 
 use crate::data::key_combination::KeyCombination;
+use crate::data::vk::*;
+use crate::data::vk_name::ANY_WIN;
 use crate::system::system_function::SystemFunction;
 
+/// Where a hotkey lives under `HKEY_CURRENT_USER`.
+#[cfg_attr(not(windows), allow(dead_code))]
 struct RegistryLocation {
-    hkey: HKEY,
     subkey: &'static str,
     value_names: &'static [&'static str],
     parser: fn(&str) -> Option<KeyCombination>,
@@ -22,7 +20,6 @@ fn get_registry_location(function: SystemFunction) -> Option<RegistryLocation> {
     match function {
         SystemFunction::SwitchLanguage | SystemFunction::SwitchLanguageBackward => {
             Some(RegistryLocation {
-                hkey: HKEY_CURRENT_USER,
                 subkey: "Keyboard Layout\\Toggle",
                 value_names: &["Hotkey", "Language Hotkey"],
                 parser: parse_language_hotkey,
@@ -31,7 +28,6 @@ fn get_registry_location(function: SystemFunction) -> Option<RegistryLocation> {
 
         SystemFunction::LockWorkstation => {
             Some(RegistryLocation {
-                hkey: HKEY_CURRENT_USER,
                 subkey: "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\Advanced",
                 value_names: &["LockWorkstationHotkey"],
                 parser: parse_win_key_combo,
@@ -40,7 +36,6 @@ fn get_registry_location(function: SystemFunction) -> Option<RegistryLocation> {
 
         SystemFunction::ShowDesktop => {
             Some(RegistryLocation {
-                hkey: HKEY_CURRENT_USER,
                 subkey: "Software\\Microsoft\\Windows\\CurrentVersion\\Explorer\\Advanced",
                 value_names: &["ShowDesktopHotkey"],
                 parser: parse_win_key_combo,
@@ -78,10 +73,10 @@ fn get_default_combination(function: SystemFunction) -> Option<KeyCombination> {
             Some(KeyCombination::from_keys(vec![VK_MENU, VK_SHIFT]))
         }
         SystemFunction::LockWorkstation => {
-            Some(KeyCombination::from_keys(vec![VK_LWIN, VK_L]))
+            Some(KeyCombination::from_keys(vec![ANY_WIN, VK_L]))
         }
         SystemFunction::ShowDesktop => {
-            Some(KeyCombination::from_keys(vec![VK_LWIN, VK_D]))
+            Some(KeyCombination::from_keys(vec![ANY_WIN, VK_D]))
         }
         SystemFunction::TaskManager => {
             Some(KeyCombination::from_keys(vec![VK_CONTROL, VK_SHIFT, VK_ESCAPE]))
@@ -90,10 +85,18 @@ fn get_default_combination(function: SystemFunction) -> Option<KeyCombination> {
     }
 }
 
+/// There is no registry to read off Windows; the defaults stand.
+#[cfg(not(windows))]
+fn read_from_registry(_location: &RegistryLocation) -> Option<KeyCombination> {
+    None
+}
+
 /// RAII guard that closes an HKEY on drop. Prevents leaking the key handle if
 /// `read_value_and_parse` ever panics between open and the explicit close.
+#[cfg(windows)]
 struct HKeyGuard(HKEY);
 
+#[cfg(windows)]
 impl Drop for HKeyGuard {
     fn drop(&mut self) {
         // SAFETY: the inner HKEY is the result of a successful RegOpenKeyExW
@@ -104,6 +107,7 @@ impl Drop for HKeyGuard {
     }
 }
 
+#[cfg(windows)]
 fn read_from_registry(location: &RegistryLocation) -> Option<KeyCombination> {
     let subkey_wide: Vec<u16> = location.subkey
         .encode_utf16()
@@ -116,7 +120,7 @@ fn read_from_registry(location: &RegistryLocation) -> Option<KeyCombination> {
     // points to a stack-local that is valid for the duration of the call.
     let open_result = unsafe {
         RegOpenKeyExW(
-            location.hkey,
+            HKEY_CURRENT_USER,
             PCWSTR(subkey_wide.as_ptr()),
             None,
             KEY_READ,
@@ -137,6 +141,7 @@ fn read_from_registry(location: &RegistryLocation) -> Option<KeyCombination> {
     None
 }
 
+#[cfg(windows)]
 fn read_value_and_parse(
     hkey: HKEY,
     value_name: &str,
@@ -205,13 +210,13 @@ fn parse_win_key_combo(value: &str) -> Option<KeyCombination> {
     if value.len() == 1 {
         let ch = value.chars().next()?;
         let vk = char_to_vk(ch)?;
-        return Some(KeyCombination::from_keys(vec![VK_LWIN, vk]));
+        return Some(KeyCombination::from_keys(vec![ANY_WIN, vk]));
     }
 
     None
 }
 
-fn char_to_vk(ch: char) -> Option<VIRTUAL_KEY> {
+fn char_to_vk(ch: char) -> Option<Vk> {
     match ch {
         'A' => Some(VK_A),
         'B' => Some(VK_B),
@@ -272,7 +277,7 @@ mod tests {
         assert!(lang_default.keys.contains(&VK_SHIFT));
 
         let lock_default = get_default_combination(SystemFunction::LockWorkstation).unwrap();
-        assert!(lock_default.keys.contains(&VK_LWIN));
+        assert!(lock_default.keys.contains(&ANY_WIN));
         assert!(lock_default.keys.contains(&VK_L));
     }
 
@@ -287,11 +292,11 @@ mod tests {
     #[test]
     fn test_parse_win_key_combo() {
         let combo = parse_win_key_combo("L").unwrap();
-        assert!(combo.keys.contains(&VK_LWIN));
+        assert!(combo.keys.contains(&ANY_WIN));
         assert!(combo.keys.contains(&VK_L));
 
         let combo2 = parse_win_key_combo("D").unwrap();
-        assert!(combo2.keys.contains(&VK_LWIN));
+        assert!(combo2.keys.contains(&ANY_WIN));
         assert!(combo2.keys.contains(&VK_D));
     }
 }
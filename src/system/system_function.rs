@@ -1,5 +1,7 @@
+#[cfg(windows)]
 use std::path::PathBuf;
 
+#[cfg(windows)]
 use windows::{
     core::*,
     Win32::{
@@ -11,6 +13,7 @@ use windows::{
     },
 };
 
+#[cfg(windows)]
 use crate::core::constants::injected_sentinel;
 use crate::data::key_combination::KeyCombination;
 use crate::system::registry;
@@ -26,6 +29,13 @@ pub enum SystemFunction {
     ToggleCapsLock,
 }
 
+impl SystemFunction {
+    pub fn get_system_combination(self) -> Option<KeyCombination> {
+        registry::get_system_hotkey(self)
+    }
+}
+
+#[cfg(windows)]
 impl SystemFunction {
     pub fn execute(&self) {
         match self {
@@ -38,10 +48,6 @@ impl SystemFunction {
         }
     }
 
-    pub fn get_system_combination(self) -> Option<KeyCombination> {
-        registry::get_system_hotkey(self)
-    }
-
     fn switch_language_forward() {
         // SAFETY: GetForegroundWindow + PostMessageW require only a valid HWND,
         // which we validate.
@@ -161,6 +167,7 @@ impl SystemFunction {
 /// when the environment variable is missing. Path-qualifying these launches
 /// prevents PATH-hijack attacks where an attacker plants a same-named binary
 /// earlier in the PATH.
+#[cfg(windows)]
 fn system32_path(exe_name: &str) -> PathBuf {
     let root = std::env::var_os("SystemRoot").unwrap_or_else(|| r"C:\Windows".into());
     let mut p = PathBuf::from(root);
@@ -185,6 +192,7 @@ mod tests {
         assert!(SystemFunction::ToggleCapsLock.get_system_combination().is_none());
     }
 
+    #[cfg(windows)]
     #[test]
     fn system32_path_includes_system32() {
         let p = system32_path("Taskmgr.exe");